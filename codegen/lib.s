@@ -52,4 +52,257 @@ _SELoop:
   addu $a1, $a1, 1
   j _SELoop
 _SEDone:
-  jr $ra
\ No newline at end of file
+  jr $ra
+
+.text
+.globl _StringLength
+_StringLength:
+  move $v0, $zero
+_SLLoop:
+  addu $t0, $a0, $v0
+  lb $t0, ($t0)
+  beqz $t0, _SLDone
+  addu $v0, $v0, 1
+  j _SLLoop
+_SLDone:
+  jr $ra
+
+# _Substring returns a new string of the chars in [$a1, $a2) of $a0, the bounds are checked by the caller
+.text
+.globl _Substring
+_Substring:
+  move $t0, $a0
+  subu $t1, $a2, $a1
+  addu $a0, $t1, 1 # 1 for the ending '\0'
+  li $v0, 9 # sbrk
+  syscall
+  addu $t0, $t0, $a1
+  move $t2, $v0
+_SSCopy:
+  beqz $t1, _SSDone
+  lb $t3, ($t0)
+  sb $t3, ($t2)
+  addu $t0, $t0, 1
+  addu $t2, $t2, 1
+  subu $t1, $t1, 1
+  j _SSCopy
+_SSDone:
+  sb $zero, ($t2)
+  jr $ra
+
+# _StringToInt parses an optional '-' and the digits after it, like atoi in c: it stops at the first non-digit char,
+# so a string not starting with digits gives 0, and an overflowing number wraps
+.text
+.globl _StringToInt
+_StringToInt:
+  move $v0, $zero
+  li $t1, 1 # the sign
+  lb $t0, ($a0)
+  bne $t0, 45, _STILoop # '-'
+  li $t1, -1
+  addu $a0, $a0, 1
+_STILoop:
+  lb $t0, ($a0)
+  subu $t0, $t0, 48 # '0'
+  bgeu $t0, 10, _STIDone
+  mul $v0, $v0, 10
+  addu $v0, $v0, $t0
+  addu $a0, $a0, 1
+  j _STILoop
+_STIDone:
+  mul $v0, $v0, $t1
+  jr $ra
+
+# _IntToString allocates 12 bytes, enough for "-2147483648" and the ending '\0', the digits are written from the end
+.text
+.globl _IntToString
+_IntToString:
+  move $t0, $a0
+  li $a0, 12
+  li $v0, 9 # sbrk
+  syscall
+  addu $t1, $v0, 11
+  sb $zero, ($t1)
+  move $t2, $t0 # the absolute value as unsigned, so that -2147483648 works
+  bgez $t0, _ITSLoop
+  negu $t2, $t0
+_ITSLoop:
+  subu $t1, $t1, 1
+  remu $t3, $t2, 10
+  addu $t3, $t3, 48 # '0'
+  sb $t3, ($t1)
+  divu $t2, $t2, 10
+  bnez $t2, _ITSLoop
+  bgez $t0, _ITSDone
+  subu $t1, $t1, 1
+  li $t3, 45 # '-'
+  sb $t3, ($t1)
+_ITSDone:
+  move $v0, $t1
+  jr $ra
+
+.text
+.globl _StringConcat
+_StringConcat:
+  move $t0, $a0
+  move $t1, $a1
+  li $a0, 1 # 1 for the ending '\0'
+  move $t2, $t0
+_SCLen1:
+  lb $t3, ($t2)
+  beqz $t3, _SCLen2Init
+  addu $a0, $a0, 1
+  addu $t2, $t2, 1
+  j _SCLen1
+_SCLen2Init:
+  move $t2, $t1
+_SCLen2:
+  lb $t3, ($t2)
+  beqz $t3, _SCAlloc
+  addu $a0, $a0, 1
+  addu $t2, $t2, 1
+  j _SCLen2
+_SCAlloc:
+  li $v0, 9 # sbrk
+  syscall
+  move $t2, $v0
+_SCCopy1:
+  lb $t3, ($t0)
+  beqz $t3, _SCCopy2
+  sb $t3, ($t2)
+  addu $t0, $t0, 1
+  addu $t2, $t2, 1
+  j _SCCopy1
+_SCCopy2:
+  lb $t3, ($t1)
+  sb $t3, ($t2) # the ending '\0' is also copied
+  beqz $t3, _SCDone
+  addu $t1, $t1, 1
+  addu $t2, $t2, 1
+  j _SCCopy2
+_SCDone:
+  jr $ra
+
+# a double is a pointer to 8 bytes holding its value, and a null pointer means 0.0
+# _DoubleLoad2 and _DoubleStore are only used in this file
+# _DoubleLoad2 loads the doubles pointed by $a0 and $a1 into $f0 and $f2
+.text
+_DoubleLoad2:
+  mtc1 $zero, $f0
+  mtc1 $zero, $f1
+  beqz $a0, _DL2Second
+  lwc1 $f0, 0($a0)
+  lwc1 $f1, 4($a0)
+_DL2Second:
+  mtc1 $zero, $f2
+  mtc1 $zero, $f3
+  beqz $a1, _DL2Done
+  lwc1 $f2, 0($a1)
+  lwc1 $f3, 4($a1)
+_DL2Done:
+  jr $ra
+
+# _DoubleStore stores $f0 into a newly allocated double, and returns to $t0 (not $ra)
+.text
+_DoubleStore:
+  li $a0, 8
+  li $v0, 9 # sbrk
+  syscall
+  swc1 $f0, 0($v0)
+  swc1 $f1, 4($v0)
+  jr $t0
+
+.text
+.globl _DoubleAdd
+_DoubleAdd:
+  move $t0, $ra
+  jal _DoubleLoad2
+  add.d $f0, $f0, $f2
+  j _DoubleStore
+
+.text
+.globl _DoubleSub
+_DoubleSub:
+  move $t0, $ra
+  jal _DoubleLoad2
+  sub.d $f0, $f0, $f2
+  j _DoubleStore
+
+.text
+.globl _DoubleMul
+_DoubleMul:
+  move $t0, $ra
+  jal _DoubleLoad2
+  mul.d $f0, $f0, $f2
+  j _DoubleStore
+
+.text
+.globl _DoubleDiv
+_DoubleDiv:
+  move $t0, $ra
+  jal _DoubleLoad2
+  div.d $f0, $f0, $f2
+  j _DoubleStore
+
+.text
+.globl _IntToDouble
+_IntToDouble:
+  move $t0, $ra
+  mtc1 $a0, $f0
+  cvt.d.w $f0, $f0
+  j _DoubleStore
+
+.text
+.globl _ReadDouble
+_ReadDouble:
+  move $t0, $ra
+  li $v0, 7 # read_double
+  syscall
+  j _DoubleStore
+
+.text
+.globl _DoubleEq
+_DoubleEq:
+  move $t0, $ra
+  jal _DoubleLoad2
+  li $v0, 1
+  c.eq.d $f0, $f2
+  bc1t _DEqDone
+  li $v0, 0
+_DEqDone:
+  jr $t0
+
+.text
+.globl _DoubleLt
+_DoubleLt:
+  move $t0, $ra
+  jal _DoubleLoad2
+  li $v0, 1
+  c.lt.d $f0, $f2
+  bc1t _DLtDone
+  li $v0, 0
+_DLtDone:
+  jr $t0
+
+.text
+.globl _DoubleLe
+_DoubleLe:
+  move $t0, $ra
+  jal _DoubleLoad2
+  li $v0, 1
+  c.le.d $f0, $f2
+  bc1t _DLeDone
+  li $v0, 0
+_DLeDone:
+  jr $t0
+
+.text
+.globl _PrintDouble
+_PrintDouble:
+  move $t0, $ra
+  move $a1, $zero
+  jal _DoubleLoad2
+  mov.d $f12, $f0
+  li $v0, 3 # print_double
+  syscall
+  jr $t0
@@ -54,6 +54,7 @@ impl Node {
 // some fields the paper mentions are not really necessary, I leave them in comments
 // some fields doesn't need to be a set, because only push(guaranteed unique) and iteration are required
 // some fields need to be a set and need to record insertion order, use IndexSet
+// the fields that the algorithm picks an element from are also IndexSet, so that the allocation result is the same in every run
 pub struct Allocator<A: AllocCtx> {
   pub nodes: Vec<Node>,
   // machine registers, preassigned a color
@@ -61,11 +62,11 @@ pub struct Allocator<A: AllocCtx> {
   // virtual registers, not preassigned a color and not yet processed by the algorithm
   initial: Vec<u32>,
   // list of low-degree non-move-related nodes
-  simplify_work_list: HashSet<u32>,
+  simplify_work_list: IndexSet<u32>,
   // low-degree move-related nodes
-  freeze_work_list: HashSet<u32>,
+  freeze_work_list: IndexSet<u32>,
   // high-degree nodes
-  spill_work_list: HashSet<u32>,
+  spill_work_list: IndexSet<u32>,
   // nodes marked for spilling during this round; initially empty
   spilled_nodes: HashSet<u32>,
   // registers that have been coalesced;
@@ -82,7 +83,7 @@ pub struct Allocator<A: AllocCtx> {
   // moves that will no longer be considered for coalescing
   // frozen_moves: HashSet<(u32, u32)>,
   // moves enabled for possible coalescing
-  pub work_list_moves: HashSet<(u32, u32)>,
+  pub work_list_moves: IndexSet<(u32, u32)>,
   // moves not yet ready for coalescing
   active_moves: HashSet<(u32, u32)>,
   adj_set: HashSet<(u32, u32)>,
@@ -93,7 +94,7 @@ impl<A: AllocCtx> Allocator<A> {
   pub fn work(ctx: &mut A) {
     // unluckily cannot use #[derive(Default)] because A may not be Default, even though PhantomData<A> is
     // I still don't know why rust has such a requirement
-    let mut a = Allocator { nodes: Vec::new(), initial: Vec::new(), simplify_work_list: HashSet::new(), freeze_work_list: HashSet::new(), spill_work_list: HashSet::new(), spilled_nodes: HashSet::new(), coalesced_nodes: HashSet::new(), select_stack: IndexSet::default(), work_list_moves: HashSet::new(), active_moves: HashSet::new(), adj_set: HashSet::new(), _p: PhantomData };
+    let mut a = Allocator { nodes: Vec::new(), initial: Vec::new(), simplify_work_list: IndexSet::default(), freeze_work_list: IndexSet::default(), spill_work_list: IndexSet::default(), spilled_nodes: HashSet::new(), coalesced_nodes: HashSet::new(), select_stack: IndexSet::default(), work_list_moves: IndexSet::default(), active_moves: HashSet::new(), adj_set: HashSet::new(), _p: PhantomData };
     // actually no information in `a` is preserved for the next loop
     // because in this simple variant of this algo, all coalesces are discarded if spill happens
     // so the only reason for creating `a` outside the loop is to reuse some memory
@@ -169,21 +170,21 @@ impl<A: AllocCtx> Allocator<A> {
   }
 
   fn assign_color(&mut self) {
-    let mut available = HashSet::with_capacity(A::K as usize);
+    let mut used = HashSet::with_capacity(A::K as usize);
     for &n in self.select_stack.iter().rev() { // pop all, need to traverse reversely
-      available.clear();
-      for i in 0..A::K { available.insert(i); }
+      used.clear();
       for &w in &self.nodes[n as usize].adj_list {
         let a = self.get_alias(w);
         match self.nodes[a as usize].color {
-          Reg::PreColored(r) | Reg::Allocated(r) => { available.remove(&r); }
+          Reg::PreColored(r) | Reg::Allocated(r) => { used.insert(r); }
           Reg::Virtual(_) => {}
         };
       }
       // PreColored nodes should never be added to select_stack
       // so this color assignment will not give a PreColored node a wrong color
-      if let Some(r) = available.iter().nth(0) {
-        self.nodes[n as usize].color = Reg::Allocated(*r);
+      // the lowest available color is chosen, instead of an arbitrary one in a hash set
+      if let Some(r) = (0..A::K).find(|r| !used.contains(r)) {
+        self.nodes[n as usize].color = Reg::Allocated(r);
       } else {
         self.spilled_nodes.insert(n);
       }
@@ -2,6 +2,7 @@ pub mod mips;
 pub mod mips_gen;
 pub mod graph_alloc;
 pub mod brute_alloc;
+pub mod peephole;
 
 pub enum AllocMethod { Graph, Brute }
 
@@ -32,7 +32,7 @@ pub mod regs {
 // only the syscalls that we used directly in codegen are listed here
 // e.g.: ReadString is handled by a library function, so it not listed here
 #[derive(Copy, Clone)]
-pub enum SysCall { PrintInt = 1, PrintString = 4, ReadInt = 5, Sbrk = 9, Exit = 10 }
+pub enum SysCall { PrintInt = 1, PrintString = 4, ReadInt = 5, Sbrk = 9, Exit = 10, PrintChar = 11 }
 
 pub const WORD_SIZE: i32 = 4;
 
@@ -60,6 +60,8 @@ pub enum Imm {
   Int(i32),
   // `Tag` is used as a placeholder for undecided immediate value
   Tag(u32),
+  // the offset of a `Tac::StackAlloc` object in the region above the spill slots, decided with `Tag`
+  Stack(i32),
 }
 
 impl AsmTemplate {
@@ -133,8 +135,9 @@ impl AsmTemplate {
     match *self {
       AsmTemplate::BinI(op, d, l, r) if d.id() == l.id() => match op {
         // And is bitwise and, but it can only be applied to bool in decaf, so And 1 is nop
-        BinOp::Add | BinOp::Sub | BinOp::Or if r == Imm::Int(0) => true,
+        BinOp::Add | BinOp::Sub | BinOp::Or | BinOp::BitOr | BinOp::BitXor | BinOp::Shl | BinOp::Shr if r == Imm::Int(0) => true,
         BinOp::Mul | BinOp::Div | BinOp::And if r == Imm::Int(1) => true,
+        BinOp::BitAnd if r == Imm::Int(-1) => true,
         _ => false
       }
       AsmTemplate::Mv(w, r) if w.id() == r.id() => true,
@@ -146,7 +149,7 @@ impl AsmTemplate {
 
 impl fmt::Debug for Imm {
   fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-    match self { Imm::Int(i) => write!(f, "{}", i), Imm::Tag(i) => write!(f, "_I{}", i), }
+    match self { Imm::Int(i) => write!(f, "{}", i), Imm::Tag(i) => write!(f, "_I{}", i), Imm::Stack(i) => write!(f, "_S{}", i), }
   }
 }
 
@@ -164,7 +167,7 @@ impl fmt::Debug for AsmTemplate {
     use AsmTemplate::*;
     match self {
       Bin(op, w1, r1, r2) => write!(f, "{} {:?}, {:?}, {:?}", bin_str(*op), w1, r1, r2),
-      BinI(op, w1, r1, i) => write!(f, "{} {:?}, {:?}, {:?}", bin_str(*op), w1, r1, i),
+      BinI(op, w1, r1, i) => write!(f, "{} {:?}, {:?}, {:?}", bin_i_str(*op), w1, r1, i),
       Un(op, w1, r1) => write!(f, "{} {:?}, {:?}", un_str(*op), w1, r1),
       Mv(w1, r1) => write!(f, "move {:?}, {:?}", w1, r1),
       Jal(l) => write!(f, "jal {}", l),
@@ -188,9 +191,14 @@ impl fmt::Debug for AsmTemplate {
 // we will output a lot of pseudo mips instructions, and depend on assembler or simulator to translate these pseudo instructions
 pub fn bin_str(op: BinOp) -> &'static str {
   use BinOp::*;
-  match op { Add => "addu", Sub => "subu", Mul => "mul", Div => "div", Mod => "rem", And => "and", Or => "or", Eq => "seq", Ne => "sne", Lt => "slt", Le => "sle", Gt => "sgt", Ge => "sge" }
+  match op { Add => "addu", Sub => "subu", Mul => "mul", Div => "div", Mod => "rem", And => "and", Or => "or", BitAnd => "and", BitOr => "or", BitXor => "xor", Shl => "sllv", Shr => "srav", Eq => "seq", Ne => "sne", Lt => "slt", Le => "sle", Gt => "sgt", Ge => "sge" }
+}
+
+// the I instruction version of bin_str, only shift instructions have different names
+pub fn bin_i_str(op: BinOp) -> &'static str {
+  match op { BinOp::Shl => "sll", BinOp::Shr => "sra", op => bin_str(op) }
 }
 
 pub fn un_str(op: UnOp) -> &'static str {
-  match op { UnOp::Neg => "neg", UnOp::Not => "not" }
+  match op { UnOp::Neg => "neg", UnOp::Not => "not", UnOp::BitNot => "not" }
 }
\ No newline at end of file
@@ -16,6 +16,8 @@ pub struct FuncGen<'a, 'b> {
   pub(crate) bb: Vec<(Vec<AsmTemplate>, [Option<u32>; 2])>,
   // map virtual reg's id to its offset from $sp
   pub(crate) spill2slot: HashMap<u32, i32>,
+  // the total size of the objects allocated by `Tac::StackAlloc`, they are placed above the spill slots
+  pub(crate) stack_obj: i32,
 }
 
 // all virtual register's id >= REG_N, all pre-colored or allocated register's id < REG_N, id can be the index in Allocator::nodes
@@ -122,7 +124,7 @@ impl<'a: 'b, 'b> FuncGen<'a, 'b> {
   pub fn work(f: &'b FuncBB<'a>, p: &'b TacProgram<'a>, m: AllocMethod) -> Vec<AsmTemplate> {
     // reg_num is not inced by K, and new_reg() doesn't either, so all usage of virtual register id need to inc K
     // including those using f's inst and those generated to meet calling convention
-    let mut fu = FuncGen { param_num: f.param_num, reg_num: f.reg_num, ch_param_num: 0, name: &f.name, program: p, bb: Vec::new(), spill2slot: HashMap::new() };
+    let mut fu = FuncGen { param_num: f.param_num, reg_num: f.reg_num, ch_param_num: 0, name: &f.name, program: p, bb: Vec::new(), spill2slot: HashMap::new(), stack_obj: 0 };
     fu.populate(f);
     match m { AllocMethod::Graph => Allocator::work(&mut fu), AllocMethod::Brute => fu.brute_alloc() }
     fu.fill_imm_tag();
@@ -207,16 +209,19 @@ impl<'a: 'b, 'b> FuncGen<'a, 'b> {
   }
 
   fn fill_imm_tag(&mut self) {
-    let self_stack = (self.spill2slot.len() as i32 + self.ch_param_num as i32) * WORD_SIZE;
+    let spill = (self.spill2slot.len() as i32 + self.ch_param_num as i32) * WORD_SIZE;
+    let self_stack = spill + self.stack_obj;
     for (b, _) in &mut self.bb {
       for t in b {
         if let Some(imm) = t.imm_mut() {
-          if let Imm::Tag(t) = *imm {
+          match *imm {
             // there are 3 places uses Imm::Tag, all can use the same way to compute
             // 1. $sp -= _ in prologue, tag = 0
             // 2. $sp += _ in epilogue, tag = 0
             // 3. the offset of arguments of this function on stack, where tag = t for t_th(0 based index) argument
-            *imm = Imm::Int(self_stack + t as i32 * WORD_SIZE);
+            Imm::Tag(t) => *imm = Imm::Int(self_stack + t as i32 * WORD_SIZE),
+            Imm::Stack(off) => *imm = Imm::Int(spill + off),
+            Imm::Int(_) => {}
           }
         }
       }
@@ -285,8 +290,11 @@ impl FuncGen<'_, '_> {
       Tac::Bin { op, dst, lr } => {
         match lr {
           [Operand::Const(l), Operand::Const(r)] => b.push(Li(vreg(dst), Imm::Int(op.eval(l, r)))),
-          [Operand::Reg(l), Operand::Const(r)] => b.push(BinI(op, vreg(dst), vreg(l), Imm::Int(r))),
-          [Operand::Const(l), Operand::Reg(r)] => if let Some(inv) = op.invert() {
+          // the shift amount of sll/sra must be in 0..32
+          [Operand::Reg(l), Operand::Const(r)] => if !self.strength_reduce(op, dst, l, r, b) {
+            b.push(BinI(op, vreg(dst), vreg(l), Imm::Int(if op == BinOp::Shl || op == BinOp::Shr { r & 31 } else { r })))
+          }
+          [Operand::Const(l), Operand::Reg(r)] => if op == BinOp::Mul && self.strength_reduce(op, dst, r, l, b) {} else if let Some(inv) = op.invert() {
             b.push(BinI(inv, vreg(dst), vreg(r), Imm::Int(l)))
           } else {
             let tmp = self.build_operand(Operand::Const(l), b);
@@ -340,10 +348,41 @@ impl FuncGen<'_, '_> {
       Tac::LoadStr { dst, s } => b.push(AsmTemplate::La(vreg(dst), format!("_STRING{}", s))),
       Tac::LoadVTbl { dst, v } => b.push(AsmTemplate::La(vreg(dst), format!("_{}", self.program.vtbl[v as usize].class))),
       Tac::LoadFunc { dst, f } => b.push(AsmTemplate::La(vreg(dst), self.program.func[f as usize].name.clone())),
+      Tac::LoadStatic { dst, v } => b.push(AsmTemplate::La(vreg(dst), self.program.static_var[v as usize].clone())),
+      Tac::StackAlloc { dst, size } => {
+        let off = (self.stack_obj, self.stack_obj += (size + WORD_SIZE - 1) / WORD_SIZE * WORD_SIZE).0;
+        b.push(BinI(BinOp::Add, vreg(dst), mreg(SP), Imm::Stack(off)));
+        // the stack frame is not cleared, but `_Alloc` returns cleared memory
+        for i in (0..size).step_by(WORD_SIZE as usize) { b.push(Sw(mreg(ZERO), vreg(dst), Imm::Int(i))); }
+      }
       Tac::Label { .. } | Tac::Ret { .. } | Tac::Jmp { .. } | Tac::Jif { .. } => unreachable!("Shouldn't meet Ret/Jmp/Jif/Label in a tac bb."),
     }
   }
 
+  // replace `l * r`, `l / r`, `l % r` with shifts and masks if `r` is a power of two, return whether it is replaced
+  // division rounds towards zero, so a bias `r - 1` is added to a negative `l` before shifting
+  fn strength_reduce(&mut self, op: BinOp, dst: u32, l: u32, r: i32, b: &mut Vec<AsmTemplate>) -> bool {
+    use AsmTemplate::*;
+    if r <= 1 || r & (r - 1) != 0 { return false; }
+    let (dst, l, k) = (vreg(dst), vreg(l), Imm::Int(r.trailing_zeros() as i32));
+    match op {
+      BinOp::Mul => b.push(BinI(BinOp::Shl, dst, l, k)),
+      BinOp::Div | BinOp::Mod => {
+        // bias = l < 0 ? r - 1 : 0, l / r = (l + bias) >> k, l % r = l - ((l + bias) & -r)
+        let (bias, tmp) = (vreg(self.new_reg()), vreg(self.new_reg()));
+        b.push(BinI(BinOp::Shr, bias, l, Imm::Int(31)));
+        b.push(BinI(BinOp::BitAnd, bias, bias, Imm::Int(r - 1)));
+        b.push(Bin(BinOp::Add, tmp, l, bias));
+        if op == BinOp::Div { b.push(BinI(BinOp::Shr, dst, tmp, k)); } else {
+          b.push(BinI(BinOp::BitAnd, tmp, tmp, Imm::Int(-r)));
+          b.push(Bin(BinOp::Sub, dst, l, tmp));
+        }
+      }
+      _ => return false,
+    }
+    true
+  }
+
   // the returned reg can only be used for read
   fn build_operand(&mut self, src: Operand, b: &mut Vec<AsmTemplate>) -> Reg {
     match src {
@@ -372,8 +411,10 @@ impl FuncGen<'_, '_> {
       _ReadInt => b.push(AsmTemplate::SysCall(SysCall::ReadInt)),
       _PrintInt => b.push(AsmTemplate::SysCall(SysCall::PrintInt)),
       _PrintString => b.push(AsmTemplate::SysCall(SysCall::PrintString)),
+      _PrintChar => b.push(AsmTemplate::SysCall(SysCall::PrintChar)),
       _Halt => b.push(AsmTemplate::SysCall(SysCall::Exit)),
-      _ReadLine | _StringEqual | _PrintBool => {
+      _ReadLine | _StringEqual | _StringConcat | _PrintBool | _DoubleAdd | _DoubleSub | _DoubleMul | _DoubleDiv |
+      _DoubleEq | _DoubleLt | _DoubleLe | _IntToDouble | _PrintDouble | _ReadDouble | _StringLength | _Substring | _StringToInt | _IntToString => {
         b.push(AsmTemplate::Jal(format!("{:?}", i)));
         return true;
       }
@@ -0,0 +1,25 @@
+use crate::mips::AsmTemplate::{self, *};
+
+// peephole optimization on the asm of a function after register allocation, the rules are:
+// 1. `sw $t0, 4($sp)` followed by `lw $t1, 4($sp)`: the lw is replaced by `move $t1, $t0`
+// 2. a jump right after another jump is unreachable, remove it
+// 3. a jump or branch to the label right after it, remove it
+// 4. the useless asm (see `AsmTemplate::useless`), e.g.: `move $t0, $t0`, remove it
+pub fn work(asm: Vec<AsmTemplate>) -> Vec<AsmTemplate> {
+  let mut ret: Vec<AsmTemplate> = Vec::with_capacity(asm.len());
+  for a in asm {
+    let a = match (ret.last(), a) {
+      (Some(Sw(r, base, imm)), Lw(w, base1, imm1)) if *base == base1 && *imm == imm1 => Mv(w, *r),
+      (Some(J(_)), J(_)) => continue,
+      (_, a) => a,
+    };
+    if let Label(l) = &a {
+      // the label has a trailing ':'
+      while let Some(J(j)) | Some(B(j, _, _)) = ret.last() {
+        if l.len() == j.len() + 1 && l.starts_with(j.as_str()) { ret.pop(); } else { break; }
+      }
+    }
+    if !a.useless() { ret.push(a); }
+  }
+  ret
+}
@@ -1,13 +1,23 @@
 use crate::{loc::{Loc, NO_LOC}, MAIN_CLASS};
 use std::fmt;
 
-pub struct Error<'a, Ty>(pub Loc, pub ErrorKind<'a, Ty>);
+// the severity is initially decided by the kind, but a warning can be promoted to an error (`-Werror`)
+pub struct Error<'a, Ty>(pub Loc, pub ErrorKind<'a, Ty>, pub Severity);
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum Severity { Error, Warning }
+
+// the warnings reported: none of them, the ones which are likely to be bugs, or all of them
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum WarnLevel { None, Default, All }
 
 // Errors implements Debug, it prints errors line by line
-pub struct Errors<'a, Ty>(pub Vec<Error<'a, Ty>>);
+// the second field is the max number of errors (not including warnings), errors and warnings after it are discarded
+// the third field is the number of errors (not including warnings) in the first field, so that `issue` doesn't need to count them
+pub struct Errors<'a, Ty>(pub Vec<Error<'a, Ty>>, pub usize, usize);
 
 impl<Ty> Default for Errors<'_, Ty> {
-  fn default() -> Self { Self(vec![]) }
+  fn default() -> Self { Self(vec![], usize::MAX, 0) }
 }
 
 impl<'a, Ty> Errors<'a, Ty> {
@@ -15,62 +25,222 @@ impl<'a, Ty> Errors<'a, Ty> {
   // because when issuing an error, it often follows return a false / error type, which is the default
   // if the compiler complains that it needs type hint, in many cases you can omit the ;, and it will be deduced to ()
   pub fn issue<T: Default>(&mut self, loc: Loc, e: ErrorKind<'a, Ty>) -> T {
-    self.0.push(Error(loc, e));
+    if self.2 < self.1 {
+      let severity = if e.is_warning() { Severity::Warning } else { Severity::Error };
+      self.2 += !e.is_warning() as usize;
+      self.0.push(Error(loc, e, severity));
+    } else if self.2 == self.1 && !e.is_warning() {
+      // the note itself is an error, so that it is added only once
+      self.2 += 1;
+      self.0.push(Error(NO_LOC, ErrorKind::TooManyErrors(self.1 as u32), Severity::Error));
+    }
     Default::default()
   }
 
+  pub fn with_limit(max_error: usize) -> Self { Self(vec![], max_error, 0) }
+
+  // the errors and warnings in `diag`, without limit
+  pub fn new(diag: Vec<Error<'a, Ty>>) -> Self {
+    let cnt = diag.iter().filter(|e| !e.is_warning()).count();
+    Self(diag, usize::MAX, cnt)
+  }
+
+  pub fn append(&mut self, other: &mut Self) {
+    self.2 += other.2;
+    self.0.append(&mut other.0);
+  }
+
+  // split the warnings out, return (errors, warnings), the relative order in each part is not changed
+  pub fn split_warnings(self) -> (Self, Self) {
+    let (w, e) = self.0.into_iter().partition(|e| e.is_warning());
+    (Errors(e, self.1, self.2), Errors(w, self.1, 0))
+  }
+
+  pub fn has_error(&self) -> bool { self.0.iter().any(|e| !e.is_warning()) }
+
+  // keep the warnings reported at `level`, and promote them to errors if `werror`
+  pub fn filter_warnings(mut self, level: WarnLevel, werror: bool) -> Self {
+    self.0.retain(|e| !e.is_warning() || e.1.warn_level() <= level);
+    if werror { for e in &mut self.0 { e.2 = Severity::Error; } }
+    self
+  }
+
   // guarantee to be stable, because there may be multiple errors in one loc
+  // TooManyErrors is kept in the end
   pub fn sorted(mut self) -> Self {
-    self.0.sort_by_key(|e| e.0);
+    self.0.sort_by_key(|e| (if let ErrorKind::TooManyErrors(_) = e.1 { true } else { false }, e.0));
     self
   }
 }
 
+impl<Ty: fmt::Debug> Errors<'_, Ty> {
+  pub fn render(&self, code: &str) -> String { self.0.iter().map(|e| e.render(code)).collect() }
+}
+
+// (source line, start byte, number of chars) of the span of an error, from the column to the end column
+// an empty span (e.g.: at the end of the file) is shown as one char
+fn span(code: &str, loc: Loc) -> Option<(&[u8], usize, usize)> {
+  let Loc(l, c, end) = loc;
+  let line = code.lines().nth(l.wrapping_sub(1) as usize).filter(|_| loc != NO_LOC)?;
+  let (line, start) = (line.as_bytes(), (c as usize).saturating_sub(1).min(line.len()));
+  Some((line, start, (end.saturating_sub(c) as usize).max(1)))
+}
+
+impl<Ty> Error<'_, Ty> {
+  pub fn is_warning(&self) -> bool { self.2 == Severity::Warning }
+}
+
+impl<Ty: fmt::Debug> Error<'_, Ty> {
+  // the message, followed by the source line and a caret under the reported column, like rustc
+  pub fn render(&self, code: &str) -> String {
+    let mut ret = format!("{:?}\n", self);
+    let (line, start, len) = match span(code, self.0) { Some(x) => x, None => return ret };
+    let l = (self.0).0;
+    // keep the tabs so that the caret is aligned, and skip the continuation bytes of utf-8 chars
+    let pad = line[..start].iter().filter(|&&b| b & 0xC0 != 0x80).map(|&b| if b == b'\t' { '\t' } else { ' ' }).collect::<String>();
+    let w = l.to_string().len();
+    ret += &format!("{} | {}\n{:w$} | {}^{}\n", l, String::from_utf8_lossy(line), "", pad, "~".repeat(len - 1), w = w);
+    ret
+  }
+}
+
 impl<Ty: fmt::Debug> fmt::Debug for Error<'_, Ty> {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    match self.0 {
-      NO_LOC => write!(f, "*** Error: {:?}", self.1),
-      loc => write!(f, "*** Error at {:?}: {:?}", loc, self.1),
-    }
+    let kind = if self.is_warning() { "Warning" } else { "Error" };
+    if self.0 == NO_LOC { write!(f, "*** {}: {:?}", kind, self.1) } else { write!(f, "*** {} at {:?}: {:?}", kind, self.0, self.1) }
   }
 }
 
-pub enum ErrorKind<'a, Ty> {
+// generate the enum, and `name`, which returns the name of the variant
+// `V { .. }` matches all kinds of variants, so the variants can be written as usual
+macro_rules! error_kind {
+  ($($v: ident $(($($tuple: tt)*))? $({ $($field: tt)* })?,)*) => {
+    pub enum ErrorKind<'a, Ty> { $($v $(($($tuple)*))? $({ $($field)* })?,)* }
+
+    impl<Ty> ErrorKind<'_, Ty> {
+      pub fn name(&self) -> &'static str {
+        match self { $(ErrorKind::$v { .. } => stringify!($v),)* }
+      }
+    }
+  };
+}
+
+error_kind! {
   UnclosedStr(&'a str),
+  UnterminatedComment,
   NewlineInStr(&'a str),
   InvalidEscape,
-  IntTooLarge(&'a str),
+  // a char literal with zero or more than one characters, e.g.: `'ab'`
+  InvalidCharLit(&'a str),
+  InvalidUnicodeEscape,
+  IntLiteralOverflow(&'a str),
+  InvalidIntLit { lit: &'a str, radix: u32 },
+  BadDigitSeparator(&'a str),
   UnrecognizedChar(char),
   SyntaxError,
   ConflictDeclaration { prev: Loc, name: &'a str },
   NoSuchClass(&'a str),
-  CyclicInheritance,
+  NoSuchInterface(&'a str),
+  NotGeneric(&'a str),
+  MissingTypeArg(&'a str),
+  BadTypeArg(Ty),
+  GenericParent(&'a str),
+  TyParamInStatic(&'a str),
+  // the classes in the cycle, starting and ending with the same class
+  CyclicInheritance(Vec<&'a str>),
+  CyclicTypeAlias(&'a str),
   NoMainClass,
   VoidArrayElement,
+  ArrayLitNoElemTy,
   VoidVar(&'a str),
+  VoidFuncTyParam,
+  BadTupleElem(Ty),
+  CannotInferType(&'a str),
   OverrideVar(&'a str),
   OverrideMismatch { func: &'a str, p: &'a str },
+  // `static_` is whether the overriding method is static (then the overridden one is not)
+  BadOverride { func: &'a str, p: &'a str, static_: bool },
+  NotImplementInterface { class: &'a str, interface: &'a str, func: &'a str },
+  NotOverrideAbstract(&'a str),
+  NewAbstract(&'a str),
+  BadCtorName { name: &'a str, class: &'a str },
+  NoDefaultCtor { class: &'a str, parent: &'a str },
   IncompatibleUnary { op: &'a str, r: Ty },
   IncompatibleBinary { l: Ty, op: &'a str, r: Ty },
+  DoubleMod,
+  NotLValue(&'a str),
+  AssignToThis,
+  AssignCapturedVar(&'a str),
+  AssignToFinal(&'a str),
+  FinalNoInit(&'a str),
+  NullToNonNull(Ty),
+  IntToDouble,
   TestNotBool,
+  SwitchNotInt,
+  CaseNotConst,
+  DuplicateCase(i32),
+  DuplicateDefault,
   BreakOutOfLoop,
+  ContinueOutOfLoop,
+  NoSuchLabel(&'a str),
+  DuplicateLabel(&'a str),
   UndeclaredVar(&'a str),
   RefInStatic { field: &'a str, func: &'a str },
   BadFieldAccess { name: &'a str, owner: Ty },
   PrivateFieldAccess { name: &'a str, owner: Ty },
   NoSuchField { name: &'a str, owner: Ty },
   NotFunc { name: &'a str, owner: Ty },
+  NotCallable(Ty),
   LengthWithArgument(u32),
-  ArgcMismatch { name: &'a str, expect: u32, actual: u32 },
+  // the arguments from `min` to `max` are accepted, `min` is less than `max` if some parameters have default values
+  ArgcMismatch { name: &'a str, min: u32, max: u32, actual: u32 },
+  LambdaArgcMismatch { expect: u32, actual: u32 },
   ArgMismatch { loc: u32, arg: Ty, param: Ty },
+  DefaultNotConst,
+  RequiredAfterDefault(&'a str),
+  NoMatchingOverload { name: &'a str, funcs: Vec<Ty> },
+  AmbiguousCall(&'a str),
   ThisInStatic,
+  SuperInStatic,
+  NoSuperClass(&'a str),
+  AbstractSuperCall(&'a str),
   NotObject(Ty),
   BadPrintArg { loc: u32, ty: Ty },
+  FormatMismatch(u32),
   ReturnMismatch { expect: Ty, actual: Ty },
+  ReturnInVoid(Ty),
+  LambdaReturnMismatch,
   NewArrayNotInt,
   IndexNotArray,
   IndexNotInt,
+  ForeachNotArray,
+  NotTuple(Ty),
+  TupleIndexOutOfRange { idx: u32, len: u32 },
   NoReturn,
+  DivideByZero,
+  TooManyErrors(u32),
+  // below are warnings, they don't stop the compilation
+  NegativeShift(i32),
+  UnusedVar(&'a str),
+  ShadowedVar { prev: Loc, name: &'a str },
+  IndexOutOfBounds { idx: i32, len: i32 },
+  StaticFieldViaInstance(&'a str),
+  NullDereference,
+  UnreachableCode,
+  ObjectIdentityCompare,
+  SelfAssign,
+}
+
+impl<Ty> ErrorKind<'_, Ty> {
+  pub fn is_warning(&self) -> bool {
+    use ErrorKind::*;
+    match self { NegativeShift(_) | UnusedVar(_) | ShadowedVar { .. } | IndexOutOfBounds { .. } | StaticFieldViaInstance(_) | NullDereference | UnreachableCode | ObjectIdentityCompare | SelfAssign => true, _ => false }
+  }
+  // the lowest level which reports this warning, unused or shadowed variables, static fields via instances and identity comparisons are not bugs
+  pub fn warn_level(&self) -> WarnLevel {
+    use ErrorKind::*;
+    match self { UnusedVar(_) | ShadowedVar { .. } | StaticFieldViaInstance(_) | ObjectIdentityCompare | SelfAssign => WarnLevel::All, _ => WarnLevel::Default }
+  }
 }
 
 impl<Ty: fmt::Debug> fmt::Debug for ErrorKind<'_, Ty> {
@@ -78,23 +248,63 @@ impl<Ty: fmt::Debug> fmt::Debug for ErrorKind<'_, Ty> {
     use ErrorKind::*;
     match self {
       UnclosedStr(s) => write!(f, "unterminated string constant \"{}", s),
+      UnterminatedComment => write!(f, "unterminated block comment"),
       NewlineInStr(s) => write!(f, "illegal newline in string constant \"{}", s),
       InvalidEscape => write!(f, "illegal escape character"),
-      IntTooLarge(s) => write!(f, "integer literal {} is too large", s),
+      InvalidCharLit(s) => write!(f, "invalid character literal {}", s),
+      InvalidUnicodeEscape => write!(f, "illegal unicode escape, expected `\\u{{...}}` with 1 to 6 hex digits of a valid code point"),
+      IntLiteralOverflow(s) => write!(f, "integer literal {} is too large", s),
+      BadDigitSeparator(s) => write!(f, "digit separator '_' must be between two digits in integer literal {}", s),
+      InvalidIntLit { lit, radix } => write!(f, "invalid digit in {} integer literal {}", match radix { 16 => "hexadecimal", 8 => "octal", 2 => "binary", _ => "decimal" }, lit),
       UnrecognizedChar(ch) => write!(f, "unrecognized character '{}'", ch),
       SyntaxError => write!(f, "syntax error"),
       ConflictDeclaration { prev, name } => write!(f, "declaration of '{}' here conflicts with earlier declaration at {:?}", name, prev),
       NoSuchClass(name) => write!(f, "class '{}' not found", name),
-      CyclicInheritance => write!(f, "illegal class inheritance (should be acyclic)"),
+      NoSuchInterface(name) => write!(f, "interface '{}' not found", name),
+      NotGeneric(name) => write!(f, "class '{}' is not generic, it can't take a type argument", name),
+      MissingTypeArg(name) => write!(f, "generic class '{}' requires a type argument", name),
+      BadTypeArg(ty) => write!(f, "type argument can't be of type {:?}", ty),
+      GenericParent(name) => write!(f, "generic class '{}' can't be inherited", name),
+      TyParamInStatic(name) => write!(f, "can not use type parameter '{}' in static member", name),
+      CyclicInheritance(cycle) => write!(f, "illegal class inheritance (should be acyclic): {}", cycle.join(" -> ")),
+      CyclicTypeAlias(name) => write!(f, "illegal type alias '{}' (should be acyclic)", name),
       NoMainClass => write!(f, "no legal Main class named '{}' was found", MAIN_CLASS),
       VoidArrayElement => write!(f, "array element type must be non-void known type"),
+      ArrayLitNoElemTy => write!(f, "cannot determine the element type of array literal"),
       VoidVar(name) => write!(f, "cannot declare identifier '{}' as void type", name),
+      VoidFuncTyParam => write!(f, "parameters in function type must be non-void known type"),
+      BadTupleElem(ty) => write!(f, "tuple element can't be of type {:?}", ty),
+      CannotInferType(name) => write!(f, "cannot infer the type of '{}'", name),
       OverrideVar(name) => write!(f, "overriding variable is not allowed for var '{}'", name),
       OverrideMismatch { func, p } => write!(f, "overriding method '{}' doesn't match the type signature in class '{}'", func, p),
+      BadOverride { func, p, static_ } => {
+        let (me, other) = if *static_ { ("static", "instance") } else { ("instance", "static") };
+        write!(f, "{} method '{}' can not override the {} method in class '{}'", me, func, other, p)
+      }
+      NotImplementInterface { class, interface, func } => write!(f, "class '{}' doesn't implement method '{}' of interface '{}'", class, func, interface),
+      NotOverrideAbstract(name) => write!(f, "'{}' is not abstract and does not override all abstract methods", name),
+      NewAbstract(name) => write!(f, "cannot instantiate abstract class '{}'", name),
+      BadCtorName { name, class } => write!(f, "constructor '{}' should have the same name as class '{}'", name, class),
+      NoDefaultCtor { class, parent } => write!(f, "class '{}' extends class '{}', which has no constructor callable without arguments", class, parent),
       IncompatibleUnary { op, r } => write!(f, "incompatible operand: {} {:?}", op, r),
       IncompatibleBinary { l, op, r } => write!(f, "incompatible operands: {:?} {} {:?}", l, op, r),
+      DoubleMod => write!(f, "operator '%' can not be applied to double"),
+      NotLValue(op) => write!(f, "lvalue required as operand of '{}'", op),
+      AssignToThis => write!(f, "cannot assign to 'this'"),
+      AssignCapturedVar(name) => write!(f, "cannot assign to captured variable '{}' in lambda expression", name),
+      AssignToFinal(name) => write!(f, "cannot assign to final variable '{}'", name),
+      FinalNoInit(name) => write!(f, "final variable '{}' must be initialized", name),
+      NullToNonNull(ty) => write!(f, "null can't be assigned to non-null type {:?}", ty),
+      IntToDouble => write!(f, "int can't be implicitly converted to double, use a double literal (e.g.: `1.0`) or multiply it by `1.0`"),
       TestNotBool => write!(f, "test expression must have bool type"),
+      SwitchNotInt => write!(f, "switch expression must have int type"),
+      CaseNotConst => write!(f, "case label must be an int constant"),
+      DuplicateCase(x) => write!(f, "duplicate case label {}", x),
+      DuplicateDefault => write!(f, "multiple default labels in one switch"),
       BreakOutOfLoop => write!(f, "'break' is only allowed inside a loop"),
+      ContinueOutOfLoop => write!(f, "'continue' is only allowed inside a loop"),
+      NoSuchLabel(label) => write!(f, "label '{}' not found in the enclosing loops", label),
+      DuplicateLabel(label) => write!(f, "label '{}' is already used by an enclosing loop", label),
       UndeclaredVar(name) => write!(f, "undeclared variable '{}'", name),
       RefInStatic { field, func } => write!(f, "can not reference a non-static field '{}' from static method '{}'", field, func),
       BadFieldAccess { name, owner } => write!(f, "cannot access field '{}' from '{:?}'", name, owner),
@@ -102,16 +312,44 @@ impl<Ty: fmt::Debug> fmt::Debug for ErrorKind<'_, Ty> {
       NoSuchField { name, owner } => write!(f, "field '{}' not found in '{:?}'", name, owner),
       LengthWithArgument(cnt) => write!(f, "function 'length' expects 0 argument(s) but {} given", cnt),
       NotFunc { name, owner } => write!(f, "'{}' is not a method in class '{:?}'", name, owner),
-      ArgcMismatch { name, expect, actual } => write!(f, "function '{}' expects {} argument(s) but {} given", name, expect, actual),
+      NotCallable(ty) => write!(f, "{:?} is not a callable type", ty),
+      ArgcMismatch { name, min, max, actual } => if min == max {
+        write!(f, "function '{}' expects {} argument(s) but {} given", name, max, actual)
+      } else { write!(f, "function '{}' expects {} to {} argument(s) but {} given", name, min, max, actual) }
+      LambdaArgcMismatch { expect, actual } => write!(f, "lambda expression expects {} argument(s) but {} given", expect, actual),
       ArgMismatch { loc, arg, param } => write!(f, "incompatible argument {}: {:?} given, {:?} expected", loc, arg, param),
+      DefaultNotConst => write!(f, "default value of parameter must be a constant"),
+      RequiredAfterDefault(name) => write!(f, "parameter '{}' without default value follows parameter with default value", name),
+      NoMatchingOverload { name, funcs } => write!(f, "no overloaded method '{}' matches the arguments, candidates are {:?}", name, funcs),
+      AmbiguousCall(name) => write!(f, "call to overloaded method '{}' is ambiguous", name),
       ThisInStatic => write!(f, "can not use this in static function"),
+      SuperInStatic => write!(f, "can not use super in static function"),
+      NoSuperClass(name) => write!(f, "can not use super in class '{}', which has no parent class", name),
+      AbstractSuperCall(name) => write!(f, "can not call abstract method '{}' through super", name),
       NotObject(ty) => write!(f, "{:?} is not a class type", ty),
-      BadPrintArg { loc, ty } => write!(f, "incompatible argument {}: {:?} given, int/bool/string expected", loc, ty),
+      BadPrintArg { loc, ty } => write!(f, "incompatible argument {}: {:?} given, int/bool/char/double/string expected", loc, ty),
+      FormatMismatch(loc) => write!(f, "argument {} doesn't match the format string", loc),
       ReturnMismatch { expect, actual } => write!(f, "incompatible return: {:?} given, {:?} expected", actual, expect),
+      ReturnInVoid(actual) => write!(f, "void function cannot return a value of type {:?}", actual),
+      LambdaReturnMismatch => write!(f, "incompatible return types in lambda expression"),
       NewArrayNotInt => write!(f, "new array length must be an integer"),
       IndexNotArray => write!(f, "[] can only be applied to arrays"),
       IndexNotInt => write!(f, "array subscript must be an integer"),
+      ForeachNotArray => write!(f, "foreach can only be applied to arrays"),
+      NotTuple(ty) => write!(f, "{:?} is not a tuple type", ty),
+      TupleIndexOutOfRange { idx, len } => write!(f, "tuple index {} is out of range for length {}", idx, len),
       NoReturn => write!(f, "missing return statement: control reaches end of non-void block"),
+      DivideByZero => write!(f, "division by zero"),
+      TooManyErrors(max) => write!(f, "too many errors, only the first {} are reported", max),
+      NegativeShift(x) => write!(f, "shift by negative amount {}", x),
+      UnusedVar(name) => write!(f, "unused variable '{}'", name),
+      ShadowedVar { prev, name } => write!(f, "declaration of '{}' here shadows the declaration at {:?}", name, prev),
+      IndexOutOfBounds { idx, len } => write!(f, "array index {} is out of bounds for length {}", idx, len),
+      StaticFieldViaInstance(name) => write!(f, "static field '{}' is accessed through an instance", name),
+      NullDereference => write!(f, "dereferencing an expression which is always null"),
+      UnreachableCode => write!(f, "unreachable statement"),
+      ObjectIdentityCompare => write!(f, "comparing objects with '==' or '!=' compares their identity"),
+      SelfAssign => write!(f, "assigning a variable to itself has no effect"),
     }
   }
 }
@@ -0,0 +1,32 @@
+// the inverse of decoding the escape characters in StringLit, so that a string can be printed as it is in the source code
+// the printed string is also accepted by tacvm and mips assembler
+pub fn escape(s: &str) -> String {
+  let mut ret = String::with_capacity(s.len());
+  for ch in s.chars() {
+    match ch {
+      '\n' => ret.push_str("\\n"),
+      '\r' => ret.push_str("\\r"),
+      '\t' => ret.push_str("\\t"),
+      '\0' => ret.push_str("\\0"),
+      '\\' => ret.push_str("\\\\"),
+      '"' => ret.push_str("\\\""),
+      _ => ret.push(ch),
+    }
+  }
+  ret
+}
+
+// the string content in json, the quotes are not included
+pub fn json_escape(s: &str) -> String {
+  let mut ret = String::with_capacity(s.len());
+  for ch in s.chars() {
+    match ch {
+      '"' => ret.push_str("\\\""),
+      '\\' => ret.push_str("\\\\"),
+      '\n' => ret.push_str("\\n"),
+      ch if (ch as u32) < 0x20 => ret.push_str(&format!("\\u{:04x}", ch as u32)),
+      _ => ret.push(ch),
+    }
+  }
+  ret
+}
@@ -4,13 +4,17 @@ pub mod ignore_result;
 pub mod indent_printer;
 pub mod r#ref;
 pub mod op;
+pub mod escape;
 
-pub use crate::{loc::*, errors::*, ignore_result::*, indent_printer::*, r#ref::*, op::*};
+pub use crate::{loc::*, errors::*, ignore_result::*, indent_printer::*, r#ref::*, op::*, escape::*};
 use hashbrown::hash_map::DefaultHashBuilder;
 
 pub const MAIN_CLASS: &str = "Main";
 pub const MAIN_METHOD: &str = "main";
 pub const LENGTH: &str = "length";
+pub const SUBSTRING: &str = "substring";
+pub const STRING_TO_INT: &str = "stringToInt";
+pub const INT_TO_STRING: &str = "intToString";
 const INDENT: u32 = 4;
 const INDENT_STR: &str = "    ";
 
@@ -1,24 +1,51 @@
-use std::fmt;
+use std::{fmt, cmp::Ordering};
 
-// Loc(line, column), counting from 1
-// so 0 is invalid for both, and Loc(0, 0) means NO_LOC
+// Loc(line, column, end column), counting from 1, the end column is exclusive
+// so 0 is invalid for all, and Loc(0, 0, 0) means NO_LOC
 // (of course we can use Option<Loc>, but I think NO_LOC is also convenient to use, and it saves space)
-#[derive(Copy, Clone, Eq, PartialEq, Default, Ord, PartialOrd)]
-pub struct Loc(pub u32, pub u32);
+// the end column is only used in showing the diagnostics, two Locs at the same position compare equal regardless of it
+// a Loc which doesn't cover any char (e.g.: the end of the file) has end column == column
+#[derive(Copy, Clone, Default)]
+pub struct Loc(pub u32, pub u32, pub u32);
 
-pub const NO_LOC: Loc = Loc(0, 0);
+pub const NO_LOC: Loc = Loc(0, 0, 0);
 
+// next_line and next_col move to the next char, and the result covers that char only
 impl Loc {
   pub fn next_line(&mut self) {
     self.0 += 1;
     self.1 = 1;
+    self.2 = 2;
   }
 
-  pub fn next_col(&mut self) { self.1 += 1; }
+  pub fn next_col(&mut self) {
+    self.1 += 1;
+    self.2 = self.1 + 1;
+  }
+
+  // the span from `self` to `end` (exclusive, e.g.: the `end` of an ast node), the diagnostics are shown in one line,
+  // so if `end` is in another line or not after `self`, the span is not extended
+  pub fn to(self, end: Loc) -> Loc {
+    if end.0 == self.0 && end.1 > self.2 { Loc(self.0, self.1, end.1) } else { self }
+  }
+}
+
+impl PartialEq for Loc {
+  fn eq(&self, other: &Loc) -> bool { (self.0, self.1) == (other.0, other.1) }
+}
+
+impl Eq for Loc {}
+
+impl PartialOrd for Loc {
+  fn partial_cmp(&self, other: &Loc) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl Ord for Loc {
+  fn cmp(&self, other: &Loc) -> Ordering { (self.0, self.1).cmp(&(other.0, other.1)) }
 }
 
 impl fmt::Debug for Loc {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     write!(f, "({},{})", self.0, self.1)
   }
-}
\ No newline at end of file
+}
@@ -2,42 +2,56 @@
 // but several other crates also use them, and don't use anything else in `syntax`
 // place them here can eliminate this dependency and(maybe?) reduce compile time
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
-pub enum BinOp { Add, Sub, Mul, Div, Mod, And, Or, Eq, Ne, Lt, Le, Gt, Ge }
+pub enum BinOp { Add, Sub, Mul, Div, Mod, And, Or, BitAnd, BitOr, BitXor, Shl, Shr, Eq, Ne, Lt, Le, Gt, Ge }
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
-pub enum UnOp { Neg, Not }
+pub enum UnOp { Neg, Not, BitNot }
 
 impl BinOp {
   // an operator style string, used in printing tac
   pub fn to_op_str(self) -> &'static str {
     use BinOp::*;
-    match self { Add => "+", Sub => "-", Mul => "*", Div => "/", Mod => "%", And => "&&", Or => "||", Eq => "==", Ne => "!=", Lt => "<", Le => "<=", Gt => ">", Ge => ">=" }
+    match self { Add => "+", Sub => "-", Mul => "*", Div => "/", Mod => "%", And => "&&", Or => "||", BitAnd => "&", BitOr => "|", BitXor => "^", Shl => "<<", Shr => ">>", Eq => "==", Ne => "!=", Lt => "<", Le => "<=", Gt => ">", Ge => ">=" }
   }
 
   // an abbreviate word for, used in printing ast
   pub fn to_word_str(self) -> &'static str {
     use BinOp::*;
-    match self { Add => "ADD", Sub => "SUB", Mul => "MUL", Div => "DIV", Mod => "MOD", And => "AND", Or => "OR", Eq => "EQ", Ne => "NE", Lt => "LT", Le => "LE", Gt => "GT", Ge => "GE" }
+    match self { Add => "ADD", Sub => "SUB", Mul => "MUL", Div => "DIV", Mod => "MOD", And => "AND", Or => "OR", BitAnd => "BITAND", BitOr => "BITOR", BitXor => "BITXOR", Shl => "SHL", Shr => "SHR", Eq => "EQ", Ne => "NE", Lt => "LT", Le => "LE", Gt => "GT", Ge => "GE" }
+  }
+
+  // the string of compound assignment operator, e.g.: Add => "+="
+  // only Add, Sub, Mul, Div, Mod can be used in compound assignment, the others return None
+  pub fn to_assign_str(self) -> Option<&'static str> {
+    use BinOp::*;
+    match self { Add => Some("+="), Sub => Some("-="), Mul => Some("*="), Div => Some("/="), Mod => Some("%="), _ => None }
   }
 
   // e.g.: x op1 y <=> y op2 x, this can be helpful because mips's I instructions use imm as rhs
   // self.invert() == Some(self) <=> self is commutative
   pub fn invert(self) -> Option<BinOp> {
     use BinOp::*;
-    match self { Add => Some(Add), Mul => Some(Mul), And => Some(And), Or => Some(Or), Eq => Some(Eq), Ne => Some(Ne), Lt => Some(Gt), Le => Some(Ge), Gt => Some(Lt), Ge => Some(Le), Sub | Div | Mod => None, }
+    match self { Add => Some(Add), Mul => Some(Mul), And => Some(And), Or => Some(Or), BitAnd => Some(BitAnd), BitOr => Some(BitOr), BitXor => Some(BitXor), Eq => Some(Eq), Ne => Some(Ne), Lt => Some(Gt), Le => Some(Ge), Gt => Some(Lt), Ge => Some(Le), Sub | Div | Mod | Shl | Shr => None, }
   }
 
   // return None if self = Div or Mod and r = 0
+  // overflow wraps around, the same as the arithmetic in the generated code (e.g.: `i32::MIN / -1 == i32::MIN`)
   pub fn try_eval(self, l: i32, r: i32) -> Option<i32> {
     use BinOp::*;
     match self {
       Add => Some(l.wrapping_add(r)),
       Sub => Some(l.wrapping_sub(r)),
       Mul => Some(l.wrapping_mul(r)),
-      Div => l.checked_div(r),
-      Mod => l.checked_rem(r),
+      Div => if r == 0 { None } else { Some(l.wrapping_div(r)) },
+      Mod => if r == 0 { None } else { Some(l.wrapping_rem(r)) },
       And => Some(((l != 0) && (r != 0)) as i32),
       Or => Some(((l != 0) || (r != 0)) as i32),
+      BitAnd => Some(l & r),
+      BitOr => Some(l | r),
+      BitXor => Some(l ^ r),
+      // like mips, only the low 5 bits of `r` are used
+      Shl => Some(l.wrapping_shl(r as u32)),
+      Shr => Some(l.wrapping_shr(r as u32)),
       Eq => Some((l == r) as i32),
       Ne => Some((l != r) as i32),
       Lt => Some((l < r) as i32),
@@ -53,14 +67,14 @@ impl BinOp {
 
 impl UnOp {
   pub fn to_op_str(self) -> &'static str {
-    match self { UnOp::Neg => "-", UnOp::Not => "!" }
+    match self { UnOp::Neg => "-", UnOp::Not => "!", UnOp::BitNot => "~" }
   }
 
   pub fn to_word_str(self) -> &'static str {
-    match self { UnOp::Neg => "NEG", UnOp::Not => "NOT" }
+    match self { UnOp::Neg => "NEG", UnOp::Not => "NOT", UnOp::BitNot => "BITNOT" }
   }
 
   pub fn eval(self, r: i32) -> i32 {
-    match self { UnOp::Neg => r.wrapping_neg(), UnOp::Not => (r == 0) as i32, }
+    match self { UnOp::Neg => r.wrapping_neg(), UnOp::Not => (r == 0) as i32, UnOp::BitNot => !r }
   }
 }
\ No newline at end of file
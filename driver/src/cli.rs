@@ -1,34 +1,109 @@
-use driver::{Pa, Alloc};
+use driver::{Pa, Alloc, CompileCfg, Stage};
+use tacopt::OptCfg;
+use common::{Errors, WarnLevel};
 use clap::{Arg, App};
-use std::{io, fs, process};
+use std::{io, fs, process, mem};
 
 fn main() -> io::Result<()> {
   let matches = App::new("decaf")
     .arg(Arg::with_name("input").required(true))
     .arg(Arg::with_name("output").long("output").short("o").takes_value(true))
     .arg(Arg::with_name("target").long("target").short("t").takes_value(true).default_value("pa5"))
+    .arg(Arg::with_name("snippet").long("snippet").help("print the source line of each error"))
+    .arg(Arg::with_name("json").long("json").help("output the errors and warnings as a json array, instead of the compiled program"))
+    .arg(Arg::with_name("dump-typed-ast").long("dump-typed-ast").takes_value(true).possible_values(&["json"])
+      .help("output the ast after type checking, instead of the compiled program"))
+    .arg(Arg::with_name("no-common-expr").long("no-common-expr").help("disable common subexpression elimination in tac optimization"))
+    .arg(Arg::with_name("no-copy-prop").long("no-copy-prop").help("disable copy propagation in tac optimization"))
+    .arg(Arg::with_name("no-const-prop").long("no-const-prop").help("disable constant propagation in tac optimization"))
+    .arg(Arg::with_name("no-bound-check-elim").long("no-bound-check-elim").help("disable array bound check elimination in counted loops"))
+    .arg(Arg::with_name("no-tail-call").long("no-tail-call").help("disable tail call optimization"))
+    .arg(Arg::with_name("stack-alloc").long("stack-alloc").help("allocate the objects which don't escape their function on the stack in mips and llvm output"))
+    .arg(Arg::with_name("inline").long("inline").takes_value(true).default_value("0").help("inline the leaf functions with at most this number of tacs, 0 means no inlining"))
+    .arg(Arg::with_name("no-peephole").long("no-peephole").help("disable peephole optimization in asm"))
+    .arg(Arg::with_name("warn").short("W").takes_value(true).multiple(true).number_of_values(1).possible_values(&["none", "default", "all", "error"])
+      .help("the warnings reported (none/default/all), or `-Werror` to treat the reported warnings as errors"))
+    .arg(Arg::with_name("max-errors").long("max-errors").takes_value(true).default_value("20").help("the max number of errors reported in one stage, 0 means no limit"))
     .get_matches();
-  let pa = match matches.value_of("target").unwrap() {
-    t if t.eq_ignore_ascii_case("pa1a") => Pa::Pa1a,
-    t if t.eq_ignore_ascii_case("pa1b") => Pa::Pa1b,
-    t if t.eq_ignore_ascii_case("pa2") => Pa::Pa2,
-    t if t.eq_ignore_ascii_case("pa3") => Pa::Pa3,
-    t if t.eq_ignore_ascii_case("pa4") => Pa::Pa4,
-    t if t.eq_ignore_ascii_case("pa5") => Pa::Pa5,
+  let cfg = match matches.value_of("target").unwrap() {
+    t if t.eq_ignore_ascii_case("pa1a") => Pa::Pa1a.to_cfg(),
+    t if t.eq_ignore_ascii_case("pa1b") => Pa::Pa1b.to_cfg(),
+    t if t.eq_ignore_ascii_case("pa2") => Pa::Pa2.to_cfg(),
+    t if t.eq_ignore_ascii_case("pa3") => Pa::Pa3.to_cfg(),
+    t if t.eq_ignore_ascii_case("pa4") => Pa::Pa4.to_cfg(),
+    t if t.eq_ignore_ascii_case("pa5") => Pa::Pa5.to_cfg(),
+    // llvm ir instead of mips asm
+    t if t.eq_ignore_ascii_case("llvm") => CompileCfg { stage: Stage::Llvm, ..Pa::Pa5.to_cfg() },
+    t if t.eq_ignore_ascii_case("wasm") => CompileCfg { stage: Stage::Wasm, ..Pa::Pa5.to_cfg() },
     t => {
-      eprintln!("invalid target pa: `{}`", t);
+      eprintln!("invalid target: `{}`", t);
       process::exit(1);
     }
   };
-  let input = matches.value_of("input").unwrap();
-  let result = match driver::compile(&fs::read_to_string(input)?, &Alloc::default(), pa.to_cfg()) {
-    Ok(p) => p,
-    Err(e) => format!("{:?}", e),
+  let max_error = match matches.value_of("max-errors").unwrap().parse::<usize>() {
+    Ok(0) => usize::MAX, // 0 means no limit
+    Ok(x) => x,
+    Err(_) => {
+      eprintln!("invalid max errors: `{}`", matches.value_of("max-errors").unwrap());
+      process::exit(1);
+    }
   };
-  if let Some(output) = matches.value_of("output") {
-    fs::write(output, result)
-  } else {
-    print!("{}", result);
-    Ok(())
+  let inline = match matches.value_of("inline").unwrap().parse::<u32>() {
+    Ok(x) => x,
+    Err(_) => {
+      eprintln!("invalid inline threshold: `{}`", matches.value_of("inline").unwrap());
+      process::exit(1);
+    }
+  };
+  let warn = matches.values_of("warn").map(|v| v.collect::<Vec<_>>()).unwrap_or_default();
+  // the last level given takes effect
+  let level = match warn.iter().rev().find(|&&w| w != "error") {
+    Some(&"none") => WarnLevel::None,
+    Some(&"all") => WarnLevel::All,
+    _ => WarnLevel::Default,
+  };
+  let werror = warn.contains(&"error");
+  let (input, snippet) = (matches.value_of("input").unwrap(), matches.is_present("snippet"));
+  let (code, alloc, mut warnings) = (fs::read_to_string(input)?, Alloc::default(), Errors::default());
+  // errors and warnings are printed in the same way, the errors are the output, and the warnings go to stderr
+  let show = |e: &Errors<_>| if snippet { e.render(&code) } else { format!("{:?}", e) };
+  if matches.is_present("dump-typed-ast") {
+    let (pr, diag) = driver::check(&code, &alloc, CompileCfg { max_error, ..cfg });
+    let (diag, warnings) = diag.filter_warnings(level, werror).split_warnings();
+    eprint!("{}", show(&warnings));
+    let result = match pr {
+      Some(pr) if !diag.has_error() => Ok(print::json::program(pr) + "\n"),
+      _ => Err(show(&diag)),
+    };
+    let (failed, result) = (result.is_err(), result.unwrap_or_else(|e| e));
+    if let Some(output) = matches.value_of("output") { fs::write(output, result)? } else { print!("{}", result) }
+    if failed { process::exit(1); }
+    return Ok(());
   }
+  let on = |opt| !matches.is_present(opt);
+  let opt = OptCfg { common_expr: on("no-common-expr"), copy_prop: on("no-copy-prop"), const_prop: on("no-const-prop"), tail_call: on("no-tail-call"), inline, stack_alloc: matches.is_present("stack-alloc") };
+  let result = driver::compile_with_warnings(&code, &alloc, CompileCfg { max_error, opt, peephole: on("no-peephole"), bound_check: on("no-bound-check-elim"), ..cfg }, &mut warnings);
+  let mut warnings = warnings.filter_warnings(level, werror);
+  // with `-Werror`, the promoted warnings fail the compilation, and they are reported together with other errors
+  let result = match result {
+    Err(mut e) if werror => (e.append(&mut warnings), Err(e.sorted())).1,
+    Ok(_) if warnings.has_error() => Err(mem::take(&mut warnings)),
+    r => r,
+  };
+  // the exit status is 1 iff there is an error-severity diagnostic, warnings only fail the compilation with `-Werror`
+  let failed = result.as_ref().err().map_or(false, |e| e.has_error());
+  let result = if matches.is_present("json") {
+    // both errors and warnings are in the json, distinguished by their severity
+    if let Err(mut e) = result { warnings.append(&mut e); }
+    print::json::errors(&mem::take(&mut warnings).sorted()) + "\n"
+  } else {
+    match result {
+      Ok(p) => p,
+      Err(e) => show(&e),
+    }
+  };
+  eprint!("{}", show(&warnings));
+  if let Some(output) = matches.value_of("output") { fs::write(output, result)? } else { print!("{}", result) }
+  if failed { process::exit(1); }
+  Ok(())
 }
\ No newline at end of file
@@ -3,9 +3,9 @@
 pub mod test_util;
 
 use common::{IndentPrinter, Errors};
-use syntax::{ASTAlloc, Ty, parser, parser_ll};
+use syntax::{ASTAlloc, Ty, Program, parser, parser_ll};
 use typeck::TypeCkAlloc;
-use tacopt::bb::FuncBB;
+use tacopt::{bb::FuncBB, OptCfg};
 use codegen::mips_gen::FuncGen;
 use tac::TacNode;
 use typed_arena::Arena;
@@ -13,7 +13,7 @@ use typed_arena::Arena;
 pub use test_util::*;
 
 #[derive(Eq, PartialEq, Copy, Clone)]
-pub enum Stage { Parse, TypeCk, Tac, TacOpt, Asm }
+pub enum Stage { Parse, TypeCk, Tac, TacOpt, Asm, Llvm, Wasm }
 
 #[derive(Copy, Clone)]
 pub enum Parser { LL, LR }
@@ -22,6 +22,13 @@ pub enum Parser { LL, LR }
 pub struct CompileCfg {
   pub stage: Stage,
   pub parser: Parser,
+  // the max number of errors reported in one stage, usize::MAX for no limit
+  pub max_error: usize,
+  pub opt: OptCfg,
+  // peephole optimization on the asm, see codegen::peephole
+  pub peephole: bool,
+  // bound check elimination in counted loops, done in tacgen, see tacgen::bound
+  pub bound_check: bool,
 }
 
 #[derive(Default)]
@@ -34,42 +41,85 @@ pub struct Alloc<'a> {
 // it is recommended to use this function to debug your compiler
 // `code` can be provided by hard-coded string literal, `cfg` can be provided by `Pa::Pax.to_cfg()`
 pub fn compile<'a>(code: &'a str, alloc: &'a Alloc<'a>, cfg: CompileCfg) -> Result<String, Errors<'a, Ty<'a>>> {
-  let mut p = IndentPrinter::default();
-  let pr = match cfg.parser {
-    Parser::LL => parser_ll::work(code, &alloc.ast)?,
-    Parser::LR => parser::work(code, &alloc.ast)?,
+  compile_with_warnings(code, alloc, cfg, &mut Errors::default())
+}
+
+// parse and type check `code` without printing anything, it is intended for embedding decaf in other tools
+// the program is returned if there is no error (the types are stored in it, e.g., `Expr::ty`)
+// the diagnostics contain both errors and warnings, sorted by location
+// only `parser` and `max_error` in `cfg` are used, the stage is always Stage::TypeCk
+pub fn check<'a>(code: &'a str, alloc: &'a Alloc<'a>, cfg: CompileCfg) -> (Option<&'a Program<'a>>, Errors<'a, Ty<'a>>) {
+  let pr = match parse(code, alloc, cfg) { Ok(pr) => pr, Err(e) => return (None, e.sorted()) };
+  let mut diag = Errors::default();
+  let pr = match typeck::work(pr, &alloc.typeck, cfg.max_error, &mut diag) {
+    Ok(()) => Some(pr),
+    Err(mut e) => (diag.append(&mut e), None).1,
   };
+  (pr, diag.sorted())
+}
+
+fn parse<'a>(code: &'a str, alloc: &'a Alloc<'a>, cfg: CompileCfg) -> Result<&'a Program<'a>, Errors<'a, Ty<'a>>> {
+  match cfg.parser {
+    Parser::LL => parser_ll::work(code, &alloc.ast, cfg.max_error),
+    Parser::LR => parser::work(code, &alloc.ast, cfg.max_error),
+  }
+}
+
+// the same as `compile`, except that warnings are appended to `warnings` instead of being discarded
+pub fn compile_with_warnings<'a>(code: &'a str, alloc: &'a Alloc<'a>, cfg: CompileCfg, warnings: &mut Errors<'a, Ty<'a>>) -> Result<String, Errors<'a, Ty<'a>>> {
+  let mut p = IndentPrinter::default();
+  let pr = parse(code, alloc, cfg)?;
   if cfg.stage == Stage::Parse {
     print::ast::program(&pr, &mut p);
     return Ok(p.finish());
   }
-  typeck::work(&pr, &alloc.typeck)?;
+  typeck::work(&pr, &alloc.typeck, cfg.max_error, warnings)?;
   if cfg.stage == Stage::TypeCk {
     print::scope::program(&pr, &mut p);
     return Ok(p.finish());
   }
-  let mut tp = tacgen::work(&pr, &alloc.tac);
+  // the unoptimized tac is printed in Stage::Tac
+  let mut tp = tacgen::work(&pr, &alloc.tac, cfg.stage != Stage::Tac && cfg.bound_check);
   if cfg.stage == Stage::Tac {
     print::tac::program(&tp, &mut p);
     return Ok(p.finish());
   }
-  if cfg.stage == Stage::Asm {
-    print::mips::data(&tp, &mut p);
+  match cfg.stage {
+    Stage::Asm => print::mips::data(&tp, &mut p),
+    Stage::Llvm => print::llvm::data(&tp, &mut p),
+    Stage::Wasm => print::wasm::data(&tp, &mut p),
+    _ => {}
   }
+  if cfg.opt.tail_call { tacopt::tail_call::work(&mut tp); }
+  tacopt::inline::work(&mut tp, cfg.opt.inline);
   let mut new_funcs = Vec::new();
+  // only the mips and llvm backends support `StackAlloc`, tacvm and wasm don't
+  let stack_alloc = cfg.opt.stack_alloc && (cfg.stage == Stage::Asm || cfg.stage == Stage::Llvm);
+  let info = if stack_alloc { Some(tacopt::escape::info(&tp)) } else { None };
   for f in &tp.func {
     let mut fu = FuncBB::new(f);
-    fu.optimizen(10);
+    fu.optimizen(10, cfg.opt);
+    if let Some(info) = &info { tacopt::escape::work(&mut fu, info); }
     if cfg.stage == Stage::Asm {
       let asm = FuncGen::work(&fu, &tp, codegen::AllocMethod::Graph);
+      let asm = if cfg.peephole { codegen::peephole::work(asm) } else { asm };
       print::mips::func(&asm, &f.name, &mut p);
+    } else if cfg.stage == Stage::Llvm {
+      print::llvm::func(&fu, &tp, &mut p);
+    } else if cfg.stage == Stage::Wasm {
+      print::wasm::func(&fu, &tp, &mut p);
     } else { // cfg.stage == Stage::TacOpt
       new_funcs.push(fu.to_tac_func());
     }
   }
-  if cfg.stage == Stage::TacOpt {
-    tp.func = new_funcs;
-    print::tac::program(&tp, &mut p);
-    Ok(p.finish())
-  } else { Ok(p.finish() + include_str!("../../codegen/lib.s")) }
+  match cfg.stage {
+    Stage::TacOpt => {
+      tp.func = new_funcs;
+      print::tac::program(&tp, &mut p);
+      Ok(p.finish())
+    }
+    Stage::Llvm => Ok(p.finish()),
+    Stage::Wasm => (print::wasm::finish(&mut p), Ok(p.finish())).1,
+    _ => Ok(p.finish() + include_str!("../../codegen/lib.s")),
+  }
 }
\ No newline at end of file
@@ -1,6 +1,7 @@
 use std::{io::{self, BufReader}, fs::{self, File}, fmt, panic, path::{Path, PathBuf}, any::Any, sync::{Arc, Mutex}, process::{Command, Stdio}};
 use colored::*;
 use crate::{CompileCfg, Parser, Stage, Alloc};
+use tacopt::OptCfg;
 
 #[derive(Copy, Clone)]
 pub enum Pa { Pa1a, Pa1b, Pa2, Pa3, Pa4, Pa5 }
@@ -16,6 +17,10 @@ impl Pa {
         Pa::Pa5 => Stage::Asm,
       },
       parser: match self { Pa::Pa1b => Parser::LL, _ => Parser::LR },
+      max_error: usize::MAX,
+      opt: OptCfg::default(),
+      peephole: true,
+      bound_check: true,
     }
   }
 }
@@ -72,7 +77,7 @@ pub fn run(i: impl AsRef<Path>, o: impl AsRef<Path>, pa: Pa) -> io::Result<Strin
   let cfg = pa.to_cfg();
   let out = match crate::compile(&fs::read_to_string(i)?, &Alloc::default(), cfg) {
     Ok(p) => match cfg.stage {
-      Stage::Parse | Stage::TypeCk => (fs::write(o, &p), p).1,
+      Stage::Parse | Stage::TypeCk | Stage::Llvm | Stage::Wasm => (fs::write(o, &p), p).1,
       Stage::Tac | Stage::TacOpt => {
         fs::write(o.with_extension("tac"), &p)?;
         tacvm::work(&p, 100_000, 1000, true, true,
@@ -0,0 +1,751 @@
+use driver::*;
+
+mod wat;
+
+// all suites run in one test, because `test_one_caught` replaces the global panic hook
+// S3 runs both unoptimized and optimized, so that the optimizations are checked against the same results
+#[test]
+fn testcase() {
+  let mut fail = 0;
+  for &(dir, pa) in &[("S1", Pa::Pa1a), ("S1-LL", Pa::Pa1b), ("S2", Pa::Pa2), ("S3", Pa::Pa3), ("S3", Pa::Pa4)] {
+    for result in test_all(format!("{}/../testcase/{}", env!("CARGO_MANIFEST_DIR"), dir), pa).unwrap() {
+      if let ResultKind::Pass = result.kind {} else {
+        println!("{:?}", result);
+        fail += 1;
+      }
+    }
+  }
+  assert_eq!(fail, 0);
+}
+
+// `Pa::Pa2` doesn't print warnings, so the files in "S2-W" are compared with all diagnostics (errors and warnings) reported by `check`
+#[test]
+fn warning() {
+  use std::fs;
+  let path = format!("{}/../testcase/S2-W", env!("CARGO_MANIFEST_DIR"));
+  let out = format!("{}/out", path);
+  fs::create_dir_all(&out).unwrap();
+  let mut files = fs::read_dir(&path).unwrap().filter_map(|f| {
+    let name = f.ok()?.file_name().into_string().ok()?;
+    if name.ends_with(".decaf") { Some(name) } else { None }
+  }).collect::<Vec<_>>();
+  files.sort_unstable();
+  let mut fail = 0;
+  for f in &files {
+    let result = format!("{}.result", &f[..f.len() - ".decaf".len()]);
+    let code = fs::read_to_string(format!("{}/{}", path, f)).unwrap();
+    let alloc = Alloc::default();
+    let o = format!("{:?}", check(&code, &alloc, Pa::Pa2.to_cfg()).1);
+    fs::write(format!("{}/{}", out, result), &o).unwrap();
+    let ans = fs::read_to_string(format!("{}/result/{}", path, result)).unwrap();
+    let kind = ResultKind::new(&o, &ans, 0);
+    if let ResultKind::Pass = kind {} else {
+      println!("{:?}", TestResult::new(format!("{}/{}", path, f), format!("{}/{}", out, result), format!("{}/result/{}", path, result), kind));
+      fail += 1;
+    }
+  }
+  assert_eq!(fail, 0);
+}
+
+// only the first `max_error` errors are reported, followed by a TooManyErrors note
+#[test]
+fn max_error() {
+  let body = (0..100).map(|i| format!("int a{} = true;", i)).collect::<Vec<_>>().join(" ");
+  let code = format!("class Main {{ static void main() {{ {} }} }}", body);
+  let alloc = Alloc::default();
+  let e = compile(&code, &alloc, CompileCfg { max_error: 20, ..Pa::Pa2.to_cfg() }).err().unwrap();
+  assert_eq!(e.0.len(), 21);
+  assert!(e.0[..20].iter().all(|e| matches!(e.1, common::ErrorKind::IncompatibleBinary { .. })));
+  assert_eq!(format!("{:?}", e.0[20].1), "too many errors, only the first 20 are reported");
+}
+
+// the caret and the end column of an error cover the whole token at its loc
+#[test]
+fn render() {
+  let code = "class Main {\n  static void main() {\n\tint x = undefined;\n  }\n}\n";
+  let alloc = Alloc::default();
+  let e = compile(code, &alloc, Pa::Pa2.to_cfg()).err().unwrap();
+  assert_eq!(e.render(code), "*** Error at (3,10): undeclared variable 'undefined'\n3 | \tint x = undefined;\n  | \t        ^~~~~~~~~\n");
+  assert!(print::json::errors(&e).contains(r#""start":{"line":3,"col":10},"end":{"line":3,"col":19}"#));
+}
+
+// both parsers record where the classes, methods, variables and statements end, and the one-line ones are underlined as a whole
+#[test]
+fn node_end() {
+  use {syntax::{ast::*, ASTAlloc}, common::Loc};
+  let code = "class Main {\n  static int f(int p) {\n    int x = p + 1;\n    if (x > 0) return x; else x += 2;\n    while (x > 0) { x = x - 1; }\n    return 0;\n    Print(x, p);\n  }\n  static void main() {}\n}\n";
+  for &ll in &[false, true] {
+    let alloc = ASTAlloc::default();
+    let pr = if ll { syntax::parser_ll::work(code, &alloc, usize::MAX) } else { syntax::parser::work(code, &alloc, usize::MAX) }.ok().unwrap();
+    let c = pr.class[0];
+    let f = match c.field[0] { FieldDef::FuncDef(f) => f, _ => unreachable!() };
+    let stmt = &f.body.as_ref().unwrap().stmt;
+    let ends = stmt.iter().map(|s| s.end).collect::<Vec<_>>();
+    assert_eq!((c.end, f.end, f.param[0].end), (Loc(10, 2, 2), Loc(8, 4, 4), Loc(2, 21, 21)));
+    assert_eq!(ends, [Loc(3, 19, 19), Loc(4, 38, 38), Loc(5, 33, 33), Loc(6, 14, 14), Loc(7, 17, 17)]);
+    if let StmtKind::LocalVarDef(v) = &stmt[0].kind { assert_eq!(v.end, Loc(3, 18, 18)); }
+    // the binary expression covers both operands
+    if let StmtKind::LocalVarDef(v) = &stmt[0].kind { assert_eq!(v.init().unwrap().end, Loc(3, 18, 18)); }
+  }
+  let alloc = Alloc::default();
+  let e = check(code, &alloc, Pa::Pa2.to_cfg()).1;
+  assert_eq!(e.render(code), "*** Warning at (7,5): unreachable statement\n7 |     Print(x, p);\n  |     ^~~~~~~~~~~~\n");
+}
+
+// every diagnostic in json has the name of its kind, and the quotes in its message are escaped
+#[test]
+fn json_errors() {
+  let code = "class Main {\n  static void main() { Print(\"abc";
+  let alloc = Alloc::default();
+  let e = compile(code, &alloc, Pa::Pa1a.to_cfg()).err().unwrap();
+  let json = print::json::errors(&e);
+  assert!(json.starts_with(r#"[{"kind":"UnclosedStr","message":"unterminated string constant \"abc","severity":"error","start":{"line":2,"col":30},"end":{"line":2,"col":34}}"#), "{}", json);
+  assert!(json.contains(r#""kind":"SyntaxError""#), "{}", json);
+}
+
+// `check` uses the parser and the max number of errors in `cfg`, both parsers report every syntax error
+#[test]
+fn check_cfg() {
+  let code = "class Main {\n  static void main() {\n    int x = ;\n    int y = ;\n  }\n}\n";
+  for &parser in &[Parser::LL, Parser::LR] {
+    let alloc = Alloc::default();
+    let (pr, e) = check(code, &alloc, CompileCfg { parser, ..Pa::Pa2.to_cfg() });
+    assert!(pr.is_none());
+    assert_eq!(format!("{:?}", e), "*** Error at (3,13): syntax error\n*** Error at (4,13): syntax error\n");
+    // the first error, and the TooManyErrors note
+    let alloc = Alloc::default();
+    assert_eq!(check(code, &alloc, CompileCfg { parser, max_error: 1, ..Pa::Pa2.to_cfg() }).1 .0.len(), 2);
+  }
+  // the ll parser doesn't support the ternary operator
+  let code = "class Main {\n  static void main() {\n    Print(true ? 1 : 2);\n  }\n}\n";
+  let alloc = Alloc::default();
+  assert!(check(code, &alloc, Pa::Pa2.to_cfg()).1 .0.is_empty());
+  let alloc = Alloc::default();
+  assert!(check(code, &alloc, CompileCfg { parser: Parser::LL, ..Pa::Pa2.to_cfg() }).0.is_none());
+}
+
+// multiple defaults are reported in typeck, so both parsers report them in the same way
+#[test]
+fn duplicate_default() {
+  let code = std::fs::read_to_string(format!("{}/../testcase/S2/switch-default.decaf", env!("CARGO_MANIFEST_DIR"))).unwrap();
+  let errors = |parser| {
+    let alloc = Alloc::default();
+    format!("{:?}", check(&code, &alloc, CompileCfg { parser, ..Pa::Pa2.to_cfg() }).1.sorted())
+  };
+  assert!(errors(Parser::LL).contains("*** Error at (13,7): multiple default labels in one switch"));
+  assert_eq!(errors(Parser::LL), errors(Parser::LR));
+}
+
+// json has no infinity, so the overflowed double literal is null in the typed ast
+#[test]
+fn dump_typed_ast() {
+  let code = "class Main {\n  static void main() {\n    Print(1.5, 1.0e999);\n  }\n}\n";
+  let alloc = Alloc::default();
+  let json = print::json::program(check(code, &alloc, Pa::Pa2.to_cfg()).0.unwrap());
+  assert!(json.contains(r#"[{"node":"DoubleLit","loc":[3,11],"ty":"double","value":1.5},{"node":"DoubleLit","loc":[3,16],"ty":"double","value":null}]"#), "{}", json);
+}
+
+// printing the ast as decaf source and parsing it again gives the same ast (except the locations), so printing it again gives the same source
+#[test]
+fn decaf_round_trip() {
+  use std::fs;
+  // (the decaf source, the ast dump without " @ (line,col)")
+  let print = |code: &str| {
+    let alloc = syntax::ASTAlloc::default();
+    let (mut p, mut a) = (common::IndentPrinter::default(), common::IndentPrinter::default());
+    syntax::parser::work(code, &alloc, usize::MAX).ok().map(|pr| {
+      print::decaf::program(pr, &mut p);
+      print::ast::program(pr, &mut a);
+      let (ast, mut stripped) = (a.finish(), String::new());
+      let mut rest = &ast[..];
+      while let Some(i) = rest.find(" @ (") {
+        stripped += &rest[..i];
+        rest = &rest[i + rest[i..].find(')').unwrap() + 1..];
+      }
+      (p.finish(), stripped + rest)
+    })
+  };
+  let mut cnt = 0;
+  for dir in &["S1", "S2", "S2-W", "S3"] {
+    let path = format!("{}/../testcase/{}", env!("CARGO_MANIFEST_DIR"), dir);
+    for f in fs::read_dir(&path).unwrap() {
+      let f = f.unwrap().path();
+      if f.extension().is_none_or(|e| e != "decaf") { continue; }
+      if let Some((s, ast)) = print(&fs::read_to_string(&f).unwrap()) {
+        let (s1, ast1) = print(&s).unwrap();
+        assert_eq!(ast1, ast, "{:?}", f);
+        assert_eq!(s1, s, "{:?}", f);
+        cnt += 1;
+      }
+    }
+  }
+  assert!(cnt > 0);
+  let s = print("class A {\n  static int a;\n  final int b;\n  A() { b = 1; }\n}\n").unwrap().0;
+  assert_eq!(s, "class A {\n    static int a;\n    final int b;\n    A() {\n        b = 1;\n    }\n}\n");
+}
+
+const QUERY_CODE: &str = "class Main {
+  int f(int x) { return x; }
+  static void main() {
+    int abc = 1;
+    Print(abc + 2);
+    new Main().f(abc);
+  }
+}
+";
+
+// the uses of a local variable and a method go to their declarations, from any position in the name
+#[test]
+fn definition_of() {
+  use common::Loc;
+  let alloc = Alloc::default();
+  let q = syntax::query::Query::new(check(QUERY_CODE, &alloc, Pa::Pa2.to_cfg()).0.unwrap());
+  assert_eq!(q.definition_of(Loc(5, 11, 11)), Some(Loc(4, 9, 12)));
+  assert_eq!(q.definition_of(Loc(5, 13, 13)), Some(Loc(4, 9, 12)));
+  assert_eq!(q.definition_of(Loc(5, 14, 14)), None);
+  assert_eq!(q.definition_of(Loc(6, 16, 16)), Some(Loc(2, 7, 8)));
+  assert_eq!(q.definition_of(Loc(2, 25, 25)), Some(Loc(2, 13, 14)));
+}
+
+// the type of the innermost expression at the position
+#[test]
+fn type_at() {
+  use common::Loc;
+  let alloc = Alloc::default();
+  let q = syntax::query::Query::new(check(QUERY_CODE, &alloc, Pa::Pa2.to_cfg()).0.unwrap());
+  assert_eq!(q.type_at(Loc(4, 15, 15)).as_deref(), Some("int"));
+  assert_eq!(q.type_at(Loc(5, 17, 17)).as_deref(), Some("int"));
+  // `new` is never null
+  assert_eq!(q.type_at(Loc(6, 5, 5)).as_deref(), Some("class Main!"));
+  // the name of the called method is a part of the call
+  assert_eq!(q.type_at(Loc(6, 16, 16)).as_deref(), Some("int"));
+  assert_eq!(q.type_at(Loc(4, 5, 5)), None);
+}
+
+// the optimized tac of `main` whose body is `body`, `f` modifies the default OptCfg
+fn main_tac(body: &str, f: impl FnOnce(&mut tacopt::OptCfg)) -> String {
+  main_tac_of(&format!("class Main {{\n  static void main() {{\n    {}\n  }}\n}}\n", body), f)
+}
+
+fn main_tac_of(code: &str, f: impl FnOnce(&mut tacopt::OptCfg)) -> String {
+  let mut cfg = Pa::Pa4.to_cfg();
+  f(&mut cfg.opt);
+  let alloc = Alloc::default();
+  let tac = compile(code, &alloc, cfg).unwrap();
+  let start = tac.find("FUNC<main>").unwrap();
+  tac[start..start + tac[start..].find("\n}\n").unwrap() + 2].to_owned()
+}
+
+#[test]
+fn const_prop() {
+  let body = "int a = 2; int b = a + 3; Print(b);";
+  assert_eq!(main_tac(body, |_| {}), "FUNC<main> {\n    parm 5\n    call _PrintInt\n    return\n}");
+  assert!(main_tac(body, |o| o.const_prop = false).contains("(%0 + 3)"));
+}
+
+// the unused `a + b` is removed, but the calls are kept
+#[test]
+fn dead_code() {
+  let tac = main_tac("int a = ReadInteger(); int b = ReadInteger(); int t = a + b; Print(a);", |_| {});
+  assert_eq!(tac, "FUNC<main> {\n    %1 = call _ReadInt\n    call _ReadInt\n    parm %1\n    call _PrintInt\n    return\n}");
+  // an unused division is only removed if it can't trap
+  assert!(!main_tac("int a = ReadInteger(); int t = a / 2; int u = a % -3;", |_| {}).contains('/'));
+  let tac = main_tac("int a = ReadInteger(); int z = 0; int t = 1 / a; int u = a % z;", |_| {});
+  assert!(tac.contains("(1 / %1)") && tac.contains("(%1 % 0)"), "{}", tac);
+}
+
+// no code is generated for the branch not selected by a constant condition, or for the body of `while (false)`, even without tac optimization
+#[test]
+fn const_branch() {
+  let code = r#"class Main { static void main() { if (false) Print("then"); else Print("else"); if (1 < 2) Print("taken"); while (false) Print("body"); } }"#;
+  let alloc = Alloc::default();
+  let tac = compile(code, &alloc, Pa::Pa3.to_cfg()).unwrap();
+  assert!(tac.contains("else") && tac.contains("taken"), "{}", tac);
+  assert!(!tac.contains("then") && !tac.contains("body") && !tac.contains("branch") && !tac.contains("if"), "{}", tac);
+}
+
+// `arr[i] * 2` is computed once, even if the two occurrences are in different basic blocks
+#[test]
+fn common_expr() {
+  let body = "int[] arr = new int[3]; int i = ReadInteger(); Print(arr[i] * 2, arr[i] * 2);";
+  assert_eq!(main_tac(body, |_| {}).matches("* 2)").count(), 1);
+  assert_eq!(main_tac(body, |o| o.common_expr = false).matches("* 2)").count(), 2);
+  // `i * 2` is not available after `i` is reassigned
+  assert_eq!(main_tac("int i = ReadInteger(); Print(i * 2); i = ReadInteger(); Print(i * 2);", |_| {}).matches("* 2)").count(), 2);
+}
+
+// the chained copies collapse, and a copy is not propagated after its source is reassigned
+#[test]
+fn copy_prop() {
+  let body = "int a = ReadInteger(); int b = a; int c = b; Print(c);";
+  assert_eq!(main_tac(body, |_| {}), "FUNC<main> {\n    %1 = call _ReadInt\n    parm %1\n    call _PrintInt\n    return\n}");
+  assert!(main_tac(body, |o| o.copy_prop = false).contains("%3 = %2"));
+  let tac = main_tac("int a = ReadInteger(); int b = a; a = ReadInteger(); Print(b, a);", |_| {});
+  assert_eq!(tac, "FUNC<main> {\n    %1 = call _ReadInt\n    %3 = call _ReadInt\n    parm %1\n    call _PrintInt\n    parm %3\n    call _PrintInt\n    return\n}");
+}
+
+// the mips asm of `main` whose body is `body`, without tac optimization
+// the registers are allocated by AllocMethod::Brute, which doesn't change the instructions except adding loads and stores
+fn main_asm(body: &str) -> String {
+  let code = format!("class Main {{\n  static void main() {{\n    {}\n  }}\n}}\n", body);
+  let (ast, typeck, tac) = (syntax::ASTAlloc::default(), typeck::TypeCkAlloc::default(), typed_arena::Arena::new());
+  let pr = syntax::parser::work(&code, &ast, usize::MAX).ok().unwrap();
+  typeck::work(pr, &typeck, usize::MAX, &mut common::Errors::default()).ok().unwrap();
+  let tp = tacgen::work(pr, &tac, false);
+  let f = tp.func.iter().find(|f| f.name == "main").unwrap();
+  let asm = codegen::mips_gen::FuncGen::work(&tacopt::bb::FuncBB::new(f), &tp, codegen::AllocMethod::Brute);
+  let mut p = common::IndentPrinter::default();
+  print::mips::func(&asm, &f.name, &mut p);
+  p.finish()
+}
+
+// only a constant power of two is replaced, and a negative `x` is biased so that the division rounds towards zero
+#[test]
+fn strength_reduce() {
+  // the arithmetic instructions computing `e`
+  let ops = |e: &str| main_asm(&format!("int x = ReadInteger(); Print({});", e)).lines().filter_map(|l| {
+    let op = l.trim().split(' ').next().unwrap();
+    if ["lw", "sw", "move", "li", "syscall", "jr", "", ".text", ".globl"].contains(&op) || l.contains("$sp") || l.ends_with(':') { None } else { Some(l.trim().to_owned()) }
+  }).collect::<Vec<_>>();
+  assert_eq!(ops("x * 8"), ["sll $t2, $t0, 3"]);
+  assert_eq!(ops("x / 4"), ["sra $t2, $t0, 31", "and $t2, $t0, 3", "addu $t2, $t0, $t1", "sra $t2, $t0, 2"]);
+  assert_eq!(ops("x % 4"), ["sra $t2, $t0, 31", "and $t2, $t0, 3", "addu $t2, $t0, $t1", "and $t2, $t0, -4", "subu $t2, $t0, $t1"]);
+  assert_eq!(ops("x * 6"), ["mul $t2, $t0, 6"]);
+  assert_eq!(ops("x / 1").len(), 1);
+  assert_eq!(ops("x / -4").len(), 1);
+}
+
+// a load from the slot just stored to becomes a move, the unreachable jump and the jump to the next label are removed, and the self move is removed
+#[test]
+fn peephole() {
+  use codegen::{Reg, mips::{AsmTemplate::*, Imm, regs::*}};
+  let r = |x: Regs| Reg::PreColored(x as u32);
+  let asm = vec![Sw(r(T0), r(SP), Imm::Int(4)), Lw(r(T1), r(SP), Imm::Int(4)), Lw(r(T2), r(SP), Imm::Int(8)), Mv(r(T2), r(T2)),
+    J("_L1".to_owned()), J("_L2".to_owned()), Label("_L1:".to_owned()), B("_L2".to_owned(), r(T0), true), Label("_L3:".to_owned()), Ret];
+  let asm = codegen::peephole::work(asm).iter().map(|a| format!("{:?}", a)).collect::<Vec<_>>();
+  assert_eq!(asm, ["sw $t0, 4($sp)", "move $t1, $t0", "lw $t2, 8($sp)", "_L1:", "beqz $t0, _L2", "_L3:", "jr $ra"]);
+}
+
+// the static getter and the constructor are inlined, the recursive function and the virtual call are not
+#[test]
+fn inline() {
+  let code = "class Node {
+  int x;
+  Node(int x) { this.x = x; }
+  int get() { return x; }
+  static int getX(class Node n) { return n.x; }
+  static int fact(int n) { if (n == 0) return 1; return n * fact(n - 1); }
+}
+
+class Main {
+  static void main() {
+    class Node n = new Node(ReadInteger());
+    Print(Node.getX(n), n.get(), Node.fact(5));
+  }
+}
+";
+  let tac = main_tac_of(code, |o| o.inline = 10);
+  assert!(!tac.contains("call _Node._new") && !tac.contains("call _Node.getX"), "{}", tac);
+  assert!(tac.contains("call _Node.fact") && tac.contains("= call %"), "{}", tac);
+  let tac = main_tac_of(code, |_| {});
+  assert!(tac.contains("call _Node._new") && tac.contains("call _Node.getX"), "{}", tac);
+}
+
+// run the tac program with the stack limit 1000 and the input `inp`, return the output and the info (e.g.: runtime errors)
+fn run_tac(tac: &str, inp: &str) -> (String, String) {
+  use std::{io::{self, Write}, rc::Rc, cell::RefCell};
+  #[derive(Clone, Default)]
+  struct Buf(Rc<RefCell<Vec<u8>>>);
+  impl Write for Buf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.borrow_mut().write(buf) }
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+  }
+  let (out, info) = (Buf::default(), Buf::default());
+  tacvm::work(tac, 10_000_000, 1000, false, false, Box::new(io::Cursor::new(inp.to_owned())), Box::new(out.clone()), Box::new(info.clone())).unwrap();
+  let s = |b: Buf| String::from_utf8(b.0.borrow().clone()).unwrap();
+  (s(out), s(info))
+}
+
+// the self-recursive tail call becomes a jump, so the recursion depth is not limited by the stack
+#[test]
+fn tail_call() {
+  let code = "class Main {
+  static int fact(int n, int acc) {
+    if (n == 0) return acc;
+    return fact(n - 1, acc * n % 10007);
+  }
+  static void main() { Print(fact(10000, 1)); }
+}
+";
+  let alloc = Alloc::default();
+  let (out, info) = run_tac(&compile(code, &alloc, Pa::Pa4.to_cfg()).unwrap(), "");
+  assert_eq!((out.as_str(), info.as_str()), ("6991", ""));
+  let alloc = Alloc::default();
+  let (_, info) = run_tac(&compile(code, &alloc, CompileCfg { opt: tacopt::OptCfg { tail_call: false, ..Default::default() }, ..Pa::Pa4.to_cfg() }).unwrap(), "");
+  assert_eq!(info, "runtime error: exceeding the stack limit 1000\n");
+}
+
+// every intrinsic is declared in the llvm ir, even if it is not used
+#[test]
+fn llvm_intrinsic() {
+  let alloc = Alloc::default();
+  let ll = compile("class Main { static void main() {} }", &alloc, CompileCfg { stage: Stage::Llvm, ..Pa::Pa5.to_cfg() }).unwrap();
+  for i in tac::Intrinsic::ALL {
+    assert!(ll.contains(&format!(" @{:?}(", i)), "{:?}", i);
+  }
+  assert!(ll.contains("declare void @_Halt() noreturn") && ll.contains("declare i32 @_Substring(i32, i32, i32)"), "{}", ll);
+}
+
+// the llvm ir of a whole function, the divisor -1 is replaced by 1 so that `i32::MIN / -1` wraps instead of being undefined
+#[test]
+fn llvm_func() {
+  let alloc = Alloc::default();
+  let code = "class Main {\n  static int f(int x, int y) { return x / y + x % 2; }\n  static void main() { Print(f(3, 2)); }\n}\n";
+  let ll = compile(code, &alloc, CompileCfg { stage: Stage::Llvm, ..Pa::Pa5.to_cfg() }).unwrap();
+  let f = &ll[ll.find("define i32 @\"_Main.f\"").unwrap()..];
+  assert_eq!(&f[..f.find("\n}\n").unwrap() + 3], r#"define i32 @"_Main.f"(i32 %a0, i32 %a1) {
+entry:
+    %r0 = alloca i32
+    %r1 = alloca i32
+    %r2 = alloca i32
+    %r3 = alloca i32
+    %r4 = alloca i32
+    store i32 %a0, ptr %r0
+    store i32 %a1, ptr %r1
+    br label %L0
+L0:
+    %t0 = load i32, ptr %r0
+    %t1 = load i32, ptr %r1
+    %t3 = icmp eq i32 %t1, -1
+    %t4 = select i1 %t3, i32 1, i32 %t1
+    %t5 = sub i32 0, %t0
+    %t6 = select i1 %t3, i32 %t5, i32 %t0
+    %t2 = sdiv i32 %t6, %t4
+    store i32 %t2, ptr %r2
+    %t7 = load i32, ptr %r0
+    %t8 = srem i32 %t7, 2
+    store i32 %t8, ptr %r3
+    %t9 = load i32, ptr %r2
+    %t10 = load i32, ptr %r3
+    %t11 = add i32 %t9, %t10
+    store i32 %t11, ptr %r4
+    %t12 = load i32, ptr %r4
+    ret i32 %t12
+}
+"#);
+}
+
+// every intrinsic except `_Alloc` is imported in the wasm module, `_Alloc` is defined in it
+#[test]
+fn wasm_intrinsic() {
+  let alloc = Alloc::default();
+  let wat = compile("class Main { static void main() {} }", &alloc, CompileCfg { stage: Stage::Wasm, ..Pa::Pa5.to_cfg() }).unwrap();
+  for &i in tac::Intrinsic::ALL {
+    assert_eq!(wat.contains(&format!("(import \"decaf\" \"{:?}\"", i)), i != tac::Intrinsic::_Alloc, "{:?}", i);
+  }
+  assert!(wat.contains("(func $_Alloc"), "{}", wat);
+}
+
+// run the wasm output under node, and compare its output with tacvm's, the host only provides the intrinsics for printing
+#[test]
+fn wasm_run() {
+  if std::process::Command::new("node").arg("--version").output().is_err() {
+    return eprintln!("node is not found, so the wasm output is not run");
+  }
+  let code = r#"class A {
+  int x;
+  void set(int v) { x = v; }
+  int get(int y) { return x * y; }
+}
+class B extends A {
+  int get(int y) { return x + y; }
+}
+class Main {
+  static int div(int a, int b) { return a / b; }
+  static int rem(int a, int b) { return a % b; }
+  static void main() {
+    int min = -2147483647 - 1;
+    Print(div(min, -1), " ", rem(min, -1), " ", div(-7, 2), " ", rem(-7, 2), " ", div(7, -1), " ", min / -1, "\n");
+    int[] arr = new int[10];
+    int s = 0;
+    for (int i = 0; i < arr.length(); i = i + 1) arr[i] = i * i;
+    for (int i = 0; i < arr.length(); i = i + 1) s = s + arr[i];
+    Print(s, " ", s << 3, " ", -s >> 2, " ", s == 285, "\n");
+    A a = new A();
+    a.set(3);
+    A b = new B();
+    b.set(3);
+    Print(a.get(4), " ", b.get(4), "\n");
+  }
+}
+"#;
+  let alloc = Alloc::default();
+  let tac = compile(code, &alloc, Pa::Pa3.to_cfg()).unwrap();
+  let alloc = Alloc::default();
+  let wasm = wat::assemble(&compile(code, &alloc, CompileCfg { stage: Stage::Wasm, ..Pa::Pa5.to_cfg() }).unwrap());
+  let file = std::env::temp_dir().join("decaf-wasm-run.wasm");
+  std::fs::write(&file, wasm).unwrap();
+  let runner = r#"
+let mem;
+const str = a => { const b = new Uint8Array(mem.buffer); let s = ''; while (b[a]) s += String.fromCharCode(b[a++]); return s; };
+const print = { _PrintInt: x => process.stdout.write(String(x)), _PrintString: a => process.stdout.write(str(a)), _PrintBool: b => process.stdout.write(b ? 'true' : 'false') };
+const decaf = new Proxy(print, { get: (o, k) => o[k] || (() => { throw new Error(k + ' is not provided'); }) });
+const instance = new WebAssembly.Instance(new WebAssembly.Module(require('fs').readFileSync(process.argv[1])), { decaf });
+mem = instance.exports.memory;
+instance.exports.main();
+"#;
+  let o = std::process::Command::new("node").args(["-e", runner, file.to_str().unwrap()]).output().unwrap();
+  assert!(o.status.success(), "{}", String::from_utf8_lossy(&o.stderr));
+  assert_eq!(String::from_utf8(o.stdout).unwrap(), run_tac(&tac, "").0);
+}
+
+// `a[i]` in the canonical counted loop has no bound check, unless `i` or `a` is assigned in the loop body
+#[test]
+fn bound_check() {
+  let tac = |body: &str, bound_check: bool| {
+    let code = format!("class Main {{\n  static void main() {{\n    int[] a = new int[3];\n    {}\n  }}\n}}\n", body);
+    let alloc = Alloc::default();
+    let tac = compile(&code, &alloc, CompileCfg { bound_check, ..Pa::Pa4.to_cfg() }).unwrap();
+    tac.matches("Array subscript out of bounds").count()
+  };
+  let canonical = "for (int i = 0; i < a.length(); i = i + 1) Print(a[i]);";
+  assert_eq!(tac(canonical, true), 0);
+  assert_eq!(tac(canonical, false), 1);
+  assert_eq!(tac("for (int i = 0; i < a.length(); i = i + 1) { Print(a[i]); i = i + 2; }", true), 1);
+  assert_eq!(tac("for (int i = 0; i < a.length(); i = i + 1) { Print(a[i]); a = new int[1]; }", true), 1);
+}
+
+// equal strings in different functions share one label in the data segment, and the class name is reused too
+#[test]
+fn string_pool() {
+  let code = "class Main {
+  static void f() { Print(\"x\\n\"); }
+  static void main() { Print(\"x\\n\", \"Main\"); f(); }
+}
+";
+  let (ast, typeck, tac) = (syntax::ASTAlloc::default(), typeck::TypeCkAlloc::default(), typed_arena::Arena::new());
+  let pr = syntax::parser::work(code, &ast, usize::MAX).ok().unwrap();
+  typeck::work(pr, &typeck, usize::MAX, &mut common::Errors::default()).ok().unwrap();
+  let tp = tacgen::work(pr, &tac, false);
+  let mut p = common::IndentPrinter::default();
+  print::mips::data(&tp, &mut p);
+  let data = p.finish();
+  assert_eq!(data.matches(".asciiz \"x\\n\"").count(), 1, "{}", data);
+  assert_eq!(data.matches(".asciiz \"Main\"").count(), 1, "{}", data);
+}
+
+// a line that is not a double is read as 0.0, like `ReadInteger()` reads it as 0
+#[test]
+fn read_double() {
+  let alloc = Alloc::default();
+  let tac = compile("class Main { static void main() { double d = ReadDouble(); Print(d * 2.0, \" \", ReadDouble(), \" \", ReadDouble()); } }", &alloc, Pa::Pa3.to_cfg()).unwrap();
+  assert_eq!(run_tac(&tac, "1.25\n  -3e2 \nabc\n"), ("2.5 -300.0 0.0".to_owned(), "".to_owned()));
+}
+
+// run the `decaf` binary with `args`, returns (exit code, stdout, stderr)
+fn decaf(args: &[&str]) -> (i32, String, String) {
+  let o = std::process::Command::new(env!("CARGO_BIN_EXE_decaf")).args(args).output().unwrap();
+  (o.status.code().unwrap(), String::from_utf8(o.stdout).unwrap(), String::from_utf8(o.stderr).unwrap())
+}
+
+// unused variables are only reported with `-Wall`, and `-Werror` turns them into errors which fail the compilation
+#[test]
+fn warn_flag() {
+  let unused = format!("{}/../testcase/S2-W/unused.decaf", env!("CARGO_MANIFEST_DIR"));
+  let (code, _, err) = decaf(&[&unused, "-t", "pa2"]);
+  assert_eq!((code, err.as_str()), (0, ""));
+  let (code, _, err) = decaf(&[&unused, "-t", "pa2", "-Wall", "-Wnone"]);
+  assert_eq!((code, err.as_str()), (0, ""));
+  let (code, _, err) = decaf(&[&unused, "-t", "pa2", "-Wall"]);
+  assert_eq!(code, 0);
+  assert!(err.starts_with("*** Warning at (5,9): unused variable 'a'\n"), "{}", err);
+  let (code, out, err) = decaf(&[&unused, "-t", "pa2", "-Wall", "-Werror"]);
+  assert_eq!((code, err.as_str()), (1, ""));
+  assert!(out.starts_with("*** Error at (5,9): unused variable 'a'\n"), "{}", out);
+  let (code, _, _) = decaf(&[&unused, "-t", "pa2", "-Werror"]);
+  assert_eq!(code, 0);
+}
+
+// at most 20 errors are reported by default, and `--max-errors 0` reports all of them
+#[test]
+fn max_errors_flag() {
+  let body = (0..30).map(|i| format!("int a{} = true;", i)).collect::<Vec<_>>().join(" ");
+  let file = std::env::temp_dir().join("decaf-max-errors.decaf");
+  std::fs::write(&file, format!("class Main {{ static void main() {{ {} }} }}", body)).unwrap();
+  let file = file.to_str().unwrap();
+  let (code, out, _) = decaf(&[file, "-t", "pa2"]);
+  assert_eq!(code, 1);
+  assert_eq!(out.lines().count(), 21, "{}", out);
+  assert!(out.ends_with("too many errors, only the first 20 are reported\n"), "{}", out);
+  let (code, out, _) = decaf(&[file, "-t", "pa2", "--max-errors", "0"]);
+  assert_eq!((code, out.lines().count()), (1, 30));
+  let (_, out, _) = decaf(&[file, "-t", "pa2", "--max-errors", "5"]);
+  assert_eq!(out.lines().count(), 6);
+}
+
+// comparing objects by identity may be intended, so it is only reported with `-Wall`
+#[test]
+fn identity_compare_level() {
+  let file = format!("{}/../testcase/S2-W/identity-compare.decaf", env!("CARGO_MANIFEST_DIR"));
+  let (code, _, err) = decaf(&[&file, "-t", "pa2"]);
+  assert_eq!((code, err.as_str()), (0, ""));
+  let (code, _, err) = decaf(&[&file, "-t", "pa2", "-Wall"]);
+  assert_eq!(code, 0);
+  assert_eq!(err.lines().count(), 2, "{}", err);
+}
+
+// the exit code is 1 iff there are errors, no matter how they are printed, and warnings alone don't fail the compilation
+#[test]
+fn exit_code() {
+  let file = |f: &str| format!("{}/../testcase/{}.decaf", env!("CARGO_MANIFEST_DIR"), f);
+  let (syntax, semantic) = (file("S1/abstract-body"), file("S2/assign-this"));
+  for args in &[vec![syntax.as_str()], vec![&semantic, "-t", "pa2"], vec![&semantic, "-t", "pa2", "--json"]] {
+    let (code, out, _) = decaf(args);
+    assert_eq!(code, 1, "{:?}", args);
+    assert!(out.contains("Error") || out.contains(r#""severity":"error""#), "{}", out);
+  }
+  let (code, _, err) = decaf(&[&file("S2-W/unreachable"), "-t", "pa2"]);
+  assert_eq!(code, 0);
+  assert!(err.contains("*** Warning at (8,5): unreachable statement"), "{}", err);
+  assert_eq!(decaf(&[&file("S3/interface"), "-t", "pa3"]).0, 0);
+}
+
+// the hash maps used in compiling have different seeds each time, but the output (including the warnings) should not depend on them
+#[test]
+fn deterministic() {
+  let code = std::fs::read_to_string(format!("{}/../testcase/S3/interface.decaf", env!("CARGO_MANIFEST_DIR"))).unwrap()
+    + "class Unused { void f() { int a; int b; string s = \"s\"; string t = \"t\"; } }\n";
+  let output = || {
+    let alloc = Alloc::default();
+    let mut out = format!("{:?}", check(&code, &alloc, Pa::Pa2.to_cfg()).1);
+    for &stage in &[Stage::Tac, Stage::TacOpt, Stage::Llvm, Stage::Wasm] {
+      let alloc = Alloc::default();
+      out += &compile(&code, &alloc, CompileCfg { stage, ..Pa::Pa5.to_cfg() }).unwrap();
+    }
+    out
+  };
+  let first = output();
+  for _ in 0..4 { assert!(output() == first); }
+}
+
+// build the cfg of `main` whose body is `body` from the unoptimized tac, and pass it to `f`
+fn main_cfg(body: &str, f: impl FnOnce(&tacopt::bb::Cfg)) {
+  let code = format!("class Main {{\n  static void main() {{\n    {}\n  }}\n}}\n", body);
+  let (ast, typeck, tac) = (syntax::ASTAlloc::default(), typeck::TypeCkAlloc::default(), typed_arena::Arena::new());
+  let pr = syntax::parser::work(&code, &ast, usize::MAX).ok().unwrap();
+  typeck::work(pr, &typeck, usize::MAX, &mut common::Errors::default()).ok().unwrap();
+  let tp = tacgen::work(pr, &tac, false);
+  f(&tacopt::bb::build_cfg(tp.func.iter().find(|f| f.name == "main").unwrap()));
+}
+
+// the successors of every bb, in index order
+fn cfg_succ(cfg: &tacopt::bb::Cfg) -> Vec<Vec<u32>> {
+  cfg.blocks().map(|(idx, _)| cfg.succ(idx).collect()).collect()
+}
+
+#[test]
+fn cfg_if_else() {
+  main_cfg("int x = ReadInteger(); if (x > 0) Print(1); else Print(2); Print(3);", |cfg| {
+    assert_eq!(cfg.block_num(), 4);
+    assert_eq!(cfg_succ(cfg), [vec![1, 2], vec![3], vec![3], vec![]]);
+    assert!(cfg.has_edge(0, 2) && !cfg.has_edge(1, 2));
+    let mut pred = cfg.pred(3).to_vec();
+    pred.sort();
+    assert_eq!(pred, [1, 2]);
+    assert_eq!((cfg.entry(), cfg.exits()), (0, vec![3]));
+    assert!(cfg.blocks().all(|(idx, b)| b.len == cfg.block(idx).len));
+  });
+}
+
+#[test]
+fn cfg_loop() {
+  main_cfg("int i = 0; while (i < 10) i = i + 1; Print(i);", |cfg| {
+    assert_eq!(cfg.block_num(), 4);
+    // the condition is placed after the body, the entry jumps to it directly
+    assert_eq!(cfg_succ(cfg), [vec![2], vec![2], vec![3, 1], vec![]]);
+    // the back edge
+    assert!(cfg.has_edge(2, 1) && !cfg.has_edge(0, 1));
+    let mut pred = cfg.pred(2).to_vec();
+    pred.sort();
+    assert_eq!(pred, [0, 1]);
+    assert_eq!(cfg.pred(3), [2]);
+    assert_eq!(cfg.exits(), [3]);
+  });
+}
+
+// the registers in bitset `bs`, in increasing order
+fn regs(cfg: &tacopt::bb::Cfg, bs: &[u32]) -> Vec<u32> {
+  (0..cfg.reg_num).filter(|&r| (bs[r as usize / 32] >> (r % 32)) & 1 != 0).collect()
+}
+
+#[test]
+fn liveness_straight() {
+  // %1 = call _ReadInt; %0 = %1; %3 = %0 + 1; %2 = %3; parm %2; call _PrintInt
+  main_cfg("int a = ReadInteger(); int b = a + 1; Print(b);", |cfg| {
+    let l = tacopt::aliveness::liveness(cfg);
+    assert!(regs(cfg, l.live_in(0)).is_empty() && regs(cfg, l.live_out(0)).is_empty());
+    let after = (0..6).map(|n| regs(cfg, &l.live_after(cfg, 0, n))).collect::<Vec<_>>();
+    assert_eq!(after, [vec![1], vec![0], vec![3], vec![2], vec![], vec![]]);
+  });
+}
+
+#[test]
+fn liveness_loop() {
+  // bb0: %0 = 0; %2 = call _ReadInt; %1 = %2; bb1: %3 = %0 + %1; %0 = %3
+  // bb2: %4 = %0 < 10; if (%4 != 0) branch bb1; bb3: parm %0; call _PrintInt
+  main_cfg("int i = 0; int s = ReadInteger(); while (i < 10) i = i + s; Print(i);", |cfg| {
+    let l = tacopt::aliveness::liveness(cfg);
+    let (live_in, live_out) = (0..4).map(|b| (regs(cfg, l.live_in(b)), regs(cfg, l.live_out(b)))).unzip::<_, _, Vec<_>, Vec<_>>();
+    // `s` is alive around the whole loop, because the back edge reads it again
+    assert_eq!(live_in, [vec![], vec![0, 1], vec![0, 1], vec![0]]);
+    assert_eq!(live_out, [vec![0, 1], vec![0, 1], vec![0, 1], vec![]]);
+    assert!(l.is_live_out(2, 1) && !l.is_live_in(3, 1));
+    assert_eq!(regs(cfg, &l.live_after(cfg, 1, 0)), [1, 3]);
+    // the condition register is read by the jump at the end of bb2
+    assert_eq!(regs(cfg, &l.live_after(cfg, 2, 0)), [0, 1, 4]);
+  });
+}
+
+#[test]
+fn dom_diamond() {
+  // bb0 branches to bb1 and bb2, which join at bb3
+  main_cfg("int x = ReadInteger(); if (x > 0) Print(1); else Print(2); Print(3);", |cfg| {
+    let d = tacopt::dom::dominators(cfg);
+    assert_eq!((0..4).map(|b| d.idom(b)).collect::<Vec<_>>(), [None, Some(0), Some(0), Some(0)]);
+    assert_eq!(d.children(0), [1, 2, 3]);
+    assert!(d.children(1).is_empty() && d.children(3).is_empty());
+    assert!(d.dominates(0, 3) && d.dominates(3, 3) && !d.dominates(1, 3) && !d.dominates(2, 3));
+  });
+}
+
+#[test]
+fn dom_loop() {
+  // bb0 jumps to the condition bb2, which branches to the body bb1 and the exit bb3, bb1 jumps back to bb2
+  main_cfg("int i = 0; while (i < 10) i = i + 1; Print(i);", |cfg| {
+    let d = tacopt::dom::dominators(cfg);
+    assert_eq!((0..4).map(|b| d.idom(b)).collect::<Vec<_>>(), [None, Some(2), Some(0), Some(2)]);
+    assert_eq!((d.children(0), d.children(2)), (&[2][..], &[1, 3][..]));
+    // the back edge target dominates its source, but not the other way around
+    assert!(d.dominates(2, 1) && !d.dominates(1, 2) && !d.dominates(1, 3));
+  });
+}
+
+// the llvm ir of `main` in `code` (which should contain class Main), with the objects allocated on the stack if they don't escape
+// the mips backend is not used here, because AllocMethod::Graph is not implemented
+fn stack_alloc_main(code: &str) -> String {
+  let alloc = Alloc::default();
+  let opt = tacopt::OptCfg { stack_alloc: true, ..Default::default() };
+  let ll = compile(code, &alloc, CompileCfg { stage: Stage::Llvm, opt, ..Pa::Pa5.to_cfg() }).unwrap();
+  ll.split("@\"main\"(").nth(1).unwrap().split("\n}").next().unwrap().to_owned()
+}
+
+#[test]
+fn stack_alloc() {
+  // the methods only access the fields of `this`, so calling them doesn't make `a` escape
+  let class = "class A {\n  int x;\n  void set(int v) { x = v; }\n  int get() { return x; }\n  A me() { return this; }\n}\n";
+  let main = stack_alloc_main(&format!("{}class Main {{\n  static void main() {{\n    A a = new A();\n    a.set(ReadInteger());\n    Print(a.get());\n  }}\n}}\n", class));
+  assert!(main.contains("alloca i8") && !main.contains("_A._new") && !main.contains("_Alloc"));
+  // `me` returns `this`, so `b` escapes
+  let main = stack_alloc_main(&format!("{}class Main {{\n  static void main() {{\n    A b = new A();\n    Print(b.me().get());\n  }}\n}}\n", class));
+  assert!(main.contains("_A._new"));
+}
@@ -0,0 +1,292 @@
+// an assembler from the webassembly text format to the binary format, only for the subset used by `print::wasm`
+// it is only used to run the wasm output in tests, so it panics on anything it doesn't understand
+
+use std::collections::HashMap;
+
+#[derive(Debug)]
+enum Sexp {
+  Atom(String),
+  Str(Vec<u8>),
+  List(Vec<Sexp>),
+}
+
+impl Sexp {
+  fn atom(&self) -> &str { if let Sexp::Atom(a) = self { a } else { panic!("expect atom, found {:?}", self) } }
+  fn list(&self) -> &[Sexp] { if let Sexp::List(l) = self { l } else { panic!("expect list, found {:?}", self) } }
+  fn str(&self) -> &[u8] { if let Sexp::Str(s) = self { s } else { panic!("expect string, found {:?}", self) } }
+  // the list whose first element is `head`
+  fn is(&self, head: &str) -> bool { matches!(self, Sexp::List(l) if matches!(l.first(), Some(Sexp::Atom(a)) if a == head)) }
+}
+
+fn parse(code: &[u8], i: &mut usize) -> Option<Sexp> {
+  while *i < code.len() && code[*i].is_ascii_whitespace() { *i += 1; }
+  match *code.get(*i)? {
+    b'(' => {
+      *i += 1;
+      let mut l = Vec::new();
+      while let Some(s) = parse(code, i) { l.push(s); }
+      assert_eq!(code[*i], b')');
+      *i += 1;
+      Some(Sexp::List(l))
+    }
+    b')' => None,
+    b'"' => {
+      let mut s = Vec::new();
+      *i += 1;
+      while code[*i] != b'"' {
+        if code[*i] == b'\\' {
+          s.push(u8::from_str_radix(std::str::from_utf8(&code[*i + 1..*i + 3]).unwrap(), 16).unwrap());
+          *i += 3;
+        } else { (s.push(code[*i]), *i += 1).1 }
+      }
+      *i += 1;
+      Some(Sexp::Str(s))
+    }
+    _ => {
+      let start = *i;
+      while *i < code.len() && !code[*i].is_ascii_whitespace() && code[*i] != b'(' && code[*i] != b')' { *i += 1; }
+      Some(Sexp::Atom(String::from_utf8(code[start..*i].to_vec()).unwrap()))
+    }
+  }
+}
+
+fn uleb(mut x: u32, out: &mut Vec<u8>) {
+  loop {
+    let b = (x & 0x7f) as u8;
+    x >>= 7;
+    if x == 0 { break out.push(b); }
+    out.push(b | 0x80);
+  }
+}
+
+fn sleb(mut x: i32, out: &mut Vec<u8>) {
+  loop {
+    let b = (x & 0x7f) as u8;
+    x >>= 7;
+    if (x == 0 && b & 0x40 == 0) || (x == -1 && b & 0x40 != 0) { break out.push(b); }
+    out.push(b | 0x80);
+  }
+}
+
+fn vec(v: &[u8], out: &mut Vec<u8>) { (uleb(v.len() as u32, out), out.extend_from_slice(v)).1 }
+
+fn section(id: u8, n: usize, content: Vec<u8>, out: &mut Vec<u8>) {
+  if n == 0 { return; }
+  let mut s = Vec::new();
+  uleb(n as u32, &mut s);
+  s.extend(content);
+  out.push(id);
+  vec(&s, out);
+}
+
+// every value is i32, so a function type is (the number of params, whether it has a result)
+#[derive(Default)]
+struct Module {
+  ty: Vec<(u32, bool)>,
+  ty_name: HashMap<String, u32>,
+  func_name: HashMap<String, u32>,
+  global_name: HashMap<String, u32>,
+}
+
+impl Module {
+  fn ty(&mut self, t: (u32, bool)) -> u32 {
+    match self.ty.iter().position(|&x| x == t) {
+      Some(idx) => idx as u32,
+      None => (self.ty.push(t), self.ty.len() as u32 - 1).1,
+    }
+  }
+
+  // the type of "(func $name (param ...) (result ...) ...)" or "(func (param ...))", and the names of the params
+  fn sig(&mut self, f: &[Sexp]) -> (u32, Vec<Option<String>>) {
+    let (mut param, mut result) = (Vec::new(), false);
+    for s in f {
+      if s.is("param") { param.extend(names(&s.list()[1..])); }
+      if s.is("result") { result = true; }
+    }
+    (self.ty((param.len() as u32, result)), param)
+  }
+}
+
+// "(i32.const x)", the only constant expression used
+fn const_expr(e: &Sexp, out: &mut Vec<u8>) {
+  let e = e.list();
+  assert_eq!(e[0].atom(), "i32.const");
+  out.push(0x41);
+  sleb(e[1].atom().parse().unwrap(), out);
+  out.push(0x0b);
+}
+
+// the names in "(param $a i32)" or "(local i32 i32)", every unnamed one is None
+fn names(s: &[Sexp]) -> Vec<Option<String>> {
+  match s.first().map(|s| s.atom()) {
+    Some(n) if n.starts_with('$') => vec![Some(n.to_owned())],
+    _ => s.iter().map(|_| None).collect(),
+  }
+}
+
+pub fn assemble(wat: &str) -> Vec<u8> {
+  let module = parse(wat.as_bytes(), &mut 0).unwrap();
+  let fields = &module.list()[1..];
+  let mut m = Module::default();
+  for f in fields.iter().filter(|f| f.is("type")) {
+    let l = f.list();
+    let t = m.sig(&l[2].list()[1..]);
+    m.ty_name.insert(l[1].atom().to_owned(), t.0);
+  }
+  let (mut import, mut func_ty, mut global, mut export) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+  let (mut import_num, mut global_num, mut export_num) = (0, 0, 0);
+  let mut funcs = Vec::new();
+  for f in fields {
+    let l = f.list();
+    match l[0].atom() {
+      "import" => {
+        let func = l[3].list();
+        let t = m.sig(&func[2..]);
+        m.func_name.insert(func[1].atom().to_owned(), import_num);
+        vec(l[1].str(), &mut import);
+        vec(l[2].str(), &mut import);
+        import.push(0x00);
+        uleb(t.0, &mut import);
+        import_num += 1;
+      }
+      "func" => {
+        m.func_name.insert(l[1].atom().to_owned(), import_num + funcs.len() as u32);
+        funcs.push(l);
+      }
+      "global" => {
+        m.global_name.insert(l[1].atom().to_owned(), global_num);
+        global.extend_from_slice(&[0x7f, if l[2].is("mut") { 1 } else { 0 }]);
+        const_expr(&l[3], &mut global);
+        global_num += 1;
+      }
+      _ => {}
+    }
+  }
+  let (mut code, mut elem, mut data, mut data_num, mut table, mut memory) = (Vec::new(), Vec::new(), Vec::new(), 0, Vec::new(), Vec::new());
+  for f in fields {
+    let l = f.list();
+    match l[0].atom() {
+      "memory" => {
+        let mut i = 1;
+        if l[1].is("export") {
+          vec(l[1].list()[1].str(), &mut export);
+          export.extend_from_slice(&[0x02, 0x00]);
+          export_num += 1;
+          i += 1;
+        }
+        memory.push(0x00);
+        uleb(l[i].atom().parse().unwrap(), &mut memory);
+      }
+      "table" => {
+        table.extend_from_slice(&[0x70, 0x00]);
+        uleb(l[1].atom().parse().unwrap(), &mut table);
+      }
+      "elem" => {
+        elem.push(0x00);
+        const_expr(&l[1], &mut elem);
+        uleb(l.len() as u32 - 2, &mut elem);
+        for f in &l[2..] { uleb(m.func_name[f.atom()], &mut elem); }
+      }
+      "data" => {
+        data.push(0x00);
+        const_expr(&l[1], &mut data);
+        vec(&l[2..].iter().flat_map(|s| s.str().to_vec()).collect::<Vec<_>>(), &mut data);
+        data_num += 1;
+      }
+      "export" => {
+        vec(l[1].str(), &mut export);
+        export.push(0x00);
+        uleb(m.func_name[l[2].list()[1].atom()], &mut export);
+        export_num += 1;
+      }
+      _ => {}
+    }
+  }
+  for f in &funcs {
+    let (t, mut local) = m.sig(&f[2..]);
+    uleb(t, &mut func_ty);
+    let param_num = local.len();
+    for s in f.iter().filter(|s| s.is("local")) { local.extend(names(&s.list()[1..])); }
+    let mut body = Vec::new();
+    if local.len() > param_num {
+      body.push(0x01);
+      uleb((local.len() - param_num) as u32, &mut body);
+      body.push(0x7f);
+    } else { body.push(0x00); }
+    instr(&f[2..], &local, &m, &mut body);
+    body.push(0x0b);
+    vec(&body, &mut code);
+  }
+  let mut ty = Vec::new();
+  for &(param, result) in &m.ty {
+    ty.push(0x60);
+    vec(&vec![0x7f; param as usize], &mut ty);
+    vec(if result { &[0x7f][..] } else { &[] }, &mut ty);
+  }
+  let mut out = b"\0asm\x01\0\0\0".to_vec();
+  section(1, m.ty.len(), ty, &mut out);
+  section(2, import_num as usize, import, &mut out);
+  section(3, funcs.len(), func_ty, &mut out);
+  section(4, (!table.is_empty()) as usize, table, &mut out);
+  section(5, (!memory.is_empty()) as usize, memory, &mut out);
+  section(6, global_num as usize, global, &mut out);
+  section(7, export_num, export, &mut out);
+  section(9, (!elem.is_empty()) as usize, elem, &mut out);
+  section(10, funcs.len(), code, &mut out);
+  section(11, data_num, data, &mut out);
+  out
+}
+
+// the instructions after the params, result and locals in a function
+fn instr(f: &[Sexp], local: &[Option<String>], m: &Module, out: &mut Vec<u8>) {
+  let mut label = Vec::new();
+  let local_idx = |s: &str| if s.starts_with('$') { local.iter().position(|l| l.as_deref() == Some(s)).unwrap() as u32 } else { s.parse().unwrap() };
+  let depth = |label: &[String], s: &str| label.len() as u32 - 1 - label.iter().rposition(|l| l == s).unwrap() as u32;
+  let mut it = f.iter().skip_while(|s| matches!(s, Sexp::List(_)));
+  while let Some(s) = it.next() {
+    let mut arg = || it.next().unwrap().atom().to_owned();
+    match s.atom() {
+      "local.get" => (out.push(0x20), uleb(local_idx(&arg()), out)).1,
+      "local.set" => (out.push(0x21), uleb(local_idx(&arg()), out)).1,
+      "global.get" => (out.push(0x23), uleb(m.global_name[&arg()], out)).1,
+      "global.set" => (out.push(0x24), uleb(m.global_name[&arg()], out)).1,
+      "i32.const" => (out.push(0x41), sleb(arg().parse().unwrap(), out)).1,
+      "call" => (out.push(0x10), uleb(m.func_name[&arg()], out)).1,
+      "call_indirect" => {
+        out.push(0x11);
+        uleb(m.ty_name[it.next().unwrap().list()[1].atom()], out);
+        out.push(0x00);
+      }
+      "i32.load" => out.extend_from_slice(&[0x28, 0x02, 0x00]),
+      "i32.store" => out.extend_from_slice(&[0x36, 0x02, 0x00]),
+      "block" | "loop" => {
+        out.extend_from_slice(&[if s.atom() == "block" { 0x02 } else { 0x03 }, 0x40]);
+        label.push(arg());
+      }
+      "end" => (out.push(0x0b), label.pop()).0,
+      "br" => (out.push(0x0c), uleb(depth(&label, &arg()), out)).1,
+      "br_if" => (out.push(0x0d), uleb(depth(&label, &arg()), out)).1,
+      "br_table" => {
+        // the labels are all the atoms until the next instruction, the last one is the default
+        let mut target = Vec::new();
+        while let Some(Sexp::Atom(a)) = it.clone().next() {
+          if !a.starts_with('$') { break; }
+          target.push(depth(&label, a));
+          it.next();
+        }
+        out.push(0x0e);
+        uleb(target.len() as u32 - 1, out);
+        for t in target { uleb(t, out); }
+      }
+      "memory.size" => out.extend_from_slice(&[0x3f, 0x00]),
+      "memory.grow" => out.extend_from_slice(&[0x40, 0x00]),
+      i => out.push(match i {
+        "unreachable" => 0x00, "return" => 0x0f, "drop" => 0x1a, "select" => 0x1b,
+        "i32.eqz" => 0x45, "i32.eq" => 0x46, "i32.ne" => 0x47, "i32.lt_s" => 0x48, "i32.gt_s" => 0x4a, "i32.le_s" => 0x4c, "i32.le_u" => 0x4d, "i32.ge_s" => 0x4e,
+        "i32.add" => 0x6a, "i32.sub" => 0x6b, "i32.mul" => 0x6c, "i32.div_s" => 0x6d, "i32.rem_s" => 0x6f,
+        "i32.and" => 0x71, "i32.or" => 0x72, "i32.xor" => 0x73, "i32.shl" => 0x74, "i32.shr_s" => 0x75,
+        i => panic!("unsupported instruction `{}`", i),
+      }),
+    }
+  }
+}
@@ -1,4 +1,4 @@
-use common::{IndentPrinter, IgnoreResult};
+use common::{IndentPrinter, IgnoreResult, Loc, escape};
 use syntax::*;
 use std::fmt::Write;
 
@@ -17,7 +17,7 @@ macro_rules! print_basic {
   )*};
 }
 
-print_basic!(i32 bool str);
+print_basic!(i32 u32 f64 bool str);
 
 impl<T: Printable> Printable for [T] {
   fn print(&self, p: &mut IndentPrinter) {
@@ -32,6 +32,32 @@ impl<T: Printable> Printable for Option<T> {
   }
 }
 
+// print nothing for None, used for the fields which are None in most cases, and shouldn't change the output then
+struct Hidden<T>(Option<T>);
+
+impl<T: Printable> Printable for Hidden<T> {
+  fn print(&self, p: &mut IndentPrinter) { if let Some(x) = &self.0 { x.print(p); } }
+}
+
+// the init / update of a for loop, which is printed as a statement if there is only one, so the common case keeps the same output
+struct Simples<'a, 'b>(&'b [Stmt<'a>]);
+
+impl Printable for Simples<'_, '_> {
+  fn print(&self, p: &mut IndentPrinter) { if let [s] = self.0 { s.print(p) } else { self.0.print(p) } }
+}
+
+// the defaults of a switch, which are printed as an optional block if there is at most one, so the valid ones keep the same output
+struct Defaults<'a, 'b>(&'b [Block<'a>]);
+
+impl Printable for Defaults<'_, '_> {
+  fn print(&self, p: &mut IndentPrinter) { if self.0.len() <= 1 { self.0.first().print(p) } else { self.0.print(p) } }
+}
+
+// an implemented interface, only its name is printed
+impl Printable for (Loc, &str) {
+  fn print(&self, p: &mut IndentPrinter) { self.1.print(p); }
+}
+
 impl<T: Printable> Printable for Box<T> {
   fn print(&self, p: &mut IndentPrinter) { self.as_ref().print(p); }
 }
@@ -49,11 +75,25 @@ impl Printable for SynTy<'_> {
     match &self.kind {
       SynTyKind::Int => write!(p, "TInt @ {:?}", self.loc).ignore(),
       SynTyKind::Bool => write!(p, "TBool @ {:?}", self.loc).ignore(),
+      SynTyKind::Char => write!(p, "TChar @ {:?}", self.loc).ignore(),
+      SynTyKind::Double => write!(p, "TDouble @ {:?}", self.loc).ignore(),
       SynTyKind::String => write!(p, "TString @ {:?}", self.loc).ignore(),
       SynTyKind::Void => write!(p, "TVoid @ {:?}", self.loc).ignore(),
-      SynTyKind::Named(c) => {
-        write!(p, "TClass @ {:?}", self.loc).ignore();
-        p.indent(|p| c.print(p));
+      SynTyKind::Var => write!(p, "TVar @ {:?}", self.loc).ignore(),
+      SynTyKind::Named(c, non_null, arg) => {
+        write!(p, "{} @ {:?}", if *non_null { "TNonNullClass" } else { "TClass" }, self.loc).ignore();
+        p.indent(|p| (c.print(p), Hidden(arg.as_ref()).print(p)).1);
+      }
+      SynTyKind::Func(ret_param) => {
+        write!(p, "TLambda @ {:?}", self.loc).ignore();
+        p.indent(|p| {
+          ret_param[0].print(p);
+          ret_param[1..].print(p);
+        });
+      }
+      SynTyKind::Tuple(elem) => {
+        write!(p, "TTuple @ {:?}", self.loc).ignore();
+        p.indent(|p| elem.print(p));
       }
     }
     for _ in 0..self.arr { p.dec(); }
@@ -87,10 +127,19 @@ macro_rules! print_enum {
 }
 
 // self.class[0] must be valid, because parser requires their are at least one class
-print_struct!(Program<'_>, self, self.class[0].loc, TopLevel, self.class);
-print_struct!(ClassDef<'_>, self, self.loc, ClassDef, self.name self.parent self.field);
-print_struct!(VarDef<'_>, self, self.loc, LocalVarDef, self.syn_ty self.name self.init());
+print_struct!(Program<'_>, self, self.class[0].loc, TopLevel, Hidden(if self.alias.is_empty() { None } else { Some(&self.alias[..]) }) self.class);
+print_struct!(TypeAlias<'_>, self, self.loc, TypeAlias, self.syn_ty self.name);
+print_struct!(ClassDef<'_>, self, self.loc, ClassDef, Hidden(if self.interface { Some("INTERFACE") } else if self.abstract_ { Some("ABSTRACT") } else { None }) self.name Hidden(self.ty_param) self.parent
+  Hidden(if self.implements.is_empty() { None } else { Some(&self.implements[..]) }) self.field);
+print_struct!(VarDef<'_>, self, self.loc, LocalVarDef, Hidden(if self.final_ { Some("FINAL") } else { None }) self.syn_ty self.name self.init());
 print_struct!(Block<'_>, self, self.loc, Block, self.stmt);
+print_struct!(Case<'_>, self, self.loc, Case, self.label self.body);
+
+impl Printable for LambdaBody<'_> {
+  fn print(&self, p: &mut IndentPrinter) {
+    match self { LambdaBody::Expr(e) => e.print(p), LambdaBody::Block(b) => b.print(p) }
+  }
+}
 
 impl Printable for FieldDef<'_> {
   fn print(&self, p: &mut IndentPrinter) {
@@ -98,6 +147,8 @@ impl Printable for FieldDef<'_> {
       FieldDef::VarDef(v) => {
         write!(p, "VarDef @ {:?}", v.loc).ignore();
         p.indent(|p| {
+          if v.static_ { "STATIC".print(p); }
+          if v.final_ { "FINAL".print(p); }
           v.syn_ty.print(p);
           v.name.print(p);
           v.init().print(p);
@@ -107,6 +158,8 @@ impl Printable for FieldDef<'_> {
         write!(p, "MethodDef @ {:?}", f.loc).ignore();
         p.indent(|p| {
           if f.static_ { "STATIC".print(p); }
+          if f.abstract_ { "ABSTRACT".print(p); }
+          if f.ctor { "CONSTRUCTOR".print(p); }
           f.name.print(p);
           f.ret.print(p);
           f.param.print(p);
@@ -122,8 +175,10 @@ impl Printable for Stmt<'_> {
   fn print(&self, p: &mut IndentPrinter) {
     use StmtKind::*;
     print_enum!(self.kind, self.loc, p, x,
-      Assign => x.dst x.src, LocalVarDef => x.syn_ty x.name x.init(), ExprEval => x, Skip => , If => x.cond x.on_true x.on_false,
-      While => x.cond x.body, For => x.init x.cond x.update x.body, Return => x, Print => x, Break => , Block => x.stmt
+      Assign => Hidden(x.op.map(|op| op.to_word_str())) x.dst x.src, LocalVarDef => x.syn_ty x.name x.init(), ExprEval => x, Skip => , If => x.cond x.on_true x.on_false,
+      While => Hidden(x.label) x.cond x.body, DoWhile => Hidden(x.label) x.body x.cond, For => Hidden(x.label) Simples(&x.init) x.cond Simples(&x.update) x.body,
+      Foreach => Hidden(x.label) x.var x.arr x.body, Switch => x.expr x.case Defaults(&x.default),
+      Return => x, Print => x, Printf => "\"".to_owned() + &escape(x.fmt) + "\"" x.arg, Break => Hidden(x.label), Continue => Hidden(x.label), Block => x.stmt
     );
   }
 }
@@ -133,10 +188,11 @@ impl Printable for Expr<'_> {
   fn print(&self, p: &mut IndentPrinter) {
     use ExprKind::*;
     print_enum!(self.kind, self.loc, p, x,
-      VarSel => x.owner x.name, IndexSel => x.arr x.idx, IntLit => x, BoolLit => x, StringLit => "\"".to_owned() + x + "\"",
+      VarSel => x.owner x.name, IndexSel => x.arr x.idx, Slice => x.arr x.lo x.hi, IntLit => x, CharLit => format!("{:?}", x), DoubleLit => x, BoolLit => x, StringLit => "\"".to_owned() + &escape(x) + "\"",
       NullLit => , Call => x.func x.arg, Unary => x.op.to_word_str() x.r, Binary => x.op.to_word_str() x.l x.r,
-      This => , ReadInt => , ReadLine => , NewClass => x.name, NewArray => x.elem x.len, ClassTest => x.expr x.name,
-      ClassCast => x.expr x.name
+      Ternary => x.cond x.on_true x.on_false, Assign => x.dst x.src, IncDec => (if x.prefix { "PRE" } else { "POST" }) x.op.to_word_str() x.e,
+      ArrayLit => x, Tuple => x.elem, TupleSel => x.tuple x.idx, This => , Super => , ReadInt => , ReadDouble => , ReadLine => , NewClass => x.name Hidden(x.ty_arg.as_ref()) Hidden(if x.arg.is_empty() { None } else { Some(&x.arg[..]) }), NewArray => x.elem x.len Hidden(if x.sub_len.is_empty() { None } else { Some(&x.sub_len[..]) }), ClassTest => x.expr x.name,
+      ClassCast => x.expr x.name, Lambda => x.param x.body
     );
   }
 }
@@ -0,0 +1,214 @@
+use common::{IndentPrinter, IgnoreResult, BinOp, UnOp, escape};
+use syntax::*;
+use std::fmt::Write;
+
+// print the ast as decaf source code, parsing the output gives the same ast (except the locations)
+// only necessary parentheses are printed, so it can be used to canonicalize the source code
+pub fn program(pr: &Program, p: &mut IndentPrinter) {
+  for a in &pr.alias { write!(p, "typedef {} {};", syn_ty(&a.syn_ty), a.name).ignore(); }
+  if !pr.alias.is_empty() { writeln!(p).ignore(); }
+  for (i, c) in pr.class.iter().enumerate() {
+    if i != 0 { writeln!(p).ignore(); }
+    class_def(c, p);
+  }
+}
+
+fn class_def(c: &ClassDef, p: &mut IndentPrinter) {
+  let parent = c.parent.map(|x| format!(" extends {}", x)).unwrap_or_default();
+  let implements = if c.implements.is_empty() { String::new() } else { format!(" implements {}", c.implements.iter().map(|i| i.1).collect::<Vec<_>>().join(", ")) };
+  if c.interface { write!(p, "interface {} {{", c.name).ignore(); } else {
+    let ty_param = c.ty_param.map(|t| format!("<{}>", t)).unwrap_or_default();
+    write!(p, "{}class {}{}{}{} {{", if c.abstract_ { "abstract " } else { "" }, c.name, ty_param, parent, implements).ignore();
+  }
+  p.indent(|p| for f in &c.field {
+    match f {
+      FieldDef::VarDef(v) => write!(p, "{};", var_def(v)).ignore(),
+      FieldDef::FuncDef(f) => {
+        let modifier = if f.static_ { "static " } else if f.abstract_ && !c.interface { "abstract " } else { "" };
+        let param = f.param.iter().map(|v| var_def(v)).collect::<Vec<_>>().join(", ");
+        let body = f.body.as_ref().map(|b| " ".to_owned() + &block(b)).unwrap_or_else(|| ";".to_owned());
+        if f.ctor { write!(p, "{}({}){}", f.name, param, body).ignore(); } else {
+          write!(p, "{}{} {}({}){}", modifier, syn_ty(&f.ret), f.name, param, body).ignore();
+        }
+      }
+    }
+  });
+  write!(p, "}}").ignore();
+}
+
+// a space is printed before the closing `>` of a nested type argument, since `>>` is lexed as one token
+fn ty_arg(t: &SynTy) -> String {
+  let s = syn_ty(t);
+  format!("<{}{}>", s, if s.ends_with('>') { " " } else { "" })
+}
+
+fn syn_ty(t: &SynTy) -> String {
+  let s = match &t.kind {
+    SynTyKind::Int => "int".to_owned(),
+    SynTyKind::Bool => "bool".to_owned(),
+    SynTyKind::Char => "char".to_owned(),
+    SynTyKind::Double => "double".to_owned(),
+    SynTyKind::String => "string".to_owned(),
+    SynTyKind::Void => "void".to_owned(),
+    SynTyKind::Var => "var".to_owned(),
+    SynTyKind::Named(c, non_null, arg) => format!("class {}{}{}", c, arg.as_ref().map(|a| ty_arg(a)).unwrap_or_default(), if *non_null { "!" } else { "" }),
+    SynTyKind::Func(ret_param) => format!("{}({})", syn_ty(&ret_param[0]), ret_param[1..].iter().map(syn_ty).collect::<Vec<_>>().join(", ")),
+    SynTyKind::Tuple(elem) => format!("({})", elem.iter().map(syn_ty).collect::<Vec<_>>().join(", ")),
+  };
+  s + &"[]".repeat(t.arr as usize)
+}
+
+// field, parameter, local variable or foreach variable, without `;`
+fn var_def(v: &VarDef) -> String {
+  let modifier = format!("{}{}", if v.static_ { "static " } else { "" }, if v.final_ { "final " } else { "" });
+  let init = v.init().map(|e| " = ".to_owned() + &expr(e, 0)).unwrap_or_default();
+  format!("{}{} {}{}", modifier, syn_ty(&v.syn_ty), v.name, init)
+}
+
+fn block(b: &Block) -> String {
+  let mut p = IndentPrinter::default();
+  p.indent(|p| for s in &b.stmt { write!(p, "{}", stmt(s)).ignore(); });
+  format!("{{\n{}}}", p.finish())
+}
+
+// the statements that can be the `init` and `update` of a `for`, without `;`
+fn simples(s: &[Stmt]) -> String { s.iter().map(simple).collect::<Vec<_>>().join(", ") }
+
+fn simple(s: &Stmt) -> String {
+  match &s.kind {
+    StmtKind::Assign(a) => format!("{} {}= {}", expr(&a.dst, 0), a.op.map(|op| op.to_op_str()).unwrap_or(""), expr(&a.src, 0)),
+    StmtKind::LocalVarDef(v) => var_def(v),
+    StmtKind::ExprEval(e) => expr(e, 0),
+    _ => String::new(), // Skip
+  }
+}
+
+fn stmt(s: &Stmt) -> String {
+  use StmtKind::*;
+  let label = |l: Option<&str>| l.map(|l| l.to_owned() + ": ").unwrap_or_default();
+  match &s.kind {
+    Assign(_) | LocalVarDef(_) | ExprEval(_) | Skip(_) => simple(s) + ";",
+    If(i) => {
+      let on_false = i.on_false.as_ref().map(|b| " else ".to_owned() + &block(b)).unwrap_or_default();
+      format!("if ({}) {}{}", expr(&i.cond, 0), block(&i.on_true), on_false)
+    }
+    While(w) => format!("{}while ({}) {}", label(w.label), expr(&w.cond, 0), block(&w.body)),
+    DoWhile(d) => format!("{}do {} while ({});", label(d.label), block(&d.body), expr(&d.cond, 0)),
+    For(f) => format!("{}for ({}; {}; {}) {}", label(f.label), simples(&f.init), expr(&f.cond, 0), simples(&f.update), block(&f.body)),
+    Foreach(f) => format!("{}for ({} : {}) {}", label(f.label), var_def(f.var), expr(&f.arr, 0), block(&f.body)),
+    Switch(sw) => {
+      let mut p = IndentPrinter::default();
+      p.indent(|p| {
+        let mut case = |head: String, b: &syntax::Block| {
+          write!(p, "{}", head).ignore();
+          p.indent(|p| for s in &b.stmt { write!(p, "{}", stmt(s)).ignore(); });
+        };
+        for c in &sw.case { case(format!("case {}:", expr(&c.label, 0)), &c.body); }
+        for d in &sw.default { case("default:".to_owned(), d); }
+      });
+      format!("switch ({}) {{\n{}}}", expr(&sw.expr, 0), p.finish())
+    }
+    Return(r) => r.as_ref().map(|e| format!("return {};", expr(e, 0))).unwrap_or_else(|| "return;".to_owned()),
+    Print(p) => format!("Print({});", expr_list(p)),
+    Printf(p) => format!("Printf(\"{}\"{});", escape(p.fmt), p.arg.iter().map(|e| ", ".to_owned() + &expr(e, 0)).collect::<String>()),
+    Break(b) => b.label.map(|l| format!("break {};", l)).unwrap_or_else(|| "break;".to_owned()),
+    Continue(c) => c.label.map(|l| format!("continue {};", l)).unwrap_or_else(|| "continue;".to_owned()),
+    Block(b) => block(b),
+  }
+}
+
+fn expr_list(l: &[Expr]) -> String { l.iter().map(|e| expr(e, 0)).collect::<Vec<_>>().join(", ") }
+
+// the precedence levels are the same as the `priority` in parser.rs, larger binds tighter
+const LAMBDA: u32 = 1;
+const TERNARY: u32 = 2;
+const UNARY: u32 = 13;
+const POSTFIX: u32 = 14;
+const ATOM: u32 = 15;
+
+fn bin_prec(op: BinOp) -> u32 {
+  use BinOp::*;
+  match op {
+    Or => 3, And => 4, BitOr => 5, BitXor => 6, BitAnd => 7, Eq | Ne => 8, Lt | Le | Gt | Ge => 9,
+    Shl | Shr => 10, Add | Sub => 11, Mul | Div | Mod => 12,
+  }
+}
+
+// a prefix operator is separated from its operand by a space if they can be lexed as one token, e.g.: `- -x`, not `--x`
+fn prefix(op: &str, r: String) -> String {
+  if r.starts_with('-') || r.starts_with('+') { format!("{} {}", op, r) } else { op.to_owned() + &r }
+}
+
+fn double(x: f64) -> String {
+  if x.is_infinite() { return "1.0e999".to_owned(); } // the literal is too large
+  // the lexer requires digits on both sides of `.`, e.g.: `1e100` should be `1.0e100`
+  let s = format!("{:?}", x);
+  match s.find('e') {
+    Some(i) if !s[..i].contains('.') => format!("{}.0{}", &s[..i], &s[i..]),
+    _ => s,
+  }
+}
+
+// `new int[2][1]` allocates a 2-D array, so a NewArray being indexed or sliced is parenthesized
+fn indexed(e: &Expr) -> String {
+  if let ExprKind::NewArray(_) = e.kind { format!("({})", expr(e, 0)) } else { expr(e, POSTFIX) }
+}
+
+// `min` is the lowest precedence that can be printed without parentheses in the context
+fn expr(e: &Expr, min: u32) -> String {
+  use ExprKind::*;
+  let (s, prec) = match &e.kind {
+    VarSel(v) => (v.owner.as_ref().map(|o| expr(o, POSTFIX) + ".").unwrap_or_default() + v.name, ATOM),
+    IndexSel(i) => (format!("{}[{}]", indexed(&i.arr), expr(&i.idx, 0)), POSTFIX),
+    Slice(s) => (format!("{}[{}..{}]", indexed(&s.arr), expr(&s.lo, 0), expr(&s.hi, 0)), POSTFIX),
+    // a negative literal comes from a non-decimal literal, `-1` would be parsed as a Unary
+    IntLit(x) => (if *x < 0 { format!("0x{:X}", *x as u32) } else { x.to_string() }, ATOM),
+    CharLit(x) => (format!("'{}'", if *x == '\'' { "\\'".to_owned() } else { escape(&x.to_string()) }), ATOM),
+    DoubleLit(x) => (double(*x), ATOM),
+    BoolLit(x) => (x.to_string(), ATOM),
+    StringLit(x) => (format!("\"{}\"", escape(x)), ATOM),
+    NullLit(_) => ("null".to_owned(), ATOM),
+    Call(c) => (format!("{}({})", expr(&c.func, POSTFIX), expr_list(&c.arg)), POSTFIX),
+    Unary(u) => (prefix(match u.op { UnOp::Neg => "-", UnOp::Not => "!", UnOp::BitNot => "~" }, expr(&u.r, UNARY)), UNARY),
+    Binary(b) => {
+      let prec = bin_prec(b.op);
+      // relational operators are not associative, so neither side can be at the same level
+      let l_min = if prec == 9 { prec + 1 } else { prec };
+      (format!("{} {} {}", expr(&b.l, l_min), b.op.to_op_str(), expr(&b.r, prec + 1)), prec)
+    }
+    IncDec(i) => {
+      let op = if i.op == BinOp::Add { "++" } else { "--" };
+      if i.prefix { (prefix(op, expr(&i.e, UNARY)), UNARY) } else { (expr(&i.e, POSTFIX) + op, POSTFIX) }
+    }
+    // always parenthesized, because the ll parser only accepts `(a = b)` in expressions
+    Assign(a) => (format!("({} = {})", expr(&a.dst, 0), expr(&a.src, 0)), ATOM),
+    Ternary(t) => (format!("{} ? {} : {}", expr(&t.cond, TERNARY + 1), expr(&t.on_true, 0), expr(&t.on_false, TERNARY)), TERNARY),
+    ArrayLit(a) => (format!("{{{}}}", expr_list(a)), ATOM),
+    Tuple(t) => (format!("({})", expr_list(&t.elem)), ATOM),
+    // `t.0.1` and `1.0` are lexed with a double literal, so such a tuple is parenthesized
+    TupleSel(t) => {
+      let tuple = match t.tuple.kind { TupleSel(_) | IntLit(_) => format!("({})", expr(&t.tuple, 0)), _ => expr(&t.tuple, POSTFIX) };
+      (format!("{}.{}", tuple, t.idx), POSTFIX)
+    }
+    This(_) => ("this".to_owned(), ATOM),
+    Super(_) => ("super".to_owned(), ATOM),
+    ReadInt(_) => ("ReadInteger()".to_owned(), ATOM),
+    ReadDouble(_) => ("ReadDouble()".to_owned(), ATOM),
+    ReadLine(_) => ("ReadLine()".to_owned(), ATOM),
+    NewClass(n) => (format!("new {}{}({})", n.name, n.ty_arg.as_ref().map(ty_arg).unwrap_or_default(), expr_list(&n.arg)), ATOM),
+    NewArray(n) => {
+      // the dimensions with inner lengths are printed after the length, like `new int[][m][n]` for `elem` = int[][]
+      let elem = syn_ty(&n.elem);
+      let sub_len = n.sub_len.iter().map(|l| format!("[{}]", expr(l, 0))).collect::<String>();
+      (format!("new {}[{}]{}", &elem[..elem.len() - 2 * n.sub_len.len()], expr(&n.len, 0), sub_len), ATOM)
+    }
+    ClassTest(c) => (format!("instanceof({}, {})", expr(&c.expr, 0), c.name), ATOM),
+    ClassCast(c) => (format!("(class {}) {}", c.name, expr(&c.expr, UNARY)), UNARY),
+    Lambda(l) => {
+      let param = l.param.iter().map(|v| var_def(v)).collect::<Vec<_>>().join(", ");
+      let body = match &l.body { LambdaBody::Expr(e) => expr(e, 0), LambdaBody::Block(b) => block(b) };
+      (format!("({}) => {}", param, body), LAMBDA)
+    }
+  };
+  if prec < min { format!("({})", s) } else { s }
+}
@@ -0,0 +1,213 @@
+use common::{Loc, Error, Errors, json_escape};
+use syntax::*;
+use std::fmt;
+
+// the typed ast in json, it should be called after typeck
+// every node is an object with "node" (its name in print::ast) and "loc", every expression also has its "ty"
+// the resolved symbols are represented by ids like "var:a@3:5", which are stable between runs, unlike the addresses
+pub fn program(pr: &Program) -> String { pr.json() }
+
+// the errors and warnings in json, every one is an object with kind, message, severity, and the start & end (exclusive) position
+pub fn errors<Ty: fmt::Debug>(e: &Errors<Ty>) -> String { e.0.json() }
+
+trait Json {
+  fn json(&self) -> String;
+}
+
+// generate a impl block for types whose Display is already valid json
+macro_rules! json_basic {
+  ($($t: ty)*) => {$(
+    impl Json for $t {
+      fn json(&self) -> String { self.to_string() }
+    }
+  )*};
+}
+
+json_basic!(i32 u32 bool);
+
+// json has no nan or infinity, they are null, like `JSON.stringify` in javascript
+impl Json for f64 {
+  fn json(&self) -> String { if self.is_finite() { self.to_string() } else { "null".to_owned() } }
+}
+
+impl Json for str {
+  fn json(&self) -> String { format!("\"{}\"", json_escape(self)) }
+}
+
+impl Json for String {
+  fn json(&self) -> String { self.as_str().json() }
+}
+
+impl Json for char {
+  fn json(&self) -> String { self.to_string().json() }
+}
+
+impl Json for Loc {
+  fn json(&self) -> String { format!("[{},{}]", self.0, self.1) }
+}
+
+impl Json for Ty<'_> {
+  fn json(&self) -> String { format!("{:?}", self).json() }
+}
+
+impl<T: Json> Json for [T] {
+  fn json(&self) -> String { format!("[{}]", self.iter().map(|x| x.json()).collect::<Vec<_>>().join(",")) }
+}
+
+impl<T: Json> Json for Vec<T> {
+  fn json(&self) -> String { self.as_slice().json() }
+}
+
+impl<T: Json> Json for Option<T> {
+  fn json(&self) -> String { if let Some(x) = self { x.json() } else { "null".to_owned() } }
+}
+
+impl<T: Json + ?Sized> Json for Box<T> {
+  fn json(&self) -> String { self.as_ref().json() }
+}
+
+impl<T: Json + ?Sized> Json for &T {
+  fn json(&self) -> String { (*self).json() }
+}
+
+// generate an object, $field are `key => value` pairs, the keys are identifiers, and the values implement Json
+macro_rules! json_obj {
+  ($($k: ident => $v: expr),*) => {
+    format!("{{{}}}", [$(format!("\"{}\":{}", stringify!($k), $v.json())),*].join(","))
+  };
+}
+
+// (line, col) in the json of errors
+struct Pos(u32, u32);
+
+impl Json for Pos {
+  fn json(&self) -> String { json_obj!(line => self.0, col => self.1) }
+}
+
+impl<Ty: fmt::Debug> Json for Error<'_, Ty> {
+  fn json(&self) -> String {
+    let Loc(l, c, end) = self.0;
+    json_obj!(kind => self.1.name(), message => format!("{:?}", self.1), severity => if self.is_warning() { "warning" } else { "error" },
+      start => Pos(l, c), end => Pos(l, end))
+  }
+}
+
+fn class_id(c: &ClassDef) -> String { format!("class:{}", c.name) }
+
+fn func_id(f: &FuncDef) -> String { format!("func:{}.{}@{}:{}", f.class.get().map(|c| c.name).unwrap_or(""), f.name, f.loc.0, f.loc.1) }
+
+fn var_id(v: &VarDef) -> String { format!("var:{}@{}:{}", v.name, v.loc.0, v.loc.1) }
+
+// generate an ast node, it is an object with "node" and "loc" before other fields
+macro_rules! obj {
+  ($node: expr, $loc: expr $(, $k: ident => $v: expr)*) => { json_obj!(node => $node, loc => $loc $(, $k => $v)*) };
+}
+
+impl Json for Program<'_> {
+  // self.class[0] must be valid, because parser requires their are at least one class
+  fn json(&self) -> String { obj!("TopLevel", self.class[0].loc, alias => self.alias, class => self.class) }
+}
+
+impl Json for TypeAlias<'_> {
+  fn json(&self) -> String { obj!("TypeAlias", self.loc, name => self.name, ty => self.ty.get()) }
+}
+
+impl Json for ClassDef<'_> {
+  fn json(&self) -> String {
+    obj!("ClassDef", self.loc, id => class_id(self), name => self.name, ty_param => self.ty_param, parent => self.parent_ref.get().map(class_id),
+      abstract_ => self.abstract_, interface => self.interface, implements => self.implements_ref.borrow().iter().map(|&c| class_id(c)).collect::<Vec<_>>(), field => self.field)
+  }
+}
+
+impl Json for FieldDef<'_> {
+  fn json(&self) -> String {
+    match self {
+      FieldDef::VarDef(v) => v.json(),
+      FieldDef::FuncDef(f) => obj!("MethodDef", f.loc, id => func_id(f), name => f.name, static_ => f.static_, abstract_ => f.abstract_, ctor => f.ctor,
+        ret => f.ret_param_ty.get().map(|rp| rp[0]), param => f.param, body => f.body),
+    }
+  }
+}
+
+impl Json for VarDef<'_> {
+  fn json(&self) -> String {
+    obj!("VarDef", self.loc, id => var_id(self), name => self.name, ty => self.ty.get(), static_ => self.static_, final_ => self.final_, init => self.init())
+  }
+}
+
+impl Json for Block<'_> {
+  fn json(&self) -> String { obj!("Block", self.loc, stmt => self.stmt) }
+}
+
+impl Json for Case<'_> {
+  fn json(&self) -> String { obj!("Case", self.loc, label => self.label, body => self.body) }
+}
+
+impl Json for Stmt<'_> {
+  fn json(&self) -> String {
+    use StmtKind::*;
+    let loc = self.loc;
+    match &self.kind {
+      Assign(x) => obj!("Assign", loc, op => x.op.map(|op| op.to_op_str()), dst => x.dst, src => x.src),
+      LocalVarDef(x) => obj!("LocalVarDef", loc, var => x),
+      ExprEval(x) => obj!("ExprEval", loc, expr => x),
+      Skip(_) => obj!("Skip", loc),
+      If(x) => obj!("If", loc, cond => x.cond, on_true => x.on_true, on_false => x.on_false),
+      While(x) => obj!("While", loc, label => x.label, cond => x.cond, body => x.body),
+      DoWhile(x) => obj!("DoWhile", loc, label => x.label, body => x.body, cond => x.cond),
+      For(x) => obj!("For", loc, label => x.label, init => x.init, cond => x.cond, update => x.update, body => x.body),
+      Foreach(x) => obj!("Foreach", loc, label => x.label, var => x.var, arr => x.arr, body => x.body),
+      Switch(x) => obj!("Switch", loc, expr => x.expr, case => x.case, default => x.default.first()),
+      Return(x) => obj!("Return", loc, expr => x),
+      Print(x) => obj!("Print", loc, expr => x),
+      Printf(x) => obj!("Printf", loc, fmt => x.fmt, arg => x.arg),
+      Break(x) => obj!("Break", loc, label => x.label),
+      Continue(x) => obj!("Continue", loc, label => x.label),
+      Block(x) => x.json(),
+    }
+  }
+}
+
+impl Json for LambdaBody<'_> {
+  fn json(&self) -> String {
+    match self { LambdaBody::Expr(e) => e.json(), LambdaBody::Block(b) => b.json() }
+  }
+}
+
+impl Json for Expr<'_> {
+  fn json(&self) -> String {
+    use ExprKind::*;
+    let (loc, ty) = (self.loc, self.ty.get());
+    match &self.kind {
+      VarSel(x) => obj!("VarSel", loc, ty => ty, owner => x.owner, name => x.name, var => x.var.get().map(var_id)),
+      IndexSel(x) => obj!("IndexSel", loc, ty => ty, arr => x.arr, idx => x.idx),
+      Slice(x) => obj!("Slice", loc, ty => ty, arr => x.arr, lo => x.lo, hi => x.hi),
+      IntLit(x) => obj!("IntLit", loc, ty => ty, value => x),
+      CharLit(x) => obj!("CharLit", loc, ty => ty, value => x),
+      DoubleLit(x) => obj!("DoubleLit", loc, ty => ty, value => x),
+      BoolLit(x) => obj!("BoolLit", loc, ty => ty, value => x),
+      StringLit(x) => obj!("StringLit", loc, ty => ty, value => x),
+      NullLit(_) => obj!("NullLit", loc, ty => ty),
+      Call(x) => obj!("Call", loc, ty => ty, func => x.func, arg => x.arg, func_ref => x.func_ref.get().map(func_id)),
+      Unary(x) => obj!("Unary", loc, ty => ty, op => x.op.to_op_str(), r => x.r),
+      Binary(x) => obj!("Binary", loc, ty => ty, op => x.op.to_op_str(), l => x.l, r => x.r),
+      IncDec(x) => obj!("IncDec", loc, ty => ty, op => if x.op == common::BinOp::Add { "++" } else { "--" }, prefix => x.prefix, e => x.e),
+      Assign(x) => obj!("Assign", loc, ty => ty, dst => x.dst, src => x.src),
+      Ternary(x) => obj!("Ternary", loc, ty => ty, cond => x.cond, on_true => x.on_true, on_false => x.on_false),
+      ArrayLit(x) => obj!("ArrayLit", loc, ty => ty, elem => x),
+      Tuple(x) => obj!("Tuple", loc, ty => ty, elem => x.elem),
+      TupleSel(x) => obj!("TupleSel", loc, ty => ty, tuple => x.tuple, idx => x.idx),
+      This(_) => obj!("This", loc, ty => ty),
+      Super(_) => obj!("Super", loc, ty => ty),
+      ReadInt(_) => obj!("ReadInt", loc, ty => ty),
+      ReadDouble(_) => obj!("ReadDouble", loc, ty => ty),
+      ReadLine(_) => obj!("ReadLine", loc, ty => ty),
+      NewClass(x) => obj!("NewClass", loc, ty => ty, name => x.name, arg => x.arg, class => x.class.get().map(class_id), ctor => x.ctor.get().map(func_id)),
+      NewArray(x) => obj!("NewArray", loc, ty => ty, len => x.len, sub_len => x.sub_len),
+      ClassTest(x) => obj!("ClassTest", loc, ty => ty, expr => x.expr, name => x.name, class => x.class.get().map(class_id)),
+      ClassCast(x) => obj!("ClassCast", loc, ty => ty, expr => x.expr, name => x.name, class => x.class.get().map(class_id)),
+      Lambda(x) => obj!("Lambda", loc, ty => ty, param => x.param, body => x.body,
+        captured => x.captured.borrow().iter().map(|v| var_id(v)).collect::<Vec<_>>()),
+    }
+  }
+}
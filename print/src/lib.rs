@@ -1,4 +1,8 @@
 pub mod ast;
 pub mod scope;
 pub mod tac;
-pub mod mips;
\ No newline at end of file
+pub mod mips;
+pub mod llvm;
+pub mod wasm;
+pub mod json;
+pub mod decaf;
\ No newline at end of file
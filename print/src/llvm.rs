@@ -0,0 +1,218 @@
+use common::{IndentPrinter, IgnoreResult, BinOp, UnOp};
+use tac::{TacProgram, Tac, CallKind, Operand, Intrinsic::{self, *}};
+use tacopt::bb::{FuncBB, NextKind};
+use std::fmt::Write;
+
+// textual llvm ir, every value in tac is a 32-bit word, so the target has 32-bit pointers, a word holding an address is converted by inttoptr
+// the runtime functions (the intrinsics in tac) are declared as external functions, they should be provided when linking
+// all pointers are opaque (`ptr`), so the output needs llvm 15 or later (or llvm 14 with `-opaque-pointers`)
+
+// string literal in llvm ir, with the terminating '\0', returns (literal, length)
+fn str_lit(s: &str) -> (String, usize) {
+  let mut ret = String::new();
+  for &b in s.as_bytes().iter().chain(&[0]) {
+    if b.is_ascii_graphic() && b != b'"' && b != b'\\' || b == b' ' { ret.push(b as char); } else { write!(ret, "\\{:02X}", b).ignore(); }
+  }
+  (ret, s.len() + 1)
+}
+
+fn args(n: u32) -> String { (0..n).map(|_| "i32").collect::<Vec<_>>().join(", ") }
+
+pub fn data(pr: &TacProgram, p: &mut IndentPrinter) {
+  write!(p, "target datalayout = \"e-p:32:32-i64:64-n32-S32\"").ignore();
+  writeln!(p).ignore();
+  for v in &pr.vtbl {
+    // the layout is the same as in mips: parent, class name, functions
+    let mut elem = vec![match v.parent {
+      Some(pa) => format!("i32 ptrtoint (ptr @\"_{}\" to i32)", pr.vtbl[pa as usize].class),
+      None => "i32 0".to_owned(),
+    }];
+    elem.push(format!("i32 ptrtoint (ptr @_STRING{} to i32)", pr.str_pool.get_full(v.class).expect("tacgen should have put class name into `str_pool`").0));
+    for &f in &v.func { elem.push(format!("i32 ptrtoint (ptr @\"{}\" to i32)", pr.func[f as usize].name)); }
+    write!(p, "@\"_{}\" = global [{} x i32] [{}]", v.class, elem.len(), elem.join(", ")).ignore();
+  }
+  for s in &pr.static_var { write!(p, "@\"{}\" = global i32 0", s).ignore(); }
+  for (idx, s) in pr.str_pool.iter().enumerate() {
+    let (s, len) = str_lit(s);
+    write!(p, "@_STRING{} = private constant [{} x i8] c\"{}\"", idx, len, s).ignore();
+  }
+  writeln!(p).ignore();
+  for &i in Intrinsic::ALL {
+    write!(p, "declare {} @{:?}({}){}", if i.has_ret() { "i32" } else { "void" }, i, args(i.param_num()), if i == _Halt { " noreturn" } else { "" }).ignore();
+  }
+  // used to clear the memory of `StackAlloc`
+  write!(p, "declare void @llvm.memset.p0.i32(ptr, i8, i32, i1)").ignore();
+  writeln!(p).ignore();
+}
+
+// registers are allocas, their loads and stores are expected to be promoted by llvm's mem2reg
+struct FuncWriter<'a, 'b> {
+  p: &'b mut IndentPrinter,
+  pr: &'b TacProgram<'a>,
+  tmp: u32,
+  // the args of `Param`s before a `Call`
+  param: Vec<String>,
+}
+
+impl FuncWriter<'_, '_> {
+  fn tmp(&mut self) -> String { (format!("%t{}", self.tmp), self.tmp += 1).0 }
+
+  // returns the value of `o`, loading it if it is a register
+  fn op(&mut self, o: Operand) -> String {
+    match o {
+      Operand::Const(c) => c.to_string(),
+      Operand::Reg(r) => {
+        let t = self.tmp();
+        write!(self.p, "{} = load i32, ptr %r{}", t, r).ignore();
+        t
+      }
+    }
+  }
+
+  fn store(&mut self, dst: u32, val: &str) { write!(self.p, "store i32 {}, ptr %r{}", val, dst).ignore(); }
+
+  // the address `base + off` as a pointer
+  fn addr(&mut self, base: Operand, off: i32) -> String {
+    let (base, (a, ptr)) = (self.op(base), (self.tmp(), self.tmp()));
+    write!(self.p, "{} = add i32 {}, {}", a, base, off).ignore();
+    write!(self.p, "{} = inttoptr i32 {} to ptr", ptr, a).ignore();
+    ptr
+  }
+
+  fn tac(&mut self, t: Tac) {
+    use Tac::*;
+    match t {
+      Bin { op, dst, lr } => {
+        let (mut l, mut r, t) = (self.op(lr[0]), self.op(lr[1]), self.tmp());
+        let inst = match op {
+          BinOp::Add => "add", BinOp::Sub => "sub", BinOp::Mul => "mul", BinOp::Div => "sdiv", BinOp::Mod => "srem",
+          BinOp::And | BinOp::BitAnd => "and", BinOp::Or | BinOp::BitOr => "or", BinOp::BitXor => "xor", BinOp::Shl => "shl", BinOp::Shr => "ashr",
+          BinOp::Eq => "icmp eq", BinOp::Ne => "icmp ne", BinOp::Lt => "icmp slt", BinOp::Le => "icmp sle", BinOp::Gt => "icmp sgt", BinOp::Ge => "icmp sge",
+        };
+        if op == BinOp::Shl || op == BinOp::Shr { // the shift amount is taken mod 32, like `BinOp::eval`
+          let amount = self.tmp();
+          write!(self.p, "{} = and i32 {}, 31", amount, r).ignore();
+          r = amount;
+        }
+        // sdiv and srem of i32::MIN by -1 are undefined, to wrap like `BinOp::eval`, the divisor -1 is replaced by 1, and the dividend is negated for sdiv
+        if (op == BinOp::Div || op == BinOp::Mod) && match lr[1] { Operand::Const(c) => c == -1, Operand::Reg(_) => true } {
+          let (neg_one, one) = (self.tmp(), self.tmp());
+          write!(self.p, "{} = icmp eq i32 {}, -1", neg_one, r).ignore();
+          write!(self.p, "{} = select i1 {}, i32 1, i32 {}", one, neg_one, r).ignore();
+          if op == BinOp::Div {
+            let (neg, dividend) = (self.tmp(), self.tmp());
+            write!(self.p, "{} = sub i32 0, {}", neg, l).ignore();
+            write!(self.p, "{} = select i1 {}, i32 {}, i32 {}", dividend, neg_one, neg, l).ignore();
+            l = dividend;
+          }
+          r = one;
+        }
+        write!(self.p, "{} = {} i32 {}, {}", t, inst, l, r).ignore();
+        if inst.starts_with("icmp") {
+          let ext = self.tmp();
+          write!(self.p, "{} = zext i1 {} to i32", ext, t).ignore();
+          self.store(dst, &ext);
+        } else { self.store(dst, &t); }
+      }
+      Un { op, dst, r } => {
+        let (r, t) = (self.op(r[0]), self.tmp());
+        match op {
+          UnOp::Neg => write!(self.p, "{} = sub i32 0, {}", t, r),
+          UnOp::BitNot => write!(self.p, "{} = xor i32 {}, -1", t, r),
+          UnOp::Not => {
+            let cmp = self.tmp();
+            write!(self.p, "{} = icmp eq i32 {}, 0", cmp, r).ignore();
+            write!(self.p, "{} = zext i1 {} to i32", t, cmp)
+          }
+        }.ignore();
+        self.store(dst, &t);
+      }
+      Assign { dst, src } => {
+        let src = self.op(src[0]);
+        self.store(dst, &src);
+      }
+      Param { src } => {
+        let src = self.op(src[0]);
+        self.param.push(format!("i32 {}", src));
+      }
+      Call { dst, kind } => {
+        let (ret, func) = match kind {
+          CallKind::Virtual(fp, _) => {
+            let (fp, ptr) = (self.op(fp[0]), self.tmp());
+            write!(self.p, "{} = inttoptr i32 {} to ptr", ptr, fp).ignore();
+            ("i32", ptr)
+          }
+          CallKind::Static(f, _) => ("i32", format!("@\"{}\"", self.pr.func[f as usize].name)),
+          CallKind::Intrinsic(i) => (if i.has_ret() { "i32" } else { "void" }, format!("@{:?}", i)),
+        };
+        let param = std::mem::take(&mut self.param).join(", ");
+        if let Some(dst) = dst {
+          let t = self.tmp();
+          write!(self.p, "{} = call {} {}({})", t, ret, func, param).ignore();
+          self.store(dst, &t);
+        } else { write!(self.p, "call {} {}({})", ret, func, param).ignore(); }
+      }
+      Load { dst, base, off, .. } => {
+        let (ptr, t) = (self.addr(base[0], off), self.tmp());
+        write!(self.p, "{} = load i32, ptr {}", t, ptr).ignore();
+        self.store(dst, &t);
+      }
+      Store { src_base, off, .. } => {
+        let (src, ptr) = (self.op(src_base[0]), self.addr(src_base[1], off));
+        write!(self.p, "store i32 {}, ptr {}", src, ptr).ignore();
+      }
+      LoadStr { dst, s } => self.store(dst, &format!("ptrtoint (ptr @_STRING{} to i32)", s)),
+      LoadVTbl { dst, v } => self.store(dst, &format!("ptrtoint (ptr @\"_{}\" to i32)", self.pr.vtbl[v as usize].class)),
+      LoadFunc { dst, f } => self.store(dst, &format!("ptrtoint (ptr @\"{}\" to i32)", self.pr.func[f as usize].name)),
+      LoadStatic { dst, v } => self.store(dst, &format!("ptrtoint (ptr @\"{}\" to i32)", self.pr.static_var[v as usize])),
+      // StackAlloc is never in a loop, so an alloca outside the entry block is executed at most once
+      StackAlloc { dst, size } => {
+        let (ptr, t) = (self.tmp(), self.tmp());
+        write!(self.p, "{} = alloca i8, i32 {}, align 4", ptr, size).ignore();
+        write!(self.p, "call void @llvm.memset.p0.i32(ptr {}, i8 0, i32 {}, i1 false)", ptr, size).ignore();
+        write!(self.p, "{} = ptrtoint ptr {} to i32", t, ptr).ignore();
+        self.store(dst, &t);
+      }
+      Ret { .. } | Jmp { .. } | Jif { .. } | Label { .. } => unreachable!("FuncBB doesn't contain control flow tac in bb."),
+    }
+  }
+}
+
+// every function returns i32, a function returning void returns 0
+pub fn func(f: &FuncBB, pr: &TacProgram, p: &mut IndentPrinter) {
+  let param = (0..f.param_num).map(|i| format!("i32 %a{}", i)).collect::<Vec<_>>().join(", ");
+  write!(p, "define i32 @\"{}\"({}) {{", f.name, param).ignore();
+  write!(p, "entry:").ignore();
+  p.indent(|p| {
+    for r in 0..f.reg_num { write!(p, "%r{} = alloca i32", r).ignore(); }
+    for i in 0..f.param_num { write!(p, "store i32 %a{}, ptr %r{}", i, i).ignore(); }
+    write!(p, "br label %L0").ignore();
+  });
+  let mut tmp = 0; // the temporary values' names are unique in a function
+  for (idx, b) in f.bb.iter().enumerate() {
+    write!(p, "L{}:", idx).ignore();
+    p.indent(|p| {
+      let mut fw = FuncWriter { p, pr, tmp, param: vec![] };
+      for t in b.iter() { fw.tac(t.tac.get()); }
+      match b.next {
+        NextKind::Ret(src) => {
+          let src = src.map(|src| fw.op(src)).unwrap_or_else(|| "0".to_owned());
+          write!(fw.p, "ret i32 {}", src).ignore();
+        }
+        NextKind::Jmp(jump) => write!(fw.p, "br label %L{}", jump).ignore(),
+        NextKind::Jif { cond, z, fail, jump } => {
+          let (cond, t) = (fw.op(Operand::Reg(cond)), fw.tmp());
+          write!(fw.p, "{} = icmp {} i32 {}, 0", t, if z { "eq" } else { "ne" }, cond).ignore();
+          write!(fw.p, "br i1 {}, label %L{}, label %L{}", t, jump, fail).ignore();
+        }
+        NextKind::Halt => {
+          write!(fw.p, "call void @_Halt()").ignore();
+          write!(fw.p, "unreachable").ignore();
+        }
+      }
+      tmp = fw.tmp;
+    });
+  }
+  write!(p, "}}").ignore();
+  writeln!(p).ignore();
+}
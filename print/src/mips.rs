@@ -1,4 +1,4 @@
-use common::{IndentPrinter, IgnoreResult};
+use common::{IndentPrinter, IgnoreResult, escape};
 use tac::{TacProgram};
 use codegen::mips::AsmTemplate;
 use std::fmt::Write;
@@ -20,11 +20,15 @@ pub fn data(pr: &TacProgram, p: &mut IndentPrinter) {
       }
     });
   }
+  for s in &pr.static_var {
+    write!(p, "{}:", s).ignore();
+    p.indent(|p| write!(p, ".word 0").ignore());
+  }
   writeln!(p).ignore();
   write!(p, ".data").ignore();
   for (idx, s) in pr.str_pool.iter().enumerate() {
     write!(p, "_STRING{}:", idx).ignore();
-    p.indent(|p| write!(p, ".asciiz \"{}\"", s).ignore());
+    p.indent(|p| write!(p, ".asciiz \"{}\"", escape(s)).ignore());
   }
   writeln!(p).ignore();
 }
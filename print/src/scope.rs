@@ -1,9 +1,13 @@
 use common::{IndentPrinter, IgnoreResult};
-use syntax::{ast::*, Scope};
+use syntax::{ast::*, Scope, Symbol};
 use std::fmt::Write;
 
 fn show_scope(s: &Scope, p: &mut IndentPrinter) {
-  let mut s = s.iter().map(|(_, &sym)| sym).collect::<Vec<_>>();
+  // overloaded methods are not in scope, except the first one
+  let mut s = s.iter().flat_map(|(_, &sym)| match sym {
+    Symbol::Func(f) => f.overloads().map(Symbol::Func).collect(),
+    sym => vec![sym],
+  }).collect::<Vec<_>>();
   s.sort_unstable_by_key(|x| x.loc());
   if s.is_empty() { write!(p, "<empty>").ignore(); } else { for s in s { write!(p, "{:?}", s).ignore(); } }
 }
@@ -30,7 +34,7 @@ pub fn func_def(f: &FuncDef, p: &mut IndentPrinter) {
   write!(p, "FORMAL SCOPE OF '{}':", f.name).ignore();
   p.indent(|p| {
     show_scope(&f.scope.borrow(), p);
-    block(&f.body, p);
+    if let Some(b) = &f.body { block(b, p); }
   });
 }
 
@@ -45,7 +49,13 @@ pub fn block(b: &Block, p: &mut IndentPrinter) {
           if let Some(on_false) = &i.on_false { block(on_false, p); }
         }
         StmtKind::While(w) => block(&w.body, p),
+        StmtKind::DoWhile(d) => block(&d.body, p),
         StmtKind::For(f) => block(&f.body, p),
+        StmtKind::Foreach(f) => block(&f.body, p),
+        StmtKind::Switch(s) => {
+          for c in &s.case { block(&c.body, p); }
+          for d in &s.default { block(d, p); }
+        }
         StmtKind::Block(b) => block(b, p),
         _ => {}
       }
@@ -1,5 +1,5 @@
 use tac::{TacProgram, Tac, CallKind};
-use common::{IndentPrinter, IgnoreResult};
+use common::{IndentPrinter, IgnoreResult, escape};
 use std::fmt::Write;
 
 pub fn program(pr: &TacProgram, p: &mut IndentPrinter) {
@@ -47,8 +47,10 @@ pub fn write_tac(t: Tac, pr: &TacProgram, p: &mut IndentPrinter) {
     Label { label } => write!(p, "%{}:", label),
     Load { dst, base, off, .. } => write!(p, "%{} = *({:?} {} {})", dst, base[0], if off >= 0 { '+' } else { '-' }, off.abs()),
     Store { src_base, off, .. } => write!(p, "*({:?} {} {}) = {:?}", src_base[1], if off >= 0 { '+' } else { '-' }, off.abs(), src_base[0]),
-    LoadStr { dst, s } => write!(p, "%{} = \"{}\"", dst, pr.str_pool.get_index(s as usize).unwrap()),
+    LoadStr { dst, s } => write!(p, "%{} = \"{}\"", dst, escape(pr.str_pool.get_index(s as usize).unwrap())),
     LoadVTbl { dst, v } => write!(p, "%{} = VTBL<_{}>", dst, pr.vtbl[v as usize].class),
     LoadFunc { dst, f } => write!(p, "%{} = FUNC<{}>", dst, pr.func[f as usize].name),
+    LoadStatic { dst, v } => write!(p, "%{} = STATIC<{}>", dst, pr.static_var[v as usize]),
+    StackAlloc { .. } => unreachable!("StackAlloc is only used in mips and llvm codegen."),
   }.ignore();
 }
\ No newline at end of file
@@ -0,0 +1,224 @@
+use common::{IndentPrinter, IgnoreResult, BinOp, UnOp};
+use tac::{TacProgram, Tac, CallKind, Operand, Intrinsic::{self, *}, INT_SIZE};
+use tacopt::bb::{FuncBB, NextKind};
+use std::fmt::Write;
+
+// webassembly text format, the output should be wrapped by `data` and `finish`, with all functions in the middle
+// - objects, arrays, strings, vtbls and static fields are in the linear memory, address 0 is left for null
+// - all functions are in the table in the order of `TacProgram::func`, so a function's index in the table is its index in `func`
+// - the intrinsics are imported from module "decaf" provided by the host, except `_Alloc`, which is a bump allocator in the module
+
+const PAGE_SIZE: u32 = 65536;
+
+// the addresses of the data in the linear memory
+struct Layout {
+  str: Vec<u32>,
+  vtbl: Vec<u32>,
+  static_var: Vec<u32>,
+  // the heap starts at the end of all data
+  heap: u32,
+}
+
+impl Layout {
+  fn new(pr: &TacProgram) -> Layout {
+    let align = |x: u32| (x + INT_SIZE as u32 - 1) & !(INT_SIZE as u32 - 1);
+    let mut cur = INT_SIZE as u32;
+    let mut alloc = |size: u32| (cur, cur = align(cur + size)).0;
+    let str = pr.str_pool.iter().map(|s| alloc(s.len() as u32 + 1)).collect();
+    let vtbl = pr.vtbl.iter().map(|v| alloc((v.func.len() as u32 + 2) * INT_SIZE as u32)).collect();
+    let static_var = pr.static_var.iter().map(|_| alloc(INT_SIZE as u32)).collect();
+    Layout { str, vtbl, static_var, heap: cur }
+  }
+}
+
+fn bytes(b: &[u8]) -> String {
+  let mut ret = String::new();
+  for &b in b {
+    if b.is_ascii_graphic() && b != b'"' && b != b'\\' || b == b' ' { ret.push(b as char); } else { write!(ret, "\\{:02x}", b).ignore(); }
+  }
+  ret
+}
+
+fn params(n: u32) -> String { (0..n).map(|_| " i32").collect::<Vec<_>>().concat() }
+
+pub fn data(pr: &TacProgram, p: &mut IndentPrinter) {
+  let l = Layout::new(pr);
+  write!(p, "(module").ignore();
+  for &i in Intrinsic::ALL.iter().filter(|&&i| i != _Alloc) {
+    let ret = if i.has_ret() { " (result i32)" } else { "" };
+    write!(p, "(import \"decaf\" \"{:?}\" (func ${:?} (param{}){}))", i, i, params(i.param_num()), ret).ignore();
+  }
+  // the types for call_indirect, indexed by the number of params
+  for n in 0..=pr.func.iter().map(|f| f.param_num).max().unwrap_or(0) {
+    write!(p, "(type $t{} (func (param{}) (result i32)))", n, params(n)).ignore();
+  }
+  write!(p, "(memory (export \"memory\") {})", l.heap.div_ceil(PAGE_SIZE)).ignore();
+  write!(p, "(table {} funcref)", pr.func.len()).ignore();
+  write!(p, "(elem (i32.const 0){})", pr.func.iter().map(|f| format!(" ${}", f.name)).collect::<Vec<_>>().concat()).ignore();
+  for (s, &addr) in pr.str_pool.iter().zip(&l.str) {
+    write!(p, "(data (i32.const {}) \"{}\\00\")", addr, bytes(s.as_bytes())).ignore();
+  }
+  for (v, &addr) in pr.vtbl.iter().zip(&l.vtbl) {
+    // the layout is the same as in mips: parent, class name, functions
+    let mut word = vec![v.parent.map(|pa| l.vtbl[pa as usize]).unwrap_or(0)];
+    word.push(l.str[pr.str_pool.get_full(v.class).expect("tacgen should have put class name into `str_pool`").0]);
+    word.extend(v.func.iter().copied());
+    write!(p, "(data (i32.const {}) \"{}\")", addr, bytes(&word.iter().flat_map(|w| w.to_le_bytes().to_vec()).collect::<Vec<_>>())).ignore();
+  }
+  write!(p, "(global $heap (mut i32) (i32.const {}))", l.heap).ignore();
+  // the memory grown is filled with 0, so the allocated memory doesn't need to be cleared
+  write!(p, "(func $_Alloc (param $size i32) (result i32) (local $ret i32)").ignore();
+  p.indent(|p| {
+    for s in &["global.get $heap", "local.set $ret", "global.get $heap", "local.get $size", "i32.add", "i32.const 3", "i32.add", "i32.const -4", "i32.and", "global.set $heap",
+      "block $done", "loop $grow", "global.get $heap", "memory.size", "i32.const 65536", "i32.mul", "i32.le_u", "br_if $done",
+      "i32.const 1", "memory.grow", "drop", "br $grow", "end", "end", "local.get $ret"] {
+      write!(p, "{}", s).ignore();
+    }
+  });
+  write!(p, ")").ignore();
+  if let Some(main) = pr.func.iter().find(|f| f.name == common::MAIN_METHOD) {
+    write!(p, "(export \"main\" (func ${}))", main.name).ignore();
+  }
+}
+
+pub fn finish(p: &mut IndentPrinter) { write!(p, ")").ignore(); }
+
+fn op(o: Operand, p: &mut IndentPrinter) {
+  match o {
+    Operand::Const(c) => write!(p, "i32.const {}", c),
+    Operand::Reg(r) => write!(p, "local.get {}", r),
+  }.ignore();
+}
+
+// the address `base + off`, the `offset` of load/store can't be negative, so it is computed by add
+fn addr(base: Operand, off: i32, p: &mut IndentPrinter) {
+  op(base, p);
+  write!(p, "i32.const {}", off).ignore();
+  write!(p, "i32.add").ignore();
+}
+
+fn tac(t: Tac, pr: &TacProgram, l: &Layout, param_num: &mut u32, p: &mut IndentPrinter) {
+  use Tac::*;
+  match t {
+    Bin { op: o, dst, lr } => {
+      // i32.div_s traps on i32::MIN / -1, to wrap like `BinOp::eval`, the dividend is negated and the divisor -1 is replaced by 1
+      // i32.rem_s doesn't trap on it, the result is 0
+      if o == BinOp::Div && match lr[1] { Operand::Const(c) => c == -1, Operand::Reg(_) => true } {
+        let select = |p: &mut IndentPrinter| {
+          op(lr[1], p);
+          write!(p, "i32.const -1").ignore();
+          write!(p, "i32.eq").ignore();
+          write!(p, "select").ignore();
+        };
+        write!(p, "i32.const 0").ignore();
+        op(lr[0], p);
+        write!(p, "i32.sub").ignore();
+        op(lr[0], p);
+        select(p);
+        write!(p, "i32.const 1").ignore();
+        op(lr[1], p);
+        select(p);
+      } else {
+        op(lr[0], p);
+        op(lr[1], p);
+      }
+      write!(p, "i32.{}", match o {
+        BinOp::Add => "add", BinOp::Sub => "sub", BinOp::Mul => "mul", BinOp::Div => "div_s", BinOp::Mod => "rem_s",
+        BinOp::And | BinOp::BitAnd => "and", BinOp::Or | BinOp::BitOr => "or", BinOp::BitXor => "xor", BinOp::Shl => "shl", BinOp::Shr => "shr_s",
+        BinOp::Eq => "eq", BinOp::Ne => "ne", BinOp::Lt => "lt_s", BinOp::Le => "le_s", BinOp::Gt => "gt_s", BinOp::Ge => "ge_s",
+      }).ignore();
+      write!(p, "local.set {}", dst).ignore();
+    }
+    Un { op: o, dst, r } => {
+      match o {
+        UnOp::Neg => (write!(p, "i32.const 0").ignore(), op(r[0], p), write!(p, "i32.sub").ignore()).2,
+        UnOp::Not => (op(r[0], p), write!(p, "i32.eqz").ignore()).1,
+        UnOp::BitNot => (op(r[0], p), write!(p, "i32.const -1").ignore(), write!(p, "i32.xor").ignore()).2,
+      }
+      write!(p, "local.set {}", dst).ignore();
+    }
+    Assign { dst, src } => (op(src[0], p), write!(p, "local.set {}", dst).ignore()).1,
+    // the args are pushed to the operand stack, and popped by the call
+    Param { src } => (op(src[0], p), *param_num += 1).1,
+    Call { dst, kind } => {
+      let has_ret = match kind {
+        CallKind::Virtual(fp, _) => {
+          op(fp[0], p);
+          write!(p, "call_indirect (type $t{})", *param_num).ignore();
+          true
+        }
+        CallKind::Static(f, _) => (write!(p, "call ${}", pr.func[f as usize].name).ignore(), true).1,
+        CallKind::Intrinsic(i) => (write!(p, "call ${:?}", i).ignore(), i.has_ret()).1,
+      };
+      *param_num = 0;
+      match dst {
+        Some(dst) => write!(p, "local.set {}", dst).ignore(),
+        None => if has_ret { write!(p, "drop").ignore(); }
+      }
+    }
+    Load { dst, base, off, .. } => {
+      addr(base[0], off, p);
+      write!(p, "i32.load").ignore();
+      write!(p, "local.set {}", dst).ignore();
+    }
+    Store { src_base, off, .. } => {
+      addr(src_base[1], off, p);
+      op(src_base[0], p);
+      write!(p, "i32.store").ignore();
+    }
+    LoadStr { dst, s } => (write!(p, "i32.const {}", l.str[s as usize]).ignore(), write!(p, "local.set {}", dst).ignore()).1,
+    LoadVTbl { dst, v } => (write!(p, "i32.const {}", l.vtbl[v as usize]).ignore(), write!(p, "local.set {}", dst).ignore()).1,
+    LoadFunc { dst, f } => (write!(p, "i32.const {}", f).ignore(), write!(p, "local.set {}", dst).ignore()).1,
+    LoadStatic { dst, v } => (write!(p, "i32.const {}", l.static_var[v as usize]).ignore(), write!(p, "local.set {}", dst).ignore()).1,
+    StackAlloc { .. } => unreachable!("StackAlloc is only used in mips and llvm codegen."),
+    Ret { .. } | Jmp { .. } | Jif { .. } | Label { .. } => unreachable!("FuncBB doesn't contain control flow tac in bb."),
+  }
+}
+
+// every function returns i32, a function returning void returns 0
+// the control flow graph is not structured, so the bbs are dispatched by `br_table` on `$pc` in a loop:
+// the bb i is placed after the i-th innermost block, so jumping to it is assigning `$pc` and continuing the loop
+pub fn func(f: &FuncBB, pr: &TacProgram, p: &mut IndentPrinter) {
+  let l = Layout::new(pr);
+  let local = (f.param_num..f.reg_num).map(|_| " i32").collect::<Vec<_>>().concat();
+  write!(p, "(func ${} (param{}) (result i32) (local{}) (local $pc i32)", f.name, params(f.param_num), local).ignore();
+  p.indent(|p| {
+    write!(p, "loop $dispatch").ignore();
+    for idx in (0..f.bb.len()).rev() { write!(p, "block $B{}", idx).ignore(); }
+    write!(p, "local.get $pc").ignore();
+    write!(p, "br_table{}", (0..f.bb.len()).map(|idx| format!(" $B{}", idx)).collect::<Vec<_>>().concat()).ignore();
+    let jmp = |jump: u32, p: &mut IndentPrinter| {
+      write!(p, "i32.const {}", jump).ignore();
+      write!(p, "local.set $pc").ignore();
+      write!(p, "br $dispatch").ignore();
+    };
+    for b in &f.bb {
+      write!(p, "end").ignore();
+      let mut param_num = 0;
+      for t in b.iter() { tac(t.tac.get(), pr, &l, &mut param_num, p); }
+      match b.next {
+        NextKind::Ret(src) => {
+          op(src.unwrap_or(Operand::Const(0)), p);
+          write!(p, "return").ignore();
+        }
+        NextKind::Jmp(jump) => jmp(jump, p),
+        NextKind::Jif { cond, z, fail, jump } => {
+          write!(p, "i32.const {}", jump).ignore();
+          write!(p, "i32.const {}", fail).ignore();
+          op(Operand::Reg(cond), p);
+          if z { write!(p, "i32.eqz").ignore(); }
+          write!(p, "select").ignore();
+          write!(p, "local.set $pc").ignore();
+          write!(p, "br $dispatch").ignore();
+        }
+        NextKind::Halt => {
+          write!(p, "call $_Halt").ignore();
+          write!(p, "unreachable").ignore();
+        }
+      }
+    }
+    write!(p, "end").ignore();
+    write!(p, "unreachable").ignore();
+  });
+  write!(p, ")").ignore();
+}
@@ -1,7 +1,7 @@
 use crate::{ty::*, symbol::*};
 use common::{Loc, Ref, BinOp, UnOp};
 use typed_arena::Arena;
-use std::cell::{Cell, RefCell};
+use std::{cell::{Cell, RefCell}, iter};
 
 #[derive(Default)]
 pub struct ASTAlloc<'a> {
@@ -9,6 +9,8 @@ pub struct ASTAlloc<'a> {
   pub(crate) func: Arena<FuncDef<'a>>,
   pub(crate) var: Arena<VarDef<'a>>,
   pub(crate) program: Arena<Program<'a>>,
+  // the decoded content of StringLit
+  pub(crate) str: Arena<String>,
 }
 
 // all Cell<Option<ref to ast node>> in `Program` and statements are initialized in symbol_pass.rs
@@ -16,28 +18,81 @@ pub struct ASTAlloc<'a> {
 
 pub struct Program<'a> {
   pub class: Vec<&'a ClassDef<'a>>,
+  pub alias: Vec<TypeAlias<'a>>,
   pub main: Cell<Option<&'a ClassDef<'a>>>,
   pub scope: RefCell<Scope<'a>>,
 }
 
+// `typedef int[] IntArray;`, the alias is referred to by its bare name in declarations, or like a class type: `class IntArray`
+pub struct TypeAlias<'a> {
+  pub loc: Loc,
+  pub name: &'a str,
+  pub syn_ty: SynTy<'a>,
+  // the underlying type, set when it is first resolved in typeck
+  pub ty: Cell<Option<Ty<'a>>>,
+  // true while resolving `syn_ty`, meeting the alias again in it means a cycle
+  pub resolving: Cell<bool>,
+}
+
 pub struct ClassDef<'a> {
   pub loc: Loc,
   pub name: &'a str,
+  // the type parameter of a generic class, e.g.: `T` in `class Box<T>`, it is referred to as `class T` in the class
+  pub ty_param: Option<&'a str>,
   pub parent: Option<&'a str>,
+  pub abstract_: bool,
+  // an interface is an abstract class with only abstract methods, it can't be extended, only implemented
+  pub interface: bool,
+  // the names of the implemented interfaces, with their locations for reporting errors
+  pub implements: Vec<(Loc, &'a str)>,
   pub field: Vec<FieldDef<'a>>,
+  // the location right after `}`
+  pub end: Loc,
   pub parent_ref: Cell<Option<&'a ClassDef<'a>>>,
+  pub implements_ref: RefCell<Vec<&'a ClassDef<'a>>>,
   pub scope: RefCell<Scope<'a>>,
 }
 
 impl<'a> ClassDef<'a> {
+  pub fn ctors(&self) -> Vec<&'a FuncDef<'a>> {
+    self.field.iter().filter_map(|f| match f { FieldDef::FuncDef(f) if f.ctor => Some(*f), _ => None }).collect()
+  }
+
+  // the constructor called without arguments, i.e., the one without parameters, or the first one whose parameters all have default values
+  pub fn default_ctor(&self) -> Option<&'a FuncDef<'a>> {
+    let ctors = self.ctors();
+    ctors.iter().find(|f| f.param.is_empty()).or_else(|| ctors.iter().find(|f| f.min_argc() == 0)).cloned()
+  }
+
+  // if `rhs` is an interface, check whether self or any of its ancestors implements it
   pub fn extends(&self, rhs: &ClassDef<'a>) -> bool {
     let mut c = self;
     loop {
-      if Ref(c) == Ref(rhs) { break true; }
+      if Ref(c) == Ref(rhs) || c.implements_ref.borrow().iter().any(|&i| Ref(i) == Ref(rhs)) { break true; }
       if let Some(p) = c.parent_ref.get() { c = p; } else { break false; }
     }
   }
 
+  // all methods named `name` in self and its ancestors, the overridden methods are excluded
+  // stop at the first non-method symbol named `name`, it hides all methods in ancestors
+  pub fn lookup_funcs(&self, name: &str) -> Vec<&'a FuncDef<'a>> {
+    let mut ret = Vec::<&FuncDef>::new();
+    let mut c = self;
+    loop {
+      match c.scope.borrow().get(name) {
+        Some(Symbol::Func(f)) => for f in f.overloads() {
+          if !ret.iter().any(|g| g.param_ty() == f.param_ty()) { ret.push(f); }
+        }
+        Some(_) => break ret,
+        None => {}
+      }
+      match c.parent_ref.get() {
+        Some(p) => c = p,
+        None => break ret,
+      }
+    }
+  }
+
   // will recursively lookup in all its parent
   pub fn lookup(&self, name: &str) -> Option<Symbol<'a>> {
     let mut c = self;
@@ -71,17 +126,35 @@ pub struct FuncDef<'a> {
   pub ret: SynTy<'a>,
   pub param: Vec<&'a VarDef<'a>>,
   pub static_: bool,
-  pub body: Block<'a>,
+  // abstract_ <=> body is None
+  pub abstract_: bool,
+  pub body: Option<Block<'a>>,
+  // the location right after the body, or after `;` for an abstract method
+  pub end: Loc,
   // placing ret and param ty in one slice is mainly to some space, especially the size of struct Ty
   // [0] is ret_ty, [1..] is parm_ty
   pub ret_param_ty: Cell<Option<&'a [Ty<'a>]>>,
   // `class` will always be set during typeck (no matter whether it is static)
   pub class: Cell<Option<&'a ClassDef<'a>>>,
+  // the next overloaded method with the same name in the same class, only the first one is in class scope
+  pub next: Cell<Option<&'a FuncDef<'a>>>,
+  // a constructor is written as `ClassName(params) { ... }`, its `ret` is void
+  // constructors are not in class scope, so they can't be called like methods, and they are not inherited
+  pub ctor: bool,
   pub scope: RefCell<Scope<'a>>,
 }
 
 impl<'a> FuncDef<'a> {
   pub fn ret_ty(&self) -> Ty<'a> { self.ret_param_ty.get().unwrap()[0] }
+
+  pub fn param_ty(&self) -> &'a [Ty<'a>] { &self.ret_param_ty.get().unwrap()[1..] }
+
+  // the count of parameters without default value, they can only be before those with default value
+  // the default values are constants, and they are evaluated at the call site, so `f()` is the same as `f(<default>)`
+  pub fn min_argc(&self) -> usize { self.param.iter().take_while(|v| v.init.is_none()).count() }
+
+  // self and all overloaded methods after it
+  pub fn overloads(&'a self) -> impl Iterator<Item=&'a FuncDef<'a>> { iter::successors(Some(self), |f| f.next.get()) }
 }
 
 pub struct VarDef<'a> {
@@ -89,10 +162,19 @@ pub struct VarDef<'a> {
   pub name: &'a str,
   pub syn_ty: SynTy<'a>,
   // if this is in an ClassDef, `init` must be None
-  // if `syn_ty` is `Var` (not in the basic framework), `init` must be Some
+  // if this is a parameter, `init` is its default value
+  // if `syn_ty` is `Var` (not in the basic framework), `init` should be Some, otherwise typeck reports CannotInferType
   pub init: Option<(Loc, Expr<'a>)>,
+  // the location right after the name, or after the init value if there is one
+  pub end: Loc,
   pub ty: Cell<Ty<'a>>,
   pub owner: Cell<Option<ScopeOwner<'a>>>,
+  // whether its value is ever read (assigning to it doesn't count), set in typeck
+  pub used: Cell<bool>,
+  // only fields can be static, a static field is stored once per class, instead of once per object
+  pub static_: bool,
+  // a final local must have an init value, a final field can only be assigned in the methods of its class
+  pub final_: bool,
 }
 
 impl<'a> VarDef<'a> {
@@ -101,6 +183,8 @@ impl<'a> VarDef<'a> {
 
 pub struct Stmt<'a> {
   pub loc: Loc,
+  // the location right after the last token of the statement (including `;`), NO_LOC for an empty Simple
+  pub end: Loc,
   pub kind: StmtKind<'a>,
 }
 
@@ -114,20 +198,29 @@ pub enum StmtKind<'a> {
   // rust-clippy tells me this variant's size is too big, use a Box to wrap it
   If(Box<If<'a>>),
   While(While<'a>),
+  DoWhile(DoWhile<'a>),
   For(For<'a>),
+  Foreach(Foreach<'a>),
+  Switch(Box<Switch<'a>>),
   Return(Option<Expr<'a>>),
   Print(Vec<Expr<'a>>),
-  Break(Break),
+  Printf(Printf<'a>),
+  Break(Break<'a>),
+  Continue(Continue<'a>),
   Block(Block<'a>),
 }
 
 pub struct Assign<'a> {
   pub dst: Expr<'a>,
   pub src: Expr<'a>,
+  // Some for compound assignment, e.g.: `op` of `a += b` is Some(Add)
+  pub op: Option<BinOp>,
 }
 
 pub struct Block<'a> {
   pub loc: Loc,
+  // the location right after `}`, or after the statement for a block made from a single statement, NO_LOC for the body of a case
+  pub end: Loc,
   pub stmt: Vec<Stmt<'a>>,
   pub scope: RefCell<Scope<'a>>,
 }
@@ -138,21 +231,108 @@ pub struct If<'a> {
   pub on_false: Option<Block<'a>>,
 }
 
+// `label` of the loops is Some for labeled loops, e.g.: `outer: while (true) { ... }`
 pub struct While<'a> {
+  pub label: Option<&'a str>,
   pub cond: Expr<'a>,
   pub body: Block<'a>,
 }
 
+pub struct DoWhile<'a> {
+  pub label: Option<&'a str>,
+  pub body: Block<'a>,
+  pub cond: Expr<'a>,
+}
+
 pub struct For<'a> {
-  pub init: Box<Stmt<'a>>,
+  pub label: Option<&'a str>,
+  // the comma separated simple statements, e.g.: `for (i = 0, j = n; i < j; i++, j--)`
+  pub init: Vec<Stmt<'a>>,
   pub cond: Expr<'a>,
-  pub update: Box<Stmt<'a>>,
+  pub update: Vec<Stmt<'a>>,
+  pub body: Block<'a>,
+}
+
+// `for (var : arr) body`, the loop variable is declared in `body`'s scope
+pub struct Foreach<'a> {
+  pub label: Option<&'a str>,
+  pub var: &'a VarDef<'a>,
+  pub arr: Expr<'a>,
   pub body: Block<'a>,
 }
 
+// there is no fall-through, a case implicitly breaks at its end, and a `break` in it exits the switch
+pub struct Switch<'a> {
+  pub expr: Expr<'a>,
+  pub case: Vec<Case<'a>>,
+  // all the defaults in the order of appearance, more than one is reported in typeck
+  pub default: Vec<Block<'a>>,
+}
+
+pub struct Case<'a> {
+  pub loc: Loc,
+  // must be an int constant, i.e., `label.result` is Some after typeck
+  pub label: Expr<'a>,
+  pub body: Block<'a>,
+}
+
+// a `Printf(fmt, args...)` statement, `fmt` must be a string literal
+pub struct Printf<'a> {
+  // the decoded format string, `%d`, `%s`, `%b` are replaced by the int, string, bool arguments in order, `%%` is `%`
+  pub fmt: &'a str,
+  pub arg: Vec<Expr<'a>>,
+}
+
+pub enum FmtPart<'a> {
+  Lit(&'a str),
+  // a specifier, with the type of the argument it requires
+  Arg(Ty<'a>),
+  // an unknown specifier, or a `%` at the end, the parts after it are dropped
+  Bad,
+}
+
+impl<'a> Printf<'a> {
+  pub fn parts(&self) -> Vec<FmtPart<'a>> {
+    let (fmt, mut ret, mut last, mut i) = (self.fmt, vec![], 0, 0);
+    let b = fmt.as_bytes();
+    while i < b.len() {
+      if b[i] != b'%' {
+        i += 1;
+        continue;
+      }
+      if last < i { ret.push(FmtPart::Lit(&fmt[last..i])); }
+      ret.push(match b.get(i + 1) {
+        Some(b'd') => FmtPart::Arg(Ty::int()),
+        Some(b's') => FmtPart::Arg(Ty::string()),
+        Some(b'b') => FmtPart::Arg(Ty::bool()),
+        Some(b'%') => FmtPart::Lit("%"),
+        _ => return (ret.push(FmtPart::Bad), ret).1,
+      });
+      i += 2;
+      last = i;
+    }
+    if last < b.len() { ret.push(FmtPart::Lit(&fmt[last..])); }
+    ret
+  }
+}
+
+// `label` is Some for `break label;` or `continue label;`, which jump out of / continue the labeled loop
+pub struct Break<'a> {
+  pub label: Option<&'a str>,
+}
+
+pub struct Continue<'a> {
+  pub label: Option<&'a str>,
+}
+
 pub struct Expr<'a> {
   pub loc: Loc,
+  // the location right after the last token of the expression, so [loc, end) covers it, except that `loc` may be after the start
+  // (e.g., `loc` of `a + b` is at `+`), and the parentheses around it are not included
+  pub end: Loc,
   pub ty: Cell<Ty<'a>>,
+  // the statically evaluated value of an int / bool expr (bool is represented as 0 / 1), set in typeck
+  pub result: Cell<Option<i32>>,
   pub kind: ExprKind<'a>,
 }
 
@@ -160,26 +340,43 @@ pub struct Expr<'a> {
 pub enum ExprKind<'a> {
   VarSel(VarSel<'a>),
   IndexSel(IndexSel<'a>),
+  Slice(Slice<'a>),
   IntLit(i32),
+  CharLit(char),
+  DoubleLit(f64),
   BoolLit(bool),
   StringLit(&'a str),
   NullLit(NullLit),
   Call(Call<'a>),
   Unary(Unary<'a>),
   Binary(Binary<'a>),
+  IncDec(IncDec<'a>),
+  // `a = b` used as an expression, its value is the assigned value, the statement form is StmtKind::Assign
+  Assign(Box<Assign<'a>>),
+  Ternary(Ternary<'a>),
+  // `[e1, e2, ...]`
+  ArrayLit(Vec<Expr<'a>>),
+  Tuple(Tuple<'a>),
+  TupleSel(TupleSel<'a>),
   This(This),
+  // `super` is `this` viewed as the parent class, and the methods called through it are statically dispatched
+  Super(Super),
   ReadInt(ReadInt),
+  ReadDouble(ReadDouble),
   ReadLine(ReadLine),
   NewClass(NewClass<'a>),
   NewArray(NewArray<'a>),
   ClassTest(ClassTest<'a>),
   ClassCast(ClassCast<'a>),
+  Lambda(Box<Lambda<'a>>),
 }
 
 pub struct VarSel<'a> {
   pub owner: Option<Box<Expr<'a>>>,
   pub name: &'a str,
   pub var: Cell<Option<&'a VarDef<'a>>>,
+  // the static method referred to as a function value, e.g.: `A.f` without calling it, set in typeck
+  pub func: Cell<Option<&'a FuncDef<'a>>>,
 }
 
 pub struct IndexSel<'a> {
@@ -187,9 +384,27 @@ pub struct IndexSel<'a> {
   pub idx: Box<Expr<'a>>,
 }
 
+// `arr[lo..hi]`, a new array of the elements in [lo, hi), it is not an lvalue
+pub struct Slice<'a> {
+  pub arr: Box<Expr<'a>>,
+  pub lo: Box<Expr<'a>>,
+  pub hi: Box<Expr<'a>>,
+}
+
+// `(e1, e2, ...)` with at least 2 elements
+pub struct Tuple<'a> {
+  pub elem: Vec<Expr<'a>>,
+}
+
+// `t.0`, the index is checked against the length of the tuple type in typeck, it is not an lvalue
+pub struct TupleSel<'a> {
+  pub tuple: Box<Expr<'a>>,
+  pub idx: u32,
+}
+
 pub struct Call<'a> {
-  // the framework only support `func` as VarSel
-  // hint: there are 2 places using `func` as VarSel, and there are 2 unimplemented!() respectively
+  // if `func` is a VarSel, it is a method call, and `func_ref` is set in typeck
+  // otherwise `func` is a function value (e.g.: a lambda), and `func_ref` is None
   pub func: Box<Expr<'a>>,
   pub arg: Vec<Expr<'a>>,
   pub func_ref: Cell<Option<&'a FuncDef<'a>>>,
@@ -206,14 +421,36 @@ pub struct Unary<'a> {
   pub r: Box<Expr<'a>>,
 }
 
+// `++` and `--`, the operand must be an lvalue (VarSel or IndexSel)
+pub struct IncDec<'a> {
+  // Add for `++`, Sub for `--`
+  pub op: BinOp,
+  pub prefix: bool,
+  pub e: Box<Expr<'a>>,
+}
+
+pub struct Ternary<'a> {
+  pub cond: Box<Expr<'a>>,
+  pub on_true: Box<Expr<'a>>,
+  pub on_false: Box<Expr<'a>>,
+}
+
 pub struct NewClass<'a> {
   pub name: &'a str,
+  // the type argument of a generic class, e.g.: `int` in `new Box<int>()`
+  pub ty_arg: Option<SynTy<'a>>,
+  pub arg: Vec<Expr<'a>>,
   pub class: Cell<Option<&'a ClassDef<'a>>>,
+  // None if the class has no constructor, then `arg` must be empty
+  pub ctor: Cell<Option<&'a FuncDef<'a>>>,
 }
 
 pub struct NewArray<'a> {
   pub elem: SynTy<'a>,
   pub len: Box<Expr<'a>>,
+  // the lengths of the inner dimensions, e.g., `new int[m][n][]` has `elem` = int[][], `len` = m, `sub_len` = [n]
+  // the inner arrays are allocated, except those of the trailing empty dimensions, which are left null
+  pub sub_len: Vec<Expr<'a>>,
 }
 
 pub struct ClassTest<'a> {
@@ -228,15 +465,33 @@ pub struct ClassCast<'a> {
   pub class: Cell<Option<&'a ClassDef<'a>>>,
 }
 
+// `(params) => expr` or `(params) => { block }`
+pub struct Lambda<'a> {
+  pub param: Vec<&'a VarDef<'a>>,
+  pub body: LambdaBody<'a>,
+  // like FuncDef::ret_param_ty, [0] is ret_ty, [1..] is parm_ty
+  pub ret_param_ty: Cell<Option<&'a [Ty<'a>]>>,
+  // the local variables declared outside the lambda and used in it, their values are copied into the closure
+  pub captured: RefCell<Vec<&'a VarDef<'a>>>,
+  pub scope: RefCell<Scope<'a>>,
+}
+
+pub enum LambdaBody<'a> {
+  Expr(Box<Expr<'a>>),
+  Block(Block<'a>),
+}
+
 // some unit struct, they exist just to make match pattern consistent(all patterns are like Xxx(x))
 pub struct Skip;
 
-pub struct Break;
-
 pub struct NullLit;
 
 pub struct This;
 
+pub struct Super;
+
 pub struct ReadInt;
 
+pub struct ReadDouble;
+
 pub struct ReadLine;
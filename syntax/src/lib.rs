@@ -5,6 +5,7 @@ pub mod parser;
 pub mod parser_ll;
 pub mod ty;
 pub mod symbol;
+pub mod query;
 
 pub use ast::*;
 pub use ty::*;
@@ -17,19 +18,61 @@ use common::{Loc, Errors, ErrorKind, NO_LOC};
 // save a little typing than writing "Default::default()"
 pub(crate) fn dft<T: Default>() -> T { T::default() }
 
-pub(crate) fn mk_stmt(loc: Loc, kind: StmtKind) -> Stmt { Stmt { loc, kind } }
+pub(crate) fn mk_stmt(loc: Loc, end: Loc, kind: StmtKind) -> Stmt { Stmt { loc, end, kind } }
 
-pub(crate) fn mk_expr(loc: Loc, kind: ExprKind) -> Expr { Expr { loc, ty: dft(), kind } }
+pub(crate) fn mk_expr(loc: Loc, end: Loc, kind: ExprKind) -> Expr { Expr { loc, end, ty: dft(), result: dft(), kind } }
+
+// the location right after the token `s` at `loc`, a token never spans multiple lines
+pub(crate) fn loc_after(loc: Loc, s: &str) -> Loc { let c = loc.1 + s.chars().count() as u32; Loc(loc.0, c, c) }
 
 pub(crate) fn mk_int_lit<'a, T>(loc: Loc, s: &'a str, error: &mut Errors<'a, T>) -> Expr<'a> {
-  let val = if s.starts_with("0x") { i32::from_str_radix(&s[2..], 16) } else { s.parse() }
-    .unwrap_or_else(|_| error.issue(loc, ErrorKind::IntTooLarge(s)));
-  mk_expr(loc, val.into())
+  let (radix, digits) = match s.get(0..2) {
+    Some("0x") | Some("0X") => (16, &s[2..]),
+    Some("0o") | Some("0O") => (8, &s[2..]),
+    Some("0b") | Some("0B") => (2, &s[2..]),
+    _ => (10, s),
+  };
+  // the location of digits[idx]
+  let loc_of = |idx: usize| {
+    let mut loc = loc;
+    for _ in s[..s.len() - digits.len() + idx].chars() { loc.next_col(); }
+    loc
+  };
+  // report the location of the first invalid digit, or the location after the prefix if there is no digit
+  let bad = digits.char_indices().find(|&(_, ch)| ch != '_' && !ch.is_digit(radix)).map(|(idx, _)| idx)
+    .or_else(|| if digits.is_empty() { Some(0) } else { None });
+  // `_` can only appear between two digits
+  let bad_sep = digits.char_indices().find(|&(idx, ch)| ch == '_' && (idx == 0 || idx + 1 == digits.len() || digits[idx + 1..].starts_with('_')));
+  let val = if let Some(idx) = bad {
+    error.issue(loc_of(idx), ErrorKind::InvalidIntLit { lit: s, radix })
+  } else if let Some((idx, _)) = bad_sep {
+    error.issue(loc_of(idx), ErrorKind::BadDigitSeparator(s))
+  } else {
+    // a literal out of the range of i32 is reported and becomes 0, so that later passes can continue
+    // like java, a non-decimal literal can use all the 32 bits, e.g.: `0xFFFFFFFF` is -1
+    let digits = digits.replace('_', "");
+    let val = if radix == 10 { i32::from_str_radix(&digits, 10).ok() } else { u32::from_str_radix(&digits, radix).ok().map(|x| x as i32) };
+    val.unwrap_or_else(|| error.issue(loc, ErrorKind::IntLiteralOverflow(s)))
+  };
+  mk_expr(loc, loc_after(loc, s), val.into())
+}
+
+// `s` includes the quotes, the escape characters are the same as those in StringLit, plus `\'`
+pub(crate) fn mk_char_lit<'a, T>(loc: Loc, s: &'a str, error: &mut Errors<'a, T>) -> Expr<'a> {
+  let (end, lit) = (loc_after(loc, s), s);
+  let s = &s[1..s.len() - 1];
+  let val = if s.starts_with('\\') {
+    match &s[1..] { "n" => '\n', "r" => '\r', "t" => '\t', "0" => '\0', "\"" => '"', "'" => '\'', "\\" => '\\', _ => error.issue(loc, ErrorKind::InvalidEscape) }
+  } else {
+    let mut it = s.chars();
+    match (it.next(), it.next()) { (Some(ch), None) => ch, _ => error.issue(loc, ErrorKind::InvalidCharLit(lit)) }
+  };
+  mk_expr(loc, end, val.into())
 }
 
 // make a block from a single statement(which may already be a block)
 fn mk_block(s: Stmt) -> Block {
-  if let StmtKind::Block(b) = s.kind { b } else { Block { loc: s.loc, stmt: vec![s], scope: dft() } }
+  if let StmtKind::Block(b) = s.kind { b } else { Block { loc: s.loc, end: s.end, stmt: vec![s], scope: dft() } }
 }
 
 pub(crate) trait VecExt: Sized {
@@ -49,28 +92,61 @@ impl<T> VecExt for Vec<T> {
 }
 
 // assume s begin with ", this is not checked
-pub(crate) fn check_str<'a, T>(s: &'a str, error: &mut Errors<'a, T>, mut loc: Loc) {
+// return the content of the string with the escape characters decoded
+pub(crate) fn decode_str<'a, T>(s: &'a str, error: &mut Errors<'a, T>, mut loc: Loc) -> String {
   if s.len() <= 1 || !s.ends_with('"') {
     error.issue(loc, ErrorKind::UnclosedStr(&s[1..]))
   }
   let s = &s[1..s.len() - 1];
   loc.next_col();
-  let mut escape = NO_LOC;
-  let mut idx = 0;
+  let (mut ret, mut escape, mut idx) = (String::new(), NO_LOC, 0);
+  // Some((loc of `\`, chars after `\u`)) if it is in the middle of `\u{...}`
+  let mut unicode = None::<(Loc, String)>;
   for ch in s.chars() {
     idx += ch.len_utf8();
-    match ch {
-      '\\' => escape = if escape == NO_LOC { loc } else { NO_LOC },
-      'n' | 'r' | 't' | '"' => escape = NO_LOC,
-      '\r' => continue, // just ignore
-      _ => {
-        if escape != NO_LOC {
-          error.issue::<()>(escape, ErrorKind::InvalidEscape);
-          escape = NO_LOC;
+    let consumed = if let Some((uloc, digits)) = &mut unicode {
+      let uloc = *uloc;
+      match ch {
+        '{' if digits.is_empty() => (digits.push(ch), true).1,
+        '}' if digits.len() > 1 => {
+          // from_u32 rejects the code points out of range and the surrogates
+          match u32::from_str_radix(&digits[1..], 16).ok().and_then(std::char::from_u32) {
+            Some(c) => ret.push(c),
+            None => error.issue(uloc, ErrorKind::InvalidUnicodeEscape),
+          }
+          unicode = None;
+          true
+        }
+        // at most 6 hex digits
+        _ if !digits.is_empty() && digits.len() <= 6 && ch.is_ascii_hexdigit() => (digits.push(ch), true).1,
+        _ => {
+          error.issue::<()>(uloc, ErrorKind::InvalidUnicodeEscape);
+          unicode = None;
+          false
+        }
+      }
+    } else { false };
+    if consumed {} else if escape != NO_LOC {
+      match ch {
+        'u' => unicode = Some((escape, String::new())),
+        'n' => ret.push('\n'),
+        'r' => ret.push('\r'),
+        't' => ret.push('\t'),
+        '0' => ret.push('\0'),
+        '\\' | '"' => ret.push(ch),
+        _ => error.issue(escape, ErrorKind::InvalidEscape),
+      }
+      escape = NO_LOC;
+    } else {
+      match ch {
+        '\\' => escape = loc,
+        '\r' => continue, // just ignore
+        _ => {
+          // for NewlineInStr error, the reported string segment is from beginning to(including) this '\n'
+          // (though I don't think it is very sensible, I think reporting the whole string will be better)
+          if ch == '\n' { error.issue(loc, ErrorKind::NewlineInStr(&s[0..idx])) }
+          ret.push(ch);
         }
-        // for NewlineInStr error, the reported string segment is from beginning to(including) this '\n'
-        // (though I don't think it is very sensible, I think reporting the whole string will be better)
-        if ch == '\n' { error.issue(loc, ErrorKind::NewlineInStr(&s[0..idx])) }
       }
     }
     if ch == '\n' { loc.next_line(); } else { loc.next_col(); }
@@ -78,4 +154,44 @@ pub(crate) fn check_str<'a, T>(s: &'a str, error: &mut Errors<'a, T>, mut loc: L
   if escape != NO_LOC {
     error.issue(escape, ErrorKind::InvalidEscape)
   }
-}
\ No newline at end of file
+  if let Some((uloc, _)) = unicode {
+    error.issue(uloc, ErrorKind::InvalidUnicodeEscape)
+  }
+  ret
+}
+
+// replace the (possibly nested) block comments with spaces, the line breaks are kept, so the locations of tokens are unchanged
+// it can't be done in the lexer, because nested comments are not regular
+// `/*` in string, char literal or line comment doesn't start a block comment
+pub(crate) fn strip_block_comment<'a, T>(code: &str, error: &mut Errors<'a, T>) -> String {
+  let s = code.as_bytes();
+  let mut ret = s.to_vec();
+  // `open` is the locations of the unclosed `/*`, `quote` is Some if it is in a string or char literal
+  let (mut open, mut quote, mut line_comment) = (Vec::new(), None, false);
+  let (mut i, mut loc) = (0, Loc(1, 1, 2));
+  while i < s.len() {
+    let (ch, next, was_open) = (s[i], s.get(i + 1).copied(), !open.is_empty());
+    let len = match (ch, next) {
+      _ if line_comment => { line_comment = ch != b'\n'; 1 },
+      (b'\\', Some(n)) if quote.is_some() && n != b'\n' => 2,
+      _ if quote.is_some() => {
+        if ch == b'\n' || Some(ch) == quote { quote = None; }
+        1
+      }
+      (b'/', Some(b'*')) => { open.push(Loc(loc.0, loc.1, loc.1 + 2)); 2 },
+      (b'*', Some(b'/')) if was_open => { open.pop(); 2 },
+      _ if was_open => 1,
+      (b'/', Some(b'/')) => { line_comment = true; 2 },
+      (b'"', _) | (b'\'', _) => { quote = Some(ch); 1 },
+      _ => 1,
+    };
+    for j in i..i + len {
+      if (was_open || !open.is_empty()) && s[j] != b'\n' { ret[j] = b' '; }
+      if s[j] == b'\n' { loc.next_line(); } else { loc.next_col(); }
+    }
+    i += len;
+  }
+  if let Some(&loc) = open.first() { error.issue(loc, ErrorKind::UnterminatedComment) }
+  // only whole chars are replaced, so it is still valid utf-8
+  String::from_utf8(ret).unwrap()
+}
@@ -1,59 +1,133 @@
-use crate::{ast::*, ty::*, VecExt, dft, check_str, mk_stmt, mk_expr, mk_int_lit, mk_block};
+use crate::{ast::*, ty::*, VecExt, dft, decode_str, mk_stmt, mk_expr, mk_int_lit, mk_char_lit, mk_block, strip_block_comment, loc_after};
 use parser_macros::lalr1;
 use common::{ErrorKind, Loc, BinOp, UnOp, Errors, NO_LOC};
 
-pub fn work<'p>(code: &'p str, alloc: &'p ASTAlloc<'p>) -> Result<&'p Program<'p>, Errors<'p, Ty<'p>>> {
-  let mut parser = Parser { alloc, error: Errors::default() };
-  let mut lexer = Lexer::new(code.as_bytes()); // Lexer can be used independently from Parser, you can use it to debug
-  match parser.parse(&mut lexer) {
-    Ok(program) if parser.error.0.is_empty() => Ok(program),
-    Err(token) => {
-      let mut error = parser.error;
-      let loc = Loc(token.line, token.col);
-      match token.ty {
-        TokenKind::_Err => error.issue(loc, ErrorKind::UnrecognizedChar(token.piece[0] as char)),
-        TokenKind::UntermString => {
-          check_str(token.str(), &mut error, loc);
-          error.issue(lexer.loc(), ErrorKind::SyntaxError)
-        }
-        _ => error.issue(loc, ErrorKind::SyntaxError),
+// the generated parser stops at the first syntax error, so the panic mode recovery is done by parsing in rounds:
+// after each error, the erroneous part is blanked with spaces (so the locations after it don't change), and the code is parsed again
+// the errors from previous rounds are kept, and the errors issued by the parser itself are taken from the last round, which goes the farthest
+pub fn work<'p>(code: &'p str, alloc: &'p ASTAlloc<'p>, max_error: usize) -> Result<&'p Program<'p>, Errors<'p, Ty<'p>>> {
+  let mut error = Errors::default(); // the limit is applied in the end, after sorting the errors from all rounds
+  let mut code = if code.contains("/*") { alloc.str.alloc(strip_block_comment(code, &mut error)).as_str() } else { code };
+  let mut syntax = Errors::default();
+  let (program, mut last) = loop {
+    let mut parser = Parser { alloc, error: Errors::default() };
+    let mut lexer = Lexer::new(code.as_bytes()); // Lexer can be used independently from Parser, you can use it to debug
+    let token = match parser.parse(&mut lexer) {
+      Ok(program) => break (Some(program), parser.error),
+      Err(token) => token,
+    };
+    let loc = token.loc();
+    if let TokenKind::_Eof = token.ty {
+      syntax.issue::<()>(loc, ErrorKind::SyntaxError);
+      break (None, parser.error);
+    }
+    let off = token.piece.as_ptr() as usize - code.as_ptr() as usize;
+    // a lexical error only blanks the token itself
+    let range = match token.ty {
+      TokenKind::_Err => {
+        syntax.issue::<()>(loc, ErrorKind::UnrecognizedChar(token.piece[0] as char));
+        off..off + token.piece.len()
+      }
+      // the syntax error is reported in the next round, at the token after the string
+      TokenKind::UntermString => {
+        decode_str(token.str(), &mut syntax, loc);
+        off..off + token.piece.len()
+      }
+      _ => {
+        syntax.issue::<()>(loc, ErrorKind::SyntaxError);
+        skip_stmt(code, off)
+      }
+    };
+    // one more error than the limit is found, so that TooManyErrors is reported
+    if syntax.0.len() > max_error { break (None, parser.error); }
+    // the range is on char boundaries, so the result is still valid utf-8
+    let mut blanked = code.as_bytes().to_vec();
+    for b in &mut blanked[range] { if *b != b'\n' { *b = b' '; } }
+    code = alloc.str.alloc(String::from_utf8(blanked).unwrap()).as_str();
+  };
+  error.append(&mut last);
+  error.append(&mut syntax);
+  match program {
+    Some(program) if error.0.is_empty() => Ok(program),
+    _ => {
+      let mut ret = Errors::with_limit(max_error);
+      for e in error.sorted().0 { ret.issue::<()>(e.0, e.1); }
+      Err(ret)
+    }
+  }
+}
+
+// the byte range of the statement (or class member) containing the token at `off`, which is skipped in panic mode
+// it starts after the last `;`, `{` or `}` before the token, and ends with the first `;` or the `}` closing a `{` after the token
+// a `}` closing an outer block is not skipped, unless it is the erroneous token and nothing is before it
+fn skip_stmt(code: &str, off: usize) -> std::ops::Range<usize> {
+  let mut lexer = Lexer::new(code.as_bytes());
+  let (mut start, mut depth) = (0, 0);
+  loop {
+    let t = lexer.next();
+    if let TokenKind::_Eof = t.ty { break start..code.len(); }
+    let (ty, t_off) = (t.ty, t.piece.as_ptr() as usize - code.as_ptr() as usize);
+    match ty {
+      _ if t_off < off => if let TokenKind::Semi | TokenKind::LBrc | TokenKind::RBrc = ty { start = t_off + 1; },
+      TokenKind::LBrc => depth += 1,
+      TokenKind::RBrc if depth == 0 => break start..if start == t_off { t_off + 1 } else { t_off },
+      TokenKind::RBrc => {
+        depth -= 1;
+        if depth == 0 { break start..t_off + 1; }
       }
-      Err(error)
+      TokenKind::Semi if depth == 0 => break start..t_off + 1,
+      _ => {}
     }
-    _ => Err(parser.error),
   }
 }
 
 pub struct Parser<'p> {
   pub alloc: &'p ASTAlloc<'p>,
-  // just some simple errors like IntTooLarger, cannot recover or record parser errors
+  // just some simple errors like IntLiteralOverflow, the syntax errors are recorded in `work`
   pub error: Errors<'p, Ty<'p>>,
 }
 
 impl<'p> Token<'p> {
   pub fn str(&self) -> &'p str { std::str::from_utf8(self.piece).unwrap() }
-  pub fn loc(&self) -> Loc { Loc(self.line, self.col) }
+  // the loc covers the whole token
+  pub fn loc(&self) -> Loc { Loc(self.line, self.col, self.end().1) }
+  pub fn end(&self) -> Loc { loc_after(Loc(self.line, self.col, self.col), self.str()) }
 }
 
 impl Lexer<'_> {
-  pub fn loc(&self) -> Loc { Loc(self.line, self.col) }
+  pub fn loc(&self) -> Loc { Loc(self.line, self.col, self.col) }
 }
 
+// the classes and the type aliases in the program
+type TopList<'p> = (Vec<&'p ClassDef<'p>>, Vec<TypeAlias<'p>>);
+
 fn mk_bin<'p>(l: Expr<'p>, r: Expr<'p>, loc: Loc, op: BinOp) -> Expr<'p> {
-  mk_expr(loc, Binary { l: Box::new(l), op, r: Box::new(r) }.into())
+  mk_expr(loc, r.end, Binary { l: Box::new(l), op, r: Box::new(r) }.into())
+}
+
+// `end` is the end of `++` / `--` for postfix ones
+fn mk_inc_dec<'p>(e: Expr<'p>, loc: Loc, end: Loc, op: BinOp, prefix: bool) -> Expr<'p> {
+  mk_expr(loc, if prefix { e.end } else { end }, IncDec { op, prefix, e: Box::new(e) }.into())
 }
 
 #[lalr1(Program)]
 #[lex(r##"
 priority = [
+  { assoc = 'right', terms = ['Assign'] },
+  { assoc = 'right', terms = ['Arrow'] },
+  { assoc = 'right', terms = ['Question', 'Colon'] },
   { assoc = 'left', terms = ['Or'] },
   { assoc = 'left', terms = ['And'] },
+  { assoc = 'left', terms = ['BitOr'] },
+  { assoc = 'left', terms = ['BitXor'] },
+  { assoc = 'left', terms = ['BitAnd'] },
   { assoc = 'left', terms = ['Eq', 'Ne'] },
   { assoc = 'no_assoc', terms = ['Le', 'Ge', 'Lt', 'Gt'] },
+  { assoc = 'left', terms = ['Shl', 'Shr'] },
   { assoc = 'left', terms = ['Add', 'Sub'] },
   { assoc = 'left', terms = ['Mul', 'Div', 'Mod'] },
-  { assoc = 'left', terms = ['UMinus', 'Not', 'RPar'] },
-  { assoc = 'left', terms = ['LBrk', 'Dot', 'LPar'] },
+  { assoc = 'left', terms = ['UMinus', 'Not', 'BitNot', 'RPar'] },
+  { assoc = 'left', terms = ['LBrk', 'Dot', 'LPar', 'Inc', 'Dec'] },
   { assoc = 'left', terms = ['Empty'] },
   { assoc = 'left', terms = ['Else'] },
 ]
@@ -62,43 +136,74 @@ priority = [
 'void' = 'Void'
 'int' = 'Int'
 'bool' = 'Bool'
+'char' = 'Char'
+'double' = 'Double'
 'string' = 'String'
 'new' = 'New'
 'null' = 'Null'
 'true' = 'True'
 'false' = 'False'
 'class' = 'Class'
+'typedef' = 'Typedef'
 'extends' = 'Extends'
+'interface' = 'Interface'
+'implements' = 'Implements'
 'this' = 'This'
+'super' = 'Super'
+'var' = 'Var'
 'while' = 'While'
+'do' = 'Do'
 'for' = 'For'
 'if' = 'If'
 'else' = 'Else'
 'return' = 'Return'
 'break' = 'Break'
+'switch' = 'Switch'
+'case' = 'Case'
+'default' = 'Default'
+'continue' = 'Continue'
 'Print' = 'Print'
+'Printf' = 'Printf'
 'ReadInteger' = 'ReadInteger'
+'ReadDouble' = 'ReadDouble'
 'ReadLine' = 'ReadLine'
 'static' = 'Static'
+'final' = 'Final'
+'abstract' = 'Abstract'
 'instanceof' = 'InstanceOf'
+'<<' = 'Shl'
+'>>' = 'Shr'
 '<=' = 'Le'
 '>=' = 'Ge'
 '==' = 'Eq'
 '!=' = 'Ne'
 '&&' = 'And'
 '\|\|' = 'Or'
+'&' = 'BitAnd'
+'\|' = 'BitOr'
+'\^' = 'BitXor'
 '\+' = 'Add'
 '-' = 'Sub'
 '\*' = 'Mul'
 '/' = 'Div'
 '%' = 'Mod'
 '=' = 'Assign'
+'=>' = 'Arrow'
+'\+=' = 'AddAssign'
+'-=' = 'SubAssign'
+'\*=' = 'MulAssign'
+'/=' = 'DivAssign'
+'\+\+' = 'Inc'
+'--' = 'Dec'
+'%=' = 'ModAssign'
 '<' = 'Lt'
 '>' = 'Gt'
+'\.\.' = 'DotDot'
 '\.' = 'Dot'
 ',' = 'Comma'
 ';' = 'Semi' # short for semicolon
 '!' = 'Not'
+'~' = 'BitNot'
 '\(' = 'LPar' # short for parenthesis
 '\)' = 'RPar'
 '\[' = 'LBrk' # short for bracket
@@ -106,56 +211,122 @@ priority = [
 '\{' = 'LBrc' # short for brace
 '\}' = 'RBrc'
 ':' = 'Colon'
+'\?' = 'Question'
 # line break in a StringLit will be reported by parser's semantic act
 '"[^"\\]*(\\.[^"\\]*)*"' = 'StringLit'
 '"[^"\\]*(\\.[^"\\]*)*' = 'UntermString'
 '//[^\n]*' = '_Eps'
 '\s+' = '_Eps'
-'\d+|(0x[0-9a-fA-F]+)' = 'IntLit'
-'[A-Za-z]\w*' = 'Id'
+'\d+\.\d+([eE][+-]?\d+)?' = 'DoubleLit'
+# the digits (after 0x / 0o / 0b) and the separators `_` are checked in mk_int_lit, so that `0b102` and `1__0` are reported as a whole
+'\d[\d_]*|(0[xXoObB]\w*)' = 'IntLit'
+# the lexer works on bytes, so a non-ascii char is matched by `[^...]+`, and the number of chars is checked in mk_char_lit
+"'([^'\\\\\\n]+|\\\\.)'" = 'CharLit'
+'[A-Za-z_]\w*' = 'Id'
 '.' = '_Err'
 "##)]
 impl<'p> Parser<'p> {
   #[rule(Program -> ClassList)]
-  fn program(&self, class: Vec<&'p ClassDef<'p>>) -> &'p Program<'p> {
-    self.alloc.program.alloc(Program { class, main: dft(), scope: dft() })
+  fn program(&self, l: TopList<'p>) -> &'p Program<'p> {
+    self.alloc.program.alloc(Program { class: l.0, alias: l.1, main: dft(), scope: dft() })
   }
 
+  // the type aliases can be placed anywhere among the classes, but there should be at least one class
   #[rule(ClassList -> ClassList ClassDef)]
-  fn class_list(l: Vec<&'p ClassDef<'p>>, r: &'p ClassDef<'p>) -> Vec<&'p ClassDef<'p>> { l.pushed(r) }
-  #[rule(ClassList -> ClassDef)]
-  fn class_list1(c: &'p ClassDef<'p>) -> Vec<&'p ClassDef<'p>> { vec![c] }
+  fn class_list(l: TopList<'p>, r: &'p ClassDef<'p>) -> TopList<'p> { (l.0.pushed(r), l.1) }
+  #[rule(ClassList -> ClassList TypeAlias)]
+  fn class_list_alias(l: TopList<'p>, r: TypeAlias<'p>) -> TopList<'p> { (l.0, l.1.pushed(r)) }
+  #[rule(ClassList -> AliasList ClassDef)]
+  fn class_list1(l: Vec<TypeAlias<'p>>, c: &'p ClassDef<'p>) -> TopList<'p> { (vec![c], l) }
+
+  #[rule(AliasList -> AliasList TypeAlias)]
+  fn alias_list(l: Vec<TypeAlias<'p>>, r: TypeAlias<'p>) -> Vec<TypeAlias<'p>> { l.pushed(r) }
+  #[rule(AliasList ->)]
+  fn alias_list0() -> Vec<TypeAlias<'p>> { vec![] }
 
-  #[rule(ClassDef -> Class Id MaybeExtends LBrc FieldList RBrc)]
-  fn class_def(&self, c: Token, name: Token, parent: Option<&'p str>, _l: Token, field: Vec<FieldDef<'p>>, _r: Token) -> &'p ClassDef<'p> {
-    self.alloc.class.alloc(ClassDef { loc: c.loc(), name: name.str(), parent, field, parent_ref: dft(), scope: dft() })
+  #[rule(TypeAlias -> Typedef VarType Id Semi)]
+  fn type_alias(_t: Token, syn_ty: SynTy<'p>, name: Token, _s: Token) -> TypeAlias<'p> {
+    TypeAlias { loc: name.loc(), name: name.str(), syn_ty, ty: dft(), resolving: dft() }
   }
 
+  #[rule(ClassDef -> Class Id MaybeTyParam MaybeExtends MaybeImplements LBrc FieldList RBrc)]
+  fn class_def(&self, c: Token, name: Token, ty_param: Option<&'p str>, parent: Option<&'p str>, implements: Vec<(Loc, &'p str)>, _l: Token, field: Vec<FieldDef<'p>>, r: Token) -> &'p ClassDef<'p> {
+    self.alloc.class.alloc(ClassDef { loc: c.loc(), name: name.str(), ty_param, parent, abstract_: false, interface: false, implements, field, end: r.end(), parent_ref: dft(), implements_ref: dft(), scope: dft() })
+  }
+  #[rule(ClassDef -> Abstract Class Id MaybeTyParam MaybeExtends MaybeImplements LBrc FieldList RBrc)]
+  fn class_def_abstract(&self, a: Token, _c: Token, name: Token, ty_param: Option<&'p str>, parent: Option<&'p str>, implements: Vec<(Loc, &'p str)>, _l: Token, field: Vec<FieldDef<'p>>, r: Token) -> &'p ClassDef<'p> {
+    self.alloc.class.alloc(ClassDef { loc: a.loc(), name: name.str(), ty_param, parent, abstract_: true, interface: false, implements, field, end: r.end(), parent_ref: dft(), implements_ref: dft(), scope: dft() })
+  }
+  #[rule(ClassDef -> Interface Id LBrc SigList RBrc)]
+  fn class_def_interface(&self, i: Token, name: Token, _l: Token, field: Vec<FieldDef<'p>>, r: Token) -> &'p ClassDef<'p> {
+    self.alloc.class.alloc(ClassDef { loc: i.loc(), name: name.str(), ty_param: None, parent: None, abstract_: true, interface: true, implements: vec![], field, end: r.end(), parent_ref: dft(), implements_ref: dft(), scope: dft() })
+  }
+
+  #[rule(MaybeTyParam -> Lt Id Gt)]
+  fn maybe_ty_param1(_l: Token, name: Token, _g: Token) -> Option<&'p str> { Some(name.str()) }
+  #[rule(MaybeTyParam ->)]
+  fn maybe_ty_param0() -> Option<&'p str> { None }
+
   #[rule(MaybeExtends -> Extends Id)]
   fn maybe_extends1(_e: Token, name: Token) -> Option<&'p str> { Some(name.str()) }
   #[rule(MaybeExtends ->)]
   fn maybe_extends0() -> Option<&'p str> { None }
 
+  #[rule(MaybeImplements -> Implements IdList)]
+  fn maybe_implements1(_i: Token, l: Vec<(Loc, &'p str)>) -> Vec<(Loc, &'p str)> { l }
+  #[rule(MaybeImplements ->)]
+  fn maybe_implements0() -> Vec<(Loc, &'p str)> { vec![] }
+
+  #[rule(IdList -> IdList Comma Id)]
+  fn id_list(l: Vec<(Loc, &'p str)>, _c: Token, r: Token) -> Vec<(Loc, &'p str)> { l.pushed((r.loc(), r.str())) }
+  #[rule(IdList -> Id)]
+  fn id_list1(i: Token) -> Vec<(Loc, &'p str)> { vec![(i.loc(), i.str())] }
+
+  // the methods in an interface are declared like abstract methods, without `abstract`
+  #[rule(SigList -> SigList VarType Id LPar VarDefListOrEmpty RPar Semi)]
+  fn sig_list(&self, l: Vec<FieldDef<'p>>, ret: SynTy<'p>, name: Token, _l: Token, param: Vec<&'p VarDef<'p>>, _r: Token, s: Token) -> Vec<FieldDef<'p>> {
+    l.pushed(FieldDef::FuncDef(self.alloc.func.alloc(FuncDef { loc: name.loc(), name: name.str(), ret, param, static_: false, abstract_: true, body: None, end: s.end(), ret_param_ty: dft(), class: dft(), next: dft(), ctor: false, scope: dft() })))
+  }
+  #[rule(SigList ->)]
+  fn sig_list0() -> Vec<FieldDef<'p>> { vec![] }
+
   #[rule(FieldList -> FieldList VarDef Semi)]
   fn field_list_v(l: Vec<FieldDef<'p>>, r: &'p VarDef<'p>, _s: Token) -> Vec<FieldDef<'p>> { l.pushed(r.into()) }
+  #[rule(FieldList -> FieldList Static VarType Id Semi)]
+  fn field_list_static_v(&self, l: Vec<FieldDef<'p>>, _s: Token, syn_ty: SynTy<'p>, name: Token, _s1: Token) -> Vec<FieldDef<'p>> {
+    l.pushed(FieldDef::VarDef(self.alloc.var.alloc(VarDef { loc: name.loc(), name: name.str(), syn_ty, init: None, end: name.end(), ty: dft(), owner: dft(), used: dft(), static_: true, final_: false })))
+  }
+  #[rule(FieldList -> FieldList Final VarType Id Semi)]
+  fn field_list_final_v(&self, l: Vec<FieldDef<'p>>, _f: Token, syn_ty: SynTy<'p>, name: Token, _s: Token) -> Vec<FieldDef<'p>> {
+    l.pushed(FieldDef::VarDef(self.alloc.var.alloc(VarDef { loc: name.loc(), name: name.str(), syn_ty, init: None, end: name.end(), ty: dft(), owner: dft(), used: dft(), static_: false, final_: true })))
+  }
   #[rule(FieldList -> FieldList FuncDef)]
   fn field_list_f(l: Vec<FieldDef<'p>>, r: &'p FuncDef<'p>) -> Vec<FieldDef<'p>> { l.pushed(r.into()) }
   #[rule(FieldList ->)]
   fn field_list0() -> Vec<FieldDef<'p>> { vec![] }
 
-  #[rule(FuncDef -> Static Type Id LPar VarDefListOrEmpty RPar Block)]
+  #[rule(FuncDef -> Static VarType Id LPar VarDefListOrEmpty RPar Block)]
   fn func_def1(&self, _s: Token, ret: SynTy<'p>, name: Token, _l: Token, param: Vec<&'p VarDef<'p>>, _r: Token, body: Block<'p>) -> &'p FuncDef<'p> {
-    self.alloc.func.alloc(FuncDef { loc: name.loc(), name: name.str(), ret, param, static_: true, body, ret_param_ty: dft(), class: dft(), scope: dft() })
+    self.alloc.func.alloc(FuncDef { loc: name.loc(), name: name.str(), ret, param, static_: true, abstract_: false, end: body.end, body: Some(body), ret_param_ty: dft(), class: dft(), next: dft(), ctor: false, scope: dft() })
   }
-  #[rule(FuncDef -> Type Id LPar VarDefListOrEmpty RPar Block)]
+  #[rule(FuncDef -> VarType Id LPar VarDefListOrEmpty RPar Block)]
   fn func_def0(&self, ret: SynTy<'p>, name: Token, _l: Token, param: Vec<&'p VarDef<'p>>, _r: Token, body: Block<'p>) -> &'p FuncDef<'p> {
-    self.alloc.func.alloc(FuncDef { loc: name.loc(), name: name.str(), ret, param, static_: false, body, ret_param_ty: dft(), class: dft(), scope: dft() })
+    self.alloc.func.alloc(FuncDef { loc: name.loc(), name: name.str(), ret, param, static_: false, abstract_: false, end: body.end, body: Some(body), ret_param_ty: dft(), class: dft(), next: dft(), ctor: false, scope: dft() })
+  }
+  #[rule(FuncDef -> Id LPar VarDefListOrEmpty RPar Block)]
+  fn func_def_ctor(&self, name: Token, _l: Token, param: Vec<&'p VarDef<'p>>, _r: Token, body: Block<'p>) -> &'p FuncDef<'p> {
+    let ret = SynTy { loc: name.loc(), arr: 0, kind: SynTyKind::Void };
+    self.alloc.func.alloc(FuncDef { loc: name.loc(), name: name.str(), ret, param, static_: false, abstract_: false, end: body.end, body: Some(body), ret_param_ty: dft(), class: dft(), next: dft(), ctor: true, scope: dft() })
+  }
+  #[rule(FuncDef -> Abstract VarType Id LPar VarDefListOrEmpty RPar Semi)]
+  fn func_def_abstract(&self, _a: Token, ret: SynTy<'p>, name: Token, _l: Token, param: Vec<&'p VarDef<'p>>, _r: Token, s: Token) -> &'p FuncDef<'p> {
+    self.alloc.func.alloc(FuncDef { loc: name.loc(), name: name.str(), ret, param, static_: false, abstract_: true, body: None, end: s.end(), ret_param_ty: dft(), class: dft(), next: dft(), ctor: false, scope: dft() })
   }
 
   // the `VarDef` in grammar only supports VarDef without init value
-  #[rule(VarDef -> Type Id)]
+  #[rule(VarDef -> VarType Id)]
   fn var_def(&self, syn_ty: SynTy<'p>, name: Token) -> &'p VarDef<'p> {
-    self.alloc.var.alloc(VarDef { loc: name.loc(), name: name.str(), syn_ty, init: None, ty: dft(), owner: dft() })
+    self.alloc.var.alloc(VarDef { loc: name.loc(), name: name.str(), syn_ty, init: None, end: name.end(), ty: dft(), owner: dft(), used: dft(), static_: false, final_: false })
   }
 
   #[rule(VarDefListOrEmpty -> VarDefList)]
@@ -163,43 +334,129 @@ impl<'p> Parser<'p> {
   #[rule(VarDefListOrEmpty ->)]
   fn var_def_list_or_empty0() -> Vec<&'p VarDef<'p>> { vec![] }
 
-  #[rule(VarDefList -> VarDefList Comma VarDef)]
+  // VarDefList is only used in parameter list, so it can contain Param
+  #[rule(VarDefList -> VarDefList Comma Param)]
   fn var_def_list(l: Vec<&'p VarDef<'p>>, _c: Token, r: &'p VarDef<'p>) -> Vec<&'p VarDef<'p>> { l.pushed(r) }
-  #[rule(VarDefList -> VarDef)]
+  #[rule(VarDefList -> Param)]
   fn var_def_list1(v: &'p VarDef<'p>) -> Vec<&'p VarDef<'p>> { vec![v] }
 
+  #[rule(Param -> VarDef)]
+  fn param(v: &'p VarDef<'p>) -> &'p VarDef<'p> { v }
+  #[rule(Param -> VarType Id Assign Expr)] // the parameter with default value
+  fn param_default(&self, syn_ty: SynTy<'p>, name: Token, a: Token, init: Expr<'p>) -> &'p VarDef<'p> {
+    self.alloc.var.alloc(VarDef { loc: name.loc(), name: name.str(), syn_ty, end: init.end, init: Some((a.loc(), init)), ty: dft(), owner: dft(), used: dft(), static_: false, final_: false })
+  }
+
+  // the parameters of lambda can't have default values, because its callers don't know them
+  #[rule(LambdaParamListOrEmpty -> LambdaParamList)]
+  fn lambda_param_list_or_empty1(l: Vec<&'p VarDef<'p>>) -> Vec<&'p VarDef<'p>> { l }
+  #[rule(LambdaParamListOrEmpty ->)]
+  fn lambda_param_list_or_empty0() -> Vec<&'p VarDef<'p>> { vec![] }
+
+  #[rule(LambdaParamList -> LambdaParamList Comma VarDef)]
+  fn lambda_param_list(l: Vec<&'p VarDef<'p>>, _c: Token, r: &'p VarDef<'p>) -> Vec<&'p VarDef<'p>> { l.pushed(r) }
+  #[rule(LambdaParamList -> VarDef)]
+  fn lambda_param_list1(v: &'p VarDef<'p>) -> Vec<&'p VarDef<'p>> { vec![v] }
+
+  // StmtList is not empty, so that nothing is reduced after `{` at the beginning of a statement before knowing whether it is a Block or an array literal
   #[rule(Block -> LBrc StmtList RBrc)]
-  fn block(l: Token, stmt: Vec<Stmt<'p>>, _r: Token) -> Block<'p> { Block { loc: l.loc(), stmt, scope: dft() } }
+  fn block(l: Token, stmt: Vec<Stmt<'p>>, r: Token) -> Block<'p> { Block { loc: l.loc(), end: r.end(), stmt, scope: dft() } }
+  #[rule(Block -> LBrc RBrc)]
+  fn block0(l: Token, r: Token) -> Block<'p> { Block { loc: l.loc(), end: r.end(), stmt: vec![], scope: dft() } }
 
   #[rule(StmtList -> StmtList Stmt)]
   fn stmt_list(l: Vec<Stmt<'p>>, r: Stmt<'p>) -> Vec<Stmt<'p>> { l.pushed(r) }
-  #[rule(StmtList ->)]
-  fn stmt_list0() -> Vec<Stmt<'p>> { vec![] }
+  #[rule(StmtList -> Stmt)]
+  fn stmt_list1(s: Stmt<'p>) -> Vec<Stmt<'p>> { vec![s] }
+  #[rule(StmtListOrEmpty -> StmtList)]
+  fn stmt_list_or_empty1(l: Vec<Stmt<'p>>) -> Vec<Stmt<'p>> { l }
+  #[rule(StmtListOrEmpty ->)]
+  fn stmt_list_or_empty0() -> Vec<Stmt<'p>> { vec![] }
 
   #[rule(Stmt -> Simple Semi)]
-  fn stmt_simple(s: Stmt<'p>, _s: Token) -> Stmt<'p> { s }
+  fn stmt_simple(s: Stmt<'p>, semi: Token) -> Stmt<'p> { Stmt { end: semi.end(), ..s } }
   #[rule(Stmt -> If LPar Expr RPar Stmt MaybeElse)]
   fn stmt_if(i: Token, _l: Token, cond: Expr<'p>, _r: Token, on_true: Stmt<'p>, on_false: Option<Block<'p>>) -> Stmt<'p> {
-    mk_stmt(i.loc(), Box::new(If { cond, on_true: mk_block(on_true), on_false }).into())
+    let end = on_false.as_ref().map_or(on_true.end, |b| b.end);
+    mk_stmt(i.loc(), end, Box::new(If { cond, on_true: mk_block(on_true), on_false }).into())
   }
-  #[rule(Stmt -> While LPar Expr RPar Stmt)]
-  fn stmt_while(w: Token, _l: Token, cond: Expr<'p>, _r: Token, body: Stmt<'p>) -> Stmt<'p> {
-    mk_stmt(w.loc(), While { cond, body: mk_block(body) }.into())
+  #[rule(Stmt -> Loop)]
+  fn stmt_loop(l: Stmt<'p>) -> Stmt<'p> { l }
+  #[rule(Stmt -> Id Colon Loop)]
+  fn stmt_labeled_loop(label: Token, _c: Token, mut l: Stmt<'p>) -> Stmt<'p> {
+    let label = Some(label.str());
+    match &mut l.kind {
+      StmtKind::While(w) => w.label = label,
+      StmtKind::DoWhile(d) => d.label = label,
+      StmtKind::For(f) => f.label = label,
+      StmtKind::Foreach(f) => f.label = label,
+      _ => unreachable!(),
+    }
+    l
   }
-  #[rule(Stmt -> For LPar Simple Semi Expr Semi Simple RPar Stmt)]
-  fn stmt_for(f: Token, _l: Token, init: Stmt<'p>, _s1: Token, cond: Expr<'p>, _s2: Token, update: Stmt<'p>, _r: Token, body: Stmt<'p>) -> Stmt<'p> {
-    mk_stmt(f.loc(), For { init: Box::new(init), cond, update: Box::new(update), body: mk_block(body) }.into())
+  #[rule(Stmt -> Switch LPar Expr RPar LBrc CaseList RBrc)]
+  fn stmt_switch(s: Token, _l: Token, expr: Expr<'p>, _r: Token, _l1: Token, case: (Vec<Case<'p>>, Vec<Block<'p>>), r: Token) -> Stmt<'p> {
+    mk_stmt(s.loc(), r.end(), Box::new(Switch { expr, case: case.0, default: case.1 }).into())
   }
-  #[rule(Stmt -> Return Expr Semi)]
-  fn stmt_return1(r: Token, expr: Expr<'p>, _s: Token) -> Stmt<'p> { mk_stmt(r.loc(), Some(expr).into()) }
+  #[rule(Stmt -> Return Init Semi)]
+  fn stmt_return1(r: Token, expr: Expr<'p>, s: Token) -> Stmt<'p> { mk_stmt(r.loc(), s.end(), Some(expr).into()) }
   #[rule(Stmt -> Return Semi)]
-  fn stmt_return0(r: Token, _s: Token) -> Stmt<'p> { mk_stmt(r.loc(), None.into()) }
+  fn stmt_return0(r: Token, s: Token) -> Stmt<'p> { mk_stmt(r.loc(), s.end(), None.into()) }
   #[rule(Stmt -> Print LPar ExprList RPar Semi)]
-  fn stmt_print(p: Token, _l: Token, print: Vec<Expr<'p>>, _r: Token, _s: Token) -> Stmt<'p> { mk_stmt(p.loc(), print.into()) }
+  fn stmt_print(p: Token, _l: Token, print: Vec<Expr<'p>>, _r: Token, s: Token) -> Stmt<'p> { mk_stmt(p.loc(), s.end(), print.into()) }
+  #[rule(Stmt -> Printf LPar StringLit PrintfArg RPar Semi)]
+  fn stmt_printf(&mut self, p: Token, _l: Token, fmt: Token, arg: Vec<Expr<'p>>, _r: Token, s: Token) -> Stmt<'p> {
+    let fmt = self.alloc.str.alloc(decode_str(fmt.str(), &mut self.error, fmt.loc())).as_str();
+    mk_stmt(p.loc(), s.end(), Printf { fmt, arg }.into())
+  }
+  #[rule(PrintfArg -> Comma ExprList)]
+  fn printf_arg(_c: Token, arg: Vec<Expr<'p>>) -> Vec<Expr<'p>> { arg }
+  #[rule(PrintfArg ->)]
+  fn printf_arg0() -> Vec<Expr<'p>> { vec![] }
   #[rule(Stmt -> Break Semi)]
-  fn stmt_break(b: Token, _s: Token) -> Stmt<'p> { mk_stmt(b.loc(), Break.into()) }
+  fn stmt_break(b: Token, s: Token) -> Stmt<'p> { mk_stmt(b.loc(), s.end(), Break { label: None }.into()) }
+  #[rule(Stmt -> Break Id Semi)]
+  fn stmt_break_label(b: Token, label: Token, s: Token) -> Stmt<'p> { mk_stmt(b.loc(), s.end(), Break { label: Some(label.str()) }.into()) }
+  #[rule(Stmt -> Continue Semi)]
+  fn stmt_continue(c: Token, s: Token) -> Stmt<'p> { mk_stmt(c.loc(), s.end(), Continue { label: None }.into()) }
+  #[rule(Stmt -> Continue Id Semi)]
+  fn stmt_continue_label(c: Token, label: Token, s: Token) -> Stmt<'p> { mk_stmt(c.loc(), s.end(), Continue { label: Some(label.str()) }.into()) }
   #[rule(Stmt -> Block)]
-  fn stmt_block(b: Block<'p>) -> Stmt<'p> { mk_stmt(b.loc, b.into()) }
+  fn stmt_block(b: Block<'p>) -> Stmt<'p> { mk_stmt(b.loc, b.end, b.into()) }
+
+  #[rule(Loop -> While LPar Expr RPar Stmt)]
+  fn loop_while(w: Token, _l: Token, cond: Expr<'p>, _r: Token, body: Stmt<'p>) -> Stmt<'p> {
+    mk_stmt(w.loc(), body.end, While { label: None, cond, body: mk_block(body) }.into())
+  }
+  #[rule(Loop -> Do Stmt While LPar Expr RPar Semi)]
+  fn loop_do_while(d: Token, body: Stmt<'p>, _w: Token, _l: Token, cond: Expr<'p>, _r: Token, s: Token) -> Stmt<'p> {
+    mk_stmt(d.loc(), s.end(), DoWhile { label: None, body: mk_block(body), cond }.into())
+  }
+  #[rule(Loop -> For LPar SimpleList Semi Expr Semi SimpleList RPar Stmt)]
+  fn loop_for(f: Token, _l: Token, init: Vec<Stmt<'p>>, _s1: Token, cond: Expr<'p>, _s2: Token, update: Vec<Stmt<'p>>, _r: Token, body: Stmt<'p>) -> Stmt<'p> {
+    mk_stmt(f.loc(), body.end, For { label: None, init, cond, update, body: mk_block(body) }.into())
+  }
+  #[rule(Loop -> For LPar VarType Id Colon Expr RPar Stmt)]
+  fn loop_foreach(&self, f: Token, _l: Token, syn_ty: SynTy<'p>, name: Token, _c: Token, arr: Expr<'p>, _r: Token, body: Stmt<'p>) -> Stmt<'p> {
+    let var = self.alloc.var.alloc(VarDef { loc: name.loc(), name: name.str(), syn_ty, init: None, end: name.end(), ty: dft(), owner: dft(), used: dft(), static_: false, final_: false });
+    mk_stmt(f.loc(), body.end, Foreach { label: None, var, arr, body: mk_block(body) }.into())
+  }
+
+  // (the cases, the defaults), the default can be anywhere among the cases, because there is no fallthrough between them
+  // multiple defaults are reported in typeck, so that both parsers report them in the same way
+  #[rule(CaseList -> CaseList SwitchCase)]
+  fn case_list(l: (Vec<Case<'p>>, Vec<Block<'p>>), r: Case<'p>) -> (Vec<Case<'p>>, Vec<Block<'p>>) { (l.0.pushed(r), l.1) }
+  #[rule(CaseList -> CaseList Default Colon StmtListOrEmpty)]
+  fn case_list_default(l: (Vec<Case<'p>>, Vec<Block<'p>>), d: Token, _c: Token, stmt: Vec<Stmt<'p>>) -> (Vec<Case<'p>>, Vec<Block<'p>>) {
+    (l.0, l.1.pushed(Block { loc: d.loc(), end: NO_LOC, stmt, scope: dft() }))
+  }
+  #[rule(CaseList ->)]
+  fn case_list0() -> (Vec<Case<'p>>, Vec<Block<'p>>) { (vec![], vec![]) }
+
+  #[rule(SwitchCase -> Case Expr Colon StmtListOrEmpty)]
+  fn switch_case(c: Token, label: Expr<'p>, _c: Token, stmt: Vec<Stmt<'p>>) -> Case<'p> {
+    Case { loc: c.loc(), label, body: Block { loc: c.loc(), end: NO_LOC, stmt, scope: dft() } }
+  }
 
   #[rule(MaybeElse -> Else Stmt)]
   fn maybe_else1(_e: Token, b: Stmt<'p>) -> Option<Block<'p>> { Some(mk_block(b)) }
@@ -207,42 +464,115 @@ impl<'p> Parser<'p> {
   #[prec(Empty)]
   fn maybe_else0() -> Option<Block<'p>> { None }
 
-  #[rule(Simple -> LValue Assign Expr)]
-  fn simple_assign(dst: Expr<'p>, a: Token, src: Expr<'p>) -> Stmt<'p> { mk_stmt(a.loc(), Assign { dst, src }.into()) }
+  #[rule(Simple -> LValue AddAssign Expr)]
+  fn simple_add_assign(dst: Expr<'p>, a: Token, src: Expr<'p>) -> Stmt<'p> { mk_stmt(a.loc(), src.end, Assign { dst, src, op: Some(BinOp::Add) }.into()) }
+  #[rule(Simple -> LValue SubAssign Expr)]
+  fn simple_sub_assign(dst: Expr<'p>, a: Token, src: Expr<'p>) -> Stmt<'p> { mk_stmt(a.loc(), src.end, Assign { dst, src, op: Some(BinOp::Sub) }.into()) }
+  #[rule(Simple -> LValue MulAssign Expr)]
+  fn simple_mul_assign(dst: Expr<'p>, a: Token, src: Expr<'p>) -> Stmt<'p> { mk_stmt(a.loc(), src.end, Assign { dst, src, op: Some(BinOp::Mul) }.into()) }
+  #[rule(Simple -> LValue DivAssign Expr)]
+  fn simple_div_assign(dst: Expr<'p>, a: Token, src: Expr<'p>) -> Stmt<'p> { mk_stmt(a.loc(), src.end, Assign { dst, src, op: Some(BinOp::Div) }.into()) }
+  #[rule(Simple -> LValue ModAssign Expr)]
+  fn simple_mod_assign(dst: Expr<'p>, a: Token, src: Expr<'p>) -> Stmt<'p> { mk_stmt(a.loc(), src.end, Assign { dst, src, op: Some(BinOp::Mod) }.into()) }
+  #[rule(SimpleList -> SimpleList Comma Simple)]
+  fn simple_list(l: Vec<Stmt<'p>>, _c: Token, r: Stmt<'p>) -> Vec<Stmt<'p>> { l.pushed(r) }
+  #[rule(SimpleList -> Simple)]
+  fn simple_list1(s: Stmt<'p>) -> Vec<Stmt<'p>> { vec![s] }
+
   #[rule(Simple -> VarDef)] // the VarDef without init
-  fn simple_var_def(v: &'p VarDef<'p>) -> Stmt<'p> { mk_stmt(v.loc, v.into()) }
-  #[rule(Simple -> Type Id Assign Expr)] // the VarDef with init
+  fn simple_var_def(v: &'p VarDef<'p>) -> Stmt<'p> { mk_stmt(v.loc, v.end, v.into()) }
+  #[rule(Simple -> VarType Id Assign Init)] // the VarDef with init
   fn simple_var_def_init(&self, syn_ty: SynTy<'p>, name: Token, a: Token, init: Expr<'p>) -> Stmt<'p> {
     let loc = name.loc();
-    mk_stmt(loc, (&*self.alloc.var.alloc(VarDef { loc, name: name.str(), syn_ty, init: Some((a.loc(), init)), ty: dft(), owner: dft() })).into())
+    mk_stmt(loc, init.end, (&*self.alloc.var.alloc(VarDef { loc, name: name.str(), syn_ty, end: init.end, init: Some((a.loc(), init)), ty: dft(), owner: dft(), used: dft(), static_: false, final_: false })).into())
+  }
+  #[rule(Simple -> Var Id Assign Init)]
+  fn simple_var_def_infer(&self, v: Token, name: Token, a: Token, init: Expr<'p>) -> Stmt<'p> {
+    let (loc, syn_ty) = (name.loc(), SynTy { loc: v.loc(), arr: 0, kind: SynTyKind::Var });
+    mk_stmt(loc, init.end, (&*self.alloc.var.alloc(VarDef { loc, name: name.str(), syn_ty, end: init.end, init: Some((a.loc(), init)), ty: dft(), owner: dft(), used: dft(), static_: false, final_: false })).into())
+  }
+  #[rule(Simple -> Final VarType Id Assign Init)]
+  fn simple_var_def_final(&self, _f: Token, syn_ty: SynTy<'p>, name: Token, a: Token, init: Expr<'p>) -> Stmt<'p> {
+    let loc = name.loc();
+    mk_stmt(loc, init.end, (&*self.alloc.var.alloc(VarDef { loc, name: name.str(), syn_ty, end: init.end, init: Some((a.loc(), init)), ty: dft(), owner: dft(), used: dft(), static_: false, final_: true })).into())
+  }
+  #[rule(Simple -> Final Var Id Assign Init)]
+  fn simple_var_def_final_infer(&self, _f: Token, v: Token, name: Token, a: Token, init: Expr<'p>) -> Stmt<'p> {
+    let (loc, syn_ty) = (name.loc(), SynTy { loc: v.loc(), arr: 0, kind: SynTyKind::Var });
+    mk_stmt(loc, init.end, (&*self.alloc.var.alloc(VarDef { loc, name: name.str(), syn_ty, end: init.end, init: Some((a.loc(), init)), ty: dft(), owner: dft(), used: dft(), static_: false, final_: true })).into())
+  }
+  #[rule(Simple -> Final VarType Id)] // always an error in typeck
+  fn simple_var_def_final0(&self, _f: Token, syn_ty: SynTy<'p>, name: Token) -> Stmt<'p> {
+    let loc = name.loc();
+    mk_stmt(loc, name.end(), (&*self.alloc.var.alloc(VarDef { loc, name: name.str(), syn_ty, init: None, end: name.end(), ty: dft(), owner: dft(), used: dft(), static_: false, final_: true })).into())
   }
+  #[rule(Simple -> Var Id)] // always an error in typeck, but it is more friendly than a syntax error
+  fn simple_var_def_infer0(&self, v: Token, name: Token) -> Stmt<'p> {
+    let (loc, syn_ty) = (name.loc(), SynTy { loc: v.loc(), arr: 0, kind: SynTyKind::Var });
+    mk_stmt(loc, name.end(), (&*self.alloc.var.alloc(VarDef { loc, name: name.str(), syn_ty, init: None, end: name.end(), ty: dft(), owner: dft(), used: dft(), static_: false, final_: false })).into())
+  }
+  // an assignment at the top level is a statement, so `Simple -> LValue Assign Expr` is not needed (and it conflicts with this)
   #[rule(Simple -> Expr)]
-  fn simple_mk_expr(e: Expr<'p>) -> Stmt<'p> { mk_stmt(e.loc, e.into()) }
+  fn simple_mk_expr(e: Expr<'p>) -> Stmt<'p> {
+    match e.kind {
+      ExprKind::Assign(a) => mk_stmt(e.loc, e.end, (*a).into()),
+      _ => mk_stmt(e.loc, e.end, e.into()),
+    }
+  }
   #[rule(Simple ->)]
-  fn simple_skip() -> Stmt<'p> { mk_stmt(NO_LOC, Skip.into()) }
+  fn simple_skip() -> Stmt<'p> { mk_stmt(NO_LOC, NO_LOC, Skip.into()) }
 
   #[rule(Expr -> LValue)]
   fn expr_lvalue(l: Expr<'p>) -> Expr<'p> { l }
-  #[rule(Expr -> VarSel LPar ExprListOrEmpty RPar)]
-  fn expr_call(func: Expr<'p>, l: Token, arg: Vec<Expr<'p>>, _r: Token) -> Expr<'p> {
-    mk_expr(l.loc(), Call { func: Box::new(func), arg, func_ref: dft() }.into())
+  #[rule(Expr -> Expr LBrk Expr DotDot Expr RBrk)]
+  fn expr_slice(arr: Expr<'p>, l: Token, lo: Expr<'p>, _d: Token, hi: Expr<'p>, r: Token) -> Expr<'p> {
+    mk_expr(l.loc(), r.end(), Slice { arr: Box::new(arr), lo: Box::new(lo), hi: Box::new(hi) }.into())
+  }
+  // `func` is not restricted to VarSel, because a function value can also be called, e.g.: `((int x) => x)(1)`
+  #[rule(Expr -> Expr LPar ExprListOrEmpty RPar)]
+  fn expr_call(func: Expr<'p>, l: Token, arg: Vec<Expr<'p>>, r: Token) -> Expr<'p> {
+    mk_expr(l.loc(), r.end(), Call { func: Box::new(func), arg, func_ref: dft() }.into())
   }
   #[rule(Expr -> IntLit)]
   fn expr_int(&mut self, i: Token) -> Expr<'p> { mk_int_lit(i.loc(), i.str(), &mut self.error) }
+  #[rule(Expr -> CharLit)]
+  fn expr_char(&mut self, c: Token) -> Expr<'p> { mk_char_lit(c.loc(), c.str(), &mut self.error) }
+  // the regex guarantees that it can be parsed, a literal too large becomes infinity
+  #[rule(Expr -> DoubleLit)]
+  fn expr_double(d: Token) -> Expr<'p> { mk_expr(d.loc(), d.end(), d.str().parse::<f64>().unwrap().into()) }
   #[rule(Expr -> True)]
-  fn expr_true(t: Token) -> Expr<'p> { mk_expr(t.loc(), true.into()) }
+  fn expr_true(t: Token) -> Expr<'p> { mk_expr(t.loc(), t.end(), true.into()) }
   #[rule(Expr -> False)]
-  fn expr_false(f: Token) -> Expr<'p> { mk_expr(f.loc(), false.into()) }
+  fn expr_false(f: Token) -> Expr<'p> { mk_expr(f.loc(), f.end(), false.into()) }
   #[rule(Expr -> StringLit)]
   fn expr_string(&mut self, s: Token) -> Expr<'p> {
-    let (loc, str) = (s.loc(), s.str());
-    check_str(str, &mut self.error, loc);
-    mk_expr(loc, str[1..str.len() - 1].into())
+    let loc = s.loc();
+    let str = decode_str(s.str(), &mut self.error, loc);
+    mk_expr(loc, s.end(), self.alloc.str.alloc(str).as_str().into())
   }
   #[rule(Expr -> Null)]
-  fn expr_null(n: Token) -> Expr<'p> { mk_expr(n.loc(), NullLit.into()) }
+  fn expr_null(n: Token) -> Expr<'p> { mk_expr(n.loc(), n.end(), NullLit.into()) }
+  // `Assign` has the lowest priority and is right associative, so `a = b = c + d` is `a = (b = (c + d))`
+  #[rule(Expr -> LValue Assign Expr)]
+  fn expr_assign(dst: Expr<'p>, a: Token, src: Expr<'p>) -> Expr<'p> { mk_expr(a.loc(), src.end, Box::new(Assign { dst, src, op: None }).into()) }
+  // not using Init, because reducing `Init -> Expr` before an operator conflicts with shifting it
+  #[rule(Expr -> LValue Assign LBrc RBrc)]
+  fn expr_assign_empty(dst: Expr<'p>, a: Token, l: Token, r: Token) -> Expr<'p> {
+    let src = mk_expr(l.loc(), r.end(), Vec::new().into());
+    mk_expr(a.loc(), src.end, Box::new(Assign { dst, src, op: None }).into())
+  }
   #[rule(Expr -> LPar Expr RPar)]
   fn expr_paren(_l: Token, m: Expr<'p>, _r: Token) -> Expr<'p> { m }
+  #[rule(Expr -> LPar Expr Comma ExprList RPar)]
+  fn expr_tuple(_l: Token, e: Expr<'p>, _c: Token, elem: Vec<Expr<'p>>, r: Token) -> Expr<'p> {
+    mk_expr(e.loc, r.end(), Tuple { elem: vec![e].into_iter().chain(elem).collect() }.into())
+  }
+  // a malformed index like `t.0x1` is parsed as `!0`, which is reported as out of range in typeck
+  // `t.0.1` is lexed as `t` `.` `0.1`, so nested selections should be written as `(t.0).1`
+  #[rule(Expr -> Expr Dot IntLit)]
+  fn expr_tuple_sel(tuple: Expr<'p>, _d: Token, idx: Token) -> Expr<'p> {
+    mk_expr(idx.loc(), idx.end(), TupleSel { tuple: Box::new(tuple), idx: idx.str().parse().unwrap_or(!0) }.into())
+  }
   #[rule(Expr -> Expr Add Expr)]
   fn expr_add(l: Expr<'p>, op: Token, r: Expr<'p>) -> Expr<'p> { mk_bin(l, r, op.loc(), BinOp::Add) }
   #[rule(Expr -> Expr Sub Expr)]
@@ -269,67 +599,154 @@ impl<'p> Parser<'p> {
   fn expr_and(l: Expr<'p>, op: Token, r: Expr<'p>) -> Expr<'p> { mk_bin(l, r, op.loc(), BinOp::And) }
   #[rule(Expr -> Expr Or Expr)]
   fn expr_or(l: Expr<'p>, op: Token, r: Expr<'p>) -> Expr<'p> { mk_bin(l, r, op.loc(), BinOp::Or) }
+  #[rule(Expr -> Expr BitAnd Expr)]
+  fn expr_bit_and(l: Expr<'p>, op: Token, r: Expr<'p>) -> Expr<'p> { mk_bin(l, r, op.loc(), BinOp::BitAnd) }
+  #[rule(Expr -> Expr BitOr Expr)]
+  fn expr_bit_or(l: Expr<'p>, op: Token, r: Expr<'p>) -> Expr<'p> { mk_bin(l, r, op.loc(), BinOp::BitOr) }
+  #[rule(Expr -> Expr BitXor Expr)]
+  fn expr_bit_xor(l: Expr<'p>, op: Token, r: Expr<'p>) -> Expr<'p> { mk_bin(l, r, op.loc(), BinOp::BitXor) }
+  #[rule(Expr -> Expr Shl Expr)]
+  fn expr_shl(l: Expr<'p>, op: Token, r: Expr<'p>) -> Expr<'p> { mk_bin(l, r, op.loc(), BinOp::Shl) }
+  #[rule(Expr -> Expr Shr Expr)]
+  fn expr_shr(l: Expr<'p>, op: Token, r: Expr<'p>) -> Expr<'p> { mk_bin(l, r, op.loc(), BinOp::Shr) }
+  #[rule(Expr -> Expr Question Expr Colon Expr)]
+  fn expr_ternary(cond: Expr<'p>, q: Token, on_true: Expr<'p>, _c: Token, on_false: Expr<'p>) -> Expr<'p> {
+    mk_expr(q.loc(), on_false.end, Ternary { cond: Box::new(cond), on_true: Box::new(on_true), on_false: Box::new(on_false) }.into())
+  }
+  // `{}` is an empty Block at the beginning of a statement, so an empty array literal is not an Expr, see Init
+  // a non-empty one at the beginning of a statement is told from a Block by the `,` / `;` / `}` after the first element
+  #[rule(Expr -> LBrc ExprList RBrc)]
+  fn expr_array_lit(l: Token, elem: Vec<Expr<'p>>, r: Token) -> Expr<'p> { mk_expr(l.loc(), r.end(), elem.into()) }
   #[rule(Expr -> ReadInteger LPar RPar)]
-  fn expr_read_int(r: Token, _l: Token, _r: Token) -> Expr<'p> { mk_expr(r.loc(), ReadInt.into()) }
+  fn expr_read_int(r: Token, _l: Token, r1: Token) -> Expr<'p> { mk_expr(r.loc(), r1.end(), ReadInt.into()) }
+  #[rule(Expr -> ReadDouble LPar RPar)]
+  fn expr_read_double(r: Token, _l: Token, r1: Token) -> Expr<'p> { mk_expr(r.loc(), r1.end(), ReadDouble.into()) }
   #[rule(Expr -> ReadLine LPar RPar)]
-  fn expr_read_line(r: Token, _l: Token, _r: Token) -> Expr<'p> { mk_expr(r.loc(), ReadLine.into()) }
-  #[rule(Expr -> This)]
-  fn expr_this(t: Token) -> Expr<'p> { mk_expr(t.loc(), This.into()) }
-  #[rule(Expr -> New Id LPar RPar)]
-  fn expr_new_class(n: Token, name: Token, _l: Token, _r: Token) -> Expr<'p> {
-    mk_expr(n.loc(), NewClass { name: name.str(), class: dft() }.into())
+  fn expr_read_line(r: Token, _l: Token, r1: Token) -> Expr<'p> { mk_expr(r.loc(), r1.end(), ReadLine.into()) }
+  #[rule(Expr -> Super)]
+  fn expr_super(t: Token) -> Expr<'p> { mk_expr(t.loc(), t.end(), Super.into()) }
+  #[rule(Expr -> New Id LPar ExprListOrEmpty RPar)]
+  fn expr_new_class(n: Token, name: Token, _l: Token, arg: Vec<Expr<'p>>, r: Token) -> Expr<'p> {
+    mk_expr(n.loc(), r.end(), NewClass { name: name.str(), ty_arg: None, arg, class: dft(), ctor: dft() }.into())
+  }
+  #[rule(Expr -> New Id Lt Type Gt LPar ExprListOrEmpty RPar)]
+  fn expr_new_generic(n: Token, name: Token, _lt: Token, ty_arg: SynTy<'p>, _gt: Token, _l: Token, arg: Vec<Expr<'p>>, r: Token) -> Expr<'p> {
+    mk_expr(n.loc(), r.end(), NewClass { name: name.str(), ty_arg: Some(ty_arg), arg, class: dft(), ctor: dft() }.into())
   }
-  #[rule(Expr -> New Type LBrk Expr RBrk)]
-  fn expr_new_array(n: Token, elem: SynTy<'p>, _l: Token, len: Expr<'p>, _r: Token) -> Expr<'p> {
-    mk_expr(n.loc(), NewArray { elem, len: Box::new(len) }.into())
+  #[rule(Expr -> New Type LBrk Expr RBrk NewDim)]
+  fn expr_new_array(n: Token, mut elem: SynTy<'p>, _l: Token, len: Expr<'p>, r: Token, dim: (Vec<Expr<'p>>, u32, Option<Loc>)) -> Expr<'p> {
+    elem.arr += dim.1;
+    mk_expr(n.loc(), dim.2.unwrap_or_else(|| r.end()), NewArray { elem, len: Box::new(len), sub_len: dim.0.reversed() }.into())
   }
+  // the element type is a bare type alias name, `new Id(...)` is still creating an object
+  #[rule(Expr -> New Id LBrk Expr RBrk NewDim)]
+  fn expr_new_alias_array(n: Token, name: Token, l: Token, len: Expr<'p>, r: Token, dim: (Vec<Expr<'p>>, u32, Option<Loc>)) -> Expr<'p> {
+    Parser::expr_new_array(n, Parser::var_type_alias(name), l, len, r, dim)
+  }
+  // (the inner lengths, the number of inner dimensions, the end of the last `]`) in `new`, empty dimensions can only be at the end
+  // `new int[m][n]` is always a 2-dimensional array, not indexing `new int[m]`
+  #[rule(NewDim -> LBrk Expr RBrk NewDim)]
+  fn new_dim(_l: Token, len: Expr<'p>, r: Token, dim: (Vec<Expr<'p>>, u32, Option<Loc>)) -> (Vec<Expr<'p>>, u32, Option<Loc>) {
+    (dim.0.pushed(len), dim.1 + 1, dim.2.or(Some(r.end())))
+  }
+  #[rule(NewDim -> EmptyDim)]
+  fn new_dim0(dim: (u32, Option<Loc>)) -> (Vec<Expr<'p>>, u32, Option<Loc>) { (vec![], dim.0, dim.1) }
+  #[rule(EmptyDim -> LBrk RBrk EmptyDim)]
+  fn empty_dim(_l: Token, r: Token, dim: (u32, Option<Loc>)) -> (u32, Option<Loc>) { (dim.0 + 1, dim.1.or(Some(r.end()))) }
+  #[rule(EmptyDim ->)]
+  #[prec(UMinus)]
+  fn empty_dim0() -> (u32, Option<Loc>) { (0, None) }
   #[rule(Expr -> InstanceOf LPar Expr Comma Id RPar)]
-  fn expr_instanceof(i: Token, _l: Token, e: Expr<'p>, _c: Tokenm, name: Token, _r: Token) -> Expr<'p> {
-    mk_expr(i.loc(), ClassTest { expr: Box::new(e), name: name.str(), class: dft() }.into())
+  fn expr_instanceof(i: Token, _l: Token, e: Expr<'p>, _c: Token, name: Token, r: Token) -> Expr<'p> {
+    mk_expr(i.loc(), r.end(), ClassTest { expr: Box::new(e), name: name.str(), class: dft() }.into())
   }
   #[rule(Expr -> LPar Class Id RPar Expr)]
   fn expr_cast(_l: Token, _c: Token, name: Token, _r: Token, e: Expr<'p>) -> Expr<'p> {
-    mk_expr(e.loc, ClassCast { expr: Box::new(e), name: name.str(), class: dft() }.into())
+    mk_expr(e.loc, e.end, ClassCast { expr: Box::new(e), name: name.str(), class: dft() }.into())
+  }
+  // the body extends as far as possible, e.g.: `(int x) => x + 1` is `(int x) => (x + 1)`
+  #[rule(Expr -> LPar LambdaParamListOrEmpty RPar Arrow Expr)]
+  fn expr_lambda(l: Token, param: Vec<&'p VarDef<'p>>, _r: Token, _a: Token, body: Expr<'p>) -> Expr<'p> {
+    mk_expr(l.loc(), body.end, Box::new(Lambda { param, body: LambdaBody::Expr(Box::new(body)), ret_param_ty: dft(), captured: dft(), scope: dft() }).into())
+  }
+  #[rule(Expr -> LPar LambdaParamListOrEmpty RPar Arrow Block)]
+  fn expr_lambda_block(l: Token, param: Vec<&'p VarDef<'p>>, _r: Token, _a: Token, body: Block<'p>) -> Expr<'p> {
+    mk_expr(l.loc(), body.end, Box::new(Lambda { param, body: LambdaBody::Block(body), ret_param_ty: dft(), captured: dft(), scope: dft() }).into())
   }
   #[rule(Expr -> Sub Expr)]
   #[prec(UMinus)]
   fn expr_neg(s: Token, r: Expr<'p>) -> Expr<'p> {
-    mk_expr(s.loc(), Unary { op: UnOp::Neg, r: Box::new(r) }.into())
+    mk_expr(s.loc(), r.end, Unary { op: UnOp::Neg, r: Box::new(r) }.into())
   }
   #[rule(Expr -> Not Expr)]
   fn expr_not(n: Token, r: Expr<'p>) -> Expr<'p> {
-    mk_expr(n.loc(), Unary { op: UnOp::Not, r: Box::new(r) }.into())
+    mk_expr(n.loc(), r.end, Unary { op: UnOp::Not, r: Box::new(r) }.into())
   }
+  #[rule(Expr -> BitNot Expr)]
+  fn expr_bit_not(n: Token, r: Expr<'p>) -> Expr<'p> {
+    mk_expr(n.loc(), r.end, Unary { op: UnOp::BitNot, r: Box::new(r) }.into())
+  }
+  #[rule(Expr -> Inc Expr)]
+  #[prec(UMinus)]
+  fn expr_pre_inc(i: Token, e: Expr<'p>) -> Expr<'p> { mk_inc_dec(e, i.loc(), NO_LOC, BinOp::Add, true) }
+  #[rule(Expr -> Dec Expr)]
+  #[prec(UMinus)]
+  fn expr_pre_dec(d: Token, e: Expr<'p>) -> Expr<'p> { mk_inc_dec(e, d.loc(), NO_LOC, BinOp::Sub, true) }
+  #[rule(Expr -> Expr Inc)]
+  fn expr_post_inc(e: Expr<'p>, i: Token) -> Expr<'p> { mk_inc_dec(e, i.loc(), i.end(), BinOp::Add, false) }
+  #[rule(Expr -> Expr Dec)]
+  fn expr_post_dec(e: Expr<'p>, d: Token) -> Expr<'p> { mk_inc_dec(e, d.loc(), d.end(), BinOp::Sub, false) }
 
   #[rule(ExprList -> ExprList Comma Expr)]
   fn expr_list(l: Vec<Expr<'p>>, _c: Token, r: Expr<'p>) -> Vec<Expr<'p>> { l.pushed(r) }
   #[rule(ExprList -> Expr)]
   fn expr_list1(e: Expr<'p>) -> Vec<Expr<'p>> { vec![e] }
 
+  // the initial value of a variable, or the returned value, the empty array literal `{}` is allowed here, its type comes from the context
+  #[rule(Init -> Expr)]
+  fn init(e: Expr<'p>) -> Expr<'p> { e }
+  #[rule(Init -> LBrc RBrc)]
+  fn init_empty_array(l: Token, r: Token) -> Expr<'p> { mk_expr(l.loc(), r.end(), Vec::new().into()) }
+
   #[rule(ExprListOrEmpty -> ExprList)]
   fn expr_list_or_empty1(e: Vec<Expr<'p>>) -> Vec<Expr<'p>> { e }
   #[rule(ExprListOrEmpty ->)]
   fn expr_list_or_empty0() -> Vec<Expr<'p>> { vec![] }
 
-  #[rule(MaybeOwner -> Expr Dot)]
-  fn maybe_owner1(e: Expr<'p>, _d: Token) -> Option<Box<Expr<'p>>> { Some(Box::new(e)) }
-  #[rule(MaybeOwner ->)]
-  fn maybe_owner0() -> Option<Box<Expr<'p>>> { None }
-
-  #[rule(VarSel -> MaybeOwner Id)]
-  fn var_sel(owner: Option<Box<Expr<'p>>>, name: Token) -> Expr<'p> {
-    mk_expr(name.loc(), VarSel { owner, name: name.str(), var: dft() }.into())
+  // not using `VarSel -> MaybeOwner Id` with `MaybeOwner -> Expr Dot | ε`
+  // because reducing the empty MaybeOwner before shifting Id conflicts with the labeled loop `Id Colon Loop`
+  #[rule(VarSel -> Expr Dot Id)]
+  fn var_sel1(owner: Expr<'p>, _d: Token, name: Token) -> Expr<'p> {
+    mk_expr(name.loc(), name.end(), VarSel { owner: Some(Box::new(owner)), name: name.str(), var: dft(), func: dft() }.into())
+  }
+  #[rule(VarSel -> Id)]
+  fn var_sel0(name: Token) -> Expr<'p> {
+    mk_expr(name.loc(), name.end(), VarSel { owner: None, name: name.str(), var: dft(), func: dft() }.into())
   }
 
   #[rule(LValue -> VarSel)]
   fn lvalue_var_sel(e: Expr<'p>) -> Expr<'p> { e }
+  // `this` is not an lvalue, it is here so that `this = ...` is reported as AssignToThis in typeck, instead of a syntax error
+  #[rule(LValue -> This)]
+  fn lvalue_this(t: Token) -> Expr<'p> { mk_expr(t.loc(), t.end(), This.into()) }
   #[rule(LValue -> Expr LBrk Expr RBrk)]
-  fn lvalue_index(arr: Expr<'p>, l: Token, idx: Expr<'p>, _r: Token) -> Expr<'p> {
-    mk_expr(l.loc(), IndexSel { arr: Box::new(arr), idx: Box::new(idx) }.into())
+  fn lvalue_index(arr: Expr<'p>, l: Token, idx: Expr<'p>, r: Token) -> Expr<'p> {
+    mk_expr(l.loc(), r.end(), IndexSel { arr: Box::new(arr), idx: Box::new(idx) }.into())
   }
 
+  // a bare type alias name is only allowed as a whole type followed by the declared name, e.g.: `IntArray a;`
+  // other uses are written like a class type, e.g.: `class IntArray[]`, since `IntArray[` could also start an indexing
+  #[rule(VarType -> Type)]
+  fn var_type(ty: SynTy<'p>) -> SynTy<'p> { ty }
+  #[rule(VarType -> Id)]
+  fn var_type_alias(name: Token) -> SynTy<'p> { SynTy { loc: name.loc(), arr: 0, kind: SynTyKind::Named(name.str(), false, None) } }
+
   #[rule(Type -> Int)]
   fn type_int(i: Token) -> SynTy<'p> { SynTy { loc: i.loc(), arr: 0, kind: SynTyKind::Int } }
+  #[rule(Type -> Char)]
+  fn type_char(c: Token) -> SynTy<'p> { SynTy { loc: c.loc(), arr: 0, kind: SynTyKind::Char } }
+  #[rule(Type -> Double)]
+  fn type_double(d: Token) -> SynTy<'p> { SynTy { loc: d.loc(), arr: 0, kind: SynTyKind::Double } }
   #[rule(Type -> Bool)]
   fn type_bool(b: Token) -> SynTy<'p> { SynTy { loc: b.loc(), arr: 0, kind: SynTyKind::Bool } }
   #[rule(Type -> Void)]
@@ -337,7 +754,37 @@ impl<'p> Parser<'p> {
   #[rule(Type -> String)]
   fn type_string(s: Token) -> SynTy<'p> { SynTy { loc: s.loc(), arr: 0, kind: SynTyKind::String } }
   #[rule(Type -> Class Id)]
-  fn type_class(c: Token, name: Token) -> SynTy<'p> { SynTy { loc: c.loc(), arr: 0, kind: SynTyKind::Named(name.str()) } }
+  fn type_class(c: Token, name: Token) -> SynTy<'p> { SynTy { loc: c.loc(), arr: 0, kind: SynTyKind::Named(name.str(), false, None) } }
+  #[rule(Type -> Class Id Not)]
+  fn type_non_null_class(c: Token, name: Token, _n: Token) -> SynTy<'p> { SynTy { loc: c.loc(), arr: 0, kind: SynTyKind::Named(name.str(), true, None) } }
+  // `>>` is lexed as one token, so nested type arguments should be separated, e.g.: `class Box<class Box<int> >`
+  #[rule(Type -> Class Id Lt Type Gt)]
+  fn type_generic(c: Token, name: Token, _l: Token, arg: SynTy<'p>, _g: Token) -> SynTy<'p> {
+    SynTy { loc: c.loc(), arr: 0, kind: SynTyKind::Named(name.str(), false, Some(Box::new(arg))) }
+  }
+  #[rule(Type -> Class Id Lt Type Gt Not)]
+  fn type_non_null_generic(c: Token, name: Token, _l: Token, arg: SynTy<'p>, _g: Token, _n: Token) -> SynTy<'p> {
+    SynTy { loc: c.loc(), arr: 0, kind: SynTyKind::Named(name.str(), true, Some(Box::new(arg))) }
+  }
   #[rule(Type -> Type LBrk RBrk)]
   fn type_array(mut ty: SynTy<'p>, _l: Token, _r: Token) -> SynTy<'p> { (ty.arr += 1, ty).1 }
+  // `int[](int)` is a function returning `int[]`, `int(int)[]` is an array of functions
+  #[rule(Type -> Type LPar TypeListOrEmpty RPar)]
+  fn type_func(ret: SynTy<'p>, _l: Token, param: Vec<SynTy<'p>>, _r: Token) -> SynTy<'p> {
+    SynTy { loc: ret.loc, arr: 0, kind: SynTyKind::Func(vec![ret].into_iter().chain(param).collect()) }
+  }
+  #[rule(Type -> LPar Type Comma TypeList RPar)]
+  fn type_tuple(l: Token, t: SynTy<'p>, _c: Token, elem: Vec<SynTy<'p>>, _r: Token) -> SynTy<'p> {
+    SynTy { loc: l.loc(), arr: 0, kind: SynTyKind::Tuple(vec![t].into_iter().chain(elem).collect()) }
+  }
+
+  #[rule(TypeListOrEmpty -> TypeList)]
+  fn type_list_or_empty1(l: Vec<SynTy<'p>>) -> Vec<SynTy<'p>> { l }
+  #[rule(TypeListOrEmpty ->)]
+  fn type_list_or_empty0() -> Vec<SynTy<'p>> { vec![] }
+
+  #[rule(TypeList -> TypeList Comma Type)]
+  fn type_list(l: Vec<SynTy<'p>>, _c: Token, r: SynTy<'p>) -> Vec<SynTy<'p>> { l.pushed(r) }
+  #[rule(TypeList -> Type)]
+  fn type_list1(t: SynTy<'p>) -> Vec<SynTy<'p>> { vec![t] }
 }
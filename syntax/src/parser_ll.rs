@@ -1,11 +1,12 @@
 // many lines are just copied from parser.rs
 // though these types have the same name(Parser, Token, Lexer, ...), actually they are different types
-use crate::{ast::*, ty::*, VecExt, dft, check_str, mk_expr, mk_stmt, mk_int_lit, mk_block};
+use crate::{ast::*, ty::*, VecExt, dft, decode_str, mk_expr, mk_stmt, mk_int_lit, mk_char_lit, mk_block, strip_block_comment, loc_after};
 use parser_macros::ll1;
 use common::{ErrorKind, Loc, NO_LOC, BinOp, UnOp, Errors, HashSet, HashMap};
 
-pub fn work<'p>(code: &'p str, alloc: &'p ASTAlloc<'p>) -> Result<&'p Program<'p>, Errors<'p, Ty<'p>>> {
-  let mut parser = Parser { alloc, error: Errors::default() };
+pub fn work<'p>(code: &'p str, alloc: &'p ASTAlloc<'p>, max_error: usize) -> Result<&'p Program<'p>, Errors<'p, Ty<'p>>> {
+  let mut parser = Parser { alloc, error: Errors::with_limit(max_error) };
+  let code = if code.contains("/*") { alloc.str.alloc(strip_block_comment(code, &mut parser.error)).as_str() } else { code };
   match parser.parse(&mut Lexer::new(code.as_bytes())) {
     Some(program) if parser.error.0.is_empty() => Ok(program),
     _ => Err(parser.error)
@@ -25,7 +26,7 @@ impl<'p> Parser<'p> {
         self.error.issue(loc, ErrorKind::UnrecognizedChar(token.piece[0] as char))
       }
       TokenKind::UntermString => {
-        check_str(token.str(), &mut self.error, loc);
+        decode_str(token.str(), &mut self.error, loc);
         self.error.issue(lexer_loc, ErrorKind::SyntaxError)
       }
       _ => if self.error.0.last().map(|x| x.0) != Some(loc) {
@@ -47,7 +48,12 @@ impl<'p> Parser<'p> {
     let table = &table[target];
     let (prod, rhs) = if let Some(x) = table.get(&(lookahead.ty as u32)) { x } else {
       self.error(lookahead, lexer.loc());
-      unimplemented!()
+      // panic mode: skip tokens until one can start `target`, or one can follow it (e.g. `;` or `}`)
+      loop {
+        if let Some(x) = table.get(&(lookahead.ty as u32)) { break x; }
+        if end.contains(&(lookahead.ty as u32)) || lookahead.ty as u32 == TokenKind::_Eof as u32 { return StackItem::_Fail; }
+        *lookahead = lexer.next();
+      }
     };
     let value_stk = rhs.iter().map(|&x| {
       if is_nt(x) {
@@ -61,40 +67,57 @@ impl<'p> Parser<'p> {
         StackItem::_Fail
       }
     }).collect::<Vec<_>>();
-    self.act(*prod, value_stk)
+    // the error is already reported, a failed child makes its parent fail without further errors
+    if value_stk.iter().any(|x| if let StackItem::_Fail = x { true } else { false }) { StackItem::_Fail } else { self.act(*prod, value_stk) }
   }
 }
 
 impl<'p> Token<'p> {
   pub fn str(&self) -> &'p str { std::str::from_utf8(self.piece).unwrap() }
-  pub fn loc(&self) -> Loc { Loc(self.line, self.col) }
+  // the loc covers the whole token
+  pub fn loc(&self) -> Loc { Loc(self.line, self.col, self.end().1) }
+  pub fn end(&self) -> Loc { loc_after(Loc(self.line, self.col, self.col), self.str()) }
 }
 
 impl Lexer<'_> {
-  pub fn loc(&self) -> Loc { Loc(self.line, self.col) }
+  pub fn loc(&self) -> Loc { Loc(self.line, self.col, self.col) }
 }
 
+// the classes and the type aliases in the program
+type TopList<'p> = (Vec<&'p ClassDef<'p>>, Vec<TypeAlias<'p>>);
+
 type Terms<'p> = Vec<(Expr<'p>, (Loc, BinOp))>;
 
 fn merge_terms<'p>(mut l: Expr<'p>, ts: Terms<'p>) -> Expr<'p> {
   for (r, (loc, op)) in ts.into_iter().rev() {
-    l = mk_expr(loc, Binary { op, l: Box::new(l), r: Box::new(r) }.into());
+    l = mk_expr(loc, r.end, Binary { op, l: Box::new(l), r: Box::new(r) }.into());
   }
   l
 }
 
+// the end of a local variable definition, `name_end` is the end of its name
+fn var_end(name_end: Loc, init: &Option<(Loc, Expr)>) -> Loc { init.as_ref().map_or(name_end, |(_, e)| e.end) }
+
+// `dst = src` as an expression if there is `= src` after `dst`
+fn chain_assign<'p>(dst: Expr<'p>, rest: Option<(Loc, Expr<'p>)>) -> Expr<'p> {
+  match rest { Some((loc, src)) => mk_expr(loc, src.end, Box::new(Assign { dst, src, op: None }).into()), None => dst }
+}
+
 fn merge_idx_id_call<'p>(mut l: Expr<'p>, ts: Vec<IndexOrIdOrCall<'p>>) -> Expr<'p> {
   for t in ts.into_iter().rev() {
     match t {
-      IndexOrIdOrCall::Index(loc, idx) =>
-        l = mk_expr(loc, IndexSel { arr: Box::new(l), idx: Box::new(idx) }.into()),
+      IndexOrIdOrCall::Index(loc, idx, None, end) =>
+        l = mk_expr(loc, end, IndexSel { arr: Box::new(l), idx: Box::new(idx) }.into()),
+      IndexOrIdOrCall::Index(loc, lo, Some(hi), end) =>
+        l = mk_expr(loc, end, Slice { arr: Box::new(l), lo: Box::new(lo), hi: Box::new(hi) }.into()),
       IndexOrIdOrCall::IdOrCall(loc, name, maybe_call) => match maybe_call {
-        Some((call_loc, arg)) => {
-          let func = Box::new(mk_expr(loc, VarSel { owner: Some(Box::new(l)), name, var: dft() }.into()));
-          l = mk_expr(call_loc, Call { func, arg, func_ref: dft() }.into());
+        Some((call_loc, arg, end)) => {
+          let func = Box::new(mk_expr(loc, loc_after(loc, name), VarSel { owner: Some(Box::new(l)), name, var: dft(), func: dft() }.into()));
+          l = mk_expr(call_loc, end, Call { func, arg, func_ref: dft() }.into());
         }
-        None => l = mk_expr(loc, VarSel { owner: Some(Box::new(l)), name, var: dft() }.into()),
+        None => l = mk_expr(loc, loc_after(loc, name), VarSel { owner: Some(Box::new(l)), name, var: dft(), func: dft() }.into()),
       }
+      IndexOrIdOrCall::TupleSel(loc, idx, end) => l = mk_expr(loc, end, TupleSel { tuple: Box::new(l), idx }.into()),
     }
   }
   l
@@ -102,13 +125,48 @@ fn merge_idx_id_call<'p>(mut l: Expr<'p>, ts: Vec<IndexOrIdOrCall<'p>>) -> Expr<
 
 // this is pub because StackItem is pub(maybe you need it? though not very likely)
 pub enum IndexOrIdOrCall<'p> {
-  Index(Loc, Expr<'p>),
-  IdOrCall(Loc, &'p str, Option<(Loc, Vec<Expr<'p>>)>),
+  // `[idx]`, or `[lo..hi]` for a slice, the last Loc is the end of `]`
+  Index(Loc, Expr<'p>, Option<Expr<'p>>, Loc),
+  // the call is (loc of `(`, arguments, end of `)`)
+  IdOrCall(Loc, &'p str, Option<(Loc, Vec<Expr<'p>>, Loc)>),
+  // `.0`, `.1`, ..., the last Loc is the end of the index
+  TupleSel(Loc, u32, Loc),
+}
+
+// what follows the first Simple in `for (`, a foreach loop's variable is parsed as a Simple to avoid the conflict with a for loop's init
+pub enum ForRem<'p> {
+  // (the other init statements in reversed order, cond, the update statements, body)
+  For(Vec<Stmt<'p>>, Expr<'p>, Vec<Stmt<'p>>, Stmt<'p>),
+  // (the array, body)
+  Foreach(Expr<'p>, Stmt<'p>),
+}
+
+// the rest of a parenthesized expression after the first expression
+pub enum ParenKind<'p> {
+  Paren,
+  // the other elements of a tuple, and the end of `)`
+  Tuple(Vec<Expr<'p>>, Loc),
+  // the source of an assignment, e.g.: `(a = b)`
+  Assign(Loc, Expr<'p>),
 }
 
 pub enum NewClassOrArray<'p> {
-  NewClass(&'p str),
-  NewArray(SynTy<'p>, Expr<'p>),
+  // the last Loc is the end of the expression
+  NewClass(&'p str, Option<SynTy<'p>>, Vec<Expr<'p>>, Loc),
+  NewArray(SynTy<'p>, Expr<'p>, Vec<Expr<'p>>, Loc),
+}
+
+// what follows the first Id in a field, a constructor's parameters and body, or the name of a field or method whose type is a bare type alias name
+pub enum CtorOrMember<'p> {
+  Ctor(Vec<&'p VarDef<'p>>, Block<'p>),
+  Member(Loc, &'p str, Option<(Vec<&'p VarDef<'p>>, Block<'p>)>),
+}
+
+// what follows the first Expr in a Simple, an assignment (or nothing), or the name of a variable whose type is the Expr
+// the Expr should be a bare type alias name, this avoids the conflict between `IntArray a;` and an expression statement
+pub enum AssignOrVarDef<'p> {
+  Assign(Option<(Loc, Option<BinOp>, Expr<'p>)>),
+  VarDef(Loc, &'p str, Option<(Loc, Expr<'p>)>),
 }
 
 #[ll1(Program)]
@@ -119,24 +177,38 @@ priority = []
 'void' = 'Void'
 'int' = 'Int'
 'bool' = 'Bool'
+'char' = 'Char'
+'double' = 'Double'
 'string' = 'String'
 'new' = 'New'
 'null' = 'Null'
 'true' = 'True'
 'false' = 'False'
 'class' = 'Class'
+'typedef' = 'Typedef'
 'extends' = 'Extends'
+'interface' = 'Interface'
+'implements' = 'Implements'
 'this' = 'This'
+'super' = 'Super'
 'while' = 'While'
+'do' = 'Do'
 'for' = 'For'
 'if' = 'If'
 'else' = 'Else'
 'return' = 'Return'
 'break' = 'Break'
+'continue' = 'Continue'
+'switch' = 'Switch'
+'case' = 'Case'
+'default' = 'Default'
 'Print' = 'Print'
+'Printf' = 'Printf'
 'ReadInteger' = 'ReadInteger'
+'ReadDouble' = 'ReadDouble'
 'ReadLine' = 'ReadLine'
 'static' = 'Static'
+'final' = 'Final'
 'instanceof' = 'InstanceOf'
 '<=' = 'Le'
 '>=' = 'Ge'
@@ -150,8 +222,14 @@ priority = []
 '/' = 'Div'
 '%' = 'Mod'
 '=' = 'Assign'
+'\+=' = 'AddAssign'
+'-=' = 'SubAssign'
+'\*=' = 'MulAssign'
+'/=' = 'DivAssign'
+'%=' = 'ModAssign'
 '<' = 'Lt'
 '>' = 'Gt'
+'\.\.' = 'DotDot'
 '\.' = 'Dot'
 ',' = 'Comma'
 ';' = 'Semi' # short for semicolon
@@ -168,51 +246,115 @@ priority = []
 '"[^"\\]*(\\.[^"\\]*)*' = 'UntermString'
 '//[^\n]*' = '_Eps'
 '\s+' = '_Eps'
-'\d+|(0x[0-9a-fA-F]+)' = 'IntLit'
-'[A-Za-z]\w*' = 'Id'
+'\d+\.\d+([eE][+-]?\d+)?' = 'DoubleLit'
+# the digits (after 0x / 0o / 0b) and the separators `_` are checked in mk_int_lit, so that `0b102` and `1__0` are reported as a whole
+'\d[\d_]*|(0[xXoObB]\w*)' = 'IntLit'
+# the lexer works on bytes, so a non-ascii char is matched by `[^...]+`, and the number of chars is checked in mk_char_lit
+"'([^'\\\\\\n]+|\\\\.)'" = 'CharLit'
+'[A-Za-z_]\w*' = 'Id'
 '.' = '_Err'
 "##)]
 impl<'p> Parser<'p> {
   #[rule(Program -> ClassList)]
-  fn program(&self, class: Vec<&'p ClassDef<'p>>) -> &'p Program<'p> {
-    self.alloc.program.alloc(Program { class: class.reversed(), main: dft(), scope: dft() })
+  fn program(&self, l: TopList<'p>) -> &'p Program<'p> {
+    self.alloc.program.alloc(Program { class: l.0.reversed(), alias: l.1.reversed(), main: dft(), scope: dft() })
   }
 
   // in this way, the classes will be pushed from left to right, so the order is wrong
   // but in Program -> ClassList, a `class.reverse()` makes it correct
   // the same method is applied in many places(for consistency, I recommend all XxxList to be reversed)
   #[rule(ClassList -> ClassDef ClassList)]
-  fn class_list(l: &'p ClassDef<'p>, r: Vec<&'p ClassDef<'p>>) -> Vec<&'p ClassDef<'p>> { r.pushed(l) }
+  fn class_list(l: &'p ClassDef<'p>, r: TopList<'p>) -> TopList<'p> { (r.0.pushed(l), r.1) }
+  #[rule(ClassList -> TypeAlias ClassList)]
+  fn class_list_alias(l: TypeAlias<'p>, r: TopList<'p>) -> TopList<'p> { (r.0, r.1.pushed(l)) }
   #[rule(ClassList ->)]
-  fn class_list1() -> Vec<&'p ClassDef<'p>> { vec![] }
+  fn class_list1() -> TopList<'p> { (vec![], vec![]) }
+
+  #[rule(TypeAlias -> Typedef VarType Id Semi)]
+  fn type_alias(_t: Token, syn_ty: SynTy<'p>, name: Token, _s: Token) -> TypeAlias<'p> {
+    TypeAlias { loc: name.loc(), name: name.str(), syn_ty, ty: dft(), resolving: dft() }
+  }
 
-  #[rule(ClassDef -> Class Id MaybeExtends LBrc FieldList RBrc)]
-  fn class_def(&self, c: Token, name: Token, parent: Option<&'p str>, _l: Token, field: Vec<FieldDef<'p>>, _r: Token) -> &'p ClassDef<'p> {
-    self.alloc.class.alloc(ClassDef { loc: c.loc(), name: name.str(), parent, field: field.reversed(), parent_ref: dft(), scope: dft() })
+  #[rule(ClassDef -> Class Id MaybeTyParam MaybeExtends MaybeImplements LBrc FieldList RBrc)]
+  fn class_def(&self, c: Token, name: Token, ty_param: Option<&'p str>, parent: Option<&'p str>, implements: Vec<(Loc, &'p str)>, _l: Token, field: Vec<FieldDef<'p>>, r: Token) -> &'p ClassDef<'p> {
+    self.alloc.class.alloc(ClassDef { loc: c.loc(), name: name.str(), ty_param, parent, abstract_: false, interface: false, implements: implements.reversed(), field: field.reversed(), end: r.end(), parent_ref: dft(), implements_ref: dft(), scope: dft() })
+  }
+  #[rule(ClassDef -> Interface Id LBrc SigList RBrc)]
+  fn class_def_interface(&self, i: Token, name: Token, _l: Token, field: Vec<FieldDef<'p>>, r: Token) -> &'p ClassDef<'p> {
+    self.alloc.class.alloc(ClassDef { loc: i.loc(), name: name.str(), ty_param: None, parent: None, abstract_: true, interface: true, implements: vec![], field: field.reversed(), end: r.end(), parent_ref: dft(), implements_ref: dft(), scope: dft() })
   }
 
+  #[rule(MaybeTyParam -> Lt Id Gt)]
+  fn maybe_ty_param1(_l: Token, name: Token, _g: Token) -> Option<&'p str> { Some(name.str()) }
+  #[rule(MaybeTyParam ->)]
+  fn maybe_ty_param0() -> Option<&'p str> { None }
+
   #[rule(MaybeExtends -> Extends Id)]
   fn maybe_extends1(_e: Token, name: Token) -> Option<&'p str> { Some(name.str()) }
   #[rule(MaybeExtends ->)]
   fn maybe_extends0() -> Option<&'p str> { None }
 
+  #[rule(MaybeImplements -> Implements Id IdListRem)]
+  fn maybe_implements1(_i: Token, l: Token, r: Vec<(Loc, &'p str)>) -> Vec<(Loc, &'p str)> { r.pushed((l.loc(), l.str())) }
+  #[rule(MaybeImplements ->)]
+  fn maybe_implements0() -> Vec<(Loc, &'p str)> { vec![] }
+  #[rule(IdListRem -> Comma Id IdListRem)]
+  fn id_list_rem(_c: Token, l: Token, r: Vec<(Loc, &'p str)>) -> Vec<(Loc, &'p str)> { r.pushed((l.loc(), l.str())) }
+  #[rule(IdListRem ->)]
+  fn id_list_rem0() -> Vec<(Loc, &'p str)> { vec![] }
+
+  // the methods in an interface are declared like abstract methods, without `abstract`
+  #[rule(SigList -> VarType Id LPar VarDefListOrEmpty RPar Semi SigList)]
+  fn sig_list(&self, ret: SynTy<'p>, name: Token, _l: Token, param: Vec<&'p VarDef<'p>>, _r: Token, s: Token, r: Vec<FieldDef<'p>>) -> Vec<FieldDef<'p>> {
+    r.pushed(FieldDef::FuncDef(self.alloc.func.alloc(FuncDef { loc: name.loc(), name: name.str(), ret, param: param.reversed(), static_: false, abstract_: true, body: None, end: s.end(), ret_param_ty: dft(), class: dft(), next: dft(), ctor: false, scope: dft() })))
+  }
+  #[rule(SigList ->)]
+  fn sig_list0() -> Vec<FieldDef<'p>> { vec![] }
+
   #[rule(FieldList -> FieldDef FieldList)]
   fn field_list(l: FieldDef<'p>, r: Vec<FieldDef<'p>>) -> Vec<FieldDef<'p>> { r.pushed(l) }
   #[rule(FieldList ->)]
   fn field_list0() -> Vec<FieldDef<'p>> { vec![] }
 
-  #[rule(FieldDef -> Static Type Id LPar VarDefListOrEmpty RPar Block)]
-  fn field_def_f1(&self, _s: Token, ret: SynTy<'p>, name: Token, _l: Token, param: Vec<&'p VarDef<'p>>, _r: Token, body: Block<'p>) -> FieldDef<'p> {
-    let (loc, name) = (name.loc(), name.str());
-    FieldDef::FuncDef(self.alloc.func.alloc(FuncDef { loc, name, ret, param: param.reversed(), static_: true, body, ret_param_ty: dft(), class: dft(), scope: dft() }))
+  #[rule(FieldDef -> Static VarType Id FuncOrVar)]
+  fn field_def_fv1(&self, _s: Token, syn_ty: SynTy<'p>, name: Token, fov: Option<(Vec<&'p VarDef<'p>>, Block<'p>)>) -> FieldDef<'p> {
+    let (loc, end, name) = (name.loc(), name.end(), name.str());
+    if let Some((param, body)) = fov {
+      FieldDef::FuncDef(self.alloc.func.alloc(FuncDef { loc, name, ret: syn_ty, param: param.reversed(), static_: true, abstract_: false, end: body.end, body: Some(body), ret_param_ty: dft(), class: dft(), next: dft(), ctor: false, scope: dft() }))
+    } else {
+      FieldDef::VarDef(self.alloc.var.alloc(VarDef { loc, name, syn_ty, init: None, end, ty: dft(), owner: dft(), used: dft(), static_: true, final_: false }))
+    }
+  }
+  #[rule(FieldDef -> Id CtorOrMember)]
+  fn field_def_id(&self, name: Token, rem: CtorOrMember<'p>) -> FieldDef<'p> {
+    match rem {
+      CtorOrMember::Ctor(param, body) => {
+        let (loc, name) = (name.loc(), name.str());
+        let ret = SynTy { loc, arr: 0, kind: SynTyKind::Void };
+        FieldDef::FuncDef(self.alloc.func.alloc(FuncDef { loc, name, ret, param: param.reversed(), static_: false, abstract_: false, end: body.end, body: Some(body), ret_param_ty: dft(), class: dft(), next: dft(), ctor: true, scope: dft() }))
+      }
+      CtorOrMember::Member(loc, member, fov) => self.field_def(Parser::var_type_alias(name), loc, member, fov),
+    }
+  }
+  #[rule(CtorOrMember -> LPar VarDefListOrEmpty RPar Block)]
+  fn ctor_or_member_c(_l: Token, param: Vec<&'p VarDef<'p>>, _r: Token, body: Block<'p>) -> CtorOrMember<'p> { CtorOrMember::Ctor(param, body) }
+  #[rule(CtorOrMember -> Id FuncOrVar)]
+  fn ctor_or_member_m(name: Token, fov: Option<(Vec<&'p VarDef<'p>>, Block<'p>)>) -> CtorOrMember<'p> { CtorOrMember::Member(name.loc(), name.str(), fov) }
+  #[rule(FieldDef -> Final VarType Id Semi)]
+  fn field_def_final(&self, _f: Token, syn_ty: SynTy<'p>, name: Token, _s: Token) -> FieldDef<'p> {
+    FieldDef::VarDef(self.alloc.var.alloc(VarDef { loc: name.loc(), name: name.str(), syn_ty, init: None, end: name.end(), ty: dft(), owner: dft(), used: dft(), static_: false, final_: true }))
   }
   #[rule(FieldDef -> Type Id FuncOrVar)]
   fn field_def_fv(&self, syn_ty: SynTy<'p>, name: Token, fov: Option<(Vec<&'p VarDef<'p>>, Block<'p>)>) -> FieldDef<'p> {
-    let (loc, name) = (name.loc(), name.str());
+    self.field_def(syn_ty, name.loc(), name.str(), fov)
+  }
+
+  // a non-static field or method
+  fn field_def(&self, syn_ty: SynTy<'p>, loc: Loc, name: &'p str, fov: Option<(Vec<&'p VarDef<'p>>, Block<'p>)>) -> FieldDef<'p> {
     if let Some((param, body)) = fov {
-      FieldDef::FuncDef(self.alloc.func.alloc(FuncDef { loc, name, ret: syn_ty, param: param.reversed(), static_: false, body, ret_param_ty: dft(), class: dft(), scope: dft() }))
+      FieldDef::FuncDef(self.alloc.func.alloc(FuncDef { loc, name, ret: syn_ty, param: param.reversed(), static_: false, abstract_: false, end: body.end, body: Some(body), ret_param_ty: dft(), class: dft(), next: dft(), ctor: false, scope: dft() }))
     } else {
-      FieldDef::VarDef(self.alloc.var.alloc(VarDef { loc, name, syn_ty, init: None, ty: dft(), owner: dft() }))
+      FieldDef::VarDef(self.alloc.var.alloc(VarDef { loc, name, syn_ty, init: None, end: loc_after(loc, name), ty: dft(), owner: dft(), used: dft(), static_: false, final_: false }))
     }
   }
 
@@ -244,14 +386,14 @@ impl<'p> Parser<'p> {
   #[rule(ExprListRem ->)]
   fn expr_list_rem0() -> Vec<Expr<'p>> { vec![] }
 
-  #[rule(VarDef -> Type Id)]
+  #[rule(VarDef -> VarType Id)]
   fn var_def(&self, syn_ty: SynTy<'p>, name: Token) -> &'p VarDef<'p> {
-    self.alloc.var.alloc(VarDef { loc: name.loc(), name: name.str(), syn_ty, init: None, ty: dft(), owner: dft() })
+    self.alloc.var.alloc(VarDef { loc: name.loc(), name: name.str(), syn_ty, init: None, end: name.end(), ty: dft(), owner: dft(), used: dft(), static_: false, final_: false })
   }
 
   #[rule(Block -> LBrc StmtList RBrc)]
-  fn block(l: Token, stmt: Vec<Stmt<'p>>, _r: Token) -> Block<'p> {
-    Block { loc: l.loc(), stmt: stmt.reversed(), scope: dft() }
+  fn block(l: Token, stmt: Vec<Stmt<'p>>, r: Token) -> Block<'p> {
+    Block { loc: l.loc(), end: r.end(), stmt: stmt.reversed(), scope: dft() }
   }
 
   #[rule(StmtList -> Stmt StmtList)]
@@ -259,57 +401,147 @@ impl<'p> Parser<'p> {
   #[rule(StmtList ->)]
   fn stmt_list0() -> Vec<Stmt<'p>> { vec![] }
 
+  // an array literal can also begin with `{`, this production appears earlier, so `{` at the beginning of a statement is always a Block
+  #[rule(Stmt -> Block)]
+  fn stmt_block(b: Block<'p>) -> Stmt<'p> { mk_stmt(b.loc, b.end, b.into()) }
   #[rule(Stmt -> Simple Semi)]
-  fn stmt_simple(s: Stmt<'p>, _s: Token) -> Stmt<'p> { s }
+  fn stmt_simple(s: Stmt<'p>, semi: Token) -> Stmt<'p> { Stmt { end: semi.end(), ..s } }
   #[rule(Stmt -> If LPar Expr RPar Stmt MaybeElse)]
   fn stmt_if(i: Token, _l: Token, cond: Expr<'p>, _r: Token, on_true: Stmt<'p>, on_false: Option<Block<'p>>) -> Stmt<'p> {
-    mk_stmt(i.loc(), Box::new(If { cond, on_true: mk_block(on_true), on_false }).into())
+    let end = on_false.as_ref().map_or(on_true.end, |b| b.end);
+    mk_stmt(i.loc(), end, Box::new(If { cond, on_true: mk_block(on_true), on_false }).into())
   }
   #[rule(Stmt -> While LPar Expr RPar Stmt)]
   fn stmt_while(w: Token, _l: Token, cond: Expr<'p>, _r: Token, body: Stmt<'p>) -> Stmt<'p> {
-    mk_stmt(w.loc(), While { cond, body: mk_block(body) }.into())
+    mk_stmt(w.loc(), body.end, While { label: None, cond, body: mk_block(body) }.into())
   }
-  #[rule(Stmt -> For LPar Simple Semi Expr Semi Simple RPar Stmt)]
-  fn stmt_for(f: Token, _l: Token, init: Stmt<'p>, _s1: Token, cond: Expr<'p>, _s2: Token, update: Stmt<'p>, _r: Token, body: Stmt<'p>) -> Stmt<'p> {
-    mk_stmt(f.loc(), For { init: Box::new(init), cond, update: Box::new(update), body: mk_block(body) }.into())
+  #[rule(Stmt -> Do Stmt While LPar Expr RPar Semi)]
+  fn stmt_do_while(d: Token, body: Stmt<'p>, _w: Token, _l: Token, cond: Expr<'p>, _r: Token, s: Token) -> Stmt<'p> {
+    mk_stmt(d.loc(), s.end(), DoWhile { label: None, body: mk_block(body), cond }.into())
   }
+  #[rule(Stmt -> For LPar Simple ForRem)]
+  fn stmt_for(&mut self, f: Token, _l: Token, init: Stmt<'p>, rem: ForRem<'p>) -> Stmt<'p> {
+    match rem {
+      ForRem::For(init_rem, cond, update, body) =>
+        mk_stmt(f.loc(), body.end, For { label: None, init: init_rem.pushed(init).reversed(), cond, update, body: mk_block(body) }.into()),
+      // like the lr parser, the variable of foreach is `Type Id`, without `final` or init
+      ForRem::Foreach(arr, body) => match init.kind {
+        StmtKind::LocalVarDef(var) if var.init.is_none() && !var.final_ => mk_stmt(f.loc(), body.end, Foreach { label: None, var, arr, body: mk_block(body) }.into()),
+        _ => (self.error.issue::<()>(init.loc, ErrorKind::SyntaxError), mk_stmt(f.loc(), body.end, Skip.into())).1,
+      }
+    }
+  }
+  #[rule(ForRem -> SimpleListRem Semi Expr Semi Simple SimpleListRem RPar Stmt)]
+  fn for_rem(init_rem: Vec<Stmt<'p>>, _s1: Token, cond: Expr<'p>, _s2: Token, update: Stmt<'p>, update_rem: Vec<Stmt<'p>>, _r: Token, body: Stmt<'p>) -> ForRem<'p> {
+    ForRem::For(init_rem, cond, update_rem.pushed(update).reversed(), body)
+  }
+  #[rule(ForRem -> Colon Expr RPar Stmt)]
+  fn for_rem_foreach(_c: Token, arr: Expr<'p>, _r: Token, body: Stmt<'p>) -> ForRem<'p> { ForRem::Foreach(arr, body) }
   #[rule(Stmt -> Return MaybeExpr Semi)]
-  fn stmt_return(r: Token, expr: Option<Expr<'p>>, _s: Token) -> Stmt<'p> { mk_stmt(r.loc(), expr.into()) }
+  fn stmt_return(r: Token, expr: Option<Expr<'p>>, s: Token) -> Stmt<'p> { mk_stmt(r.loc(), s.end(), expr.into()) }
   #[rule(Stmt -> Print LPar ExprList RPar Semi)]
-  fn stmt_print(p: Token, _l: Token, print: Vec<Expr<'p>>, _r: Token, _s: Token) -> Stmt<'p> { mk_stmt(p.loc(), print.reversed().into()) }
+  fn stmt_print(p: Token, _l: Token, print: Vec<Expr<'p>>, _r: Token, s: Token) -> Stmt<'p> { mk_stmt(p.loc(), s.end(), print.reversed().into()) }
+  #[rule(Stmt -> Printf LPar StringLit PrintfArg RPar Semi)]
+  fn stmt_printf(&mut self, p: Token, _l: Token, fmt: Token, arg: Vec<Expr<'p>>, _r: Token, s: Token) -> Stmt<'p> {
+    let fmt = self.alloc.str.alloc(decode_str(fmt.str(), &mut self.error, fmt.loc())).as_str();
+    mk_stmt(p.loc(), s.end(), Printf { fmt, arg }.into())
+  }
+  #[rule(PrintfArg -> Comma ExprList)]
+  fn printf_arg(_c: Token, arg: Vec<Expr<'p>>) -> Vec<Expr<'p>> { arg.reversed() }
+  #[rule(PrintfArg ->)]
+  fn printf_arg0() -> Vec<Expr<'p>> { vec![] }
   #[rule(Stmt -> Break Semi)]
-  fn stmt_break(b: Token, _s: Token) -> Stmt<'p> { mk_stmt(b.loc(), Break.into()) }
-  #[rule(Stmt -> Block)]
-  fn stmt_block(b: Block<'p>) -> Stmt<'p> { mk_stmt(b.loc, b.into()) }
+  fn stmt_break(b: Token, s: Token) -> Stmt<'p> { mk_stmt(b.loc(), s.end(), Break { label: None }.into()) }
+  #[rule(Stmt -> Continue Semi)]
+  fn stmt_continue(c: Token, s: Token) -> Stmt<'p> { mk_stmt(c.loc(), s.end(), Continue { label: None }.into()) }
+  #[rule(Stmt -> Switch LPar Expr RPar LBrc CaseList RBrc)]
+  fn stmt_switch(s: Token, _l: Token, expr: Expr<'p>, _r: Token, _l1: Token, case: (Vec<Case<'p>>, Vec<Block<'p>>), r: Token) -> Stmt<'p> {
+    mk_stmt(s.loc(), r.end(), Box::new(Switch { expr, case: case.0.reversed(), default: case.1.reversed() }).into())
+  }
 
-  #[rule(Simple -> Expr MaybeAssign)]
-  fn simple_assign_or_expr(e: Expr<'p>, assign: Option<(Loc, Expr<'p>)>) -> Stmt<'p> {
-    if let Some((loc, src)) = assign {
-      mk_stmt(loc, Assign { dst: e, src }.into())
-    } else {
-      mk_stmt(e.loc, e.into())
+  #[rule(Simple -> Expr AssignOrVarDef)]
+  fn simple_assign_or_expr(&mut self, e: Expr<'p>, rem: AssignOrVarDef<'p>) -> Stmt<'p> {
+    match rem {
+      AssignOrVarDef::Assign(Some((loc, op, src))) => mk_stmt(loc, src.end, Assign { dst: e, src, op }.into()),
+      AssignOrVarDef::Assign(None) => mk_stmt(e.loc, e.end, e.into()),
+      AssignOrVarDef::VarDef(loc, name, init) => {
+        let syn_ty = match &e.kind {
+          ExprKind::VarSel(VarSel { owner: None, name, .. }) => SynTy { loc: e.loc, arr: 0, kind: SynTyKind::Named(name, false, None) },
+          _ => (self.error.issue::<()>(loc, ErrorKind::SyntaxError), SynTy { loc: e.loc, arr: 0, kind: SynTyKind::Void }).1,
+        };
+        let end = var_end(loc_after(loc, name), &init);
+        mk_stmt(loc, end, (&*self.alloc.var.alloc(VarDef { loc, name, syn_ty, init, end, ty: dft(), owner: dft(), used: dft(), static_: false, final_: false })).into())
+      }
     }
   }
+  #[rule(AssignOrVarDef -> AssignRem)]
+  fn assign_or_var_def_a(assign: Option<(Loc, Option<BinOp>, Expr<'p>)>) -> AssignOrVarDef<'p> { AssignOrVarDef::Assign(assign) }
+  #[rule(AssignOrVarDef -> Id MaybeAssign)]
+  fn assign_or_var_def_v(name: Token, init: Option<(Loc, Expr<'p>)>) -> AssignOrVarDef<'p> { AssignOrVarDef::VarDef(name.loc(), name.str(), init) }
   #[rule(Simple -> Type Id MaybeAssign)]
   fn simple_var_def(&self, syn_ty: SynTy<'p>, name: Token, init: Option<(Loc, Expr<'p>)>) -> Stmt<'p> {
-    let loc = name.loc();
-    mk_stmt(loc, (&*self.alloc.var.alloc(VarDef { loc, name: name.str(), syn_ty, init, ty: dft(), owner: dft() })).into())
+    let (loc, end) = (name.loc(), var_end(name.end(), &init));
+    mk_stmt(loc, end, (&*self.alloc.var.alloc(VarDef { loc, name: name.str(), syn_ty, init, end, ty: dft(), owner: dft(), used: dft(), static_: false, final_: false })).into())
+  }
+  #[rule(Simple -> Final VarType Id MaybeAssign)]
+  fn simple_var_def_final(&self, _f: Token, syn_ty: SynTy<'p>, name: Token, init: Option<(Loc, Expr<'p>)>) -> Stmt<'p> {
+    let (loc, end) = (name.loc(), var_end(name.end(), &init));
+    mk_stmt(loc, end, (&*self.alloc.var.alloc(VarDef { loc, name: name.str(), syn_ty, init, end, ty: dft(), owner: dft(), used: dft(), static_: false, final_: true })).into())
   }
   #[rule(Simple ->)]
-  fn simple_skip() -> Stmt<'p> { mk_stmt(NO_LOC, Skip.into()) }
+  fn simple_skip() -> Stmt<'p> { mk_stmt(NO_LOC, NO_LOC, Skip.into()) }
 
-  #[rule(MaybeAssign -> Assign Expr)]
-  fn maybe_assign1(a: Token, src: Expr<'p>) -> Option<(Loc, Expr<'p>)> { Some((a.loc(), src)) }
+  #[rule(SimpleListRem -> Comma Simple SimpleListRem)]
+  fn simple_list_rem(_c: Token, l: Stmt<'p>, r: Vec<Stmt<'p>>) -> Vec<Stmt<'p>> { r.pushed(l) }
+  #[rule(SimpleListRem ->)]
+  fn simple_list_rem0() -> Vec<Stmt<'p>> { vec![] }
+
+  // `a = b = c` is `a = (b = c)`, the chained assignments after the first one are expressions
+  #[rule(MaybeAssign -> Assign Expr MaybeAssign)]
+  fn maybe_assign1(a: Token, src: Expr<'p>, rest: Option<(Loc, Expr<'p>)>) -> Option<(Loc, Expr<'p>)> {
+    Some((a.loc(), chain_assign(src, rest)))
+  }
   #[rule(MaybeAssign ->)]
   fn maybe_assign0() -> Option<(Loc, Expr<'p>)> { None }
 
+  // `a op= b = c` is `a op= (b = c)`, the same as the LR parser
+  #[rule(AssignRem -> CompoundAssign Expr MaybeAssign)]
+  fn assign_rem_compound(op: (Loc, BinOp), src: Expr<'p>, rest: Option<(Loc, Expr<'p>)>) -> Option<(Loc, Option<BinOp>, Expr<'p>)> {
+    Some((op.0, Some(op.1), chain_assign(src, rest)))
+  }
+  #[rule(AssignRem -> MaybeAssign)]
+  fn assign_rem(assign: Option<(Loc, Expr<'p>)>) -> Option<(Loc, Option<BinOp>, Expr<'p>)> { assign.map(|(loc, src)| (loc, None, src)) }
+
+  #[rule(CompoundAssign -> AddAssign)]
+  fn compound_add(a: Token) -> (Loc, BinOp) { (a.loc(), BinOp::Add) }
+  #[rule(CompoundAssign -> SubAssign)]
+  fn compound_sub(a: Token) -> (Loc, BinOp) { (a.loc(), BinOp::Sub) }
+  #[rule(CompoundAssign -> MulAssign)]
+  fn compound_mul(a: Token) -> (Loc, BinOp) { (a.loc(), BinOp::Mul) }
+  #[rule(CompoundAssign -> DivAssign)]
+  fn compound_div(a: Token) -> (Loc, BinOp) { (a.loc(), BinOp::Div) }
+  #[rule(CompoundAssign -> ModAssign)]
+  fn compound_mod(a: Token) -> (Loc, BinOp) { (a.loc(), BinOp::Mod) }
+
   #[rule(Blocked -> Stmt)]
-  fn blocked(s: Stmt<'p>) -> Block<'p> {
-    if let StmtKind::Block(b) = s.kind { b } else { Block { loc: s.loc, stmt: vec![s], scope: dft() } }
+  fn blocked(s: Stmt<'p>) -> Block<'p> { mk_block(s) }
+
+  // (the cases in reversed order, the defaults in reversed order), like the lr parser, multiple defaults are reported in typeck
+  #[rule(CaseList -> Case Expr Colon StmtList CaseList)]
+  fn case_list(c: Token, label: Expr<'p>, _c: Token, stmt: Vec<Stmt<'p>>, r: (Vec<Case<'p>>, Vec<Block<'p>>)) -> (Vec<Case<'p>>, Vec<Block<'p>>) {
+    (r.0.pushed(Case { loc: c.loc(), label, body: Block { loc: c.loc(), end: NO_LOC, stmt: stmt.reversed(), scope: dft() } }), r.1)
   }
+  #[rule(CaseList -> Default Colon StmtList CaseList)]
+  fn case_list_default(d: Token, _c: Token, stmt: Vec<Stmt<'p>>, r: (Vec<Case<'p>>, Vec<Block<'p>>)) -> (Vec<Case<'p>>, Vec<Block<'p>>) {
+    (r.0, r.1.pushed(Block { loc: d.loc(), end: NO_LOC, stmt: stmt.reversed(), scope: dft() }))
+  }
+  #[rule(CaseList ->)]
+  fn case_list0() -> (Vec<Case<'p>>, Vec<Block<'p>>) { (vec![], vec![]) }
 
   // maybe_else1/0 will cause a conflict, and will choose this production because it appears earlier
-  // this is the ONLY conflict allowed in our parser
+  // the only other conflicts are Block with the array literal at the beginning of Stmt (see stmt_block), NewDim and the ArrayDim after it with indexing, `new int[m][n]` is never indexing `new int[m]`,
+  // and the tuple type with the parenthesized expression at the beginning of Simple, the latter is chosen,
+  // so in this parser a local variable of tuple type can only be declared with `final`
   #[rule(MaybeElse -> Else Blocked)]
   fn maybe_else1(_e: Token, b: Block<'p>) -> Option<Block<'p>> { Some(b) }
   #[rule(MaybeElse ->)]
@@ -405,110 +637,200 @@ impl<'p> Parser<'p> {
 
   #[rule(Expr7 -> Op7 Expr7)] // not, neg
   fn expr7_op8(o: (Loc, UnOp), r: Expr<'p>) -> Expr<'p> {
-    mk_expr(o.0, Unary { op: o.1, r: Box::new(r) }.into())
+    mk_expr(o.0, r.end, Unary { op: o.1, r: Box::new(r) }.into())
   }
   #[rule(Expr7 -> LPar ParenOrCast)]
   fn expr7_par_or_cast(_l: Token, e: Expr<'p>) -> Expr<'p> { e }
   #[rule(Expr7 -> Expr8)]
   fn expr7_8(e: Expr<'p>) -> Expr<'p> { e }
 
-  #[rule(ParenOrCast -> Expr RPar Term8)]
-  fn paren_or_cast_p(l: Expr<'p>, _r: Token, ts: Vec<IndexOrIdOrCall<'p>>) -> Expr<'p> { merge_idx_id_call(l, ts) }
+  #[rule(ParenOrCast -> Expr ParenRem)]
+  fn paren_or_cast_p(l: Expr<'p>, rem: (ParenKind<'p>, Vec<IndexOrIdOrCall<'p>>)) -> Expr<'p> {
+    let l = match rem.0 {
+      ParenKind::Paren => l,
+      ParenKind::Tuple(elem, end) => mk_expr(l.loc, end, Tuple { elem: vec![l].into_iter().chain(elem.reversed()).collect() }.into()),
+      ParenKind::Assign(loc, src) => chain_assign(l, Some((loc, src))),
+    };
+    merge_idx_id_call(l, rem.1)
+  }
+  // (the kind of the parenthesized expression, the terms after it)
+  #[rule(ParenRem -> RPar Term8)]
+  fn paren_rem0(_r: Token, ts: Vec<IndexOrIdOrCall<'p>>) -> (ParenKind<'p>, Vec<IndexOrIdOrCall<'p>>) { (ParenKind::Paren, ts) }
+  #[rule(ParenRem -> Comma ExprList RPar Term8)]
+  fn paren_rem1(_c: Token, elem: Vec<Expr<'p>>, r: Token, ts: Vec<IndexOrIdOrCall<'p>>) -> (ParenKind<'p>, Vec<IndexOrIdOrCall<'p>>) { (ParenKind::Tuple(elem, r.end()), ts) }
+  #[rule(ParenRem -> Assign Expr MaybeAssign RPar Term8)]
+  fn paren_rem2(a: Token, src: Expr<'p>, rest: Option<(Loc, Expr<'p>)>, _r: Token, ts: Vec<IndexOrIdOrCall<'p>>) -> (ParenKind<'p>, Vec<IndexOrIdOrCall<'p>>) {
+    (ParenKind::Assign(a.loc(), chain_assign(src, rest)), ts)
+  }
   #[rule(ParenOrCast -> Class Id RPar Expr7)]
   fn paren_or_cast_c(_c: Token, name: Token, _r: Token, e: Expr<'p>) -> Expr<'p> {
-    mk_expr(e.loc, ClassCast { name: name.str(), expr: Box::new(e), class: dft() }.into())
+    mk_expr(e.loc, e.end, ClassCast { name: name.str(), expr: Box::new(e), class: dft() }.into())
   }
 
   #[rule(Expr8 -> Expr9 Term8)]
   fn expr8(l: Expr<'p>, ts: Vec<IndexOrIdOrCall<'p>>) -> Expr<'p> { merge_idx_id_call(l, ts) }
 
-  #[rule(Term8 -> LBrk Expr RBrk Term8)]
-  fn term8_index(l: Token, idx: Expr<'p>, _r: Token, r: Vec<IndexOrIdOrCall<'p>>) -> Vec<IndexOrIdOrCall<'p>> { r.pushed(IndexOrIdOrCall::Index(l.loc(), idx)) }
-  #[rule(Term8 -> Dot Id IdOrCall Term8)]
-  fn term8_id_or_call(_d: Token, name: Token, arg: Option<(Loc, Vec<Expr<'p>>)>, r: Vec<IndexOrIdOrCall<'p>>) -> Vec<IndexOrIdOrCall<'p>> {
-    r.pushed(IndexOrIdOrCall::IdOrCall(name.loc(), name.str(), arg))
-  }
+  #[rule(Term8 -> LBrk Expr IndexRem Term8)]
+  fn term8_index(l: Token, idx: Expr<'p>, hi: (Option<Expr<'p>>, Loc), r: Vec<IndexOrIdOrCall<'p>>) -> Vec<IndexOrIdOrCall<'p>> { r.pushed(IndexOrIdOrCall::Index(l.loc(), idx, hi.0, hi.1)) }
+  // (the upper bound of a slice, the end of `]`)
+  #[rule(IndexRem -> RBrk)]
+  fn index_rem0(r: Token) -> (Option<Expr<'p>>, Loc) { (None, r.end()) }
+  #[rule(IndexRem -> DotDot Expr RBrk)]
+  fn index_rem1(_d: Token, hi: Expr<'p>, r: Token) -> (Option<Expr<'p>>, Loc) { (Some(hi), r.end()) }
+  #[rule(Term8 -> Dot Member Term8)]
+  fn term8_member(_d: Token, m: IndexOrIdOrCall<'p>, r: Vec<IndexOrIdOrCall<'p>>) -> Vec<IndexOrIdOrCall<'p>> { r.pushed(m) }
+  #[rule(Member -> Id IdOrCall)]
+  fn member_id_or_call(name: Token, arg: Option<(Loc, Vec<Expr<'p>>, Loc)>) -> IndexOrIdOrCall<'p> { IndexOrIdOrCall::IdOrCall(name.loc(), name.str(), arg) }
+  // a malformed index like `t.0x1` is parsed as `!0`, which is reported as out of range in typeck
+  #[rule(Member -> IntLit)]
+  fn member_tuple_sel(idx: Token) -> IndexOrIdOrCall<'p> { IndexOrIdOrCall::TupleSel(idx.loc(), idx.str().parse().unwrap_or(!0), idx.end()) }
   #[rule(Term8 ->)]
   fn term8_0() -> Vec<IndexOrIdOrCall<'p>> { vec![] }
 
   #[rule(IdOrCall -> LPar ExprListOrEmpty RPar)]
-  fn id_or_call_c(l: Token, arg: Vec<Expr<'p>>, _r: Token) -> Option<(Loc, Vec<Expr<'p>>)> { Some((l.loc(), arg.reversed())) }
+  fn id_or_call_c(l: Token, arg: Vec<Expr<'p>>, r: Token) -> Option<(Loc, Vec<Expr<'p>>, Loc)> { Some((l.loc(), arg.reversed(), r.end())) }
   #[rule(IdOrCall ->)]
-  fn id_or_call_i() -> Option<(Loc, Vec<Expr<'p>>)> { None }
+  fn id_or_call_i() -> Option<(Loc, Vec<Expr<'p>>, Loc)> { None }
 
   #[rule(Expr9 -> IntLit)]
   fn expr9_int(&mut self, i: Token) -> Expr<'p> { mk_int_lit(i.loc(), i.str(), &mut self.error) }
+  #[rule(Expr9 -> CharLit)]
+  fn expr9_char(&mut self, c: Token) -> Expr<'p> { mk_char_lit(c.loc(), c.str(), &mut self.error) }
+  // the regex guarantees that it can be parsed, a literal too large becomes infinity
+  #[rule(Expr9 -> DoubleLit)]
+  fn expr9_double(d: Token) -> Expr<'p> { mk_expr(d.loc(), d.end(), d.str().parse::<f64>().unwrap().into()) }
   #[rule(Expr9 -> True)]
-  fn expr9_true(t: Token) -> Expr<'p> { mk_expr(t.loc(), true.into()) }
+  fn expr9_true(t: Token) -> Expr<'p> { mk_expr(t.loc(), t.end(), true.into()) }
   #[rule(Expr9 -> False)]
-  fn expr9_false(f: Token) -> Expr<'p> { mk_expr(f.loc(), false.into()) }
+  fn expr9_false(f: Token) -> Expr<'p> { mk_expr(f.loc(), f.end(), false.into()) }
   #[rule(Expr9 -> StringLit)]
   fn expr9_string(&mut self, s: Token) -> Expr<'p> {
-    let (loc, str) = (s.loc(), s.str());
-    check_str(str, &mut self.error, loc);
-    mk_expr(loc, str[1..str.len() - 1].into())
+    let loc = s.loc();
+    let str = decode_str(s.str(), &mut self.error, loc);
+    mk_expr(loc, s.end(), self.alloc.str.alloc(str).as_str().into())
   }
   #[rule(Expr9 -> Null)]
-  fn expr9_null(n: Token) -> Expr<'p> { mk_expr(n.loc(), NullLit.into()) }
+  fn expr9_null(n: Token) -> Expr<'p> { mk_expr(n.loc(), n.end(), NullLit.into()) }
   #[rule(Expr9 -> ReadInteger LPar RPar)]
-  fn expr9_read_integer(r: Token, _l: Token, _r: Token) -> Expr<'p> { mk_expr(r.loc(), ReadInt.into()) }
+  fn expr9_read_integer(r: Token, _l: Token, r1: Token) -> Expr<'p> { mk_expr(r.loc(), r1.end(), ReadInt.into()) }
+  #[rule(Expr9 -> ReadDouble LPar RPar)]
+  fn expr9_read_double(r: Token, _l: Token, r1: Token) -> Expr<'p> { mk_expr(r.loc(), r1.end(), ReadDouble.into()) }
   #[rule(Expr9 -> ReadLine LPar RPar)]
-  fn expr9_read_line(r: Token, _l: Token, _r: Token) -> Expr<'p> { mk_expr(r.loc(), ReadLine.into()) }
+  fn expr9_read_line(r: Token, _l: Token, r1: Token) -> Expr<'p> { mk_expr(r.loc(), r1.end(), ReadLine.into()) }
+  #[rule(Expr9 -> LBrc ExprListOrEmpty RBrc)]
+  fn expr9_array_lit(l: Token, elem: Vec<Expr<'p>>, r: Token) -> Expr<'p> { mk_expr(l.loc(), r.end(), elem.reversed().into()) }
   #[rule(Expr9 -> This)]
-  fn expr9_this(t: Token) -> Expr<'p> { mk_expr(t.loc(), This.into()) }
+  fn expr9_this(t: Token) -> Expr<'p> { mk_expr(t.loc(), t.end(), This.into()) }
+  #[rule(Expr9 -> Super)]
+  fn expr9_super(t: Token) -> Expr<'p> { mk_expr(t.loc(), t.end(), Super.into()) }
   #[rule(Expr9 -> InstanceOf LPar Expr Comma Id RPar)]
-  fn expr9_instanceof(i: Token, _l: Token, expr: Expr<'p>, _c: Tokenm, name: Token, _r: Token) -> Expr<'p> {
-    mk_expr(i.loc(), ClassTest { expr: Box::new(expr), name: name.str(), class: dft() }.into())
+  fn expr9_instanceof(i: Token, _l: Token, expr: Expr<'p>, _c: Token, name: Token, r: Token) -> Expr<'p> {
+    mk_expr(i.loc(), r.end(), ClassTest { expr: Box::new(expr), name: name.str(), class: dft() }.into())
   }
   #[rule(Expr9 -> Id IdOrCall)]
-  fn expr9_id_or_call(name: Token, ioc: Option<(Loc, Vec<Expr<'p>>)>) -> Expr<'p> {
+  fn expr9_id_or_call(name: Token, ioc: Option<(Loc, Vec<Expr<'p>>, Loc)>) -> Expr<'p> {
     match ioc {
-      Some((loc, arg)) => {
-        let func = Box::new(mk_expr(name.loc(), VarSel { owner: None, name: name.str(), var: dft() }.into()));
-        mk_expr(loc, Call { func, arg, func_ref: dft() }.into())
+      Some((loc, arg, end)) => {
+        let func = Box::new(mk_expr(name.loc(), name.end(), VarSel { owner: None, name: name.str(), var: dft(), func: dft() }.into()));
+        mk_expr(loc, end, Call { func, arg, func_ref: dft() }.into())
       }
-      None => mk_expr(name.loc(), VarSel { owner: None, name: name.str(), var: dft() }.into()),
+      None => mk_expr(name.loc(), name.end(), VarSel { owner: None, name: name.str(), var: dft(), func: dft() }.into()),
     }
   }
   #[rule(Expr9 -> New NewClassOrArray)]
   fn expr9_new(n: Token, noa: NewClassOrArray<'p>) -> Expr<'p> {
     let loc = n.loc();
     match noa {
-      NewClassOrArray::NewClass(name) => mk_expr(loc, NewClass { name, class: dft() }.into()),
-      NewClassOrArray::NewArray(elem, len) => mk_expr(loc, NewArray { elem, len: Box::new(len) }.into()),
+      NewClassOrArray::NewClass(name, ty_arg, arg, end) => mk_expr(loc, end, NewClass { name, ty_arg, arg, class: dft(), ctor: dft() }.into()),
+      NewClassOrArray::NewArray(elem, len, sub_len, end) => mk_expr(loc, end, NewArray { elem, len: Box::new(len), sub_len }.into()),
     }
   }
 
-  #[rule(NewClassOrArray -> Id LPar RPar)]
-  fn new_class_or_array_c(name: Token, _l: Token, _r: Token) -> NewClassOrArray<'p> {
-    NewClassOrArray::NewClass(name.str())
+  // `new Id(...)` creates an object, and `new Id[...]` creates an array whose element type is a bare type alias name
+  #[rule(NewClassOrArray -> Id NewIdRem)]
+  fn new_class_or_array_id(name: Token, rem: NewClassOrArray<'p>) -> NewClassOrArray<'p> {
+    match rem {
+      NewClassOrArray::NewClass(_, ty_arg, arg, end) => NewClassOrArray::NewClass(name.str(), ty_arg, arg, end),
+      NewClassOrArray::NewArray(ty, len, sub_len, end) => NewClassOrArray::NewArray(SynTy { arr: ty.arr, ..Parser::var_type_alias(name) }, len, sub_len, end),
+    }
+  }
+  // the class name and the element type are filled in NewClassOrArray
+  #[rule(NewIdRem -> MaybeTyArg LPar ExprListOrEmpty RPar)]
+  fn new_id_rem_c(ty_arg: Option<SynTy<'p>>, _l: Token, arg: Vec<Expr<'p>>, r: Token) -> NewClassOrArray<'p> {
+    NewClassOrArray::NewClass("", ty_arg, arg.reversed(), r.end())
+  }
+  #[rule(NewIdRem -> LBrk NewArrayRem)]
+  fn new_id_rem_a(l: Token, dim_len: (u32, Expr<'p>, Vec<Expr<'p>>, Loc)) -> NewClassOrArray<'p> {
+    NewClassOrArray::NewArray(SynTy { loc: l.loc(), arr: dim_len.0, kind: SynTyKind::Void }, dim_len.1, dim_len.2, dim_len.3)
   }
   #[rule(NewClassOrArray -> SimpleType LBrk NewArrayRem)]
-  fn new_class_or_array_a(mut ty: SynTy<'p>, _l: Token, dim_len: (u32, Expr<'p>)) -> NewClassOrArray<'p> {
+  fn new_class_or_array_a(mut ty: SynTy<'p>, _l: Token, dim_len: (u32, Expr<'p>, Vec<Expr<'p>>, Loc)) -> NewClassOrArray<'p> {
     ty.arr = dim_len.0;
-    NewClassOrArray::NewArray(ty, dim_len.1)
+    NewClassOrArray::NewArray(ty, dim_len.1, dim_len.2, dim_len.3)
   }
 
+  // (the number of dimensions of the element, the length, the inner lengths, the end of the last `]`)
   #[rule(NewArrayRem -> RBrk LBrk NewArrayRem)]
-  fn new_array_rem(_r: Token, l: Token, mut dim_len: (u32, Expr<'p>)) -> (u32, Expr<'p>) { (dim_len.0 += 1, dim_len).1 }
-  #[rule(NewArrayRem -> Expr RBrk)]
-  fn new_array_rem0(len: Expr<'p>, _r: Token) -> (u32, Expr<'p>) { (0, len) }
+  fn new_array_rem(_r: Token, l: Token, mut dim_len: (u32, Expr<'p>, Vec<Expr<'p>>, Loc)) -> (u32, Expr<'p>, Vec<Expr<'p>>, Loc) { (dim_len.0 += 1, dim_len).1 }
+  #[rule(NewArrayRem -> Expr RBrk NewDim)]
+  fn new_array_rem0(len: Expr<'p>, r: Token, dim: (Vec<Expr<'p>>, u32, Option<Loc>)) -> (u32, Expr<'p>, Vec<Expr<'p>>, Loc) {
+    (dim.1, len, dim.0.reversed(), dim.2.unwrap_or_else(|| r.end()))
+  }
+
+  // (the inner lengths, the number of inner dimensions, the end of the last `]`), empty dimensions can only be at the end
+  #[rule(NewDim -> LBrk NewDimRem)]
+  fn new_dim(_l: Token, dim: (Vec<Expr<'p>>, u32, Option<Loc>)) -> (Vec<Expr<'p>>, u32, Option<Loc>) { dim }
+  #[rule(NewDim ->)]
+  fn new_dim0() -> (Vec<Expr<'p>>, u32, Option<Loc>) { (vec![], 0, None) }
+  #[rule(NewDimRem -> Expr RBrk NewDim)]
+  fn new_dim_rem(len: Expr<'p>, r: Token, dim: (Vec<Expr<'p>>, u32, Option<Loc>)) -> (Vec<Expr<'p>>, u32, Option<Loc>) {
+    (dim.0.pushed(len), dim.1 + 1, dim.2.or(Some(r.end())))
+  }
+  #[rule(NewDimRem -> RBrk ArrayDim)]
+  fn new_dim_rem0(r: Token, dim: (u32, Option<Loc>)) -> (Vec<Expr<'p>>, u32, Option<Loc>) { (vec![], dim.0 + 1, dim.1.or(Some(r.end()))) }
+
+  // a bare type alias name is only allowed as a whole type followed by the declared name, e.g.: `IntArray a;`
+  // other uses are written like a class type, e.g.: `class IntArray[]`, the same as the LR parser
+  #[rule(VarType -> Type)]
+  fn var_type(ty: SynTy<'p>) -> SynTy<'p> { ty }
+  #[rule(VarType -> Id)]
+  fn var_type_alias(name: Token) -> SynTy<'p> { SynTy { loc: name.loc(), arr: 0, kind: SynTyKind::Named(name.str(), false, None) } }
 
   #[rule(SimpleType -> Int)]
   fn type_int(i: Token) -> SynTy<'p> { SynTy { loc: i.loc(), arr: 0, kind: SynTyKind::Int } }
+  #[rule(SimpleType -> Char)]
+  fn type_char(c: Token) -> SynTy<'p> { SynTy { loc: c.loc(), arr: 0, kind: SynTyKind::Char } }
+  #[rule(SimpleType -> Double)]
+  fn type_double(d: Token) -> SynTy<'p> { SynTy { loc: d.loc(), arr: 0, kind: SynTyKind::Double } }
   #[rule(SimpleType -> Bool)]
   fn type_bool(b: Token) -> SynTy<'p> { SynTy { loc: b.loc(), arr: 0, kind: SynTyKind::Bool } }
   #[rule(SimpleType -> Void)]
   fn type_void(v: Token) -> SynTy<'p> { SynTy { loc: v.loc(), arr: 0, kind: SynTyKind::Void } }
   #[rule(SimpleType -> String)]
   fn type_string(s: Token) -> SynTy<'p> { SynTy { loc: s.loc(), arr: 0, kind: SynTyKind::String } }
-  #[rule(SimpleType -> Class Id)]
-  fn type_class(c: Token, name: Token) -> SynTy<'p> { SynTy { loc: c.loc(), arr: 0, kind: SynTyKind::Named(name.str()) } }
+  #[rule(SimpleType -> Class Id MaybeTyArg)]
+  fn type_class(c: Token, name: Token, ty_arg: Option<SynTy<'p>>) -> SynTy<'p> {
+    SynTy { loc: c.loc(), arr: 0, kind: SynTyKind::Named(name.str(), false, ty_arg.map(Box::new)) }
+  }
+  // `>>` is lexed as one token, so nested type arguments should be separated, e.g.: `class Box<class Box<int> >`
+  #[rule(MaybeTyArg -> Lt Type Gt)]
+  fn maybe_ty_arg1(_l: Token, t: SynTy<'p>, _g: Token) -> Option<SynTy<'p>> { Some(t) }
+  #[rule(MaybeTyArg ->)]
+  fn maybe_ty_arg0() -> Option<SynTy<'p>> { None }
+  #[rule(SimpleType -> LPar Type Comma Type TypeListRem RPar)]
+  fn type_tuple(l: Token, t0: SynTy<'p>, _c: Token, t1: SynTy<'p>, r: Vec<SynTy<'p>>, _r: Token) -> SynTy<'p> {
+    SynTy { loc: l.loc(), arr: 0, kind: SynTyKind::Tuple(vec![t0, t1].into_iter().chain(r.reversed()).collect()) }
+  }
+  #[rule(TypeListRem -> Comma Type TypeListRem)]
+  fn type_list_rem(_c: Token, l: SynTy<'p>, r: Vec<SynTy<'p>>) -> Vec<SynTy<'p>> { r.pushed(l) }
+  #[rule(TypeListRem ->)]
+  fn type_list_rem0() -> Vec<SynTy<'p>> { vec![] }
   #[rule(Type -> SimpleType ArrayDim)]
-  fn type_array(mut ty: SynTy<'p>, dim: u32) -> SynTy<'p> { (ty.arr = dim, ty).1 }
+  fn type_array(mut ty: SynTy<'p>, dim: (u32, Option<Loc>)) -> SynTy<'p> { (ty.arr = dim.0, ty).1 }
 
+  // (the number of dimensions, the end of the last `]`)
   #[rule(ArrayDim -> LBrk RBrk ArrayDim)]
-  fn array_type(l: Token, _r: Token, dim: u32) -> u32 { dim + 1 }
+  fn array_type(l: Token, r: Token, dim: (u32, Option<Loc>)) -> (u32, Option<Loc>) { (dim.0 + 1, dim.1.or(Some(r.end()))) }
   #[rule(ArrayDim ->)]
-  fn array_type0() -> u32 { 0 }
+  fn array_type0() -> (u32, Option<Loc>) { (0, None) }
 }
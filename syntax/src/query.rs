@@ -0,0 +1,148 @@
+use crate::{ast::*, TyKind};
+use common::Loc;
+
+// the index from source locations to the resolved symbols and types, for IDE features like go-to-definition and hover
+// it should be built after typeck, because it uses the symbols resolved in typeck, e.g., `VarSel::var`
+pub struct Query {
+  // (loc, length) of a name, and the loc of its declaration, a declaration refers to itself
+  def: Vec<(Loc, u32, Loc)>,
+  // the span [start, end) of an expression, its depth in the expression tree, and its type
+  ty: Vec<(Loc, Loc, u32, String)>,
+  depth: u32,
+}
+
+impl Query {
+  pub fn new(pr: &Program) -> Query {
+    let mut q = Query { def: vec![], ty: vec![], depth: 0 };
+    for c in &pr.class {
+      for f in &c.field {
+        match f {
+          FieldDef::VarDef(v) => q.var_def(v),
+          FieldDef::FuncDef(f) => {
+            q.def.push((f.loc, f.name.len() as u32, f.loc));
+            for v in &f.param { q.var_def(v); }
+            if let Some(b) = &f.body { q.block(b); }
+          }
+        }
+      }
+    }
+    q
+  }
+
+  // the loc of the declaration of the symbol at `loc`, `loc` can be any position in the symbol's name
+  pub fn definition_of(&self, loc: Loc) -> Option<Loc> {
+    self.def.iter().find(|&&(Loc(l, c, _), len, _)| loc.0 == l && c <= loc.1 && loc.1 < c + len).map(|x| x.2)
+  }
+
+  // the type of the innermost expression containing `loc`
+  pub fn type_at(&self, loc: Loc) -> Option<String> {
+    self.ty.iter().filter(|x| x.0 <= loc && loc < x.1).max_by_key(|x| x.2).map(|x| x.3.clone())
+  }
+
+  fn var_def(&mut self, v: &VarDef) {
+    self.def.push((v.loc, v.name.len() as u32, v.loc));
+    if let Some(e) = v.init() { self.expr(e); }
+  }
+
+  fn block(&mut self, b: &Block) { for s in &b.stmt { self.stmt(s); } }
+
+  fn stmt(&mut self, s: &Stmt) {
+    use StmtKind::*;
+    match &s.kind {
+      Assign(a) => {
+        self.expr(&a.dst);
+        self.expr(&a.src);
+      }
+      LocalVarDef(v) => self.var_def(v),
+      ExprEval(e) => { self.expr(e); }
+      Skip(_) | Break(_) | Continue(_) => {}
+      If(i) => {
+        self.expr(&i.cond);
+        self.block(&i.on_true);
+        if let Some(b) = &i.on_false { self.block(b); }
+      }
+      While(w) => (self.expr(&w.cond), self.block(&w.body)).1,
+      DoWhile(d) => {
+        self.block(&d.body);
+        self.expr(&d.cond);
+      }
+      For(f) => {
+        for s in &f.init { self.stmt(s); }
+        self.expr(&f.cond);
+        for s in &f.update { self.stmt(s); }
+        self.block(&f.body);
+      }
+      Foreach(f) => (self.var_def(f.var), self.expr(&f.arr), self.block(&f.body)).2,
+      Switch(s) => {
+        self.expr(&s.expr);
+        for c in &s.case {
+          self.expr(&c.label);
+          self.block(&c.body);
+        }
+        for d in &s.default { self.block(d); }
+      }
+      Return(r) => if let Some(e) = r { self.expr(e); }
+      Print(p) => for e in p { self.expr(e); }
+      Printf(p) => for e in &p.arg { self.expr(e); }
+      Block(b) => self.block(b),
+    }
+  }
+
+  // return the span [start, end) of `e`, the end is recorded by the parser, and the start is the leftmost loc of `e` and its children
+  // the parentheses around it are not included
+  fn expr(&mut self, e: &Expr) -> (Loc, Loc) {
+    use ExprKind::*;
+    self.depth += 1;
+    let mut span = (e.loc, e.end);
+    let mut merge = |(l, r): (Loc, Loc)| span = (span.0.min(l), span.1.max(r));
+    match &e.kind {
+      VarSel(v) => {
+        if let Some(o) = &v.owner { merge(self.expr(o)); }
+        let def = match (v.var.get(), e.ty.get().kind) {
+          (Some(var), _) => Some(var.loc),
+          // `A` in `A.f()`, whose type is the class itself
+          (None, TyKind::Class(c)) if e.ty.get().arr == 0 => Some(c.loc),
+          _ => None,
+        };
+        if let Some(def) = def { self.def.push((e.loc, v.name.len() as u32, def)); }
+      }
+      Call(c) => {
+        match (&c.func.kind, c.func_ref.get()) {
+          (VarSel(v), Some(f)) => {
+            if let Some(o) = &v.owner { merge(self.expr(o)); }
+            merge((c.func.loc, c.func.end));
+            self.def.push((c.func.loc, v.name.len() as u32, f.loc));
+          }
+          _ => merge(self.expr(&c.func)),
+        }
+        for a in &c.arg { merge(self.expr(a)); }
+      }
+      IndexSel(i) => (merge(self.expr(&i.arr)), merge(self.expr(&i.idx))).1,
+      Slice(s) => (merge(self.expr(&s.arr)), merge(self.expr(&s.lo)), merge(self.expr(&s.hi))).2,
+      Unary(u) => merge(self.expr(&u.r)),
+      Binary(b) => (merge(self.expr(&b.l)), merge(self.expr(&b.r))).1,
+      IncDec(i) => merge(self.expr(&i.e)),
+      Assign(a) => (merge(self.expr(&a.dst)), merge(self.expr(&a.src))).1,
+      Ternary(t) => (merge(self.expr(&t.cond)), merge(self.expr(&t.on_true)), merge(self.expr(&t.on_false))).2,
+      ArrayLit(a) => for e in a { merge(self.expr(e)); }
+      Tuple(t) => for e in &t.elem { merge(self.expr(e)); }
+      TupleSel(t) => merge(self.expr(&t.tuple)),
+      // clicking `new` goes to the class
+      NewClass(n) => {
+        for a in &n.arg { merge(self.expr(a)); }
+        if let Some(c) = n.class.get() { self.def.push((e.loc, 3, c.loc)); }
+      }
+      NewArray(n) => (merge(self.expr(&n.len)), for l in &n.sub_len { merge(self.expr(l)); }).1,
+      ClassTest(c) => merge(self.expr(&c.expr)),
+      ClassCast(c) => merge(self.expr(&c.expr)),
+      Lambda(l) => {
+        for v in &l.param { self.var_def(v); }
+        match &l.body { LambdaBody::Expr(e) => merge(self.expr(e)), LambdaBody::Block(b) => self.block(b) }
+      }
+      IntLit(_) | CharLit(_) | DoubleLit(_) | BoolLit(_) | StringLit(_) | NullLit(_) | This(_) | Super(_) | ReadInt(_) | ReadDouble(_) | ReadLine(_) => {}
+    }
+    self.depth -= 1;
+    self.ty.push((span.0, span.1, self.depth, e.ty.get().to_string()));
+    span
+  }
+}
@@ -1,4 +1,4 @@
-use crate::{Block, ClassDef, FuncDef, VarDef, Program, Ty};
+use crate::{Block, ClassDef, FuncDef, VarDef, Lambda, Program, Ty};
 use common::{Loc, HashMap};
 use std::{cell::{RefMut, Ref}, fmt};
 
@@ -50,6 +50,8 @@ impl<'a> Symbol<'a> {
 pub enum ScopeOwner<'a> {
   Local(&'a Block<'a>),
   Param(&'a FuncDef<'a>),
+  // the parameters of a lambda
+  Lambda(&'a Lambda<'a>),
   Class(&'a ClassDef<'a>),
   Global(&'a Program<'a>),
 }
@@ -58,16 +60,17 @@ impl<'a> ScopeOwner<'a> {
   // boilerplate code...
   pub fn scope(&self) -> Ref<'a, Scope<'a>> {
     use ScopeOwner::*;
-    match self { Local(x) => x.scope.borrow(), Param(x) => x.scope.borrow(), Class(x) => x.scope.borrow(), Global(x) => x.scope.borrow(), }
+    match self { Local(x) => x.scope.borrow(), Param(x) => x.scope.borrow(), Lambda(x) => x.scope.borrow(), Class(x) => x.scope.borrow(), Global(x) => x.scope.borrow(), }
   }
 
   pub fn scope_mut(&self) -> RefMut<'a, Scope<'a>> {
     use ScopeOwner::*;
-    match self { Local(x) => x.scope.borrow_mut(), Param(x) => x.scope.borrow_mut(), Class(x) => x.scope.borrow_mut(), Global(x) => x.scope.borrow_mut(), }
+    match self { Local(x) => x.scope.borrow_mut(), Param(x) => x.scope.borrow_mut(), Lambda(x) => x.scope.borrow_mut(), Class(x) => x.scope.borrow_mut(), Global(x) => x.scope.borrow_mut(), }
   }
 
   pub fn is_local(&self) -> bool { if let ScopeOwner::Local(_) = self { true } else { false } }
   pub fn is_param(&self) -> bool { if let ScopeOwner::Param(_) = self { true } else { false } }
+  pub fn is_lambda(&self) -> bool { if let ScopeOwner::Lambda(_) = self { true } else { false } }
   pub fn is_class(&self) -> bool { if let ScopeOwner::Class(_) = self { true } else { false } }
   pub fn is_global(&self) -> bool { if let ScopeOwner::Global(_) = self { true } else { false } }
 }
@@ -1,14 +1,25 @@
 use crate::{ClassDef, FuncDef};
 use common::{Loc, Ref};
+use typed_arena::Arena;
 use std::fmt;
 
 #[derive(Eq, PartialEq)]
 pub enum SynTyKind<'a> {
   Int,
   Bool,
+  Char,
+  Double,
   String,
   Void,
-  Named(&'a str),
+  // the bool is true for a non-null class type, e.g.: `class A!`
+  // the type argument is Some for an instantiation of a generic class, e.g.: `class Box<int>`
+  Named(&'a str, bool, Option<Box<SynTy<'a>>>),
+  // `var` in `var x = 1;`, the real type is inferred from the init value in typeck
+  Var,
+  // a function type `ret(param...)`, [0] is ret, [1..] is param, e.g.: `int(int, bool)`
+  Func(Vec<SynTy<'a>>),
+  // a tuple type `(elem...)` with at least 2 elements, e.g.: `(int, string)`
+  Tuple(Vec<SynTy<'a>>),
 }
 
 #[derive(Eq, PartialEq)]
@@ -18,20 +29,35 @@ pub struct SynTy<'a> {
   pub kind: SynTyKind<'a>,
 }
 
-#[derive(Clone, Copy, Eq, PartialEq)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
 pub enum TyKind<'a> {
   Int,
   Bool,
+  // a char is an int in runtime, but the only operations on it are comparisons
+  Char,
+  // a double is a pointer to 8 bytes holding its value in runtime, and a null pointer means 0.0
+  // so that the zero-initialized fields and array elements are 0.0
+  Double,
   String,
   Void,
   Error,
   Null,
   // `Object` is `class A a` <- this `a`
-  Object(Ref<'a, ClassDef<'a>>),
+  // the bool is true if it is non-null, i.e., `class A! a`, `null` can't be assigned to it
+  // it is only a static restriction, a non-null object can still be null at runtime, e.g.: an uninitialized field
+  // the type argument is Some iff the class is generic, instantiations with different type arguments are different types
+  Object(Ref<'a, ClassDef<'a>>, bool, Option<&'a Ty<'a>>),
+  // the type parameter of a generic class, it is opaque in the class: it can only be assigned, passed and returned
+  // it never escapes the class: the type argument of the owner is substituted into the member's type at every use outside (see `subst`),
+  // and it can't be used in static members, which have no owner
+  // the code is not duplicated for each type argument, which is fine because every value takes exactly one word in runtime
+  Param(Ref<'a, ClassDef<'a>>),
   // `Class` is `Class A { }` <- this `A`
   Class(Ref<'a, ClassDef<'a>>),
   // [0] = ret, [1..] = param
   Func(&'a [Ty<'a>]),
+  // a tuple is a pointer to its elements in runtime, each element takes one word
+  Tuple(&'a [Ty<'a>]),
 }
 
 impl Default for TyKind<'_> {
@@ -39,7 +65,7 @@ impl Default for TyKind<'_> {
 }
 
 // arr > 0 <-> is array, for error/void type, arr can only be 0
-#[derive(Clone, Copy, Eq, PartialEq, Default)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Default)]
 pub struct Ty<'a> {
   pub arr: u32,
   pub kind: TyKind<'a>,
@@ -60,13 +86,19 @@ impl<'a> Ty<'a> {
       (Error, _) | (_, Error) => true,
       _ if self.arr == rhs.arr => if self.arr == 0 {
         match (self.kind, rhs.kind) {
-          (Int, Int) | (Bool, Bool) | (String, String) | (Void, Void) => true,
-          (Object(c1), Object(Ref(c2))) => c1.extends(c2),
-          (Null, Object(_)) => true,
+          (Int, Int) | (Bool, Bool) | (Char, Char) | (Double, Double) | (String, String) | (Void, Void) => true,
+          // a generic class can't be inherited, so only the same class needs to compare the type arguments
+          // a nullable object can't be assigned to a non-null one
+          (Object(c1, n1, a1), Object(Ref(c2), n2, a2)) => (n1 || !n2) && if c1 == Ref(c2) { a1 == a2 } else { c1.extends(c2) },
+          (Null, Object(_, non_null, _)) => !non_null,
+          (Param(c1), Param(c2)) => c1 == c2,
+          // covariant in return type, contravariant in parameter types, and the arity must be the same
           (Func(rp1), Func(rp2)) => {
             let (r1, p1, r2, p2) = (&rp1[0], &rp1[1..], &rp2[0], &rp2[1..]);
             r1.assignable_to(*r2) && p1.len() == p2.len() && p1.iter().zip(p2.iter()).all(|(p1, p2)| p2.assignable_to(*p1))
           }
+          // element-wise, tuples are immutable, so it is safe to be covariant
+          (Tuple(e1), Tuple(e2)) => e1.len() == e2.len() && e1.iter().zip(e2.iter()).all(|(e1, e2)| e1.assignable_to(*e2)),
           _ => false,
         }
       } else { *self == rhs }
@@ -74,16 +106,35 @@ impl<'a> Ty<'a> {
     }
   }
 
+  // the "wider" one of self and rhs, or None if neither of them is assignable to the other
+  // e.g.: common(null, class A) = class A, common(class B, class A) = class A (if B extends A)
+  // common(null, class A!) = class A, because the result can be null
+  pub fn common(self, rhs: Ty<'a>) -> Option<Ty<'a>> {
+    let (l, r) = if self == Ty::null() || rhs == Ty::null() { (self.nullable(), rhs.nullable()) } else { (self, rhs) };
+    if l.assignable_to(r) { Some(r) } else if r.assignable_to(l) { Some(l) } else { None }
+  }
+
+  // clear the non-null flag of object type, other types are returned as is
+  pub fn nullable(self) -> Ty<'a> {
+    match self {
+      Ty { arr: 0, kind: TyKind::Object(c, _, a) } => Ty::new(TyKind::Object(c, false, a)),
+      _ => self
+    }
+  }
+
   // why don't use const items?
   // it seems that const items can only have type Ty<'static>, which can NOT be casted to Ty<'a>
   pub const fn error() -> Ty<'a> { Ty::new(TyKind::Error) }
   pub const fn null() -> Ty<'a> { Ty::new(TyKind::Null) }
   pub const fn int() -> Ty<'a> { Ty::new(TyKind::Int) }
   pub const fn bool() -> Ty<'a> { Ty::new(TyKind::Bool) }
+  pub const fn char() -> Ty<'a> { Ty::new(TyKind::Char) }
+  pub const fn double() -> Ty<'a> { Ty::new(TyKind::Double) }
   pub const fn void() -> Ty<'a> { Ty::new(TyKind::Void) }
   pub const fn string() -> Ty<'a> { Ty::new(TyKind::String) }
 
-  pub fn mk_obj(c: &'a ClassDef<'a>) -> Ty<'a> { Ty::new(TyKind::Object(Ref(c))) }
+  // for a generic class, the type argument should be given explicitly
+  pub fn mk_obj(c: &'a ClassDef<'a>) -> Ty<'a> { Ty::new(TyKind::Object(Ref(c), false, None)) }
   pub fn mk_class(c: &'a ClassDef<'a>) -> Ty<'a> { Ty::new(TyKind::Class(Ref(c))) }
   pub fn mk_func(f: &'a FuncDef<'a>) -> Ty<'a> { Ty::new(TyKind::Func(f.ret_param_ty.get().unwrap())) }
 
@@ -91,7 +142,22 @@ impl<'a> Ty<'a> {
   pub fn is_arr(&self) -> bool { self.arr > 0 }
   pub fn is_func(&self) -> bool { self.arr == 0 && if let TyKind::Func(_) = self.kind { true } else { false } }
   pub fn is_class(&self) -> bool { self.arr == 0 && if let TyKind::Class(_) = self.kind { true } else { false } }
-  pub fn is_object(&self) -> bool { self.arr == 0 && if let TyKind::Object(_) = self.kind { true } else { false } }
+  pub fn is_object(&self) -> bool { self.arr == 0 && if let TyKind::Object(..) = self.kind { true } else { false } }
+  pub fn is_tuple(&self) -> bool { self.arr == 0 && if let TyKind::Tuple(_) = self.kind { true } else { false } }
+  pub fn is_param(&self) -> bool { self.arr == 0 && if let TyKind::Param(_) = self.kind { true } else { false } }
+
+  // replace the type parameter in self with `arg`, the types in a generic class's members can only refer to its own parameter
+  // `class T[]` with T = `int[]` becomes `int[][]`
+  pub fn subst(self, arg: Ty<'a>, alloc: &'a Arena<Ty<'a>>) -> Ty<'a> {
+    match self.kind {
+      TyKind::Param(_) if arg == Ty::error() => arg,
+      TyKind::Param(_) => Ty { arr: self.arr + arg.arr, kind: arg.kind },
+      TyKind::Object(c, n, Some(a)) => Ty { arr: self.arr, kind: TyKind::Object(c, n, Some(alloc.alloc(a.subst(arg, alloc)))) },
+      TyKind::Func(ret_param) => Ty { arr: self.arr, kind: TyKind::Func(alloc.alloc_extend(ret_param.iter().map(|t| t.subst(arg, alloc)))) },
+      TyKind::Tuple(elem) => Ty { arr: self.arr, kind: TyKind::Tuple(alloc.alloc_extend(elem.iter().map(|t| t.subst(arg, alloc)))) },
+      _ => self,
+    }
+  }
 }
 
 impl fmt::Debug for Ty<'_> {
@@ -99,11 +165,19 @@ impl fmt::Debug for Ty<'_> {
     match &self.kind {
       TyKind::Int => write!(f, "int"),
       TyKind::Bool => write!(f, "bool"),
+      TyKind::Char => write!(f, "char"),
+      TyKind::Double => write!(f, "double"),
       TyKind::String => write!(f, "string"),
       TyKind::Void => write!(f, "void"),
       TyKind::Error => write!(f, "error"), // we don't expect to reach this case in printing scope info
       TyKind::Null => write!(f, "null"),
-      TyKind::Object(c) | TyKind::Class(c) => write!(f, "class {}", c.name),
+      TyKind::Object(c, non_null, a) => {
+        write!(f, "class {}", c.name)?;
+        if let Some(a) = a { write!(f, "<{:?}>", a)?; }
+        write!(f, "{}", if *non_null { "!" } else { "" })
+      }
+      TyKind::Class(c) => write!(f, "class {}", c.name),
+      TyKind::Param(c) => write!(f, "class {}", c.ty_param.unwrap()),
       // the printing format may be different from other experiment framework's
       // it is not because their format is hard to implement in rust, but because I simply don't like their format,
       // which introduces unnecessary complexity, and doesn't increase readability
@@ -115,8 +189,20 @@ impl fmt::Debug for Ty<'_> {
         }
         write!(f, ")")
       }
+      TyKind::Tuple(elem) => {
+        write!(f, "(")?;
+        for (idx, e) in elem.iter().enumerate() {
+          write!(f, "{:?}{}", e, if idx + 1 == elem.len() { "" } else { ", " })?;
+        }
+        write!(f, ")")
+      }
     }?;
     for _ in 0..self.arr { write!(f, "[]")?; }
     Ok(())
   }
+}
+
+// the same as Debug, for the human-readable output, e.g., the hover information in IDE
+impl fmt::Display for Ty<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> { fmt::Debug::fmt(self, f) }
 }
\ No newline at end of file
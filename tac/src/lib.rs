@@ -10,7 +10,11 @@ use typed_arena::Arena;
 pub struct TacProgram<'a> {
   pub vtbl: Vec<VTbl<'a>>,
   pub func: Vec<TacFunc<'a>>,
+  // all string literals and compiler generated strings (e.g., class names, runtime error messages) in decoded form
+  // equal strings share one entry, so they share one label in the data segment, `==` on strings still compares the contents
   pub str_pool: IndexSet<&'a str>,
+  // the labels of static fields, they are stored in the data segment, and initialized to 0
+  pub static_var: Vec<String>,
 }
 
 pub struct VTbl<'a> {
@@ -85,6 +89,11 @@ pub enum Tac {
   LoadVTbl { dst: u32, v: u32 },
   // v: the index in TacProgram::func
   LoadFunc { dst: u32, f: u32 },
+  // v: the index in TacProgram::static_var, `dst` is the address of this static field
+  LoadStatic { dst: u32, v: u32 },
+  // `dst` is the address of `size` bytes in the stack frame, which are all 0, like `_Alloc` but freed when the function returns
+  // it is only generated by tacopt::escape for the mips and llvm backends, so it is never printed as tac or wasm, or executed by tacvm
+  StackAlloc { dst: u32, size: i32 },
 }
 
 impl Tac {
@@ -100,7 +109,7 @@ impl Tac {
       Jmp { .. } | Label { .. } => (&[], None),
       Jif { cond, .. } => (cond, None),
       Store { src_base, .. } => (src_base, None),
-      LoadStr { dst, .. } | LoadVTbl { dst, .. } | LoadFunc { dst, .. } => (&[], Some(*dst)),
+      LoadStr { dst, .. } | LoadVTbl { dst, .. } | LoadFunc { dst, .. } | LoadStatic { dst, .. } | StackAlloc { dst, .. } => (&[], Some(*dst)),
     }
   }
 
@@ -118,7 +127,7 @@ impl Tac {
       Jmp { .. } | Label { .. } => (&mut [], None),
       Jif { cond, .. } => (cond, None),
       Store { src_base, .. } => (src_base, None),
-      LoadStr { dst, .. } | LoadVTbl { dst, .. } | LoadFunc { dst, .. } => (&mut [], Some(dst)),
+      LoadStr { dst, .. } | LoadVTbl { dst, .. } | LoadFunc { dst, .. } | LoadStatic { dst, .. } | StackAlloc { dst, .. } => (&mut [], Some(dst)),
     }
   }
 }
@@ -140,13 +149,40 @@ impl Debug for Operand {
   }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
-pub enum Intrinsic { _Alloc, _ReadLine, _ReadInt, _StringEqual, _PrintInt, _PrintString, _PrintBool, _Halt }
+// generate the enum, and `Intrinsic::ALL`, which contains all intrinsics in the order of declaration
+macro_rules! intrinsic {
+  ($($i: ident),*) => {
+    #[derive(Copy, Clone, Eq, PartialEq, Debug)]
+    pub enum Intrinsic { $($i),* }
+
+    impl Intrinsic {
+      pub const ALL: &'static [Intrinsic] = &[$(Intrinsic::$i),*];
+    }
+  };
+}
+
+intrinsic!(_Alloc, _ReadLine, _ReadInt, _StringEqual, _StringConcat, _PrintInt, _PrintString, _PrintBool, _PrintChar, _Halt,
+  _DoubleAdd, _DoubleSub, _DoubleMul, _DoubleDiv, _DoubleEq, _DoubleLt, _DoubleLe, _IntToDouble, _PrintDouble, _ReadDouble, _StringLength, _Substring, _StringToInt, _IntToString);
 
 impl Intrinsic {
   pub fn has_ret(self) -> bool {
     use Intrinsic::*;
-    match self { _Alloc | _ReadLine | _ReadInt | _StringEqual => true, _PrintInt | _PrintString | _PrintBool | _Halt => false }
+    match self {
+      _Alloc | _ReadLine | _ReadInt | _StringEqual | _StringConcat | _StringLength | _Substring | _StringToInt | _IntToString => true,
+      _DoubleAdd | _DoubleSub | _DoubleMul | _DoubleDiv | _DoubleEq | _DoubleLt | _DoubleLe | _IntToDouble | _ReadDouble => true,
+      _PrintInt | _PrintString | _PrintBool | _PrintChar | _PrintDouble | _Halt => false,
+    }
+  }
+
+  // the number of `Param`s before calling it
+  pub fn param_num(self) -> u32 {
+    use Intrinsic::*;
+    match self {
+      _ReadLine | _ReadInt | _ReadDouble | _Halt => 0,
+      _Alloc | _PrintInt | _PrintString | _PrintBool | _PrintChar | _PrintDouble | _IntToDouble | _StringLength | _StringToInt | _IntToString => 1,
+      _StringEqual | _StringConcat | _DoubleAdd | _DoubleSub | _DoubleMul | _DoubleDiv | _DoubleEq | _DoubleLt | _DoubleLe => 2,
+      _Substring => 3,
+    }
   }
 }
 
@@ -161,8 +197,11 @@ pub struct CallHint {
 
 pub const INT_SIZE: i32 = 4;
 
-pub const INDEX_OUT_OF_BOUND: &str = r#"Decaf runtime error: Array subscript out of bounds\n"#;
-pub const NEW_ARR_NEG: &str = r#"Decaf runtime error: Cannot create negative-sized array\n"#;
-pub const BAD_CAST1: &str = r#"Decaf runtime error: "#;
-pub const BAD_CAST2: &str = r#" cannot be cast to "#;
-pub const BAD_CAST3: &str = r#"\n"#;
\ No newline at end of file
+// these strings are stored in TacProgram::str_pool in the decoded form, like StringLit
+pub const INDEX_OUT_OF_BOUND: &str = "Decaf runtime error: Array subscript out of bounds\n";
+pub const NEW_ARR_NEG: &str = "Decaf runtime error: Cannot create negative-sized array\n";
+pub const SUBSTR_OUT_OF_BOUND: &str = "Decaf runtime error: String index out of bounds\n";
+pub const NULL_STR: &str = "Decaf runtime error: Using null as a string\n";
+pub const BAD_CAST1: &str = "Decaf runtime error: ";
+pub const BAD_CAST2: &str = " cannot be cast to ";
+pub const BAD_CAST3: &str = "\n";
\ No newline at end of file
@@ -0,0 +1,147 @@
+use syntax::{ast::*, ScopeOwner};
+use common::{Ref, BinOp, HashSet};
+
+// find the `arr[i]`s in the body of a counted loop whose bound check can be eliminated, the loop should be like:
+// `for (i = c; i < arr.length(); i = i + 1) body` (or `int i = c`, `i += 1`, `i++`, `++i`), where `c` is a constant >= 0
+// `i` and `arr` must be local variables which are never assigned in `body`, so `0 <= i < arr.length()` always holds in `body`
+// the `arr[i]`s in lambdas are not included, because lambdas may be called outside the loop
+// the loops with multiple init or update statements are not considered
+pub fn counted_loop<'a>(f: &'a For<'a>, safe: &mut HashSet<Ref<'a, Expr<'a>>>) {
+  let (init, update) = match (&f.init[..], &f.update[..]) { ([init], [update]) => (init, update), _ => return };
+  let i = match &init.kind {
+    StmtKind::LocalVarDef(v) if v.init().is_some_and(non_neg) => *v,
+    StmtKind::Assign(a) if a.op.is_none() && non_neg(&a.src) => if let Some(v) = local(&a.dst) { v } else { return; },
+    _ => return,
+  };
+  let arr = match &f.cond.kind {
+    ExprKind::Binary(b) if b.op == BinOp::Lt && local(&b.l).map(Ref) == Some(Ref(i)) => match &b.r.kind {
+      ExprKind::Call(c) if c.arg.is_empty() => match &c.func.kind {
+        ExprKind::VarSel(VarSel { owner: Some(o), name: "length", .. }) if o.ty.get().is_arr() => if let Some(v) = local(o) { v } else { return; },
+        _ => return,
+      }
+      _ => return,
+    }
+    _ => return,
+  };
+  let one = |e: &Expr| value(e) == Some(1);
+  let inc = match &update.kind {
+    StmtKind::Assign(a) if local(&a.dst).map(Ref) == Some(Ref(i)) => match (a.op, &a.src.kind) {
+      (Some(BinOp::Add), _) => one(&a.src),
+      (None, ExprKind::Binary(b)) => b.op == BinOp::Add && local(&b.l).map(Ref) == Some(Ref(i)) && one(&b.r),
+      _ => false,
+    }
+    StmtKind::ExprEval(Expr { kind: ExprKind::IncDec(x), .. }) => x.op == BinOp::Add && local(&x.e).map(Ref) == Some(Ref(i)),
+    _ => false,
+  };
+  if !inc { return; }
+  let mut s = Scan { i, arr, assigned: false, safe: vec![], lambda: 0 };
+  s.block(&f.body);
+  if !s.assigned { safe.extend(s.safe); }
+}
+
+fn value(e: &Expr) -> Option<i32> { if let ExprKind::IntLit(x) = e.kind { Some(x) } else { e.result.get() } }
+
+fn non_neg(e: &Expr) -> bool { value(e).is_some_and(|x| x >= 0) }
+
+// the local variable that `e` refers to, if `e` is a VarSel of a local variable
+fn local<'a>(e: &Expr<'a>) -> Option<&'a VarDef<'a>> {
+  match &e.kind {
+    ExprKind::VarSel(VarSel { owner: None, var, .. }) => var.get().filter(|v| matches!(v.owner.get(), Some(ScopeOwner::Local(_)) | Some(ScopeOwner::Param(_)) | Some(ScopeOwner::Lambda(_)))),
+    _ => None,
+  }
+}
+
+struct Scan<'a> {
+  i: &'a VarDef<'a>,
+  arr: &'a VarDef<'a>,
+  // whether `i` or `arr` is assigned
+  assigned: bool,
+  safe: Vec<Ref<'a, Expr<'a>>>,
+  // the depth of the enclosing lambdas
+  lambda: u32,
+}
+
+impl<'a> Scan<'a> {
+  fn is(&self, e: &Expr<'a>, v: &'a VarDef<'a>) -> bool { local(e).map(Ref) == Some(Ref(v)) }
+
+  fn write(&mut self, e: &'a Expr<'a>) {
+    if self.is(e, self.i) || self.is(e, self.arr) { self.assigned = true; }
+    self.expr(e);
+  }
+
+  fn block(&mut self, b: &'a Block<'a>) { for s in &b.stmt { self.stmt(s); } }
+
+  fn stmt(&mut self, s: &'a Stmt<'a>) {
+    use StmtKind::*;
+    match &s.kind {
+      Assign(a) => {
+        self.write(&a.dst);
+        self.expr(&a.src);
+      }
+      LocalVarDef(v) => if let Some(e) = v.init() { self.expr(e); }
+      ExprEval(e) => self.expr(e),
+      Skip(_) | Break(_) | Continue(_) => {}
+      If(i) => {
+        self.expr(&i.cond);
+        self.block(&i.on_true);
+        if let Some(b) = &i.on_false { self.block(b); }
+      }
+      While(w) => (self.expr(&w.cond), self.block(&w.body)).1,
+      DoWhile(d) => (self.block(&d.body), self.expr(&d.cond)).1,
+      For(f) => {
+        for s in &f.init { self.stmt(s); }
+        self.expr(&f.cond);
+        for s in &f.update { self.stmt(s); }
+        self.block(&f.body);
+      }
+      Foreach(f) => (self.expr(&f.arr), self.block(&f.body)).1,
+      Switch(s) => {
+        self.expr(&s.expr);
+        for c in &s.case {
+          self.expr(&c.label);
+          self.block(&c.body);
+        }
+        for d in &s.default { self.block(d); }
+      }
+      Return(r) => if let Some(e) = r { self.expr(e); }
+      Print(p) => for e in p { self.expr(e); }
+      Printf(p) => for e in &p.arg { self.expr(e); }
+      Block(b) => self.block(b),
+    }
+  }
+
+  fn expr(&mut self, e: &'a Expr<'a>) {
+    use ExprKind::*;
+    match &e.kind {
+      VarSel(v) => if let Some(o) = &v.owner { self.expr(o); }
+      IndexSel(x) => {
+        if self.lambda == 0 && self.is(&x.arr, self.arr) && self.is(&x.idx, self.i) { self.safe.push(Ref(e)); }
+        self.expr(&x.arr);
+        self.expr(&x.idx);
+      }
+      Slice(s) => (self.expr(&s.arr), self.expr(&s.lo), self.expr(&s.hi)).2,
+      Call(c) => {
+        self.expr(&c.func);
+        for a in &c.arg { self.expr(a); }
+      }
+      Unary(u) => self.expr(&u.r),
+      Binary(b) => (self.expr(&b.l), self.expr(&b.r)).1,
+      IncDec(x) => self.write(&x.e),
+      Assign(a) => (self.write(&a.dst), self.expr(&a.src)).1,
+      Ternary(t) => (self.expr(&t.cond), self.expr(&t.on_true), self.expr(&t.on_false)).2,
+      ArrayLit(a) => for e in a { self.expr(e); }
+      Tuple(t) => for e in &t.elem { self.expr(e); }
+      TupleSel(t) => self.expr(&t.tuple),
+      NewClass(n) => for a in &n.arg { self.expr(a); }
+      NewArray(n) => (self.expr(&n.len), for l in &n.sub_len { self.expr(l); }).1,
+      ClassTest(c) => self.expr(&c.expr),
+      ClassCast(c) => self.expr(&c.expr),
+      Lambda(l) => {
+        self.lambda += 1;
+        match &l.body { LambdaBody::Expr(e) => self.expr(e), LambdaBody::Block(b) => self.block(b) }
+        self.lambda -= 1;
+      }
+      IntLit(_) | CharLit(_) | DoubleLit(_) | BoolLit(_) | StringLit(_) | NullLit(_) | This(_) | Super(_) | ReadInt(_) | ReadDouble(_) | ReadLine(_) => {}
+    }
+  }
+}
@@ -1,10 +1,12 @@
-use syntax::FuncDef;
+use syntax::{FuncDef, Ty};
 use common::IndexMap;
+use tac::{Operand, MemHint};
 
 // these structs are used in tacgen to keep some intermediate information
 
 pub struct VarInfo {
   // if the var is a VarDef in class, `off` is the offset in object pointer
+  // if the var is a static VarDef in class, `off` is the index in TacProgram::static_var
   // if the var is a VarDef in function, `off` is a virtual register number
   pub off: u32,
 }
@@ -21,5 +23,18 @@ pub struct ClassInfo<'a> {
   pub field_num: u32,
   // which vtbl it's vtbl is in TacProgram (index in TacProgram::vtbl)
   pub idx: u32,
-  pub vtbl: IndexMap<&'a str, &'a FuncDef<'a>>,
+  // key is (name, parameter types), because overloaded methods have the same name
+  pub vtbl: IndexMap<(&'a str, &'a [Ty<'a>]), &'a FuncDef<'a>>,
+  // the slots for the methods of the implemented interfaces, which are after the slots in `vtbl`, see TacGen::interface_slot
+  // None is a hole, which is never called
+  pub islot: Vec<Option<&'a FuncDef<'a>>>,
+}
+
+// where the value of an lvalue is stored, its sub-expressions are already evaluated, see TacGen::place
+#[derive(Copy, Clone)]
+pub enum Place {
+  // a local variable or a parameter in this register
+  Var(u32),
+  // *(base + off)
+  Mem(Operand, i32, MemHint),
 }
\ No newline at end of file
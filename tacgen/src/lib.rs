@@ -1,9 +1,11 @@
 mod info;
+mod bound;
 
 use syntax::{ast::*, ty::*, ScopeOwner};
 use ::tac::{self, *, Tac::{self, *}, Operand::*, Intrinsic::*};
-use common::{Ref, MAIN_METHOD, BinOp::*, UnOp::*, IndexSet, IndexMap, HashMap};
+use common::{Loc, Ref, MAIN_METHOD, LENGTH, STRING_TO_INT, BinOp::{self, *}, UnOp::*, IndexSet, IndexMap, HashMap, HashSet};
 use typed_arena::Arena;
+use std::iter;
 use crate::info::*;
 
 #[derive(Default)]
@@ -11,23 +13,41 @@ struct TacGen<'a> {
   // `reg_num` and `label_num` are manually set at the beginning of every function
   reg_num: u32,
   label_num: u32,
-  loop_stk: Vec<u32>,
+  // (loop's label in source code, tac label for break, tac label for continue) of the enclosing loops and switches
+  // a switch has the same continue label as the enclosing loop, or None if it is not in a loop
+  loop_stk: Vec<(Option<&'a str>, u32, Option<u32>)>,
+  // whether the current method is static, if not, `this` is stored in the closures created in it
+  cur_static: bool,
+  // the functions generated from lambdas, they are placed after all methods in TacProgram::func
+  lambda: Vec<TacFunc<'a>>,
+  // the number of `_Xxx._new` functions and methods, i.e., the index of the first lambda function
+  func_num: u32,
+  // the index of the function wrapping a static method used as a function value, it is placed in `lambda`
+  func_value: HashMap<Ref<'a, FuncDef<'a>>, u32>,
   // Id & Index will behave differently when they are the lhs of an assignment
   // cur_assign contains the current assign rhs operand, or None if the current handling expr doesn't involve in assign
   cur_assign: Option<Operand>,
   str_pool: IndexSet<&'a str>,
+  // the labels of static fields, see TacProgram::static_var
+  static_var: Vec<String>,
   // `*_info` just works like extra fields to those structs, their specific meaning can be found at `struct *Info`
   var_info: HashMap<Ref<'a, VarDef<'a>>, VarInfo>,
   func_info: HashMap<Ref<'a, FuncDef<'a>>, FuncInfo>,
   class_info: HashMap<Ref<'a, ClassDef<'a>>, ClassInfo<'a>>,
+  // the non-abstract classes implementing each interface
+  implementors: HashMap<Ref<'a, ClassDef<'a>>, Vec<&'a ClassDef<'a>>>,
+  // whether to eliminate the bound check of the `arr[i]`s in `safe_index`, see bound::counted_loop
+  elim_bound_check: bool,
+  safe_index: HashSet<Ref<'a, Expr<'a>>>,
 }
 
-pub fn work<'a>(p: &'a Program<'a>, alloc: &'a Arena<TacNode<'a>>) -> TacProgram<'a> {
-  TacGen::default().program(p, alloc)
+// `elim_bound_check`: whether to eliminate the bound check of array access in counted loops
+pub fn work<'a>(p: &'a Program<'a>, alloc: &'a Arena<TacNode<'a>>, elim_bound_check: bool) -> TacProgram<'a> {
+  TacGen { elim_bound_check, ..TacGen::default() }.program(p, alloc)
 }
 
 impl<'a> TacGen<'a> {
-  fn program(mut self, p: &Program<'a>, alloc: &'a Arena<TacNode<'a>>) -> TacProgram<'a> {
+  fn program(mut self, p: &'a Program<'a>, alloc: &'a Arena<TacNode<'a>>) -> TacProgram<'a> {
     let mut tp = TacProgram::default();
     for (idx, &c) in p.class.iter().enumerate() {
       self.define_str(c.name);
@@ -35,6 +55,10 @@ impl<'a> TacGen<'a> {
       self.class_info.get_mut(&Ref(c)).unwrap().idx = idx as u32;
       tp.func.push(self.build_new(c, alloc));
     }
+    for &i in &p.class {
+      if i.interface { self.implementors.insert(Ref(i), p.class.iter().copied().filter(|c| !c.abstract_ && c.extends(i)).collect()); }
+    }
+    self.interface_slot(p);
     {
       let mut idx = tp.func.len() as u32; // their are already some `_Xxx._new` functions in tp.func, so can't start from 0
       for &c in &p.class {
@@ -45,10 +69,15 @@ impl<'a> TacGen<'a> {
           }
         }
       }
+      self.func_num = idx;
     }
     for &c in &p.class {
+      // the count of the methods with the same name before, used to give different names to overloaded methods
+      let mut overload_cnt = HashMap::new();
       for f in &c.field {
         if let FieldDef::FuncDef(fu) = f {
+          let cnt = overload_cnt.entry(fu.name).or_insert(0);
+          let overload_idx = (*cnt, *cnt += 1).0;
           let this = if fu.static_ { 0 } else { 1 };
           for (idx, p) in fu.param.iter().enumerate() {
             self.var_info.insert(Ref(p), VarInfo { off: idx as u32 + this });
@@ -56,38 +85,116 @@ impl<'a> TacGen<'a> {
           // these regs are occupied by parameters
           self.reg_num = fu.param.len() as u32 + this;
           self.label_num = 0;
-          let name = if Ref(c) == Ref(p.main.get().unwrap()) && fu.name == MAIN_METHOD { MAIN_METHOD.into() } else { format!("_{}.{}", c.name, fu.name) };
+          self.cur_static = fu.static_;
+          let name = if Ref(c) == Ref(p.main.get().unwrap()) && fu.name == MAIN_METHOD && overload_idx == 0 { MAIN_METHOD.into() } else if overload_idx == 0 { format!("_{}.{}", c.name, fu.name) } else { format!("_{}.{}.{}", c.name, fu.name, overload_idx) };
           let mut f = TacFunc::empty(alloc, name, self.reg_num);
-          self.block(&fu.body, &mut f);
+          // an abstract method is never called, but it still occupies a slot in vtbl, so an empty function is generated
+          if let Some(b) = &fu.body { self.block(b, &mut f); }
           f.reg_num = self.reg_num;
           // add an return at the end of return-void function
-          if fu.ret_ty() == Ty::void() { f.push(Tac::Ret { src: None }); }
+          if fu.ret_ty() == Ty::void() || fu.abstract_ { f.push(Tac::Ret { src: None }); }
           tp.func.push(f);
         }
       }
     }
+    tp.func.append(&mut self.lambda);
     for &c in &p.class {
+      let ClassInfo { idx, vtbl, islot, .. } = &self.class_info[&Ref(c)];
       tp.vtbl.push(tac::VTbl {
         parent: c.parent_ref.get().map(|p| self.class_info[&Ref(p)].idx),
         class: c.name,
-        func: self.class_info[&Ref(c)].vtbl.iter().map(|(_, &f)| self.func_info[&Ref(f)].idx).collect(),
+        // a hole is filled with the class's `_new` function, which has the same index as its vtbl, so `func` can still be a list of function indices
+        func: vtbl.values().map(|&f| self.func_info[&Ref(f)].idx).chain(islot.iter().map(|f| f.map_or(*idx, |f| self.func_info[&Ref(f)].idx))).collect(),
       });
     }
     tp.str_pool = self.str_pool;
+    tp.static_var = self.static_var;
     tp
   }
 
-  fn block(&mut self, b: &Block<'a>, f: &mut TacFunc<'a>) {
+  fn block(&mut self, b: &'a Block<'a>, f: &mut TacFunc<'a>) {
     for s in &b.stmt { self.stmt(s, f); }
   }
 
-  fn stmt(&mut self, s: &Stmt<'a>, f: &mut TacFunc<'a>) {
+  // return the value assigned
+  fn assign(&mut self, a: &'a syntax::ast::Assign<'a>, f: &mut TacFunc<'a>) -> Operand {
+    if let Some(op) = a.op {
+      // `a op= b` is translated to `a = a op b`, the sub-expressions of `a` (if any) are evaluated only once, before `b`
+      let place = self.place(&a.dst, f);
+      let (l, r) = (self.load_place(place, f), self.expr(&a.src, f));
+      let src = self.binary(op, a.dst.ty.get(), a.src.ty.get(), l, r, f);
+      self.store_place(place, src, f);
+      return src;
+    }
+    let src = self.expr(&a.src, f);
+    self.cur_assign = Some(src);
+    self.expr(&a.dst, f);
+    src
+  }
+
+  // evaluate the sub-expressions of the lvalue `e` and check the index bound, so that it can be read and written without evaluating them again
+  fn place(&mut self, e: &'a Expr<'a>, f: &mut TacFunc<'a>) -> Place {
+    match &e.kind {
+      ExprKind::VarSel(v) => {
+        let var = v.var.get().unwrap();
+        let off = self.var_info[&Ref(var)].off;
+        match var.owner.get().unwrap() {
+          ScopeOwner::Class(_) if var.static_ => {
+            if let Some(o) = v.owner.as_ref() { let _ = self.expr(o, f); }
+            let addr = self.reg();
+            f.push(LoadStatic { dst: addr, v: off });
+            Place::Mem(Reg(addr), 0, MemHint::Obj)
+          }
+          ScopeOwner::Class(_) => {
+            // the owner is copied, because it may be a local variable assigned in the rhs
+            let (owner, base) = (v.owner.as_ref().map(|o| self.expr(o, f)).unwrap_or(Reg(0)), self.reg());
+            f.push(Tac::Assign { dst: base, src: [owner] });
+            Place::Mem(Reg(base), off as i32 * INT_SIZE, MemHint::Obj)
+          }
+          _ => Place::Var(off),
+        }
+      }
+      ExprKind::IndexSel(i) => {
+        let (arr, idx) = (self.expr(&i.arr, f), self.expr(&i.idx, f));
+        if !self.safe_index.contains(&Ref(e)) {
+          let (ok, len, cmp, pass) = (self.reg(), self.length(arr, f), self.reg(), self.label());
+          f.push(Bin { op: Ge, dst: ok, lr: [idx, Const(0)] })
+            .push(Bin { op: Lt, dst: cmp, lr: [idx, len] })
+            .push(Bin { op: And, dst: ok, lr: [Reg(ok), Reg(cmp)] })
+            .push(Jif { label: pass, z: false, cond: [Reg(ok)] });
+          self.re(INDEX_OUT_OF_BOUND, f);
+          f.push(Label { label: pass });
+        }
+        let addr = self.reg();
+        f.push(Bin { op: Mul, dst: addr, lr: [idx, Const(INT_SIZE)] })
+          .push(Bin { op: Add, dst: addr, lr: [Reg(addr), arr] });
+        Place::Mem(Reg(addr), 0, MemHint::Arr)
+      }
+      _ => unreachable!("typeck guarantees that it is an lvalue"),
+    }
+  }
+
+  // the value is read into a new register, in case that the variable is assigned before the value is used
+  fn load_place(&mut self, p: Place, f: &mut TacFunc<'a>) -> Operand {
+    let dst = self.reg();
+    match p {
+      Place::Var(r) => f.push(Tac::Assign { dst, src: [Reg(r)] }),
+      Place::Mem(base, off, hint) => f.push(Load { dst, base: [base], off, hint }),
+    };
+    Reg(dst)
+  }
+
+  fn store_place(&mut self, p: Place, src: Operand, f: &mut TacFunc<'a>) {
+    match p {
+      Place::Var(r) => f.push(Tac::Assign { dst: r, src: [src] }),
+      Place::Mem(base, off, hint) => f.push(Store { src_base: [src, base], off, hint }),
+    };
+  }
+
+  fn stmt(&mut self, s: &'a Stmt<'a>, f: &mut TacFunc<'a>) {
     use StmtKind::*;
     match &s.kind {
-      Assign(a) => {
-        self.cur_assign = Some(self.expr(&a.src, f));
-        self.expr(&a.dst, f);
-      }
+      Assign(a) => { self.assign(a, f); }
       LocalVarDef(v) => {
         let reg = self.reg();
         self.var_info.insert(Ref(v), VarInfo { off: reg });
@@ -96,6 +203,8 @@ impl<'a> TacGen<'a> {
       }
       ExprEval(e) => { self.expr(e, f); }
       Skip(_) => {}
+      // a constant condition has no side effect, so the dead branch is not translated at all
+      If(i) if i.cond.result.get().is_some() => if i.cond.result.get() != Some(0) { self.block(&i.on_true, f) } else if let Some(of) = &i.on_false { self.block(of, f) },
       If(i) => {
         let before_else = self.label();
         let cond = self.expr(&i.cond, f);
@@ -111,6 +220,7 @@ impl<'a> TacGen<'a> {
           f.push(Label { label: before_else });
         }
       }
+      While(w) if w.cond.result.get() == Some(0) => {}
       While(w) => {
         //   jump before_cond
         // before_body:
@@ -120,7 +230,7 @@ impl<'a> TacGen<'a> {
         //   if cond jump before_body
         // after_body: (for break's use)
         let (before_cond, before_body, after_body) = (self.label(), self.label(), self.label());
-        self.loop_stk.push(after_body);
+        self.loop_stk.push((w.label, after_body, Some(before_cond)));
         f.push(Jmp { label: before_cond });
         f.push(Label { label: before_body });
         self.block(&w.body, f);
@@ -130,29 +240,114 @@ impl<'a> TacGen<'a> {
         f.push(Label { label: after_body });
         self.loop_stk.pop();
       }
+      DoWhile(d) => {
+        // before_body:
+        //   body
+        // before_cond: (for continue's use)
+        //   compute cond
+        //   if cond jump before_body
+        // after_body: (for break's use)
+        let (before_cond, before_body, after_body) = (self.label(), self.label(), self.label());
+        self.loop_stk.push((d.label, after_body, Some(before_cond)));
+        f.push(Label { label: before_body });
+        self.block(&d.body, f);
+        f.push(Label { label: before_cond });
+        let cond = self.expr(&d.cond, f);
+        f.push(Jif { label: before_body, z: false, cond: [cond] });
+        f.push(Label { label: after_body });
+        self.loop_stk.pop();
+      }
       For(fo) => {
         // init
         //   jump before_cond
         // before_body:
         //   body
+        // before_update: (for continue's use)
         //   update
         // before_cond:
         //   compute cond
         //   if cond jump before_body
         // after_body: (for break's use)
-        let (before_cond, before_body, after_body) = (self.label(), self.label(), self.label());
-        self.loop_stk.push(after_body);
-        self.stmt(&fo.init, f);
+        let (before_cond, before_body, before_update, after_body) = (self.label(), self.label(), self.label(), self.label());
+        self.loop_stk.push((fo.label, after_body, Some(before_update)));
+        if self.elim_bound_check { bound::counted_loop(fo, &mut self.safe_index); }
+        for s in &fo.init { self.stmt(s, f); }
         f.push(Jmp { label: before_cond });
         f.push(Label { label: before_body });
         self.block(&fo.body, f);
-        self.stmt(&fo.update, f);
+        f.push(Label { label: before_update });
+        for s in &fo.update { self.stmt(s, f); }
         f.push(Label { label: before_cond });
         let cond = self.expr(&fo.cond, f);
         f.push(Jif { label: before_body, z: false, cond: [cond] });
         f.push(Label { label: after_body });
         self.loop_stk.pop();
       }
+      Foreach(fe) => {
+        //   arr = compute arr, len = length of arr, i = 0
+        //   jump before_cond
+        // before_body:
+        //   var = arr[i]
+        //   body
+        // before_update: (for continue's use)
+        //   i = i + 1
+        // before_cond:
+        //   if i < len jump before_body
+        // after_body: (for break's use)
+        // arr is copied, so that assigning to the original variable in body doesn't affect the iteration
+        let (arr, i, cmp, ptr, var) = (self.reg(), self.reg(), self.reg(), self.reg(), self.reg());
+        let (before_cond, before_body, before_update, after_body) = (self.label(), self.label(), self.label(), self.label());
+        self.var_info.insert(Ref(fe.var), VarInfo { off: var });
+        let src = self.expr(&fe.arr, f);
+        f.push(Tac::Assign { dst: arr, src: [src] });
+        let len = self.length(Reg(arr), f);
+        f.push(Tac::Assign { dst: i, src: [Const(0)] }).push(Jmp { label: before_cond }).push(Label { label: before_body })
+          .push(Bin { op: Mul, dst: ptr, lr: [Reg(i), Const(INT_SIZE)] })
+          .push(Bin { op: Add, dst: ptr, lr: [Reg(ptr), Reg(arr)] })
+          .push(Load { dst: var, base: [Reg(ptr)], off: 0, hint: MemHint::Arr });
+        self.loop_stk.push((fe.label, after_body, Some(before_update)));
+        self.block(&fe.body, f);
+        self.loop_stk.pop();
+        f.push(Label { label: before_update })
+          .push(Bin { op: Add, dst: i, lr: [Reg(i), Const(1)] })
+          .push(Label { label: before_cond })
+          .push(Bin { op: Lt, dst: cmp, lr: [Reg(i), len] })
+          .push(Jif { label: before_body, z: false, cond: [Reg(cmp)] })
+          .push(Label { label: after_body });
+      }
+      Switch(sw) => {
+        //   compute expr
+        //   if expr == case0.label jump before_case0
+        //   ...
+        //   jump before_default (or after_switch if there is no default)
+        // before_case0:
+        //   case0.body
+        //   jump after_switch
+        // ...
+        // before_default:
+        //   default
+        // after_switch: (for break's use)
+        let expr = self.expr(&sw.expr, f);
+        let before_case = sw.case.iter().map(|_| self.label()).collect::<Vec<_>>();
+        let (before_default, after_switch) = (self.label(), self.label());
+        for (c, &label) in sw.case.iter().zip(before_case.iter()) {
+          let (l, cmp) = (self.expr(&c.label, f), self.reg());
+          f.push(Bin { op: Eq, dst: cmp, lr: [expr, l] }).push(Jif { label, z: false, cond: [Reg(cmp)] });
+        }
+        f.push(Jmp { label: before_default });
+        // `continue` in switch continues the enclosing loop
+        let cont = self.loop_stk.last().and_then(|l| l.2);
+        self.loop_stk.push((None, after_switch, cont));
+        for (c, &label) in sw.case.iter().zip(before_case.iter()) {
+          f.push(Label { label });
+          self.block(&c.body, f);
+          f.push(Jmp { label: after_switch });
+        }
+        f.push(Label { label: before_default });
+        for d in &sw.default { self.block(d, f); }
+        f.push(Label { label: after_switch });
+        self.loop_stk.pop();
+      }
       Return(r) => {
         let src = r.as_ref().map(|e| [self.expr(e, f)]);
         f.push(Ret { src });
@@ -163,29 +358,67 @@ impl<'a> TacGen<'a> {
         match e.ty.get() {
           t if t == Ty::int() => { self.intrinsic(_PrintInt, f); }
           t if t == Ty::bool() => { self.intrinsic(_PrintBool, f); }
+          t if t == Ty::char() => { self.intrinsic(_PrintChar, f); }
+          t if t == Ty::double() => { self.intrinsic(_PrintDouble, f); }
           t if t == Ty::string() => { self.intrinsic(_PrintString, f); }
           t => unreachable!("Shouldn't meet type {:?} in Print in these phase, type checking should have reported error.", t),
         }
       }
-      Break(_) => { f.push(Jmp { label: *self.loop_stk.last().unwrap() }); }
+      Printf(p) => {
+        let mut arg = p.arg.iter();
+        for part in p.parts() {
+          let print = match part {
+            FmtPart::Lit(s) => {
+              let (dst, s) = (self.reg(), self.define_str(s));
+              f.push(LoadStr { dst, s }).push(Param { src: [Reg(dst)] });
+              _PrintString
+            }
+            FmtPart::Arg(t) => {
+              let reg = self.expr(arg.next().unwrap(), f);
+              f.push(Param { src: [reg] });
+              if t == Ty::int() { _PrintInt } else if t == Ty::bool() { _PrintBool } else { _PrintString }
+            }
+            FmtPart::Bad => unreachable!("Shouldn't meet bad format in Printf in these phase, type checking should have reported error."),
+          };
+          self.intrinsic(print, f);
+        }
+      }
+      Break(b) => { f.push(Jmp { label: self.jump_target(b.label).1 }); }
+      Continue(c) => { f.push(Jmp { label: self.jump_target(c.label).2.expect("continue outside loop") }); }
       Block(b) => self.block(b, f),
     }
   }
 
-  fn expr(&mut self, e: &Expr<'a>, f: &mut TacFunc<'a>) -> Operand {
+  fn expr(&mut self, e: &'a Expr<'a>, f: &mut TacFunc<'a>) -> Operand {
     use ExprKind::*;
+    // an lvalue is never statically evaluated, so `cur_assign` must be None here
+    if let Some(x) = e.result.get() { return Const(x); }
     let assign = self.cur_assign.take();
     match &e.kind {
       VarSel(v) => {
+        if let Some(fu) = v.func.get() { return self.func_value(fu, f); }
         // if `e` is a class name, v.var.get() may be None, this happens only when calling a static function with class name
         let var = if let Some(var) = v.var.get() { var } else { return Reg(0); };
         let off = self.var_info[&Ref(var)].off; // may be register id or offset in class
         match var.owner.get().unwrap() {
-          ScopeOwner::Local(_) | ScopeOwner::Param(_) => if let Some(src) = assign { // `off` is register
+          ScopeOwner::Local(_) | ScopeOwner::Param(_) | ScopeOwner::Lambda(_) => if let Some(src) = assign { // `off` is register
             f.push(Tac::Assign { dst: off, src: [src] });
             // the return value won't be used, so just return a meaningless Reg(0), the below Reg(0)s are the same
             Reg(0)
           } else { Reg(off) }
+          ScopeOwner::Class(_) if var.static_ => { // `off` is index in static_var
+            if let Some(o) = v.owner.as_ref() { let _ = self.expr(o, f); }
+            let addr = self.reg();
+            f.push(LoadStatic { dst: addr, v: off });
+            if let Some(src) = assign {
+              f.push(Store { src_base: [src, Reg(addr)], off: 0, hint: MemHint::Obj });
+              Reg(0)
+            } else {
+              let dst = self.reg();
+              f.push(Load { dst, base: [Reg(addr)], off: 0, hint: MemHint::Obj });
+              Reg(dst)
+            }
+          }
           ScopeOwner::Class(_) => { // `off` is offset
             // `this` is at argument 0
             let owner = v.owner.as_ref().map(|o| self.expr(o, f)).unwrap_or(Reg(0));
@@ -203,12 +436,16 @@ impl<'a> TacGen<'a> {
       }
       IndexSel(i) => {
         let (arr, idx) = (self.expr(&i.arr, f), self.expr(&i.idx, f));
-        let (ok, len, cmp) = (self.reg(), self.length(arr, f), self.reg());
-        let (err, after) = (self.label(), self.label());
-        f.push(Bin { op: Ge, dst: ok, lr: [idx, Const(0)] })
-          .push(Bin { op: Lt, dst: cmp, lr: [idx, len] })
-          .push(Bin { op: And, dst: ok, lr: [Reg(ok), Reg(cmp)] })
-          .push(Jif { label: err, z: true, cond: [Reg(ok)] });
+        // the range check is not needed in counted loops, see bound::counted_loop
+        let check = if self.safe_index.contains(&Ref(e)) { None } else {
+          let (ok, len, cmp) = (self.reg(), self.length(arr, f), self.reg());
+          let (err, after) = (self.label(), self.label());
+          f.push(Bin { op: Ge, dst: ok, lr: [idx, Const(0)] })
+            .push(Bin { op: Lt, dst: cmp, lr: [idx, len] })
+            .push(Bin { op: And, dst: ok, lr: [Reg(ok), Reg(cmp)] })
+            .push(Jif { label: err, z: true, cond: [Reg(ok)] });
+          Some((err, after))
+        };
         // range check passed if reach here
         let off = self.reg();
         f.push(Bin { op: Mul, dst: off, lr: [idx, Const(INT_SIZE)] })
@@ -221,12 +458,44 @@ impl<'a> TacGen<'a> {
           f.push(Load { dst, base: [Reg(off)], off: 0, hint: MemHint::Arr });
           Reg(dst)
         };
-        f.push(Jmp { label: after });
-        self.re(INDEX_OUT_OF_BOUND, f.push(Label { label: err }));
-        f.push(Label { label: after });
+        if let Some((err, after)) = check {
+          f.push(Jmp { label: after });
+          self.re(INDEX_OUT_OF_BOUND, f.push(Label { label: err }));
+          f.push(Label { label: after });
+        }
         ret
       }
+      Slice(s) => {
+        let (arr, lo, hi) = (self.expr(&s.arr, f), self.expr(&s.lo, f), self.expr(&s.hi, f));
+        let len = self.length(arr, f);
+        // 0 <= lo <= hi <= len
+        let (ok, cmp, pass) = (self.reg(), self.reg(), self.label());
+        f.push(Bin { op: Ge, dst: ok, lr: [lo, Const(0)] })
+          .push(Bin { op: Le, dst: cmp, lr: [lo, hi] })
+          .push(Bin { op: And, dst: ok, lr: [Reg(ok), Reg(cmp)] })
+          .push(Bin { op: Le, dst: cmp, lr: [hi, len] })
+          .push(Bin { op: And, dst: ok, lr: [Reg(ok), Reg(cmp)] })
+          .push(Jif { label: pass, z: false, cond: [Reg(ok)] });
+        self.re(INDEX_OUT_OF_BOUND, f);
+        f.push(Label { label: pass });
+        let (n, src) = (self.reg(), self.reg());
+        f.push(Bin { op: Sub, dst: n, lr: [hi, lo] })
+          .push(Bin { op: Mul, dst: src, lr: [lo, Const(INT_SIZE)] })
+          .push(Bin { op: Add, dst: src, lr: [Reg(src), arr] }); // now src = &arr[lo]
+        let new = self.alloc_arr(Reg(n), f);
+        self.copy_arr(Reg(new), Reg(src), Reg(n), f);
+        Reg(new)
+      }
       IntLit(i) => Const(*i),
+      CharLit(c) => Const(*c as i32),
+      DoubleLit(d) => {
+        // see TyKind::Double, the lower 32 bits are at the lower address
+        f.push(Param { src: [Const(2 * INT_SIZE)] });
+        let (dst, bits) = (self.intrinsic(_Alloc, f).unwrap(), d.to_bits());
+        f.push(Store { src_base: [Const(bits as i32), Reg(dst)], off: 0, hint: MemHint::Immutable })
+          .push(Store { src_base: [Const((bits >> 32) as i32), Reg(dst)], off: INT_SIZE, hint: MemHint::Immutable });
+        Reg(dst)
+      }
       BoolLit(b) => Const(*b as i32),
       StringLit(s) => {
         let dst = self.reg();
@@ -235,16 +504,52 @@ impl<'a> TacGen<'a> {
       }
       NullLit(_) => Const(0),
       Call(c) => {
-        let v = if let ExprKind::VarSel(v) = &c.func.kind { v } else { unimplemented!() };
+        let v = match &c.func.kind {
+          ExprKind::VarSel(v) if v.var.get().is_none() => v,
+          // a function value, including a variable of function type like `obj.f(args)`
+          _ => {
+            let closure = self.expr(&c.func, f);
+            return self.call_closure(closure, &c.arg, e.ty.get(), f);
+          }
+        };
         match &v.owner {
           Some(o) if o.ty.get().is_arr() => {
             let arr = self.expr(o, f);
             self.length(arr, f)
           }
+          // typeck guarantees that it is `length()` or `substring(start, end)`
+          Some(o) if o.ty.get() == Ty::string() => {
+            let s = self.expr(o, f);
+            let arg = c.arg.iter().map(|a| self.expr(a, f)).collect::<Vec<_>>();
+            self.null_str_check(s, f);
+            let len = Reg(self.intrinsic(_StringLength, f.push(Param { src: [s] })).unwrap());
+            if v.name == LENGTH { return len; }
+            // 0 <= start <= end <= len
+            let (ok, cmp, err, after) = (self.reg(), self.reg(), self.label(), self.label());
+            f.push(Bin { op: Ge, dst: ok, lr: [arg[0], Const(0)] })
+              .push(Bin { op: Le, dst: cmp, lr: [arg[0], arg[1]] })
+              .push(Bin { op: And, dst: ok, lr: [Reg(ok), Reg(cmp)] })
+              .push(Bin { op: Le, dst: cmp, lr: [arg[1], len] })
+              .push(Bin { op: And, dst: ok, lr: [Reg(ok), Reg(cmp)] })
+              .push(Jif { label: err, z: true, cond: [Reg(ok)] });
+            let dst = self.intrinsic(_Substring, f.push(Param { src: [s] }).push(Param { src: [arg[0]] }).push(Param { src: [arg[1]] })).unwrap();
+            f.push(Jmp { label: after });
+            self.re(SUBSTR_OUT_OF_BOUND, f.push(Label { label: err }));
+            f.push(Label { label: after });
+            Reg(dst)
+          }
+          // `stringToInt(s)` or `intToString(i)`
+          None if c.func_ref.get().is_none() => {
+            let a = self.expr(&c.arg[0], f);
+            if v.name == STRING_TO_INT { self.null_str_check(a, f); }
+            f.push(Param { src: [a] });
+            Reg(self.intrinsic(if v.name == STRING_TO_INT { _StringToInt } else { _IntToString }, f).unwrap())
+          }
           _ => {
             let fu = c.func_ref.get().unwrap();
             let ret = if fu.ret_ty() != Ty::void() { Some(self.reg()) } else { None };
-            let args = c.arg.iter().map(|a| self.expr(a, f)).collect::<Vec<_>>();
+            // the omitted arguments are filled with the default values (which are constants)
+            let args = c.arg.iter().chain(fu.param[c.arg.len()..].iter().map(|v| v.init().unwrap())).map(|a| self.expr(a, f)).collect::<Vec<_>>();
             let hint = CallHint {
               arg_obj: c.arg.iter().any(|a| a.ty.get().is_class()) || !fu.static_,
               arg_arr: c.arg.iter().any(|a| a.ty.get().arr > 0),
@@ -253,86 +558,142 @@ impl<'a> TacGen<'a> {
               if let Some(o) = v.owner.as_ref() { let _ = self.expr(o, f); }
               for a in args { f.push(Param { src: [a] }); }
               f.push(Tac::Call { dst: ret, kind: CallKind::Static(self.func_info[&Ref(fu)].idx, hint) });
+            } else if let Some(ExprKind::Super(_)) = v.owner.as_ref().map(|o| &o.kind) {
+              // super.method() is not dispatched by vtbl, the parent's method is called directly
+              f.push(Param { src: [Reg(0)] });
+              for a in args { f.push(Param { src: [a] }); }
+              f.push(Tac::Call { dst: ret, kind: CallKind::Static(self.func_info[&Ref(fu)].idx, hint) });
             } else {
               // Reg(0) is `this`
               let owner = v.owner.as_ref().map(|o| self.expr(o, f)).unwrap_or(Reg(0));
+              let slot = self.method_slot(owner, fu, f);
               f.push(Param { src: [owner] });
               for a in args { f.push(Param { src: [a] }); }
-              let slot = self.reg();
-              let off = self.func_info[&Ref(fu)].off;
-              f.push(Load { dst: slot, base: [owner], off: 0, hint: MemHint::Immutable })
-                .push(Load { dst: slot, base: [Reg(slot)], off: off as i32 * INT_SIZE, hint: MemHint::Immutable });
               f.push(Tac::Call { dst: ret, kind: CallKind::Virtual([Reg(slot)], hint) });
             }
             Reg(ret.unwrap_or(0)) // if ret is None, the result can't be assigned to others, so 0 will not be used
           }
         }
       }
-      Unary(u) => {
+      Unary(u) if u.r.ty.get() == Ty::double() => {
+        // -x = 0.0 - x, and Const(0) is 0.0
+        let r = self.expr(&u.r, f);
+        f.push(Param { src: [Const(0)] }).push(Param { src: [r] });
+        Reg(self.intrinsic(_DoubleSub, f).unwrap())
+      }
+      // `!(e instanceof C)` is computed by `check_cast` directly, without an extra `Un`
+      Unary(u) => if let (Not, ClassTest(t)) = (u.op, &u.r.kind) {
+        let obj = self.expr(&t.expr, f);
+        self.check_cast(obj, t.class.get().unwrap(), true, f)
+      } else {
         let (r, dst) = (self.expr(&u.r, f), self.reg());
         f.push(Un { op: u.op, dst, r: [r] });
         Reg(dst)
       }
+      // short-circuit: `r` is not evaluated if `l` is false for `&&`, or true for `||`
+      Binary(b) if b.op == And || b.op == Or => {
+        let (dst, end) = (self.reg(), self.label());
+        let l = self.expr(&b.l, f);
+        f.push(Tac::Assign { dst, src: [l] }).push(Jif { label: end, z: b.op == And, cond: [Reg(dst)] });
+        let r = self.expr(&b.r, f);
+        f.push(Tac::Assign { dst, src: [r] }).push(Label { label: end });
+        Reg(dst)
+      }
       Binary(b) => {
         let (l, r) = (self.expr(&b.l, f), self.expr(&b.r, f));
-        match b.op {
-          Eq | Ne if b.l.ty.get() == Ty::string() => {
-            f.push(Param { src: [l] }).push(Param { src: [r] });
-            let dst = self.intrinsic(_StringEqual, f).unwrap();
-            if b.op == Ne {
-              f.push(Un { op: Not, dst, r: [Reg(dst)] });
-            }
-            Reg(dst)
-          }
-          op => {
-            let dst = self.reg();
-            f.push(Bin { op, dst, lr: [l, r] });
-            Reg(dst)
-          }
-        }
+        self.binary(b.op, b.l.ty.get(), b.r.ty.get(), l, r, f)
       }
-      This(_) => Reg(0),
+      Ternary(t) => {
+        let (dst, before_else, after_else) = (self.reg(), self.label(), self.label());
+        let cond = self.expr(&t.cond, f);
+        f.push(Jif { label: before_else, z: true, cond: [cond] });
+        let on_true = self.expr(&t.on_true, f);
+        f.push(Tac::Assign { dst, src: [on_true] }).push(Jmp { label: after_else }).push(Label { label: before_else });
+        let on_false = self.expr(&t.on_false, f);
+        f.push(Tac::Assign { dst, src: [on_false] }).push(Label { label: after_else });
+        Reg(dst)
+      }
+      Assign(a) => {
+        // `src` may be the register of a local variable modified later in the expression, so copy it
+        let (src, dst) = (self.assign(a, f), self.reg());
+        f.push(Tac::Assign { dst, src: [src] });
+        Reg(dst)
+      }
+      IncDec(i) => {
+        // like compound assignment, the sub-expressions of `i.e` (if any) are evaluated only once
+        let place = self.place(&i.e, f);
+        let (old, new) = (self.load_place(place, f), self.reg());
+        f.push(Bin { op: i.op, dst: new, lr: [old, Const(1)] });
+        self.store_place(place, Reg(new), f);
+        if i.prefix { Reg(new) } else { old }
+      }
+      This(_) | Super(_) => Reg(0),
       ReadInt(_) => Reg(self.intrinsic(_ReadInt, f).unwrap()),
+      ReadDouble(_) => Reg(self.intrinsic(_ReadDouble, f).unwrap()),
       ReadLine(_) => Reg(self.intrinsic(_ReadLine, f).unwrap()),
       NewClass(n) => {
         let dst = self.reg();
         // by design, a class's new func in functions have the same index as its vtbl in vtbls
         f.push(Tac::Call { dst: Some(dst), kind: CallKind::Static(self.class_info[&Ref(n.class.get().unwrap())].idx, CallHint { arg_obj: false, arg_arr: false }) });
+        let args = n.ctor.get().map(|fu| self.ctor_args(fu, &n.arg, f));
+        // like `super()` in java, the constructor of the parent class is called before the class's own one
+        // the ancestors' constructors are called from the root class down, they are called without arguments (TypePass checks that they exist)
+        let mut chain = Vec::new();
+        let mut c = n.class.get().unwrap().parent_ref.get();
+        while let Some(p) = c { (chain.extend(p.default_ctor()), c = p.parent_ref.get()); }
+        for fu in chain.into_iter().rev() {
+          let args = self.ctor_args(fu, &[], f);
+          self.call_ctor(dst, fu, args, f);
+        }
+        if let (Some(fu), Some(args)) = (n.ctor.get(), args) { self.call_ctor(dst, fu, args, f); }
         Reg(dst)
       }
       NewArray(n) => {
-        let len = self.expr(&n.len, f);
-        let (ok, before_cond, before_body) = (self.label(), self.label(), self.label());
-        let (cmp, ptr) = (self.reg(), self.reg());
-        f.push(Bin { op: Lt, dst: cmp, lr: [len, Const(0)] })
-          .push(Jif { label: ok, z: true, cond: [Reg(cmp)] });
-        self.re(NEW_ARR_NEG, f);
-        f.push(Label { label: ok });
-        let arr = self.intrinsic(_Alloc, f
-          .push(Bin { op: Mul, dst: ptr, lr: [len, Const(INT_SIZE)] })
-          .push(Bin { op: Add, dst: ptr, lr: [Reg(ptr), Const(INT_SIZE)] }) // now ptr = bytes to allocate
-          .push(Param { src: [Reg(ptr)] })).unwrap();
-        f.push(Bin { op: Add, dst: ptr, lr: [Reg(arr), Reg(ptr)] }); // now ptr = end of array
+        // all lengths are evaluated and checked before allocating, so `new int[0][-1]` is also an error
+        let len = iter::once(&*n.len).chain(&n.sub_len).map(|l| self.expr(l, f)).collect::<Vec<_>>();
+        for &l in &len {
+          let (ok, cmp) = (self.label(), self.reg());
+          f.push(Bin { op: Lt, dst: cmp, lr: [l, Const(0)] })
+            .push(Jif { label: ok, z: true, cond: [Reg(cmp)] });
+          self.re(NEW_ARR_NEG, f);
+          f.push(Label { label: ok });
+        }
+        self.new_array(&len, f)
+      }
+      ArrayLit(a) => {
+        let elem = a.iter().map(|e| self.expr(e, f)).collect::<Vec<_>>();
+        let arr = self.intrinsic(_Alloc, f.push(Param { src: [Const((elem.len() as i32 + 1) * INT_SIZE)] })).unwrap();
         f.push(Bin { op: Add, dst: arr, lr: [Reg(arr), Const(INT_SIZE)] }); // now arr = begin of array([0])
-        f.push(Jmp { label: before_cond }) // loop(reversely), set all to 0
-          .push(Label { label: before_body })
-          .push(Bin { op: Sub, dst: ptr, lr: [Reg(ptr), Const(INT_SIZE)] })
-          .push(Store { src_base: [Const(0), Reg(ptr)], off: 0, hint: MemHint::Arr })
-          .push(Label { label: before_cond })
-          .push(Bin { op: Eq, dst: cmp, lr: [Reg(ptr), Reg(arr)] })
-          .push(Jif { label: before_body, z: true, cond: [Reg(cmp)] }); // when ptr == arr, loop end
-        f.push(Store { src_base: [len, Reg(arr)], off: -INT_SIZE, hint: MemHint::Immutable }); // arr[-1] = len
+        f.push(Store { src_base: [Const(elem.len() as i32), Reg(arr)], off: -INT_SIZE, hint: MemHint::Immutable }); // arr[-1] = len
+        for (i, e) in elem.into_iter().enumerate() {
+          f.push(Store { src_base: [e, Reg(arr)], off: i as i32 * INT_SIZE, hint: MemHint::Arr });
+        }
         Reg(arr)
       }
+      // a tuple is packed into a block of words, which is never modified after construction
+      Tuple(t) => {
+        let elem = t.elem.iter().map(|e| self.expr(e, f)).collect::<Vec<_>>();
+        let tuple = self.intrinsic(_Alloc, f.push(Param { src: [Const(elem.len() as i32 * INT_SIZE)] })).unwrap();
+        for (i, e) in elem.into_iter().enumerate() {
+          f.push(Store { src_base: [e, Reg(tuple)], off: i as i32 * INT_SIZE, hint: MemHint::Immutable });
+        }
+        Reg(tuple)
+      }
+      TupleSel(t) => {
+        let (tuple, dst) = (self.expr(&t.tuple, f), self.reg());
+        f.push(Load { dst, base: [tuple], off: t.idx as i32 * INT_SIZE, hint: MemHint::Immutable });
+        Reg(dst)
+      }
       ClassTest(t) => {
         let obj = self.expr(&t.expr, f);
-        self.check_cast(obj, self.class_info[&Ref(t.class.get().unwrap())].idx, f)
+        self.check_cast(obj, t.class.get().unwrap(), false, f)
       }
       ClassCast(t) => {
         let obj = self.expr(&t.expr, f);
-        let check = self.check_cast(obj, self.class_info[&Ref(t.class.get().unwrap())].idx, f);
+        let check = self.check_cast(obj, t.class.get().unwrap(), false, f);
         let (msg, vtbl, ok) = (self.reg(), self.reg(), self.label());
-        f.push(Jif { label: ok, z: false, cond: [check] });
+        // casting null always succeeds, and the error message can't be printed for it since it has no vtbl
+        f.push(Jif { label: ok, z: false, cond: [check] }).push(Jif { label: ok, z: true, cond: [obj] });
         let s = self.define_str(BAD_CAST1); // borrow checker...
         self.intrinsic(_PrintString, f.push(LoadStr { dst: msg, s }).push(Param { src: [Reg(msg)] }));
         self.intrinsic(_PrintString, f.push(Load { dst: vtbl, base: [obj], off: 0, hint: MemHint::Immutable })
@@ -347,18 +708,37 @@ impl<'a> TacGen<'a> {
         f.push(Label { label: ok });
         obj
       }
+      Lambda(l) => self.lambda(l, e.loc, f),
     }
   }
 }
 
 impl<'a> TacGen<'a> {
   // define a string in str pool and return its id, this id can be used in Tac::LoadStr
+  // the arguments not given are the default values of the parameters
+  fn ctor_args(&mut self, fu: &'a FuncDef<'a>, arg: &'a [Expr<'a>], f: &mut TacFunc<'a>) -> Vec<Operand> {
+    arg.iter().chain(fu.param[arg.len()..].iter().map(|v| v.init().unwrap())).map(|a| self.expr(a, f)).collect()
+  }
+
+  // the new object is passed as `this` to the constructor, like calling a method
+  fn call_ctor(&mut self, obj: u32, fu: &'a FuncDef<'a>, args: Vec<Operand>, f: &mut TacFunc<'a>) {
+    let hint = CallHint { arg_obj: true, arg_arr: fu.param.iter().any(|v| v.ty.get().arr > 0) };
+    f.push(Param { src: [Reg(obj)] });
+    for a in args { f.push(Param { src: [a] }); }
+    f.push(Tac::Call { dst: None, kind: CallKind::Static(self.func_info[&Ref(fu)].idx, hint) });
+  }
+
   fn define_str(&mut self, s: &'a str) -> u32 { self.str_pool.insert_full(s).0 as u32 }
 
   fn reg(&mut self) -> u32 { (self.reg_num, self.reg_num += 1).0 }
 
   fn label(&mut self) -> u32 { (self.label_num, self.label_num += 1).0 }
 
+  // the innermost loop with `label`, or the innermost loop if `label` is None, typeck guarantees it exists
+  fn jump_target(&self, label: Option<&'a str>) -> (Option<&'a str>, u32, Option<u32>) {
+    *self.loop_stk.iter().rev().find(|l| label.is_none() || l.0 == label).unwrap()
+  }
+
   // if you don't need to modify the returned register, it is more recommended to use Const(i)
   fn int(&mut self, i: i32, f: &mut TacFunc<'a>) -> u32 {
     let dst = self.reg();
@@ -373,6 +753,58 @@ impl<'a> TacGen<'a> {
     dst
   }
 
+  // `l_ty` and `r_ty` are the types of operands, some operators behave differently on string and double
+  fn binary(&mut self, op: BinOp, l_ty: Ty<'a>, r_ty: Ty<'a>, l: Operand, r: Operand, f: &mut TacFunc<'a>) -> Operand {
+    match op {
+      _ if l_ty == Ty::double() || r_ty == Ty::double() => {
+        // mixed int and double are promoted to double
+        let mut to_double = |ty: Ty<'a>, x: Operand| if ty == Ty::int() {
+          f.push(Param { src: [x] });
+          Reg(self.intrinsic(_IntToDouble, f).unwrap())
+        } else { x };
+        let (l, r) = (to_double(l_ty, l), to_double(r_ty, r));
+        // a > b = b < a, a >= b = b <= a, a != b = !(a == b)
+        let (i, l, r) = match op {
+          Add => (_DoubleAdd, l, r), Sub => (_DoubleSub, l, r), Mul => (_DoubleMul, l, r), Div => (_DoubleDiv, l, r),
+          Eq | Ne => (_DoubleEq, l, r), Lt => (_DoubleLt, l, r), Le => (_DoubleLe, l, r), Gt => (_DoubleLt, r, l), Ge => (_DoubleLe, r, l),
+          _ => unreachable!("Shouldn't meet operator {} on double in these phase, type checking should have reported error.", op.to_op_str()),
+        };
+        f.push(Param { src: [l] }).push(Param { src: [r] });
+        let dst = self.intrinsic(i, f).unwrap();
+        if op == Ne { f.push(Un { op: Not, dst, r: [Reg(dst)] }); }
+        Reg(dst)
+      }
+      Eq | Ne if l_ty == Ty::string() => {
+        f.push(Param { src: [l] }).push(Param { src: [r] });
+        let dst = self.intrinsic(_StringEqual, f).unwrap();
+        if op == Ne {
+          f.push(Un { op: Not, dst, r: [Reg(dst)] });
+        }
+        Reg(dst)
+      }
+      Add if l_ty == Ty::string() => {
+        f.push(Param { src: [l] }).push(Param { src: [r] });
+        Reg(self.intrinsic(_StringConcat, f).unwrap())
+      }
+      Add if l_ty.is_arr() => {
+        let (l_len, r_len) = (self.length(l, f), self.length(r, f));
+        let (n, mid) = (self.reg(), self.reg());
+        f.push(Bin { op: Add, dst: n, lr: [l_len, r_len] });
+        let new = self.alloc_arr(Reg(n), f);
+        f.push(Bin { op: Mul, dst: mid, lr: [l_len, Const(INT_SIZE)] })
+          .push(Bin { op: Add, dst: mid, lr: [Reg(mid), Reg(new)] }); // now mid = &new[l.length()]
+        self.copy_arr(Reg(new), l, l_len, f);
+        self.copy_arr(Reg(mid), r, r_len, f);
+        Reg(new)
+      }
+      op => {
+        let dst = self.reg();
+        f.push(Bin { op, dst, lr: [l, r] });
+        Reg(dst)
+      }
+    }
+  }
+
   // read the length of `arr` (caller should guarantee `arr` is really an array)
   fn length(&mut self, arr: Operand, f: &mut TacFunc<'a>) -> Operand {
     let dst = self.reg();
@@ -381,6 +813,14 @@ impl<'a> TacGen<'a> {
   }
 
   // re is short for for runtime error; this function prints a message and call halt
+  // the string intrinsics don't accept null
+  fn null_str_check(&mut self, s: Operand, f: &mut TacFunc<'a>) {
+    let ok = self.label();
+    f.push(Jif { label: ok, z: false, cond: [s] });
+    self.re(NULL_STR, f);
+    f.push(Label { label: ok });
+  }
+
   fn re(&mut self, msg: &'static str, f: &mut TacFunc<'a>) {
     let src = self.reg();
     let s = self.define_str(msg);
@@ -388,20 +828,200 @@ impl<'a> TacGen<'a> {
     self.intrinsic(_Halt, f);
   }
 
-  fn check_cast(&mut self, obj: Operand, vtbl_idx: u32, f: &mut TacFunc<'a>) -> Operand {
+  // the closure is [function, this, captured variables...], and the function's parameters are (closure, params...)
+  // the function loads the captured variables and `this` from the closure at the beginning, then Reg(0) is `this` as in methods
+  fn lambda(&mut self, l: &'a Lambda<'a>, loc: Loc, f: &mut TacFunc<'a>) -> Operand {
+    let captured = l.captured.borrow();
+    let (reg_num, label_num, loop_stk) = (self.reg_num, self.label_num, std::mem::take(&mut self.loop_stk));
+    for (idx, p) in l.param.iter().enumerate() {
+      self.var_info.insert(Ref(p), VarInfo { off: idx as u32 + 1 });
+    }
+    self.reg_num = l.param.len() as u32 + 1;
+    self.label_num = 0;
+    let mut lf = TacFunc::empty(f.alloc, format!("_lambda.{}.{}", loc.0, loc.1), self.reg_num);
+    // the captured variables use new registers in the lambda function, the old ones are restored after it
+    let old = captured.iter().enumerate().map(|(idx, &v)| {
+      let reg = self.reg();
+      lf.push(Load { dst: reg, base: [Reg(0)], off: (idx as i32 + 2) * INT_SIZE, hint: MemHint::Immutable });
+      self.var_info.insert(Ref(v), VarInfo { off: reg }).unwrap()
+    }).collect::<Vec<_>>();
+    lf.push(Load { dst: 0, base: [Reg(0)], off: INT_SIZE, hint: MemHint::Immutable });
+    let void = l.ret_param_ty.get().unwrap()[0] == Ty::void();
+    match &l.body {
+      LambdaBody::Expr(e) => {
+        let ret = self.expr(e, &mut lf);
+        lf.push(Ret { src: if void { None } else { Some([ret]) } });
+      }
+      LambdaBody::Block(b) => {
+        self.block(b, &mut lf);
+        if void { lf.push(Ret { src: None }); }
+      }
+    }
+    lf.reg_num = self.reg_num;
+    for (&v, old) in captured.iter().zip(old) { self.var_info.insert(Ref(v), old); }
+    (self.reg_num = reg_num, self.label_num = label_num, self.loop_stk = loop_stk);
+    self.lambda.push(lf);
+    let idx = self.func_num + self.lambda.len() as u32 - 1;
+    // build the closure in `f`
+    f.push(Param { src: [Const((captured.len() as i32 + 2) * INT_SIZE)] });
+    let (closure, fp) = (self.intrinsic(_Alloc, f).unwrap(), self.reg());
+    f.push(LoadFunc { dst: fp, f: idx })
+      .push(Store { src_base: [Reg(fp), Reg(closure)], off: 0, hint: MemHint::Immutable })
+      .push(Store { src_base: [if self.cur_static { Const(0) } else { Reg(0) }, Reg(closure)], off: INT_SIZE, hint: MemHint::Immutable });
+    for (idx, &v) in captured.iter().enumerate() {
+      f.push(Store { src_base: [Reg(self.var_info[&Ref(v)].off), Reg(closure)], off: (idx as i32 + 2) * INT_SIZE, hint: MemHint::Immutable });
+    }
+    Reg(closure)
+  }
+
+  // a static method as a function value, the closure is [wrapper, 0], and the wrapper is called like a lambda function
+  // the wrapper's parameters are (closure, params...), it ignores the closure and calls the method with the params
+  fn func_value(&mut self, fu: &'a FuncDef<'a>, f: &mut TacFunc<'a>) -> Operand {
+    let idx = if let Some(&idx) = self.func_value.get(&Ref(fu)) { idx } else {
+      let n = fu.param.len() as u32;
+      let ret = if fu.ret_ty() != Ty::void() { Some(n + 1) } else { None };
+      let mut wf = TacFunc::empty(f.alloc, format!("_value.{}.{}", fu.loc.0, fu.loc.1), n + 1);
+      for i in 1..=n { wf.push(Param { src: [Reg(i)] }); }
+      wf.push(Tac::Call { dst: ret, kind: CallKind::Static(self.func_info[&Ref(fu)].idx, CallHint { arg_obj: true, arg_arr: true }) })
+        .push(Ret { src: ret.map(|r| [Reg(r)]) });
+      wf.reg_num = n + 2;
+      self.lambda.push(wf);
+      let idx = self.func_num + self.lambda.len() as u32 - 1;
+      self.func_value.insert(Ref(fu), idx);
+      idx
+    };
+    f.push(Param { src: [Const(2 * INT_SIZE)] });
+    let (closure, fp) = (self.intrinsic(_Alloc, f).unwrap(), self.reg());
+    f.push(LoadFunc { dst: fp, f: idx })
+      .push(Store { src_base: [Reg(fp), Reg(closure)], off: 0, hint: MemHint::Immutable })
+      .push(Store { src_base: [Const(0), Reg(closure)], off: INT_SIZE, hint: MemHint::Immutable });
+    Reg(closure)
+  }
+
+  // `ret` is the return type of the function, the closure itself is passed as the first argument
+  fn call_closure(&mut self, closure: Operand, arg: &'a [Expr<'a>], ret: Ty<'a>, f: &mut TacFunc<'a>) -> Operand {
+    let ret = if ret != Ty::void() { Some(self.reg()) } else { None };
+    let args = arg.iter().map(|a| self.expr(a, f)).collect::<Vec<_>>();
+    f.push(Param { src: [closure] });
+    for a in args { f.push(Param { src: [a] }); }
+    let fp = self.reg();
+    f.push(Load { dst: fp, base: [closure], off: 0, hint: MemHint::Immutable });
+    // the function is unknown, so it may change any object or array
+    f.push(Tac::Call { dst: ret, kind: CallKind::Virtual([Reg(fp)], CallHint { arg_obj: true, arg_arr: true }) });
+    Reg(ret.unwrap_or(0))
+  }
+
+  // allocate an array of length `len[0]`, and if `len.len() > 1`, allocate its elements with lengths `len[1..]` recursively
+  // the lengths should have been checked to be non-negative
+  fn new_array(&mut self, len: &[Operand], f: &mut TacFunc<'a>) -> Operand {
+    let (len, sub_len) = (len[0], &len[1..]);
+    let (before_cond, before_body) = (self.label(), self.label());
+    let (cmp, ptr) = (self.reg(), self.reg());
+    let arr = self.intrinsic(_Alloc, f
+      .push(Bin { op: Mul, dst: ptr, lr: [len, Const(INT_SIZE)] })
+      .push(Bin { op: Add, dst: ptr, lr: [Reg(ptr), Const(INT_SIZE)] }) // now ptr = bytes to allocate
+      .push(Param { src: [Reg(ptr)] })).unwrap();
+    f.push(Bin { op: Add, dst: ptr, lr: [Reg(arr), Reg(ptr)] }); // now ptr = end of array
+    f.push(Bin { op: Add, dst: arr, lr: [Reg(arr), Const(INT_SIZE)] }); // now arr = begin of array([0])
+    f.push(Jmp { label: before_cond }) // loop(reversely), set all to 0
+      .push(Label { label: before_body })
+      .push(Bin { op: Sub, dst: ptr, lr: [Reg(ptr), Const(INT_SIZE)] })
+      .push(Store { src_base: [Const(0), Reg(ptr)], off: 0, hint: MemHint::Arr })
+      .push(Label { label: before_cond })
+      .push(Bin { op: Eq, dst: cmp, lr: [Reg(ptr), Reg(arr)] })
+      .push(Jif { label: before_body, z: true, cond: [Reg(cmp)] }); // when ptr == arr, loop end
+    f.push(Store { src_base: [len, Reg(arr)], off: -INT_SIZE, hint: MemHint::Immutable }); // arr[-1] = len
+    if !sub_len.is_empty() {
+      // for (i = 0; i < len; i++) arr[i] = new_array(sub_len)
+      let (i, addr, before_cond, after_body) = (self.int(0, f), self.reg(), self.label(), self.label());
+      f.push(Label { label: before_cond })
+        .push(Bin { op: Lt, dst: cmp, lr: [Reg(i), len] })
+        .push(Jif { label: after_body, z: true, cond: [Reg(cmp)] });
+      let elem = self.new_array(sub_len, f);
+      f.push(Bin { op: Mul, dst: addr, lr: [Reg(i), Const(INT_SIZE)] })
+        .push(Bin { op: Add, dst: addr, lr: [Reg(arr), Reg(addr)] })
+        .push(Store { src_base: [elem, Reg(addr)], off: 0, hint: MemHint::Arr })
+        .push(Bin { op: Add, dst: i, lr: [Reg(i), Const(1)] })
+        .push(Jmp { label: before_cond })
+        .push(Label { label: after_body });
+    }
+    Reg(arr)
+  }
+
+  // allocate an array of `len` elements without initializing them, `len` should be non-negative
+  fn alloc_arr(&mut self, len: Operand, f: &mut TacFunc<'a>) -> u32 {
+    let size = self.reg();
+    let arr = self.intrinsic(_Alloc, f
+      .push(Bin { op: Mul, dst: size, lr: [len, Const(INT_SIZE)] })
+      .push(Bin { op: Add, dst: size, lr: [Reg(size), Const(INT_SIZE)] }) // now size = bytes to allocate
+      .push(Param { src: [Reg(size)] })).unwrap();
+    f.push(Bin { op: Add, dst: arr, lr: [Reg(arr), Const(INT_SIZE)] }) // now arr = begin of array([0])
+      .push(Store { src_base: [len, Reg(arr)], off: -INT_SIZE, hint: MemHint::Immutable }); // arr[-1] = len
+    arr
+  }
+
+  // copy `len` elements from address `src` to address `dst`, the operands themselves are not modified
+  fn copy_arr(&mut self, dst: Operand, src: Operand, len: Operand, f: &mut TacFunc<'a>) {
+    let (d, s, end, val) = (self.reg(), self.reg(), self.reg(), self.reg());
+    let (before_cond, after_body) = (self.label(), self.label());
+    f.push(Tac::Assign { dst: d, src: [dst] })
+      .push(Tac::Assign { dst: s, src: [src] })
+      .push(Bin { op: Mul, dst: end, lr: [len, Const(INT_SIZE)] })
+      .push(Bin { op: Add, dst: end, lr: [Reg(end), dst] }) // now end = end of `dst`
+      .push(Label { label: before_cond })
+      .push(Bin { op: Eq, dst: val, lr: [Reg(d), Reg(end)] })
+      .push(Jif { label: after_body, z: false, cond: [Reg(val)] }) // when d == end, loop end
+      .push(Load { dst: val, base: [Reg(s)], off: 0, hint: MemHint::Arr })
+      .push(Store { src_base: [Reg(val), Reg(d)], off: 0, hint: MemHint::Arr })
+      .push(Bin { op: Add, dst: d, lr: [Reg(d), Const(INT_SIZE)] })
+      .push(Bin { op: Add, dst: s, lr: [Reg(s), Const(INT_SIZE)] })
+      .push(Jmp { label: before_cond })
+      .push(Label { label: after_body });
+  }
+
+  // load the address of method `fu` from `obj`'s vtbl
+  // an interface method also has a fixed offset in all the implementing classes, see interface_slot
+  fn method_slot(&mut self, obj: Operand, fu: &'a FuncDef<'a>, f: &mut TacFunc<'a>) -> u32 {
+    let (slot, vtbl) = (self.reg(), self.reg());
+    f.push(Load { dst: vtbl, base: [obj], off: 0, hint: MemHint::Immutable })
+      .push(Load { dst: slot, base: [Reg(vtbl)], off: self.func_info[&Ref(fu)].off as i32 * INT_SIZE, hint: MemHint::Immutable });
+    slot
+  }
+
+  // computes `obj instanceof target`, or `!(obj instanceof target)` if `neg` is true
+  fn check_cast(&mut self, obj: Operand, target: &'a ClassDef<'a>, neg: bool, f: &mut TacFunc<'a>) -> Operand {
+    if target.interface {
+      // `obj` is an instance of an interface iff its class is one of the implementing classes
+      let (ret, vtbl, cmp, after) = (self.int(0, f), self.reg(), self.reg(), self.label());
+      f.push(Jif { label: after, z: true, cond: [obj] }) // null is not an instance of any interface
+        .push(Load { dst: vtbl, base: [obj], off: 0, hint: MemHint::Immutable });
+      for c in self.implementors[&Ref(target)].clone() {
+        f.push(LoadVTbl { dst: cmp, v: self.class_info[&Ref(c)].idx })
+          .push(Bin { op: Eq, dst: cmp, lr: [Reg(vtbl), Reg(cmp)] })
+          .push(Bin { op: Or, dst: ret, lr: [Reg(ret), Reg(cmp)] });
+      }
+      f.push(Label { label: after });
+      if neg { f.push(Un { op: Not, dst: ret, r: [Reg(ret)] }); }
+      return Reg(ret);
+    }
     // ret = 0
-    // while (cur)
-    //   ret = (cur == target)
-    //   if ret = 1
-    //     break
-    //   cur = cur->parent
-    let (ret, cur, target) = (self.int(0, f), self.reg(), self.reg());
+    // if (obj)
+    //   cur = obj->vtbl
+    //   while (cur)
+    //     ret = (cur == target)
+    //     if ret = 1
+    //       break
+    //     cur = cur->parent
+    // (if `neg`, ret starts from 1, and `==` is replaced by `!=`)
+    let vtbl_idx = self.class_info[&Ref(target)].idx;
+    let (ret, cur, target) = (self.int(neg as i32, f), self.reg(), self.reg());
     let (before_cond, after_body) = (self.label(), self.label());
+    f.push(Jif { label: after_body, z: true, cond: [obj] }); // null is not an instance of any class
     f.push(LoadVTbl { dst: target, v: vtbl_idx });
     f.push(Load { dst: cur, base: [obj], off: 0, hint: MemHint::Immutable });
     f.push(Label { label: before_cond });
     f.push(Jif { label: after_body, z: true, cond: [Reg(cur)] });
-    f.push(Bin { op: Eq, dst: ret, lr: [Reg(cur), Reg(target)] }).push(Jif { label: after_body, z: false, cond: [Reg(ret)] });
+    f.push(Bin { op: if neg { Ne } else { Eq }, dst: ret, lr: [Reg(cur), Reg(target)] }).push(Jif { label: after_body, z: neg, cond: [Reg(ret)] });
     f.push(Load { dst: cur, base: [Reg(cur)], off: 0, hint: MemHint::Immutable });
     f.push(Jmp { label: before_cond });
     f.push(Label { label: after_body });
@@ -422,26 +1042,48 @@ impl<'a> TacGen<'a> {
       } else { (1, IndexMap::default()) };
       for f in &c.field {
         match f {
-          FieldDef::FuncDef(f) => if !f.static_ {
-            if let Some((idx, _, p_f)) = vtbl.get_full_mut(f.name) {
+          // constructors are always called statically, so they are not in vtbl
+          FieldDef::FuncDef(f) => if !f.static_ && !f.ctor {
+            if let Some((idx, _, p_f)) = vtbl.get_full_mut(&(f.name, f.param_ty())) {
               // + 2, because 0 is parent vtbl, 1 is class name
               self.func_info.insert(Ref(f), FuncInfo { off: idx as u32 + 2, idx: 0 });
               *p_f = f; // override
             } else {
               self.func_info.insert(Ref(f), FuncInfo { off: vtbl.len() as u32 + 2, idx: 0 });
-              vtbl.insert(f.name, f);
+              vtbl.insert((f.name, f.param_ty()), f);
             }
           } else {
-            // `off` is useless for static functions
+            // `off` is useless for static functions and constructors
             self.func_info.insert(Ref(f), FuncInfo { off: 0, idx: 0 });
           }
-          FieldDef::VarDef(v) => {
+          FieldDef::VarDef(v) => if v.static_ {
+            self.var_info.insert(Ref(v), VarInfo { off: self.static_var.len() as u32 });
+            self.static_var.push(format!("_STATIC.{}.{}", c.name, v.name));
+          } else {
             self.var_info.insert(Ref(v), VarInfo { off: field_num });
             field_num += 1;
           }
         }
       }
-      self.class_info.insert(Ref(c), ClassInfo { field_num, idx: 0, vtbl });
+      self.class_info.insert(Ref(c), ClassInfo { field_num, idx: 0, vtbl, islot: vec![] });
+    }
+  }
+
+  // give each interface a range of slots after all the slots of its implementing classes, so its methods have fixed offsets in all of them
+  // `off` of an interface method is changed to the offset in this range, and the implementing methods are copied into the classes' `islot`
+  // the ranges are allocated in the order of the interfaces, so a class may have holes before a range
+  fn interface_slot(&mut self, p: &'a Program<'a>) {
+    for &i in p.class.iter().filter(|i| i.interface) {
+      let imp = self.implementors[&Ref(i)].clone();
+      let methods = self.class_info[&Ref(i)].vtbl.values().copied().collect::<Vec<_>>();
+      // + 2, because 0 is parent vtbl, 1 is class name
+      let base = imp.iter().map(|c| { let ci = &self.class_info[&Ref(*c)]; ci.vtbl.len() + ci.islot.len() }).max().unwrap_or(0) as u32 + 2;
+      for (k, &fu) in methods.iter().enumerate() { self.func_info.get_mut(&Ref(fu)).unwrap().off = base + k as u32; }
+      for c in imp {
+        let ci = self.class_info.get_mut(&Ref(c)).unwrap();
+        ci.islot.resize(base as usize - 2 - ci.vtbl.len(), None);
+        for &fu in &methods { ci.islot.push(Some(ci.vtbl[&(fu.name, fu.param_ty())])); }
+      }
     }
   }
 
@@ -1,5 +1,106 @@
-use crate::bb::FuncBB;
+use crate::{bb::{FuncBB, BB, Cfg}, flow::{FlowElem, Flow, Or}};
+use tac::{Tac, Operand};
+use common::BinOp;
+use bitset::traits::*;
 
-pub fn work(_f: &mut FuncBB) {
-  unimplemented!()
-}
\ No newline at end of file
+// dead code elimination based on aliveness analysis
+// the tac writing a register which is not alive after it is deleted, unless it has side effect
+// a call's result can be discarded, but the call itself is always kept, and so is a division which may trap
+pub fn work(f: &mut FuncBB) {
+  let LivenessResult { each, mut live_out, .. } = liveness(f);
+  for (idx, b) in f.bb.iter_mut().enumerate() {
+    let off = idx * each;
+    do_optimize(b, &mut live_out[off..off + each]);
+  }
+}
+
+// the registers alive at the beginning and the end of each bb, as bitsets indexed by register id
+pub struct LivenessResult {
+  each: usize,
+  live_in: Vec<u32>,
+  live_out: Vec<u32>,
+}
+
+impl LivenessResult {
+  pub fn live_in(&self, b: u32) -> &[u32] { &self.live_in[b as usize * self.each..(b as usize + 1) * self.each] }
+
+  pub fn live_out(&self, b: u32) -> &[u32] { &self.live_out[b as usize * self.each..(b as usize + 1) * self.each] }
+
+  pub fn is_live_in(&self, b: u32, r: u32) -> bool { self.live_in(b).bsget(r) }
+
+  pub fn is_live_out(&self, b: u32, r: u32) -> bool { self.live_out(b).bsget(r) }
+
+  // the registers alive right after the `n`-th tac in `b`, `n >= b.len - 1` means the point before `b.next`
+  // it is computed by going backward from the end of `b`, `cfg` should be the one this result is computed from
+  pub fn live_after(&self, cfg: &Cfg, b: u32, n: u32) -> Vec<u32> {
+    let (bb, mut live) = (&cfg.bb[b as usize], self.live_out(b).to_vec());
+    if let Some(r) = bb.next_r() { live.bsset(r); }
+    for t in bb.iter().rev().take(bb.len.saturating_sub(n + 1) as usize) {
+      let tac = t.tac.get();
+      let (r, w) = tac.rw();
+      if let Some(w) = w { live.bsdel(w); }
+      for r in r { if let Operand::Reg(r) = *r { live.bsset(r); } }
+    }
+    live
+  }
+}
+
+pub fn liveness(cfg: &Cfg) -> LivenessResult {
+  // aliveness is a backward flow, see the comment of `Flow` for how it is mapped to a forward flow
+  let mut aliveness_flow = Flow::<Or>::new(cfg.bb.len(), cfg.reg_num as usize);
+  let each = aliveness_flow.each();
+  let FlowElem { gen: use_, kill: def, .. } = aliveness_flow.split();
+  for (idx, b) in cfg.bb.iter().enumerate() {
+    let off = idx * each;
+    compute_use_def(b, &mut use_[off..off + each], &mut def[off..off + each]);
+  }
+  aliveness_flow.solve(cfg.bb.iter().enumerate().map(|b| (b.0, b.1.next().iter().filter_map(|n| n.map(|n| n as usize)).collect::<Vec<_>>())));
+  let FlowElem { in_, out, .. } = aliveness_flow.split();
+  LivenessResult { each, live_in: out.to_vec(), live_out: in_.to_vec() }
+}
+
+fn compute_use_def(b: &BB, use_: &mut [u32], def: &mut [u32]) {
+  if let Some(r) = b.next_r() { use_.bsset(r); }
+  for t in b.iter().rev() {
+    let tac = t.tac.get();
+    let (r, w) = tac.rw();
+    if let Some(w) = w {
+      def.bsset(w);
+      use_.bsdel(w);
+    }
+    for r in r {
+      if let Operand::Reg(r) = *r {
+        use_.bsset(r);
+        def.bsdel(r);
+      }
+    }
+  }
+}
+
+// `live` is the registers alive at the end of `b`
+fn do_optimize(b: &mut BB, live: &mut [u32]) {
+  if let Some(r) = b.next_r() { live.bsset(r); }
+  for t in b.iter().rev() {
+    let mut tac = t.tac.get();
+    match tac.rw().1 {
+      Some(w) if !live.bsget(w) => match &mut tac {
+        Tac::Call { dst, .. } => {
+          *dst = None;
+          t.tac.set(tac);
+        }
+        // div 0 or mod 0 traps at runtime, so the tac is kept unless the divisor is a non-zero constant
+        Tac::Bin { op: BinOp::Div, lr: [_, r], .. } | Tac::Bin { op: BinOp::Mod, lr: [_, r], .. }
+          if match *r { Operand::Const(c) => c == 0, Operand::Reg(_) => true } => {}
+        _ => {
+          b.del(t);
+          continue;
+        }
+      }
+      Some(w) => live.bsdel(w),
+      None => {}
+    }
+    for r in tac.rw().0 {
+      if let Operand::Reg(r) = *r { live.bsset(r); }
+    }
+  }
+}
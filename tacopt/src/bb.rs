@@ -1,6 +1,7 @@
 use tac::{TacNode, Tac, TacFunc, Operand, TacIter, CallKind, Intrinsic::_Halt};
 use common::Ref;
 use typed_arena::Arena;
+use crate::OptCfg;
 
 pub struct BB<'a> {
   pub len: u32,
@@ -197,17 +198,41 @@ impl<'a> FuncBB<'a> {
     FuncBB { param_num: f.param_num, reg_num: f.reg_num, alloc: f.alloc, bb: simplify(bb), name: f.name.clone() }
   }
 
-  pub fn optimize(&mut self) {
-    crate::common_expr::work(self);
-    crate::copy_prop::work(self);
-    crate::const_prop::work(self);
+  pub fn optimize(&mut self, cfg: OptCfg) {
+    if cfg.common_expr { crate::common_expr::work(self); }
+    if cfg.copy_prop { crate::copy_prop::work(self); }
+    if cfg.const_prop { crate::const_prop::work(self); }
     crate::aliveness::work(self);
   }
 
-  pub fn optimizen(&mut self, n: u32) {
-    for _ in 0..n { self.optimize(); }
+  pub fn optimizen(&mut self, n: u32, cfg: OptCfg) {
+    for _ in 0..n { self.optimize(cfg); }
   }
 
+  // the entry is always the first bb, `simplify` keeps it at index 0
+  pub fn entry(&self) -> u32 { 0 }
+
+  // the bbs are indexed by `0..block_num()`, the indices are what `succ`, `pred` and `NextKind` use
+  pub fn block_num(&self) -> u32 { self.bb.len() as u32 }
+
+  pub fn block(&self, idx: u32) -> &BB<'a> { &self.bb[idx as usize] }
+
+  // yielding every bb together with its index, in index order
+  pub fn blocks<'b>(&'b self) -> impl Iterator<Item=(u32, &'b BB<'a>)> + 'b {
+    self.bb.iter().enumerate().map(|(idx, b)| (idx as u32, b))
+  }
+
+  // the bbs ending with a return or a halt
+  pub fn exits(&self) -> Vec<u32> {
+    self.blocks().filter(|(_, b)| b.next().iter().all(Option::is_none)).map(|(idx, _)| idx).collect()
+  }
+
+  pub fn succ(&self, idx: u32) -> impl Iterator<Item=u32> { self.bb[idx as usize].next().to_vec().into_iter().flatten() }
+
+  pub fn pred(&self, idx: u32) -> &[u32] { &self.bb[idx as usize].prev }
+
+  pub fn has_edge(&self, from: u32, to: u32) -> bool { self.bb[from as usize].next().contains(&Some(to)) }
+
   pub fn new_reg(&mut self) -> u32 {
     (self.reg_num, self.reg_num += 1).0
   }
@@ -234,6 +259,12 @@ impl<'a> FuncBB<'a> {
   }
 }
 
+// `FuncBB` is the control flow graph of a function, this name is for those who write their own analyses on it
+pub type Cfg<'a> = FuncBB<'a>;
+
+// the unreachable bbs are removed, so every bb in the result is reachable from `entry()`
+pub fn build_cfg<'a>(func: &TacFunc<'a>) -> Cfg<'a> { FuncBB::new(func) }
+
 // `simplify` will remove all unreachable nodes, and set each node's `prev` to the proper value
 // the old value of each node's `prev` is not used here
 // it is possible to trigger `index out of bounds` here (if constraint is violated), see the comment in `FuncBB::new`
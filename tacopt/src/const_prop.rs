@@ -25,7 +25,8 @@ fn transfer(tac: Tac, env: &mut [Value]) {
         [Reg(l), Reg(r)] => (env[l as usize], env[r as usize]),
       };
       env[dst as usize] = match lr {
-        (C(l), C(r)) => C(op.eval(l, r)),
+        // div 0 or mod 0 is not folded below, so its result is not a constant
+        (C(l), C(r)) => op.try_eval(l, r).map_or(Nac, C),
         (Nac, _) | (_, Nac) => Nac,
         _ => Unk, // neither is Nac and not both Const => Unk
       };
@@ -39,7 +40,7 @@ fn transfer(tac: Tac, env: &mut [Value]) {
     Load { dst, .. } => env[dst as usize] = Nac,
     // actually LoadStr and LoadVTbl won't give `dst` a Unk
     // but as long as the implementation is correct, `dst` can never be used in calculation, so giving them Unk is okay
-    LoadStr { dst, .. } | LoadVTbl { dst, .. } | LoadFunc { dst, .. } => env[dst as usize] = Unk,
+    LoadStr { dst, .. } | LoadVTbl { dst, .. } | LoadFunc { dst, .. } | LoadStatic { dst, .. } | StackAlloc { dst, .. } => env[dst as usize] = Unk,
     Param { .. } | Ret { .. } | Jmp { .. } | Label { .. } | Jif { .. } | Store { .. } => {}
   }
 }
@@ -76,6 +77,14 @@ pub fn work(f: &mut FuncBB) {
           if let Value::Const(r1) = env[r1 as usize] { *r = Operand::Const(r1); }
         }
       }
+      // fold the tac whose operands all become constants, e.g.: `b = 2 + 3` => `b = 5`
+      // div 0 or mod 0 is kept, it is left to runtime
+      tac = match tac {
+        Tac::Bin { op, dst, lr: [Operand::Const(l), Operand::Const(r)] } =>
+          op.try_eval(l, r).map(|v| Tac::Assign { dst, src: [Operand::Const(v)] }).unwrap_or(tac),
+        Tac::Un { op, dst, r: [Operand::Const(r)] } => Tac::Assign { dst, src: [Operand::Const(op.eval(r))] },
+        tac => tac,
+      };
       transfer(t.tac.get(), env); // old value
       t.tac.set(tac);
     }
@@ -0,0 +1,70 @@
+use crate::bb::Cfg;
+
+// the dominator tree, computed by the iterative algorithm in "A Simple, Fast Dominance Algorithm" (Cooper, Harvey & Kennedy)
+// `simplify` has removed the unreachable bbs, but the bbs not reachable from the entry are still handled: they have no idom
+pub struct DomTree {
+  // idom[entry] == entry, idom[x] == !0 if x is unreachable
+  idom: Vec<u32>,
+  // children[x] = the bbs immediately dominated by x, in increasing order
+  children: Vec<Vec<u32>>,
+}
+
+impl DomTree {
+  // the immediate dominator of `b`, None for the entry and unreachable bbs
+  pub fn idom(&self, b: u32) -> Option<u32> {
+    match self.idom[b as usize] { x if x == !0 || x == b => None, x => Some(x) }
+  }
+
+  // whether `a` dominates `b`, every reachable bb dominates itself
+  pub fn dominates(&self, a: u32, mut b: u32) -> bool {
+    if self.idom[b as usize] == !0 { return false; }
+    loop {
+      if a == b { return true; }
+      match self.idom(b) { Some(x) => b = x, None => return false }
+    }
+  }
+
+  // the bbs immediately dominated by `b`
+  pub fn children(&self, b: u32) -> &[u32] { &self.children[b as usize] }
+}
+
+pub fn dominators(cfg: &Cfg) -> DomTree {
+  let n = cfg.bb.len();
+  // post order of the bbs reachable from the entry
+  fn dfs(x: u32, cfg: &Cfg, vis: &mut [bool], order: &mut Vec<u32>) {
+    vis[x as usize] = true;
+    for y in cfg.succ(x) { if !vis[y as usize] { dfs(y, cfg, vis, order); } }
+    order.push(x);
+  }
+  let (mut vis, mut order) = (vec![false; n], Vec::with_capacity(n));
+  if n != 0 { dfs(cfg.entry(), cfg, &mut vis, &mut order); }
+  let mut po = vec![!0; n]; // po[bb id] = index in `order`
+  for (idx, &x) in order.iter().enumerate() { po[x as usize] = idx as u32; }
+  let mut idom = vec![!0; n];
+  if n != 0 { idom[cfg.entry() as usize] = cfg.entry(); }
+  let intersect = |idom: &[u32], mut x: u32, mut y: u32| {
+    while x != y {
+      while po[x as usize] < po[y as usize] { x = idom[x as usize]; }
+      while po[y as usize] < po[x as usize] { y = idom[y as usize]; }
+    }
+    x
+  };
+  let mut changed = true;
+  while changed {
+    changed = false;
+    // reverse post order, skipping the entry
+    for &x in order.iter().rev().skip(1) {
+      let new = cfg.pred(x).iter().copied().filter(|&p| idom[p as usize] != !0)
+        .fold(!0, |acc, p| if acc == !0 { p } else { intersect(&idom, acc, p) });
+      if idom[x as usize] != new {
+        idom[x as usize] = new;
+        changed = true;
+      }
+    }
+  }
+  let mut dom = DomTree { idom, children: vec![vec![]; n] };
+  for x in 0..n as u32 {
+    if let Some(p) = dom.idom(x) { dom.children[p as usize].push(x); }
+  }
+  dom
+}
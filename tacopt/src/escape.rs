@@ -0,0 +1,154 @@
+use crate::{bb::{FuncBB, NextKind}, inline::iter};
+use tac::{TacProgram, TacNode, Tac, CallKind, Operand, MemHint, Intrinsic::_Alloc};
+use common::{HashMap, HashSet, BinOp};
+
+// what `work` needs to know about the other functions, computed by `info` before any function is changed
+pub struct Info {
+  // (size, vtbl) of the `_new` functions generated by tacgen, None for other functions
+  new: Vec<Option<(i32, u32)>>,
+  // param[f][i] = whether the i-th param of function `f` may escape `f`
+  param: Vec<Vec<bool>>,
+  // the functions in each vtbl, vtbl[v][i] is at offset (i + 2) * 4, see tacgen::info::FuncInfo
+  vtbl: Vec<Vec<u32>>,
+}
+
+// a `_new` function is `parm size; %0 = call _Alloc; %1 = VTBL<..>; *(%0 + 0) = %1; *(%0 + 4) = 0; ...; return %0`
+// a param escapes a function if `escape` says so on that function's tac, this is computed as the least fixed point
+// starting from no param escaping, so a (mutually) recursive function passing its `this` to itself is still handled
+pub fn info(p: &TacProgram) -> Info {
+  let new = p.func.iter().map(|f| if f.name.ends_with("._new") {
+    let (mut size, mut vtbl) = (None, None);
+    for t in iter(f) {
+      match t.tac.get() {
+        Tac::Param { src: [Operand::Const(s)] } => size = Some(s),
+        Tac::LoadVTbl { v, .. } => vtbl = Some(v),
+        _ => {}
+      }
+    }
+    size.zip(vtbl)
+  } else { None }).collect();
+  let vtbl = p.vtbl.iter().map(|v| v.func.clone()).collect();
+  let param = p.func.iter().map(|f| vec![false; f.param_num as usize]).collect();
+  let mut info = Info { new, param, vtbl };
+  let tacs = p.func.iter().map(|f| iter(f).map(|t| t.tac.get()).collect::<Vec<_>>()).collect::<Vec<_>>();
+  let mut changed = true;
+  while changed {
+    changed = false;
+    for (f, ts) in tacs.iter().enumerate() {
+      for i in 0..info.param[f].len() {
+        // the dynamic type of a param is unknown, so the virtual calls on it are not resolved
+        if !info.param[f][i] && escape(ts, i as u32, None, &info) {
+          info.param[f][i] = true;
+          changed = true;
+        }
+      }
+    }
+  }
+  info
+}
+
+// allocate the objects which never escape this function in the stack frame, `info` is the result of `info`
+// an allocation is either a `_Alloc` with a constant size or a call to a `_new` function (which is not inlined)
+// the allocation must not be in a loop, otherwise the objects from different iterations would share one place in the stack frame
+// it should be applied after other optimizations, and the result can only be used in mips and llvm codegen, see `Tac::StackAlloc`
+pub fn work(f: &mut FuncBB, info: &Info) {
+  let mut site = Vec::new(); // (bb id, the call, the object, size, vtbl)
+  for (idx, b) in f.bb.iter().enumerate() {
+    for t in b.iter() {
+      match t.tac.get() {
+        Tac::Call { dst: Some(dst), kind: CallKind::Intrinsic(_Alloc) } => if let Some(p) = t.prev.get() {
+          if let Tac::Param { src: [Operand::Const(size)] } = p.tac.get() { site.push((idx, t, dst, size, None)); }
+        }
+        Tac::Call { dst: Some(dst), kind: CallKind::Static(callee, _) } => if let Some((size, vtbl)) = info.new[callee as usize] {
+          site.push((idx, t, dst, size, Some(vtbl)));
+        }
+        _ => {}
+      }
+    }
+  }
+  // the ret of each bb is a tac in `escape`'s view
+  let tacs = f.bb.iter().flat_map(|b| b.iter().map(|t| t.tac.get())
+    .chain(if let NextKind::Ret(src) = b.next { Some(Tac::Ret { src: src.map(|src| [src]) }) } else { None })).collect::<Vec<_>>();
+  for (idx, t, dst, size, vtbl) in site {
+    if in_loop(f, idx as u32) || escape(&tacs, dst, vtbl, info) { continue; }
+    t.tac.set(Tac::StackAlloc { dst, size });
+    match vtbl {
+      // the `_Alloc`'s param is no longer needed
+      None => f.bb[idx].del(t.prev.get().unwrap()),
+      // the fields are already cleared by `StackAlloc`, only the vtbl is stored
+      Some(v) => {
+        let (tmp, alloc) = (f.new_reg(), f.alloc);
+        let node = |t: Tac| &*alloc.alloc(TacNode { tac: t.into(), prev: None.into(), next: None.into() });
+        let (load, store) = (node(Tac::LoadVTbl { dst: tmp, v }), node(Tac::Store { src_base: [Operand::Reg(tmp), Operand::Reg(dst)], off: 0, hint: MemHint::Immutable }));
+        f.bb[idx].insert_after(t, load);
+        f.bb[idx].insert_after(load, store);
+      }
+    }
+  }
+}
+
+// whether `b` can reach itself
+fn in_loop(f: &FuncBB, b: u32) -> bool {
+  let (mut vis, mut stack) = (vec![false; f.bb.len()], f.succ(b).collect::<Vec<_>>());
+  while let Some(x) = stack.pop() {
+    if x == b { return true; }
+    if !vis[x as usize] {
+      vis[x as usize] = true;
+      stack.extend(f.succ(x));
+    }
+  }
+  false
+}
+
+// whether the object in register `obj` may escape the function whose tac is `ts`, `vtbl` is the object's vtbl if it is known
+// the analysis is flow-insensitive and conservative, the registers holding the object or copied from such registers escape if they are:
+// - passed to a call, unless the callee is known and that param doesn't escape the callee
+// - returned, or stored into memory
+// - used in any computation other than `==` and `!=`
+// the fields of the object can still be loaded and stored, since the base of a Load/Store doesn't escape
+// the callee of a virtual call is known if the vtbl is loaded from the object and `vtbl` is known
+fn escape(ts: &[Tac], obj: u32, vtbl: Option<u32>, info: &Info) -> bool {
+  let mut alias = HashSet::new();
+  alias.insert(obj);
+  // propagate the copies until no new alias is found
+  loop {
+    let n = alias.len();
+    for t in ts {
+      if let Tac::Assign { dst, src: [Operand::Reg(src)] } = *t {
+        if alias.contains(&src) { alias.insert(dst); }
+      }
+    }
+    if alias.len() == n { break; }
+  }
+  // def[r] = the only tac writing r, None if there are more than one
+  let mut def = HashMap::new();
+  for t in ts {
+    if let Some(w) = t.rw().1 { def.entry(w).and_modify(|d| *d = None).or_insert(Some(*t)); }
+  }
+  let callee = |kind: CallKind| match kind {
+    CallKind::Static(f, _) => Some(f),
+    CallKind::Virtual([Operand::Reg(slot)], _) => match def.get(&slot) {
+      Some(Some(Tac::Load { base: [Operand::Reg(v)], off, .. })) => match def.get(v) {
+        Some(Some(Tac::Load { base: [Operand::Reg(o)], off: 0, .. })) if alias.contains(o) =>
+          vtbl.and_then(|v| info.vtbl[v as usize].get((*off / 4 - 2) as usize).copied()),
+        _ => None,
+      }
+      _ => None,
+    }
+    _ => None,
+  };
+  let is = |o: &Operand| if let Operand::Reg(r) = o { alias.contains(r) } else { false };
+  let mut arg = Vec::new(); // whether each param before the current call is an alias
+  ts.iter().any(|t| match *t {
+    Tac::Assign { .. } | Tac::Load { .. } | Tac::Bin { op: BinOp::Eq, .. } | Tac::Bin { op: BinOp::Ne, .. } => false,
+    Tac::Param { src } => (arg.push(is(&src[0])), false).1,
+    Tac::Call { kind, .. } => {
+      let param = callee(kind).map(|f| &info.param[f as usize]);
+      let esc = arg.iter().enumerate().any(|(i, &a)| a && param.is_none_or(|p| p.get(i).copied().unwrap_or(true)));
+      arg.clear();
+      esc
+    }
+    Tac::Store { src_base, .. } => is(&src_base[0]),
+    t => t.rw().0.iter().any(&is),
+  })
+}
@@ -0,0 +1,81 @@
+use tac::{TacProgram, TacFunc, TacIter, Tac, CallKind, Operand};
+
+// a function that can be inlined, with its tac copied out
+struct Inline {
+  param_num: u32,
+  reg_num: u32,
+  label_num: u32,
+  body: Vec<Tac>,
+}
+
+// TacFunc doesn't store the number of tac, so it is counted first, `collect` trusts the exact length given to TacIter
+pub(crate) fn iter<'a>(f: &TacFunc<'a>) -> TacIter<'a> {
+  let (mut len, mut t) = (0, f.first);
+  while let Some(t1) = t {
+    len += 1;
+    t = t1.next.get();
+  }
+  TacIter::new(f.first, f.last, len)
+}
+
+// label id in a function starts from 0, so the max label id + 1 is the number of labels
+pub(crate) fn label_num(f: &TacFunc) -> u32 {
+  iter(f).filter_map(|t| match t.tac.get() { Tac::Jmp { label } | Tac::Jif { label, .. } | Tac::Label { label } => Some(label + 1), _ => None })
+    .max().unwrap_or(0)
+}
+
+// inline the static calls to small leaf functions, a leaf function doesn't call any non-intrinsic function, so it is not recursive
+// virtual calls are never inlined, because the callee is unknown
+// all calls to instance methods (including getters) are virtual, except `super.f()`, so a getter should be a static method to be inlined
+// `threshold` is the max number of tac in an inlined function, 0 means never inline
+// it works on `TacProgram`, so it should be applied before building `FuncBB` and other optimizations
+pub fn work(p: &mut TacProgram, threshold: u32) {
+  if threshold == 0 { return; }
+  let inline = p.func.iter().map(|f| {
+    let body = iter(f).map(|t| t.tac.get()).collect::<Vec<_>>();
+    let leaf = body.iter().all(|t| match t { Tac::Call { kind: CallKind::Intrinsic(_), .. } => true, Tac::Call { .. } => false, _ => true });
+    if leaf && body.len() <= threshold as usize {
+      Some(Inline { param_num: f.param_num, reg_num: f.reg_num, label_num: label_num(f), body })
+    } else { None }
+  }).collect::<Vec<_>>();
+  for f in &mut p.func {
+    let (mut new, mut label_off) = (TacFunc::empty(f.alloc, f.name.clone(), f.param_num), label_num(f));
+    new.reg_num = f.reg_num;
+    // the consecutive `Param`s before a `Call`, they become assignments to the inlined function's first `param_num` registers
+    let mut param = Vec::new();
+    for t in iter(f) {
+      match t.tac.get() {
+        Tac::Param { src } => param.push(src),
+        Tac::Call { dst, kind: CallKind::Static(callee, _) } if inline[callee as usize].as_ref().map(|i| i.param_num) == Some(param.len() as u32) => {
+          let i = inline[callee as usize].as_ref().unwrap();
+          let (reg_off, end) = (new.reg_num, label_off + i.label_num);
+          new.reg_num += i.reg_num;
+          for (idx, src) in param.drain(..).enumerate() { new.push(Tac::Assign { dst: reg_off + idx as u32, src }); }
+          for &t in &i.body {
+            let mut t = t;
+            let (r, w) = t.rw_mut();
+            for r in r { if let Operand::Reg(r) = r { *r += reg_off; } }
+            if let Some(w) = w { *w += reg_off; }
+            match &mut t {
+              Tac::Jmp { label } | Tac::Jif { label, .. } | Tac::Label { label } => *label += label_off,
+              Tac::Ret { src } => {
+                // the return value is assigned to the caller's `dst`
+                if let (Some(dst), Some(src)) = (dst, *src) { new.push(Tac::Assign { dst, src }); }
+                t = Tac::Jmp { label: end };
+              }
+              _ => {}
+            }
+            new.push(t);
+          }
+          new.push(Tac::Label { label: end });
+          label_off = end + 1;
+        }
+        t => {
+          for src in param.drain(..) { new.push(Tac::Param { src }); }
+          new.push(t);
+        }
+      }
+    }
+    *f = new;
+  }
+}
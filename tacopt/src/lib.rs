@@ -1,6 +1,27 @@
 pub mod bb;
 pub mod flow;
+pub mod dom;
 pub mod common_expr;
 pub mod const_prop;
 pub mod copy_prop;
-pub mod aliveness;
\ No newline at end of file
+pub mod aliveness;
+pub mod inline;
+pub mod tail_call;
+pub mod escape;
+
+// the tac optimizations that can be turned off, all of them except inlining and stack allocation are on by default
+#[derive(Copy, Clone)]
+pub struct OptCfg {
+  pub common_expr: bool,
+  pub copy_prop: bool,
+  pub const_prop: bool,
+  pub tail_call: bool,
+  // the max number of tac in an inlined function, 0 means no inlining, see inline::work
+  pub inline: u32,
+  // allocate the objects which don't escape their function on the stack, only used in mips and llvm codegen, see escape::work
+  pub stack_alloc: bool,
+}
+
+impl Default for OptCfg {
+  fn default() -> Self { OptCfg { common_expr: true, copy_prop: true, const_prop: true, tail_call: true, inline: 0, stack_alloc: false } }
+}
\ No newline at end of file
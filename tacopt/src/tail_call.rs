@@ -0,0 +1,40 @@
+use tac::{TacProgram, TacFunc, Tac, CallKind, Operand};
+use crate::inline::{iter, label_num};
+
+// convert a static self-recursive call directly followed by returning its result into assigning the params and jumping to the entry
+// the args are first copied to new registers, because they may use the params, e.g.: `return f(n - 1, acc * n)`
+// it works on `TacProgram`, so it should be applied before building `FuncBB` and other optimizations
+pub fn work(p: &mut TacProgram) {
+  for (idx, f) in p.func.iter_mut().enumerate() {
+    let tac = iter(f).map(|t| t.tac.get()).collect::<Vec<_>>();
+    let param_num = f.param_num as usize;
+    // the call at `i` is a tail call, and the `param_num` tacs before it are its `Param`s
+    let tail = |i: usize| i >= param_num && tac[i - param_num..i].iter().all(|t| matches!(t, Tac::Param { .. })) && match (tac[i], tac.get(i + 1)) {
+      (Tac::Call { dst: Some(dst), kind: CallKind::Static(callee, _) }, Some(Tac::Ret { src: Some([Operand::Reg(src)]) })) => callee as usize == idx && dst == *src,
+      (Tac::Call { dst: None, kind: CallKind::Static(callee, _) }, Some(Tac::Ret { src: None })) => callee as usize == idx,
+      _ => false,
+    };
+    if !(0..tac.len()).any(&tail) { continue; }
+    let (mut new, entry) = (TacFunc::empty(f.alloc, f.name.clone(), f.param_num), label_num(f));
+    new.reg_num = f.reg_num;
+    let mut out = vec![Tac::Label { label: entry }];
+    let mut i = 0;
+    while i < tac.len() {
+      if tail(i) {
+        let param = out.split_off(out.len() - param_num);
+        let tmp = (0..param_num).map(|_| (new.reg_num, new.reg_num += 1).0).collect::<Vec<_>>();
+        for (&tmp, param) in tmp.iter().zip(param) {
+          if let Tac::Param { src } = param { out.push(Tac::Assign { dst: tmp, src }); }
+        }
+        for (idx, &tmp) in tmp.iter().enumerate() { out.push(Tac::Assign { dst: idx as u32, src: [Operand::Reg(tmp)] }); }
+        out.push(Tac::Jmp { label: entry });
+        i += 2; // skip the call and the ret
+      } else {
+        out.push(tac[i]);
+        i += 1;
+      }
+    }
+    for t in out { new.push(t); }
+    *f = new;
+  }
+}
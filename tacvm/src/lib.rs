@@ -0,0 +1,29 @@
+// the vm running the tac printed by `print::tac`, it has the same `work` and intrinsics as https://github.com/MashPlant/tacvm
+// it is in the workspace so that the intrinsics of new language features can be added along with them
+
+mod program;
+mod vm;
+
+use std::io::{self, BufRead, BufWriter, Write};
+use crate::vm::{Vm, Exit};
+
+// parse and execute the tac program `code`, which should be in the format printed by `print::tac`
+// the output of the program is written to `out`; the runtime errors, and the statistics enabled by `dump_inst` and `dump_func`, are written to `info`
+// `inst_limit` is the max number of instructions to execute, `stack_limit` is the max depth of function calls
+// a syntax error in `code` is returned as an `io::ErrorKind::InvalidData` error, and a runtime error is not an `Err`
+#[allow(clippy::too_many_arguments)]
+pub fn work(code: &str, inst_limit: u32, stack_limit: u32, dump_inst: bool, dump_func: bool,
+            inp: Box<dyn BufRead>, out: Box<dyn Write>, mut info: Box<dyn Write>) -> io::Result<()> {
+  let p = program::parse(code).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+  let mut vm = Vm::new(&p, inp, Box::new(BufWriter::new(out)));
+  let ret = vm.run(inst_limit, stack_limit);
+  vm.flush();
+  if let Err(Exit::Error(e)) = ret { writeln!(info, "runtime error: {}", e)?; }
+  if dump_inst { writeln!(info, "instruction count: {}", vm.inst_count)?; }
+  if dump_func {
+    for (f, &cnt) in p.func.iter().zip(vm.func_count.iter()) {
+      if cnt != 0 { writeln!(info, "{}: called {} time(s)", f.name, cnt)?; }
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+#[derive(Copy, Clone)]
+pub enum Operand { Reg(u32), Const(i32) }
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum BinOp { Add, Sub, Mul, Div, Mod, And, Or, BitAnd, BitOr, BitXor, Shl, Shr, Eq, Ne, Lt, Le, Gt, Ge }
+
+#[derive(Copy, Clone)]
+pub enum UnOp { Neg, Not, BitNot }
+
+#[derive(Copy, Clone, Debug)]
+pub enum Intrinsic { _Alloc, _ReadLine, _ReadInt, _StringEqual, _StringConcat, _PrintInt, _PrintString, _PrintBool, _PrintChar, _Halt,
+  _DoubleAdd, _DoubleSub, _DoubleMul, _DoubleDiv, _DoubleEq, _DoubleLt, _DoubleLe, _IntToDouble, _PrintDouble, _ReadDouble, _StringLength, _Substring, _StringToInt, _IntToString }
+
+impl Intrinsic {
+  fn from_name(name: &str) -> Option<Intrinsic> {
+    use Intrinsic::*;
+    Some(match name {
+      "_Alloc" => _Alloc, "_ReadLine" => _ReadLine, "_ReadInt" => _ReadInt, "_StringEqual" => _StringEqual,
+      "_StringConcat" => _StringConcat, "_PrintInt" => _PrintInt, "_PrintString" => _PrintString, "_PrintBool" => _PrintBool, "_PrintChar" => _PrintChar, "_Halt" => _Halt,
+      "_DoubleAdd" => _DoubleAdd, "_DoubleSub" => _DoubleSub, "_DoubleMul" => _DoubleMul, "_DoubleDiv" => _DoubleDiv,
+      "_DoubleEq" => _DoubleEq, "_DoubleLt" => _DoubleLt, "_DoubleLe" => _DoubleLe, "_IntToDouble" => _IntToDouble, "_PrintDouble" => _PrintDouble, "_ReadDouble" => _ReadDouble,
+      "_StringLength" => _StringLength, "_Substring" => _Substring, "_StringToInt" => _StringToInt, "_IntToString" => _IntToString,
+      _ => return None,
+    })
+  }
+}
+
+#[derive(Copy, Clone)]
+pub enum Callee { Func(u32), Reg(u32), Intrinsic(Intrinsic) }
+
+// the loading instructions (string, vtbl, func) are resolved to `Assign` of constant addresses
+// the labels are resolved to instruction indices
+#[derive(Copy, Clone)]
+pub enum Inst {
+  Bin { op: BinOp, dst: u32, l: Operand, r: Operand },
+  Un { op: UnOp, dst: u32, r: Operand },
+  Assign { dst: u32, src: Operand },
+  Param { src: Operand },
+  Call { dst: Option<u32>, callee: Callee },
+  Ret { src: Option<Operand> },
+  Jmp { target: u32 },
+  Jif { z: bool, cond: Operand, target: u32 },
+  Load { dst: u32, base: Operand, off: i32 },
+  Store { src: Operand, base: Operand, off: i32 },
+}
+
+pub struct Func {
+  pub name: String,
+  pub code: Vec<Inst>,
+}
+
+// the address of a function is (its index + 1) * FUNC_ALIGN, so [0, data_start) is never accessed as memory
+pub const FUNC_ALIGN: i32 = 4;
+
+pub struct Program {
+  pub func: Vec<Func>,
+  pub main: u32,
+  // the initial memory, containing the vtbls, the strings and the static variables, starting from address 0
+  pub mem: Vec<u8>,
+  // the first address which can be accessed as memory
+  pub data_start: i32,
+}
+
+fn err<T>(line: usize, msg: impl AsRef<str>) -> Result<T, String> { Err(format!("line {}: {}", line + 1, msg.as_ref())) }
+
+// the name in `prefix...>`, e.g.: ("FUNC<main>", "FUNC<") => "main"
+fn angle<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+  if s.starts_with(prefix) && s.ends_with('>') && s.len() > prefix.len() { Some(&s[prefix.len()..s.len() - 1]) } else { None }
+}
+
+fn decode_str(s: &str) -> Option<Vec<u8>> {
+  let (mut ret, mut it) = (Vec::with_capacity(s.len()), s.bytes());
+  while let Some(b) = it.next() {
+    ret.push(if b == b'\\' {
+      match it.next()? { b'n' => b'\n', b'r' => b'\r', b't' => b'\t', b'0' => 0, b'\\' => b'\\', b'"' => b'"', _ => return None }
+    } else { b });
+  }
+  Some(ret)
+}
+
+struct Loader<'a> {
+  mem: Vec<u8>,
+  func_idx: HashMap<&'a str, u32>,
+  vtbl_addr: HashMap<&'a str, i32>,
+  // the static variables are allocated when they are first referred to, their initial values are 0
+  static_addr: HashMap<&'a str, i32>,
+}
+
+impl<'a> Loader<'a> {
+  fn alloc(&mut self, size: usize) -> i32 {
+    let addr = self.mem.len();
+    self.mem.resize(addr + size.div_ceil(4) * 4, 0);
+    addr as i32
+  }
+
+  fn store(&mut self, addr: i32, val: i32) { self.mem[addr as usize..addr as usize + 4].copy_from_slice(&val.to_le_bytes()); }
+
+  fn str(&mut self, s: &[u8]) -> i32 {
+    let addr = self.alloc(s.len() + 1);
+    self.mem[addr as usize..addr as usize + s.len()].copy_from_slice(s);
+    addr
+  }
+
+  fn func_addr(&self, name: &str) -> Option<i32> { self.func_idx.get(name).map(|&i| (i as i32 + 1) * FUNC_ALIGN) }
+
+  fn operand(&self, s: &str, line: usize) -> Result<Operand, String> {
+    if let Some(r) = s.strip_prefix('%') {
+      r.parse().map(Operand::Reg).or_else(|_| err(line, format!("invalid register `{}`", s)))
+    } else { s.parse().map(Operand::Const).or_else(|_| err(line, format!("invalid operand `{}`", s))) }
+  }
+
+  fn reg(&self, s: &str, line: usize) -> Result<u32, String> {
+    match self.operand(s, line)? { Operand::Reg(r) => Ok(r), _ => err(line, format!("expect a register, found `{}`", s)) }
+  }
+
+  fn label(&self, s: &str, line: usize) -> Result<u32, String> {
+    if let Some(l) = s.strip_prefix('%') { l.parse().or_else(|_| err(line, format!("invalid label `{}`", s))) } else { err(line, format!("invalid label `{}`", s)) }
+  }
+
+  // `*(base + off)` or `*(base - off)`, returns (base, off)
+  fn mem_ref(&self, s: &str, line: usize) -> Result<(Operand, i32), String> {
+    if !(s.starts_with("*(") && s.ends_with(')')) { return err(line, format!("invalid memory operand `{}`", s)); }
+    let inner = &s[2..s.len() - 1];
+    let v = inner.split_whitespace().collect::<Vec<_>>();
+    if v.len() != 3 { return err(line, format!("invalid memory operand `{}`", s)); }
+    let off: i32 = v[2].parse().or_else(|_| err(line, format!("invalid offset `{}`", v[2])))?;
+    match v[1] { "+" => Ok((self.operand(v[0], line)?, off)), "-" => Ok((self.operand(v[0], line)?, -off)), _ => err(line, format!("invalid memory operand `{}`", s)) }
+  }
+
+  // the rhs of `%dst = rhs`
+  fn rhs(&mut self, dst: u32, s: &'a str, line: usize) -> Result<Inst, String> {
+    use BinOp::*;
+    if s.starts_with('"') && s.ends_with('"') && s.len() >= 2 {
+      let s = decode_str(&s[1..s.len() - 1]).ok_or_else(|| format!("line {}: invalid string literal", line + 1))?;
+      return Ok(Inst::Assign { dst, src: Operand::Const(self.str(&s)) });
+    }
+    if s.starts_with("*(") {
+      let (base, off) = self.mem_ref(s, line)?;
+      return Ok(Inst::Load { dst, base, off });
+    }
+    if s.starts_with('(') && s.ends_with(')') {
+      let v = s[1..s.len() - 1].split_whitespace().collect::<Vec<_>>();
+      if v.len() != 3 { return err(line, format!("invalid binary expression `{}`", s)); }
+      let op = match v[1] {
+        "+" => Add, "-" => Sub, "*" => Mul, "/" => Div, "%" => Mod, "&&" => And, "||" => Or, "&" => BitAnd, "|" => BitOr, "^" => BitXor,
+        "<<" => Shl, ">>" => Shr, "==" => Eq, "!=" => Ne, "<" => Lt, "<=" => Le, ">" => Gt, ">=" => Ge,
+        op => return err(line, format!("invalid binary operator `{}`", op)),
+      };
+      return Ok(Inst::Bin { op, dst, l: self.operand(v[0], line)?, r: self.operand(v[2], line)? });
+    }
+    if let Some(f) = s.strip_prefix("call ") {
+      return Ok(Inst::Call { dst: Some(dst), callee: self.callee(f.trim(), line)? });
+    }
+    if let Some(v) = angle(s, "VTBL<_") {
+      let addr = *self.vtbl_addr.get(v).ok_or_else(|| format!("line {}: undefined vtbl `{}`", line + 1, v))?;
+      return Ok(Inst::Assign { dst, src: Operand::Const(addr) });
+    }
+    if let Some(f) = angle(s, "FUNC<") {
+      let addr = self.func_addr(f).ok_or_else(|| format!("line {}: undefined function `{}`", line + 1, f))?;
+      return Ok(Inst::Assign { dst, src: Operand::Const(addr) });
+    }
+    if let Some(v) = angle(s, "STATIC<") {
+      let addr = match self.static_addr.get(v) {
+        Some(&addr) => addr,
+        None => { let addr = self.alloc(4); self.static_addr.insert(v, addr); addr }
+      };
+      return Ok(Inst::Assign { dst, src: Operand::Const(addr) });
+    }
+    let v = s.split_whitespace().collect::<Vec<_>>();
+    match v.as_slice() {
+      [op, r] => {
+        let op = match *op { "-" => UnOp::Neg, "!" => UnOp::Not, "~" => UnOp::BitNot, op => return err(line, format!("invalid unary operator `{}`", op)) };
+        Ok(Inst::Un { op, dst, r: self.operand(r, line)? })
+      }
+      [src] => Ok(Inst::Assign { dst, src: self.operand(src, line)? }),
+      _ => err(line, format!("invalid instruction `{}`", s)),
+    }
+  }
+
+  fn callee(&self, f: &str, line: usize) -> Result<Callee, String> {
+    if f.starts_with('%') { return Ok(Callee::Reg(self.reg(f, line)?)); }
+    if let Some(&idx) = self.func_idx.get(f) { return Ok(Callee::Func(idx)); }
+    Intrinsic::from_name(f).map(Callee::Intrinsic).ok_or_else(|| format!("line {}: undefined function `{}`", line + 1, f))
+  }
+
+  // parse the body of a function, `lines` are trimmed and non-empty
+  fn func(&mut self, lines: &[(usize, &'a str)]) -> Result<Vec<Inst>, String> {
+    // (inst index, label) of branches, they are filled after all labels are known
+    let (mut code, mut label2idx, mut unfill) = (Vec::new(), HashMap::new(), Vec::new());
+    for &(line, s) in lines {
+      if let Some(label) = s.strip_suffix(':') {
+        label2idx.insert(self.label(label, line)?, code.len() as u32);
+        continue;
+      }
+      let inst = if s.starts_with('%') {
+        let eq = s.find(" = ").ok_or_else(|| format!("line {}: invalid instruction `{}`", line + 1, s))?;
+        let dst = self.reg(&s[..eq], line)?;
+        self.rhs(dst, s[eq + 3..].trim(), line)?
+      } else if s.starts_with("*(") {
+        let eq = s.find(") = ").ok_or_else(|| format!("line {}: invalid instruction `{}`", line + 1, s))?;
+        let (base, off) = self.mem_ref(&s[..eq + 1], line)?;
+        Inst::Store { src: self.operand(s[eq + 4..].trim(), line)?, base, off }
+      } else if let Some(src) = s.strip_prefix("parm ") {
+        Inst::Param { src: self.operand(src.trim(), line)? }
+      } else if let Some(f) = s.strip_prefix("call ") {
+        Inst::Call { dst: None, callee: self.callee(f.trim(), line)? }
+      } else if s == "return" {
+        Inst::Ret { src: None }
+      } else if let Some(src) = s.strip_prefix("return ") {
+        Inst::Ret { src: Some(self.operand(src.trim(), line)?) }
+      } else if let Some(l) = s.strip_prefix("branch ") {
+        unfill.push((code.len(), self.label(l.trim(), line)?, line));
+        Inst::Jmp { target: 0 }
+      } else if let Some(rest) = s.strip_prefix("if (") {
+        // if (cond == 0) branch %L
+        let v = rest.split_whitespace().collect::<Vec<_>>();
+        if v.len() != 5 || v[2] != "0)" || v[3] != "branch" { return err(line, format!("invalid instruction `{}`", s)); }
+        let z = match v[1] { "==" => true, "!=" => false, _ => return err(line, format!("invalid instruction `{}`", s)) };
+        unfill.push((code.len(), self.label(v[4], line)?, line));
+        Inst::Jif { z, cond: self.operand(v[0], line)?, target: 0 }
+      } else { return err(line, format!("invalid instruction `{}`", s)); };
+      code.push(inst);
+    }
+    for (idx, label, line) in unfill {
+      let t = *label2idx.get(&label).ok_or_else(|| format!("line {}: undefined label `%{}`", line + 1, label))?;
+      match &mut code[idx] { Inst::Jmp { target } | Inst::Jif { target, .. } => *target = t, _ => unreachable!() }
+    }
+    Ok(code)
+  }
+}
+
+// (kind, name, line of the head, lines in it), kind is "VTBL" or "FUNC"
+type Block<'a> = (&'a str, &'a str, usize, Vec<(usize, &'a str)>);
+
+pub fn parse(code: &str) -> Result<Program, String> {
+  // split the code into blocks
+  let mut blocks = Vec::new();
+  let mut cur: Option<Block> = None;
+  for (line, s) in code.lines().enumerate() {
+    let s = s.trim();
+    if s.is_empty() { continue; }
+    if let Some((_, _, _, lines)) = &mut cur {
+      if s == "}" { blocks.push(cur.take().unwrap()); } else { lines.push((line, s)); }
+    } else if let Some(head) = s.strip_suffix('{') {
+      let head = head.trim();
+      if let Some(name) = angle(head, "VTBL<_") {
+        cur = Some(("VTBL", name, line, Vec::new()));
+      } else if let Some(name) = angle(head, "FUNC<") {
+        cur = Some(("FUNC", name, line, Vec::new()));
+      } else { return err(line, format!("invalid definition `{}`", s)); }
+    } else { return err(line, format!("invalid definition `{}`", s)); }
+  }
+  if let Some((_, _, line, _)) = cur { return err(line, "unclosed definition"); }
+
+  let func_num = blocks.iter().filter(|b| b.0 == "FUNC").count();
+  let mut l = Loader { mem: vec![0; (func_num + 1) * FUNC_ALIGN as usize], func_idx: HashMap::new(), vtbl_addr: HashMap::new(), static_addr: HashMap::new() };
+  for (i, b) in blocks.iter().filter(|b| b.0 == "FUNC").enumerate() {
+    if l.func_idx.insert(b.1, i as u32).is_some() { return err(b.2, format!("duplicate function `{}`", b.1)); }
+  }
+  let vtbl = blocks.iter().filter(|b| b.0 == "VTBL").collect::<Vec<_>>();
+  // allocate all vtbls first, because a vtbl can refer to the vtbl defined after it
+  for b in &vtbl {
+    let addr = l.alloc(b.3.len() * 4);
+    if l.vtbl_addr.insert(b.1, addr).is_some() { return err(b.2, format!("duplicate vtbl `{}`", b.1)); }
+  }
+  for b in &vtbl {
+    let addr = l.vtbl_addr[b.1];
+    for (i, &(line, s)) in b.3.iter().enumerate() {
+      let val = if s == "0" { 0 } else if let Some(p) = angle(s, "VTBL<_") {
+        *l.vtbl_addr.get(p).ok_or_else(|| format!("line {}: undefined vtbl `{}`", line + 1, p))?
+      } else if let Some(f) = angle(s, "FUNC<") {
+        l.func_addr(f).ok_or_else(|| format!("line {}: undefined function `{}`", line + 1, f))?
+      } else if s.starts_with('"') && s.ends_with('"') && s.len() >= 2 {
+        let s = decode_str(&s[1..s.len() - 1]).ok_or_else(|| format!("line {}: invalid string literal", line + 1))?;
+        l.str(&s)
+      } else { return err(line, format!("invalid vtbl entry `{}`", s)); };
+      l.store(addr + i as i32 * 4, val);
+    }
+  }
+  let mut func = Vec::with_capacity(func_num);
+  for b in blocks.iter().filter(|b| b.0 == "FUNC") {
+    func.push(Func { name: b.1.to_owned(), code: l.func(&b.3)? });
+  }
+  let main = *l.func_idx.get("main").ok_or_else(|| "no `main` function".to_owned())?;
+  Ok(Program { func, main, mem: l.mem, data_start: (func_num as i32 + 1) * FUNC_ALIGN })
+}
@@ -0,0 +1,259 @@
+use crate::program::{*, Operand::*};
+use std::io::{BufRead, Write};
+
+struct Frame {
+  func: u32,
+  pc: u32,
+  reg: Vec<i32>,
+  // the register in the caller to store the returned value
+  ret_dst: Option<u32>,
+}
+
+impl Frame {
+  fn get(&self, o: Operand) -> i32 {
+    match o { Reg(r) => self.reg.get(r as usize).cloned().unwrap_or(0), Const(c) => c }
+  }
+
+  fn set(&mut self, r: u32, val: i32) {
+    if self.reg.len() <= r as usize { self.reg.resize(r as usize + 1, 0); }
+    self.reg[r as usize] = val;
+  }
+}
+
+pub struct Vm<'a> {
+  p: &'a Program,
+  mem: Vec<u8>,
+  inp: Box<dyn BufRead>,
+  out: Box<dyn Write>,
+  pub inst_count: u32,
+  // the number of times each function is called
+  pub func_count: Vec<u32>,
+}
+
+// the reason why the execution stops
+pub enum Exit { Halt, Error(String) }
+
+fn error<T>(msg: impl Into<String>) -> Result<T, Exit> { Err(Exit::Error(msg.into())) }
+
+impl<'a> Vm<'a> {
+  pub fn new(p: &'a Program, inp: Box<dyn BufRead>, out: Box<dyn Write>) -> Vm<'a> {
+    Vm { p, mem: p.mem.clone(), inp, out, inst_count: 0, func_count: vec![0; p.func.len()] }
+  }
+
+  fn check(&self, addr: i32, size: i32) -> Result<usize, Exit> {
+    if addr == 0 { return error("null pointer access"); }
+    if addr < self.p.data_start || addr as usize + size as usize > self.mem.len() { return error(format!("invalid memory access at {}", addr)); }
+    Ok(addr as usize)
+  }
+
+  fn load(&self, addr: i32) -> Result<i32, Exit> {
+    if addr % 4 != 0 { return error(format!("unaligned memory access at {}", addr)); }
+    let a = self.check(addr, 4)?;
+    let mut b = [0; 4];
+    b.copy_from_slice(&self.mem[a..a + 4]);
+    Ok(i32::from_le_bytes(b))
+  }
+
+  fn store(&mut self, addr: i32, val: i32) -> Result<(), Exit> {
+    if addr % 4 != 0 { return error(format!("unaligned memory access at {}", addr)); }
+    let a = self.check(addr, 4)?;
+    self.mem[a..a + 4].copy_from_slice(&val.to_le_bytes());
+    Ok(())
+  }
+
+  // the allocated memory is filled with 0
+  fn alloc(&mut self, size: i32) -> Result<i32, Exit> {
+    if size < 0 { return error(format!("allocating negative size {}", size)); }
+    let addr = self.mem.len();
+    self.mem.resize(addr + (size as usize).div_ceil(4) * 4, 0);
+    Ok(addr as i32)
+  }
+
+  // the bytes of the '\0' terminated string at `addr` (not including the '\0')
+  fn str(&self, addr: i32) -> Result<&[u8], Exit> {
+    let a = self.check(addr, 1)?;
+    match self.mem[a..].iter().position(|&b| b == 0) {
+      Some(len) => Ok(&self.mem[a..a + len]),
+      None => error(format!("unterminated string at {}", addr)),
+    }
+  }
+
+  fn new_str(&mut self, s: &[u8]) -> Result<i32, Exit> {
+    let addr = self.alloc(s.len() as i32 + 1)?;
+    self.mem[addr as usize..addr as usize + s.len()].copy_from_slice(s);
+    Ok(addr)
+  }
+
+  // a double is the address of 8 bytes, the lower 32 bits are at the lower address
+  // the address 0 is 0.0, like in lib.s, so the double fields and array elements are initially 0.0
+  fn double(&self, addr: i32) -> Result<f64, Exit> {
+    if addr == 0 { return Ok(0.0); }
+    let (lo, hi) = (self.load(addr)? as u32 as u64, self.load(addr.wrapping_add(4))? as u32 as u64);
+    Ok(f64::from_bits(hi << 32 | lo))
+  }
+
+  fn new_double(&mut self, d: f64) -> Result<i32, Exit> {
+    let (addr, bits) = (self.alloc(8)?, d.to_bits());
+    self.store(addr, bits as i32)?;
+    self.store(addr + 4, (bits >> 32) as i32)?;
+    Ok(addr)
+  }
+
+  fn read_line(&mut self) -> Result<String, Exit> {
+    let mut s = String::new();
+    if let Err(e) = self.inp.read_line(&mut s) { return error(format!("failed to read input: {}", e)); }
+    if s.ends_with('\n') { s.pop(); }
+    if s.ends_with('\r') { s.pop(); }
+    Ok(s)
+  }
+
+  fn write(&mut self, s: &[u8]) -> Result<(), Exit> {
+    self.out.write_all(s).or_else(|e| error(format!("failed to write output: {}", e)))
+  }
+
+  fn intrinsic(&mut self, i: Intrinsic, arg: &[i32]) -> Result<Option<i32>, Exit> {
+    use Intrinsic::*;
+    let arg_num = match i {
+      _ReadLine | _ReadInt | _ReadDouble | _Halt => 0,
+      _Alloc | _PrintInt | _PrintString | _PrintBool | _PrintChar | _IntToDouble | _PrintDouble | _StringLength | _StringToInt | _IntToString => 1,
+      _StringEqual | _StringConcat | _DoubleAdd | _DoubleSub | _DoubleMul | _DoubleDiv | _DoubleEq | _DoubleLt | _DoubleLe => 2,
+      _Substring => 3,
+    };
+    if arg.len() != arg_num { return error(format!("{:?} expects {} argument(s), found {}", i, arg_num, arg.len())); }
+    Ok(match i {
+      _Alloc => Some(self.alloc(arg[0])?),
+      _ReadLine => {
+        let s = self.read_line()?;
+        Some(self.new_str(s.as_bytes())?)
+      }
+      _ReadInt => Some(self.read_line()?.trim().parse().unwrap_or(0)),
+      _ReadDouble => {
+        let d = self.read_line()?.trim().parse().unwrap_or(0.0);
+        Some(self.new_double(d)?)
+      }
+      _StringEqual => Some((self.str(arg[0])? == self.str(arg[1])?) as i32),
+      _StringConcat => {
+        let s = [self.str(arg[0])?, self.str(arg[1])?].concat();
+        Some(self.new_str(&s)?)
+      }
+      _StringLength => Some(self.str(arg[0])?.len() as i32),
+      // the bounds are checked by the caller, like in lib.s, but they are checked here again to avoid panicking
+      _Substring => {
+        let (s, l, r) = (self.str(arg[0])?, arg[1], arg[2]);
+        if l < 0 || l > r || r as usize > s.len() { return error(format!("substring [{}, {}) out of bounds for length {}", l, r, s.len())); }
+        let s = s[l as usize..r as usize].to_vec();
+        Some(self.new_str(&s)?)
+      }
+      // like `_StringToInt` in lib.s: an optional '-' and the digits after it, stopping at the first non-digit, and an overflowing number wraps
+      _StringToInt => {
+        let s = self.str(arg[0])?;
+        let (neg, s) = if s.first() == Some(&b'-') { (true, &s[1..]) } else { (false, s) };
+        let v = s.iter().take_while(|b| b.is_ascii_digit()).fold(0i32, |v, &b| v.wrapping_mul(10).wrapping_add((b - b'0') as i32));
+        Some(if neg { v.wrapping_neg() } else { v })
+      }
+      _IntToString => Some(self.new_str(arg[0].to_string().as_bytes())?),
+      _PrintInt => (self.write(arg[0].to_string().as_bytes())?, None).1,
+      _PrintString => {
+        let s = self.str(arg[0])?.to_vec();
+        (self.write(&s)?, None).1
+      }
+      _PrintBool => (self.write(if arg[0] != 0 { b"true" } else { b"false" })?, None).1,
+      // a char is its unicode code point, it is printed in utf-8
+      _PrintChar => match std::char::from_u32(arg[0] as u32) {
+        Some(ch) => (self.write(ch.encode_utf8(&mut [0; 4]).as_bytes())?, None).1,
+        None => return error(format!("printing an invalid char {}", arg[0])),
+      }
+      _DoubleAdd | _DoubleSub | _DoubleMul | _DoubleDiv => {
+        let (l, r) = (self.double(arg[0])?, self.double(arg[1])?);
+        Some(self.new_double(match i { _DoubleAdd => l + r, _DoubleSub => l - r, _DoubleMul => l * r, _ => l / r })?)
+      }
+      _DoubleEq | _DoubleLt | _DoubleLe => {
+        let (l, r) = (self.double(arg[0])?, self.double(arg[1])?);
+        Some(match i { _DoubleEq => l == r, _DoubleLt => l < r, _ => l <= r } as i32)
+      }
+      _IntToDouble => Some(self.new_double(arg[0] as f64)?),
+      // the same format as MARS, e.g.: `1.0` and `0.1`
+      _PrintDouble => {
+        let s = format!("{:?}", self.double(arg[0])?);
+        (self.write(s.as_bytes())?, None).1
+      }
+      _Halt => return Err(Exit::Halt),
+    })
+  }
+
+  pub fn run(&mut self, inst_limit: u32, stack_limit: u32) -> Result<(), Exit> {
+    use BinOp::*;
+    let p = self.p;
+    let mut stk = vec![Frame { func: p.main, pc: 0, reg: Vec::new(), ret_dst: None }];
+    self.func_count[p.main as usize] += 1;
+    let mut arg = Vec::new();
+    loop {
+      let fr = stk.last_mut().unwrap();
+      let inst = match p.func[fr.func as usize].code.get(fr.pc as usize) { Some(&inst) => inst, None => Inst::Ret { src: None } };
+      fr.pc += 1;
+      if self.inst_count == inst_limit { return error(format!("exceeding the instruction limit {}", inst_limit)); }
+      self.inst_count += 1;
+      match inst {
+        Inst::Bin { op, dst, l, r } => {
+          let (l, r) = (fr.get(l), fr.get(r));
+          let val = match op {
+            Add => l.wrapping_add(r), Sub => l.wrapping_sub(r), Mul => l.wrapping_mul(r),
+            Div | Mod => {
+              if r == 0 { return error("division by zero"); }
+              if op == Div { l.wrapping_div(r) } else { l.wrapping_rem(r) }
+            }
+            And => (l != 0 && r != 0) as i32, Or => (l != 0 || r != 0) as i32,
+            BitAnd => l & r, BitOr => l | r, BitXor => l ^ r, Shl => l.wrapping_shl(r as u32), Shr => l.wrapping_shr(r as u32),
+            Eq => (l == r) as i32, Ne => (l != r) as i32, Lt => (l < r) as i32, Le => (l <= r) as i32, Gt => (l > r) as i32, Ge => (l >= r) as i32,
+          };
+          fr.set(dst, val);
+        }
+        Inst::Un { op, dst, r } => {
+          let r = fr.get(r);
+          fr.set(dst, match op { UnOp::Neg => r.wrapping_neg(), UnOp::Not => (r == 0) as i32, UnOp::BitNot => !r });
+        }
+        Inst::Assign { dst, src } => { let val = fr.get(src); fr.set(dst, val); }
+        Inst::Param { src } => arg.push(fr.get(src)),
+        Inst::Call { dst, callee } => {
+          let f = match callee {
+            Callee::Func(f) => f,
+            Callee::Reg(r) => {
+              let addr = fr.get(Reg(r));
+              if addr <= 0 || addr % FUNC_ALIGN != 0 || addr / FUNC_ALIGN > p.func.len() as i32 { return error(format!("calling an invalid function address {}", addr)); }
+              (addr / FUNC_ALIGN - 1) as u32
+            }
+            Callee::Intrinsic(i) => {
+              let ret = self.intrinsic(i, &arg)?;
+              arg.clear();
+              if let (Some(dst), Some(ret)) = (dst, ret) { stk.last_mut().unwrap().set(dst, ret); }
+              continue;
+            }
+          };
+          if stk.len() == stack_limit as usize { return error(format!("exceeding the stack limit {}", stack_limit)); }
+          self.func_count[f as usize] += 1;
+          stk.push(Frame { func: f, pc: 0, reg: arg.split_off(0), ret_dst: dst });
+        }
+        Inst::Ret { src } => {
+          let (val, ret_dst) = (src.map(|src| fr.get(src)), fr.ret_dst);
+          stk.pop();
+          match stk.last_mut() {
+            Some(fr) => if let (Some(dst), Some(val)) = (ret_dst, val) { fr.set(dst, val); }
+            None => return Ok(()),
+          }
+        }
+        Inst::Jmp { target } => fr.pc = target,
+        Inst::Jif { z, cond, target } => if (fr.get(cond) == 0) == z { fr.pc = target; }
+        Inst::Load { dst, base, off } => {
+          let val = self.load(fr.get(base).wrapping_add(off))?;
+          stk.last_mut().unwrap().set(dst, val);
+        }
+        Inst::Store { src, base, off } => {
+          let (src, addr) = (fr.get(src), fr.get(base).wrapping_add(off));
+          self.store(addr, src)?;
+        }
+      }
+    }
+  }
+
+  pub fn flush(&mut self) { let _ = self.out.flush(); }
+}
@@ -2,8 +2,8 @@ mod scope_stack;
 mod symbol_pass;
 mod type_pass;
 
-use common::{Errors, ErrorKind::*, Ref};
-use syntax::{FuncDef, ClassDef, SynTy, SynTyKind, ScopeOwner, Ty, TyKind, Program, VarDef};
+use common::{Errors, ErrorKind::*, Loc, Ref, HashMap, HashSet};
+use syntax::{FuncDef, ClassDef, SynTy, SynTyKind, ScopeOwner, Ty, TyKind, Program, VarDef, Lambda, TypeAlias};
 use typed_arena::Arena;
 use std::ops::{Deref, DerefMut};
 use crate::{symbol_pass::SymbolPass, type_pass::TypePass, scope_stack::ScopeStack};
@@ -14,50 +14,143 @@ pub struct TypeCkAlloc<'a> {
   pub ty: Arena<Ty<'a>>,
 }
 
-pub fn work<'a>(p: &'a Program<'a>, alloc: &'a TypeCkAlloc<'a>) -> Result<(), Errors<'a, Ty<'a>>> {
-  let mut s = SymbolPass(TypeCk { errors: Errors(vec![]), scopes: ScopeStack::new(p), loop_cnt: 0, cur_used: false, cur_func: None, cur_class: None, cur_var_def: None, alloc });
+// the warnings are appended to `warnings` (no matter whether there are errors), they are not contained in the returned errors
+// at most `max_error` errors are reported, followed by a TooManyErrors
+pub fn work<'a>(p: &'a Program<'a>, alloc: &'a TypeCkAlloc<'a>, max_error: usize, warnings: &mut Errors<'a, Ty<'a>>) -> Result<(), Errors<'a, Ty<'a>>> {
+  let mut s = SymbolPass(TypeCk { errors: Errors::with_limit(max_error), scopes: ScopeStack::new(p), loop_stk: vec![], switch_cnt: 0, lambda_stk: vec![], cur_used: false, cur_lvalue: false, assigned: HashSet::new(), non_null_assigned: HashSet::new(), const_index: vec![], null_deref: vec![], narrowed: vec![], cur_func: None, cur_class: None, in_static: false, cur_var_def: None, alias: HashMap::new(), alloc });
   s.program(p);
-  if !s.errors.0.is_empty() { return Err(s.0.errors.sorted()); }
   let mut t = TypePass(s.0);
-  t.program(p);
-  if !t.errors.0.is_empty() { return Err(t.0.errors.sorted()); }
-  Ok(())
+  if !t.errors.has_error() { t.program(p); }
+  let (errors, mut w) = t.0.errors.sorted().split_warnings();
+  warnings.append(&mut w);
+  if errors.0.is_empty() { Ok(()) } else { Err(errors) }
 }
 
 struct TypeCk<'a> {
   errors: Errors<'a, Ty<'a>>,
   scopes: ScopeStack<'a>,
-  loop_cnt: u32,
+  // the labels of the enclosing loops, None for unlabeled loops
+  loop_stk: Vec<Option<&'a str>>,
+  // an unlabeled `break` is also allowed if switch_cnt > 0
+  switch_cnt: u32,
+  // the enclosing lambdas, the innermost one is the last
+  lambda_stk: Vec<LambdaCtx<'a>>,
   // `cur_used` is only used to determine 2 kinds of errors:
   // Class.var (cur_used == true) => BadFieldAssess; Class (cur_used == false) => UndeclaredVar
   cur_used: bool,
+  // true if the VarSel being checked is the lhs of an assignment, then it is not marked as used
+  cur_lvalue: bool,
+  // the local variables assigned in current function
+  assigned: HashSet<Ref<'a, VarDef<'a>>>,
+  // the local variables assigned a value other than `null` in current function
+  non_null_assigned: HashSet<Ref<'a, VarDef<'a>>>,
+  // (loc, index, length, variable) of the constant out of bounds indexes in current function
+  // if `variable` is Some, the length comes from its init value, so the warning is valid only if it is never assigned
+  const_index: Vec<(Loc, i32, i32, Option<&'a VarDef<'a>>)>,
+  // (loc, variable) of the dereferences of the local variables initialized with `null` in current function
+  // the warning is valid only if the variable is never assigned a value other than `null`
+  null_deref: Vec<(Loc, &'a VarDef<'a>)>,
+  // (variable, whether it is null) narrowed by the enclosing `if (p == null)` / `if (p != null)`, the innermost is the last
+  narrowed: Vec<(&'a VarDef<'a>, bool)>,
   cur_func: Option<&'a FuncDef<'a>>,
   cur_class: Option<&'a ClassDef<'a>>,
+  // true when checking a static member of cur_class, the type parameter of a generic class can't be used there,
+  // because a static member is shared by all the instantiations
+  in_static: bool,
   // actually only use cur_var_def's loc
   // if cur_var_def is Some, will use it's loc to search for symbol in TypePass::var_sel
   // this can reject code like `int a = a;`
   cur_var_def: Option<&'a VarDef<'a>>,
+  // the type aliases declared in the program, they share the namespace of classes
+  alias: HashMap<&'a str, &'a TypeAlias<'a>>,
   alloc: &'a TypeCkAlloc<'a>,
 }
 
+struct LambdaCtx<'a> {
+  l: &'a Lambda<'a>,
+  // the loc of the lambda expression, a local variable is declared outside the lambda iff its loc < `loc`
+  loc: Loc,
+  // the cur_var_def outside the lambda, the lambda can't capture it either
+  var_def: Option<&'a VarDef<'a>>,
+  // the types of all return statements in the body (if it is a block)
+  ret: Vec<(Loc, Ty<'a>)>,
+}
+
 impl<'a> TypeCk<'a> {
   // is_arr can be helpful if you want the type of array while only having its element type (to avoid cloning other fields)
   fn ty(&mut self, s: &SynTy<'a>, is_arr: bool) -> Ty<'a> {
+    let mut arr = s.arr + (is_arr as u32);
     let kind = match &s.kind {
       SynTyKind::Int => TyKind::Int,
       SynTyKind::Bool => TyKind::Bool,
+      SynTyKind::Char => TyKind::Char,
+      SynTyKind::Double => TyKind::Double,
       SynTyKind::String => TyKind::String,
       SynTyKind::Void => TyKind::Void,
-      SynTyKind::Named(name) => if let Some(c) = self.scopes.lookup_class(name) {
-        TyKind::Object(Ref(c))
+      // set in TypePass when checking the LocalVarDef
+      SynTyKind::Var => TyKind::Error,
+      // the type parameter of current class hides the classes and aliases with the same name
+      &SynTyKind::Named(name, _, None) if self.cur_class.and_then(|c| c.ty_param) == Some(name) =>
+        if self.in_static { self.issue(s.loc, TyParamInStatic(name)) } else { TyKind::Param(Ref(self.cur_class.unwrap())) },
+      // the dimensions of an alias to array type are added to the dimensions in `s`
+      &SynTyKind::Named(name, non_null, ref arg) => if let Some(&a) = self.alias.get(name) {
+        if arg.is_some() { return self.issue(s.loc, NotGeneric(name)); }
+        let t = self.alias_ty(a);
+        arr += t.arr;
+        match t.kind { TyKind::Object(c, n, a) if t.arr == 0 => TyKind::Object(c, n || non_null, a), k => k }
+      } else if let Some(c) = self.scopes.lookup_class(name) {
+        self.obj_ty(c, non_null, arg.as_deref(), s.loc)
       } else { self.issue(s.loc, NoSuchClass(name)) },
+      SynTyKind::Func(ret_param) => {
+        let ret_param = ret_param.iter().map(|s| self.ty(s, false)).collect::<Vec<_>>();
+        if ret_param[1..].contains(&Ty::void()) { self.issue(s.loc, VoidFuncTyParam) } else if ret_param.contains(&Ty::error()) { TyKind::Error } else {
+          TyKind::Func(self.alloc.ty.alloc_extend(ret_param))
+        }
+      }
+      SynTyKind::Tuple(elem) => {
+        let elem = elem.iter().map(|s| self.ty(s, false)).collect::<Vec<_>>();
+        if let Some(&e) = elem.iter().find(|&&e| e == Ty::void()) { self.issue(s.loc, BadTupleElem(e)) } else if elem.contains(&Ty::error()) { TyKind::Error } else {
+          TyKind::Tuple(self.alloc.ty.alloc_extend(elem))
+        }
+      }
     };
     match kind {
       TyKind::Error => Ty::error(),
       TyKind::Void if s.arr != 0 => self.issue(s.loc, VoidArrayElement),
-      _ => Ty { arr: s.arr + (is_arr as u32), kind }
+      _ => Ty { arr, kind }
     }
   }
+
+  // the object type of class `c`, the type argument `arg` must be given iff `c` is generic
+  fn obj_ty(&mut self, c: &'a ClassDef<'a>, non_null: bool, arg: Option<&SynTy<'a>>, loc: Loc) -> TyKind<'a> {
+    let arg = match (c.ty_param, arg) {
+      (Some(_), Some(arg)) => match self.ty(arg, false) {
+        a if a == Ty::error() => return TyKind::Error,
+        a if a == Ty::void() => return self.issue(arg.loc, BadTypeArg(a)),
+        a => Some(&*self.alloc.ty.alloc(a)),
+      }
+      (Some(_), None) => return self.issue(loc, MissingTypeArg(c.name)),
+      (None, Some(_)) => return self.issue(loc, NotGeneric(c.name)),
+      (None, None) => None,
+    };
+    TyKind::Object(Ref(c), non_null, arg)
+  }
+
+  // the type of `this` in class `c`, it is never null, and the type argument of a generic class is its own parameter
+  fn this_ty(&self, c: &'a ClassDef<'a>) -> Ty<'a> {
+    Ty::new(TyKind::Object(Ref(c), true, c.ty_param.map(|_| &*self.alloc.ty.alloc(Ty::new(TyKind::Param(Ref(c)))))))
+  }
+
+  // the underlying type of `a`, it is resolved at the first call
+  fn alias_ty(&mut self, a: &'a TypeAlias<'a>) -> Ty<'a> {
+    if let Some(t) = a.ty.get() { return t; }
+    if a.resolving.get() { return self.issue(a.loc, CyclicTypeAlias(a.name)); }
+    a.resolving.set(true);
+    let t = self.ty(&a.syn_ty, false);
+    a.resolving.set(false);
+    a.ty.set(Some(t));
+    t
+  }
 }
 
 impl<'a> Deref for TypeCk<'a> {
@@ -26,10 +26,30 @@ impl<'a> SymbolPass<'a> {
         self.scopes.declare(Symbol::Class(c));
       }
     }
+    for a in &p.alias {
+      if let Some(prev) = self.scopes.lookup_class(a.name).map(|c| c.loc).or_else(|| self.alias.get(a.name).map(|a| a.loc)) {
+        self.issue(a.loc, ConflictDeclaration { prev, name: a.name })
+      } else {
+        self.alias.insert(a.name, a);
+      }
+    }
+    // resolve all aliases here, so that a cyclic alias is reported even if it is never used
+    for a in &p.alias { self.alias_ty(a); }
     for c in &p.class {
       if let Some(p) = c.parent {
-        c.parent_ref.set(self.scopes.lookup_class(p));
-        if c.parent_ref.get().is_none() { self.issue(c.loc, NoSuchClass(p)) }
+        // an interface can't be extended, so it is not found here
+        c.parent_ref.set(self.scopes.lookup_class(p).filter(|p| !p.interface));
+        match c.parent_ref.get() {
+          None => self.issue(c.loc, NoSuchClass(p)),
+          Some(pc) if pc.ty_param.is_some() => self.issue(c.loc, GenericParent(p)),
+          _ => {}
+        }
+      }
+      for &(loc, i) in &c.implements {
+        match self.scopes.lookup_class(i) {
+          Some(i) if i.interface => c.implements_ref.borrow_mut().push(i),
+          _ => self.issue(loc, NoSuchInterface(i)),
+        }
       }
     }
     // detect cyclic inheritance
@@ -44,14 +64,25 @@ impl<'a> SymbolPass<'a> {
             if let Some(p) = c.parent_ref.get() { (last = c, c = p); } else { break; }
           }
           Entry::Occupied(o) => {
-            if *o.get() == idx { self.issue(last.loc, CyclicInheritance) }
+            if *o.get() == idx {
+              // `c` is the first class in the cycle, and `last` is the one extending it
+              let (mut cycle, mut x) = (vec![c.name], c);
+              loop {
+                x = x.parent_ref.get().unwrap();
+                cycle.push(x.name);
+                if Ref(x) == Ref(c) { break; }
+              }
+              self.issue::<()>(last.loc, CyclicInheritance(cycle));
+              // break the cycle, so that walking up the inheritance chain always terminates in later passes
+              last.parent_ref.set(None);
+            }
             break;
           }
         }
       }
     }
     // errors related to inheritance are considered as fatal errors, return after these checks if a error occurred
-    if !self.errors.0.is_empty() { return; }
+    if self.errors.has_error() { return; }
     let mut checked = HashSet::new();
     for c in &p.class {
       self.class_def(c, &mut checked);
@@ -67,34 +98,49 @@ impl<'a> SymbolPass<'a> {
     if let Some(p) = c.parent_ref.get() { self.class_def(p, checked); }
     self.cur_class = Some(c);
     self.scoped(ScopeOwner::Class(c), |s| for f in &c.field {
+      s.in_static = match f { FieldDef::FuncDef(f) => f.static_, FieldDef::VarDef(v) => v.static_ };
       match f { FieldDef::FuncDef(f) => s.func_def(f), FieldDef::VarDef(v) => s.var_def(v) };
     });
+    // the parent's constructor is implicitly called without arguments when constructing `c`, see tacgen's NewClass
+    if let Some(p) = c.parent_ref.get() {
+      if !p.ctors().is_empty() && p.default_ctor().is_none() { self.issue(c.loc, NoDefaultCtor { class: c.name, parent: p.name }) }
+    }
   }
 
   fn func_def(&mut self, f: &'a FuncDef<'a>) {
     let ret_ty = self.ty(&f.ret, false);
+    // the default values are checked in class scope in TypePass, so they are visited here
+    for v in &f.param { if let Some(e) = v.init() { self.expr(e); } }
     self.scoped(ScopeOwner::Param(f), |s| {
       if !f.static_ { s.scopes.declare(Symbol::This(f)); }
       for v in &f.param { s.var_def(v); }
-      s.block(&f.body);
+      if let Some(b) = &f.body { s.block(b); }
     });
     let ret_param_ty = iter::once(ret_ty).chain(f.param.iter().map(|v| v.ty.get()));
     let ret_param_ty = self.alloc.ty.alloc_extend(ret_param_ty);
     f.ret_param_ty.set(Some(ret_param_ty));
     f.class.set(self.cur_class);
+    if f.ctor {
+      // constructors are not declared in class scope, they are found by `ClassDef::ctors`
+      let c = self.cur_class.unwrap();
+      if f.name != c.name {
+        self.issue(f.loc, BadCtorName { name: f.name, class: c.name })
+      } else if let Some(prev) = c.ctors().into_iter().take_while(|g| Ref(*g) != Ref(f)).find(|g| g.param_ty() == f.param_ty()) {
+        self.issue(f.loc, ConflictDeclaration { prev: prev.loc, name: f.name })
+      }
+      return;
+    }
     let ok = if let Some((sym, owner)) = self.scopes.lookup(f.name) {
-      match (self.scopes.cur_owner(), owner) {
-        (ScopeOwner::Class(c), ScopeOwner::Class(p)) if Ref(c) != Ref(p) => {
-          match sym {
-            Symbol::Func(pf) => {
-              if f.static_ || pf.static_ {
-                self.issue(f.loc, ConflictDeclaration { prev: pf.loc, name: f.name })
-              } else if !Ty::mk_func(f).assignable_to(Ty::mk_func(pf)) {
-                self.issue(f.loc, OverrideMismatch { func: f.name, p: p.name })
-              } else { true }
-            }
-            _ => self.issue(f.loc, ConflictDeclaration { prev: sym.loc(), name: f.name }),
+      match (self.scopes.cur_owner(), owner, sym) {
+        (ScopeOwner::Class(c), ScopeOwner::Class(p), Symbol::Func(_)) if Ref(c) != Ref(p) => self.check_override(f, c),
+        // overloading in the same class, methods with the same parameter types conflict
+        (ScopeOwner::Class(c), ScopeOwner::Class(_), Symbol::Func(first)) => {
+          if let Some(prev) = first.overloads().find(|g| g.param_ty() == f.param_ty()) {
+            self.issue(f.loc, ConflictDeclaration { prev: prev.loc, name: f.name })
+          } else if self.check_override(f, c) {
+            first.overloads().last().unwrap().next.set(Some(f));
           }
+          false // only the first one is declared in scope
         }
         _ => self.issue(f.loc, ConflictDeclaration { prev: sym.loc(), name: f.name }),
       }
@@ -102,6 +148,20 @@ impl<'a> SymbolPass<'a> {
     if ok { self.scopes.declare(Symbol::Func(f)); }
   }
 
+  // if there is a method in `c`'s ancestors with the same name and parameter types as `f`, `f` overrides it
+  // otherwise `f` is a new overloaded method, so nothing to check
+  // the overriding method's return type can be a subtype of the overridden one's (checked by `assignable_to` on function types)
+  // the parameter types are always equal here, and a static method can neither override nor be overridden
+  fn check_override(&mut self, f: &'a FuncDef<'a>, c: &'a ClassDef<'a>) -> bool {
+    let pf = c.parent_ref.get().and_then(|p| p.lookup_funcs(f.name).into_iter().find(|g| g.param_ty() == f.param_ty()));
+    match pf {
+      Some(pf) if f.static_ || pf.static_ => self.issue(f.loc, BadOverride { func: f.name, p: pf.class.get().unwrap().name, static_: f.static_ }),
+      Some(pf) if !Ty::mk_func(f).assignable_to(Ty::mk_func(pf)) =>
+        self.issue(f.loc, OverrideMismatch { func: f.name, p: pf.class.get().unwrap().name }),
+      _ => true,
+    }
+  }
+
   fn var_def(&mut self, v: &'a VarDef<'a>) {
     v.ty.set(self.ty(&v.syn_ty, false));
     if v.ty.get() == Ty::void() { self.issue(v.loc, VoidVar(v.name)) }
@@ -109,8 +169,12 @@ impl<'a> SymbolPass<'a> {
       match (self.scopes.cur_owner(), owner) {
         (ScopeOwner::Class(c1), ScopeOwner::Class(c2)) if Ref(c1) != Ref(c2) && sym.is_var() =>
           self.issue(v.loc, OverrideVar(v.name)),
-        (ScopeOwner::Class(_), ScopeOwner::Class(_)) | (_, ScopeOwner::Param(_)) | (_, ScopeOwner::Local(_)) =>
-          self.issue(v.loc, ConflictDeclaration { prev: sym.loc(), name: v.name }),
+        (ScopeOwner::Class(_), ScopeOwner::Class(_)) => self.issue(v.loc, ConflictDeclaration { prev: sym.loc(), name: v.name }),
+        // redeclaring in the same scope is an error, shadowing a local variable or parameter in an enclosing scope is only a warning
+        // shadowing a field is allowed silently
+        (cur, _) if cur.scope().contains_key(v.name) => self.issue(v.loc, ConflictDeclaration { prev: sym.loc(), name: v.name }),
+        (_, ScopeOwner::Param(_)) | (_, ScopeOwner::Lambda(_)) | (_, ScopeOwner::Local(_)) =>
+          (self.issue::<()>(v.loc, ShadowedVar { prev: sym.loc(), name: v.name }), true).1,
         _ => true,
       }
     } else { true };
@@ -124,21 +188,100 @@ impl<'a> SymbolPass<'a> {
     self.scoped(ScopeOwner::Local(b), |s| for st in &b.stmt { s.stmt(st); });
   }
 
+  // the expressions are visited only to find lambdas, which contain scopes
   fn stmt(&mut self, s: &'a Stmt<'a>) {
     match &s.kind {
-      StmtKind::LocalVarDef(v) => self.var_def(v),
+      StmtKind::Assign(a) => {
+        self.expr(&a.dst);
+        self.expr(&a.src);
+      }
+      StmtKind::LocalVarDef(v) => {
+        if let Some(e) = v.init() { self.expr(e); }
+        self.var_def(v);
+      }
+      StmtKind::ExprEval(e) => self.expr(e),
       StmtKind::If(i) => {
+        self.expr(&i.cond);
         self.block(&i.on_true);
         if let Some(of) = &i.on_false { self.block(of); }
       }
-      StmtKind::While(w) => self.block(&w.body),
+      StmtKind::While(w) => {
+        self.expr(&w.cond);
+        self.block(&w.body);
+      }
+      StmtKind::DoWhile(d) => {
+        self.block(&d.body);
+        self.expr(&d.cond);
+      }
       StmtKind::For(f) => self.scoped(ScopeOwner::Local(&f.body), |s| {
-        s.stmt(&f.init);
-        s.stmt(&f.update);
+        for st in &f.init { s.stmt(st); }
+        s.expr(&f.cond);
+        for st in &f.update { s.stmt(st); }
         for st in &f.body.stmt { s.stmt(st); }
       }),
+      StmtKind::Foreach(fe) => {
+        self.expr(&fe.arr);
+        self.scoped(ScopeOwner::Local(&fe.body), |s| {
+          s.var_def(fe.var);
+          for st in &fe.body.stmt { s.stmt(st); }
+        });
+      }
+      StmtKind::Switch(sw) => {
+        self.expr(&sw.expr);
+        for c in &sw.case {
+          self.expr(&c.label);
+          self.block(&c.body);
+        }
+        for d in &sw.default { self.block(d); }
+      }
+      StmtKind::Return(r) => if let Some(e) = r { self.expr(e); }
+      StmtKind::Print(p) => for e in p { self.expr(e); }
+      StmtKind::Printf(p) => for e in &p.arg { self.expr(e); }
       StmtKind::Block(b) => self.block(b),
-      _ => {}
+      StmtKind::Skip(_) | StmtKind::Break(_) | StmtKind::Continue(_) => {}
     };
   }
+
+  fn expr(&mut self, e: &'a Expr<'a>) {
+    use ExprKind::*;
+    match &e.kind {
+      VarSel(v) => if let Some(o) = &v.owner { self.expr(o); }
+      IndexSel(i) => {
+        self.expr(&i.arr);
+        self.expr(&i.idx);
+      }
+      Slice(s) => (self.expr(&s.arr), self.expr(&s.lo), self.expr(&s.hi)).2,
+      Call(c) => {
+        self.expr(&c.func);
+        for a in &c.arg { self.expr(a); }
+      }
+      Unary(u) => self.expr(&u.r),
+      Binary(b) => {
+        self.expr(&b.l);
+        self.expr(&b.r);
+      }
+      IncDec(i) => self.expr(&i.e),
+      Assign(a) => {
+        self.expr(&a.dst);
+        self.expr(&a.src);
+      }
+      Ternary(t) => {
+        self.expr(&t.cond);
+        self.expr(&t.on_true);
+        self.expr(&t.on_false);
+      }
+      ArrayLit(a) => for e in a { self.expr(e); }
+      Tuple(t) => for e in &t.elem { self.expr(e); }
+      TupleSel(t) => self.expr(&t.tuple),
+      NewClass(n) => for a in &n.arg { self.expr(a); }
+      NewArray(n) => (self.expr(&n.len), for l in &n.sub_len { self.expr(l); }).1,
+      ClassTest(c) => self.expr(&c.expr),
+      ClassCast(c) => self.expr(&c.expr),
+      Lambda(l) => self.scoped(ScopeOwner::Lambda(l), |s| {
+        for v in &l.param { s.var_def(v); }
+        match &l.body { LambdaBody::Expr(e) => s.expr(e), LambdaBody::Block(b) => s.block(b) }
+      }),
+      IntLit(_) | CharLit(_) | DoubleLit(_) | BoolLit(_) | StringLit(_) | NullLit(_) | This(_) | Super(_) | ReadInt(_) | ReadDouble(_) | ReadLine(_) => {}
+    }
+  }
 }
\ No newline at end of file
@@ -1,11 +1,14 @@
-use crate::{TypeCk, TypeCkTrait};
-use common::{ErrorKind::*, Loc, LENGTH, BinOp, UnOp, ErrorKind, Ref};
+use crate::{TypeCk, TypeCkTrait, LambdaCtx};
+use common::{ErrorKind::*, Loc, NO_LOC, LENGTH, SUBSTRING, STRING_TO_INT, INT_TO_STRING, BinOp, UnOp, ErrorKind, Ref};
 use syntax::ast::*;
 use syntax::{ScopeOwner, Symbol, ty::*};
-use std::ops::{Deref, DerefMut};
+use std::{ops::{Deref, DerefMut}, iter, mem};
 
 pub(crate) struct TypePass<'a>(pub TypeCk<'a>);
 
+// the return value of `stmt` for a stmt that doesn't return and has no branch
+const FALL_THROUGH: Option<Loc> = Some(NO_LOC);
+
 impl<'a> Deref for TypePass<'a> {
   type Target = TypeCk<'a>;
   fn deref(&self) -> &Self::Target { &self.0 }
@@ -22,88 +25,290 @@ impl<'a> TypePass<'a> {
 
   fn class_def(&mut self, c: &'a ClassDef<'a>) {
     self.cur_class = Some(c);
+    // a non-abstract class must override all abstract methods, including those declared in itself
+    // there is no cyclic inheritance in this phase, so the loop will end
+    if !c.abstract_ && iter::successors(Some(c), |c| c.parent_ref.get()).flat_map(|p| p.field.iter()).any(|f| match f {
+      FieldDef::FuncDef(f) if f.abstract_ => c.lookup_funcs(f.name).iter().any(|f| f.abstract_),
+      _ => false,
+    }) { self.issue(c.loc, NotOverrideAbstract(c.name)) }
+    // a non-abstract class must implement all methods in the interfaces implemented by it and its ancestors
+    // the implementation is found and checked like an overriding method
+    if !c.abstract_ {
+      for i in iter::successors(Some(c), |c| c.parent_ref.get()).flat_map(|p| p.implements_ref.borrow().clone()) {
+        for f in &i.field {
+          if let FieldDef::FuncDef(f) = f {
+            let imp = c.lookup_funcs(f.name).into_iter().find(|g| g.param_ty() == f.param_ty());
+            if !imp.map_or(false, |g| !g.static_ && !g.abstract_ && Ty::mk_func(g).assignable_to(Ty::mk_func(f))) {
+              self.issue(c.loc, NotImplementInterface { class: c.name, interface: i.name, func: f.name })
+            }
+          }
+        }
+      }
+    }
     self.scoped(ScopeOwner::Class(c), |s| for f in &c.field {
       if let FieldDef::FuncDef(f) = f {
         s.cur_func = Some(f);
-        let ret = s.scoped(ScopeOwner::Param(f), |s| s.block(&f.body));
-        if !ret && f.ret_ty() != Ty::void() { s.issue(f.body.loc, ErrorKind::NoReturn) }
+        s.in_static = f.static_;
+        s.check_default(f);
+        if let Some(b) = &f.body {
+          if let Some(loc) = s.scoped(ScopeOwner::Param(f), |s| s.block(b)) {
+            if f.ret_ty() != Ty::void() { s.issue(loc, ErrorKind::NoReturn) }
+          }
+          for (loc, idx, len, var) in mem::take(&mut s.const_index) {
+            if var.map(|v| !s.assigned.contains(&Ref(v))).unwrap_or(true) { s.issue(loc, IndexOutOfBounds { idx, len }) }
+          }
+          for (loc, var) in mem::take(&mut s.null_deref) {
+            if !s.non_null_assigned.contains(&Ref(var)) { s.issue(loc, NullDereference) }
+          }
+          s.assigned.clear();
+          s.non_null_assigned.clear();
+        }
       };
     });
   }
 
-  fn block(&mut self, b: &'a Block<'a>) -> bool {
-    let mut ret = false;
-    self.scoped(ScopeOwner::Local(b), |s| for st in &b.stmt { ret = s.stmt(st); });
+  // see `stmt` for the meaning of return value, a block is a branch, so NO_LOC is never returned
+  fn block(&mut self, b: &'a Block<'a>) -> Option<Loc> {
+    let ret = self.scoped(ScopeOwner::Local(b), |s| s.block_stmt(b));
+    self.check_unused(b);
     ret
   }
 
-  // return whether this stmt has a return value
-  fn stmt(&mut self, s: &'a Stmt<'a>) -> bool {
+  // check `b` with the variable narrowed to null or non-null
+  fn narrowed_block(&mut self, b: &'a Block<'a>, narrowed: Option<(&'a VarDef<'a>, bool)>) -> Option<Loc> {
+    let len = self.narrowed.len();
+    self.narrowed.extend(narrowed);
+    let ret = self.block(b);
+    self.narrowed.truncate(len);
+    ret
+  }
+
+  // the statements of `b` with its scope already opened, used by `block` and the loops whose variables are in the body's scope
+  // only the first statement after a terminating one is reported as unreachable
+  fn block_stmt(&mut self, b: &'a Block<'a>) -> Option<Loc> {
+    let (mut ret, mut ended, mut reported) = (FALL_THROUGH, false, false);
+    for st in &b.stmt {
+      if ended && !reported { self.issue(st.loc.to(st.end), UnreachableCode) }
+      reported |= ended;
+      // once all paths have returned, the unreachable statements after can't make the block fall through
+      let r = self.stmt(st);
+      if ret.is_some() { ret = r; }
+      ended |= terminates(st);
+    }
+    ret.map(|loc| if loc == NO_LOC { b.loc } else { loc })
+  }
+
+  // the variables whose names begin with `_` are intended to be unused
+  // they are reported in the order of definition, not the order in the hash map, so that the output is deterministic
+  fn check_unused(&mut self, b: &'a Block<'a>) {
+    let mut unused = b.scope.borrow().values().filter_map(|sym| match sym {
+      Symbol::Var(v) if !v.used.get() && !v.name.starts_with('_') => Some(*v),
+      _ => None,
+    }).collect::<Vec<_>>();
+    unused.sort_unstable_by_key(|v| v.loc);
+    for v in unused { self.issue(v.loc.to(v.end), UnusedVar(v.name)) }
+  }
+
+  // the statement `a = b` / `a op= b`, or the expression `a = b`, return the type of the value assigned, i.e., `b` / `a op b`
+  // which may be a subtype of `a`'s type, if it doesn't type check, the type of `a` is returned to avoid more errors
+  fn assign(&mut self, a: &'a Assign<'a>, loc: Loc) -> Ty<'a> {
+    // `a = b` doesn't read `a`, but `a += b` does
+    if let (ExprKind::VarSel(VarSel { owner: None, .. }), None) = (&a.dst.kind, a.op) { self.cur_lvalue = true; }
+    let l = self.expr(&a.dst);
+    let op_str = a.op.map_or("=", |op| op.to_assign_str().unwrap());
+    // the lr parser only guarantees that `dst` looks like an lvalue, and the ll parser accepts any expression
+    // a VarSel without a variable is a method or a class name
+    let bad = match &a.dst.kind {
+      ExprKind::This(_) => Some(AssignToThis),
+      ExprKind::VarSel(VarSel { var, .. }) if var.get().is_none() => Some(NotLValue(op_str)),
+      ExprKind::VarSel(_) | ExprKind::IndexSel(_) => None,
+      _ => Some(NotLValue(op_str)),
+    };
+    if let Some(e) = bad {
+      if l != Ty::error() { self.issue(loc, e) }
+      return (self.expr(&a.src), Ty::error()).1;
+    }
+    self.check_captured_assign(&a.dst, loc);
+    self.check_final_assign(&a.dst, loc);
+    if let ExprKind::VarSel(VarSel { owner: None, var, .. }) = &a.dst.kind {
+      if let Some(var) = var.get() {
+        self.assigned.insert(Ref(var));
+        if a.op.is_some() || !is_null(&a.src) { self.non_null_assigned.insert(Ref(var)); }
+      }
+    }
+    let r = self.expr_expect(&a.src, l);
+    if let Some(op) = a.op {
+      // `a op= b` is checked like `a = a op b`, but the error message uses `op=` as the operator
+      // the result of `a op b` may not be assignable to `a`, e.g.: `i += 1.5` where `i` is int, or array concatenation
+      // if `a op b` itself doesn't type check, the error is already reported
+      self.check_const_rhs(op, l, r, &a.src, loc);
+      let (ret, ok) = self.binary_ty(op, op_str, l, r, loc);
+      if ok && !ret.assignable_to(l) { self.issue(loc, IncompatibleBinary { l, op: op_str, r }) }
+      if ok && ret.assignable_to(l) { ret } else { l }
+    } else {
+      self.check_assignable(r, l, loc, IncompatibleBinary { l, op: "=", r });
+      if r.assignable_to(l) { r } else { l }
+    }
+  }
+
+  // return None if this stmt always returns a value, otherwise return the loc of the first path falling through it
+  // the loc is the innermost branch (e.g.: an if without else, a block) on that path, or NO_LOC if there is no branch
+  fn stmt(&mut self, s: &'a Stmt<'a>) -> Option<Loc> {
     match &s.kind {
       StmtKind::Assign(a) => {
-        let (l, r) = (self.expr(&a.dst), self.expr(&a.src));
-        if !r.assignable_to(l) { self.issue(s.loc, IncompatibleBinary { l, op: "=", r }) }
-        false
+        self.assign(a, s.loc);
+        if a.op.is_none() && self_assign(a) { self.issue(s.loc, SelfAssign) }
+        FALL_THROUGH
       }
       StmtKind::LocalVarDef(v) => {
         self.cur_var_def = Some(v);
-        if let Some((loc, e)) = &v.init {
-          let (l, r) = (v.ty.get(), self.expr(e));
-          if !r.assignable_to(l) { self.issue(*loc, IncompatibleBinary { l, op: "=", r }) }
+        if v.final_ && v.init.is_none() { self.issue(v.loc, FinalNoInit(v.name)) }
+        if v.syn_ty.kind == SynTyKind::Var {
+          let r = v.init.as_ref().map(|(_, e)| self.expr(e)).unwrap_or(Ty::void());
+          // `r` is error => v.ty is already error
+          // like a declared type, the inferred type is nullable unless `!` is written, e.g.: `var a = new A();` has type `class A`
+          if r == Ty::null() || r == Ty::void() || r.is_class() { self.issue(v.loc, CannotInferType(v.name)) } else { v.ty.set(r.nullable()) }
+        } else if let Some((loc, e)) = &v.init {
+          let (l, r) = (v.ty.get(), self.expr_expect(e, v.ty.get()));
+          self.check_assignable(r, l, *loc, IncompatibleBinary { l, op: "=", r });
         }
         self.cur_var_def = None;
-        false
+        FALL_THROUGH
       }
       StmtKind::ExprEval(e) => {
         self.expr(e);
-        false
+        FALL_THROUGH
       }
-      StmtKind::Skip(_) => false,
+      StmtKind::Skip(_) => FALL_THROUGH,
       StmtKind::If(i) => {
         self.check_bool(&i.cond);
-        // `&` is not short-circuit evaluated
-        self.block(&i.on_true) & i.on_false.as_ref().map(|b| self.block(b)).unwrap_or(false)
+        let test = null_test(&i.cond);
+        // both branches should be checked, and the missing else branch is reported as the if stmt
+        let on_true = self.narrowed_block(&i.on_true, test);
+        let on_false = i.on_false.as_ref().map(|b| self.narrowed_block(b, test.map(|(v, null)| (v, !null)))).unwrap_or(Some(s.loc));
+        // if the condition is constant, only the selected branch is reachable
+        match i.cond.result.get() { Some(0) => on_false, Some(_) => on_true, None => on_true.or(on_false) }
       }
       StmtKind::While(w) => {
         self.check_bool(&w.cond);
-        self.loop_cnt += 1;
+        self.push_loop(w.label, s.loc);
         self.block(&w.body);
-        self.loop_cnt -= 1;
-        false
-      }
-      StmtKind::For(f) => self.scoped(ScopeOwner::Local(&f.body), |s| {
-        s.stmt(&f.init);
-        s.check_bool(&f.cond);
-        s.stmt(&f.update);
-        s.loop_cnt += 1;
-        for st in &f.body.stmt { s.stmt(st); } // not calling block(), because the scope is already opened
-        s.loop_cnt -= 1;
-        false
+        self.loop_stk.pop();
+        // `while (true)` without `break` never completes, so the statements after it are not required to return
+        if infinite(w) { None } else { FALL_THROUGH }
+      }
+      StmtKind::DoWhile(d) => {
+        self.push_loop(d.label, s.loc);
+        self.block(&d.body);
+        self.loop_stk.pop();
+        self.check_bool(&d.cond);
+        // even if the body returns, a `break` in it can still skip the return
+        FALL_THROUGH
+      }
+      StmtKind::For(f) => self.scoped(ScopeOwner::Local(&f.body), |st| {
+        for s in &f.init { st.stmt(s); }
+        st.check_bool(&f.cond);
+        for s in &f.update { st.stmt(s); }
+        st.push_loop(f.label, s.loc);
+        st.block_stmt(&f.body);
+        st.loop_stk.pop();
+        st.check_unused(&f.body);
+        FALL_THROUGH
       }),
+      StmtKind::Foreach(fe) => {
+        // `arr` is checked outside the loop variable's scope
+        let arr = self.expr(&fe.arr);
+        let elem = match arr {
+          Ty { arr, kind } if arr > 0 => Ty { arr: arr - 1, kind },
+          e => e.error_or(|| self.issue(fe.arr.loc, ForeachNotArray)),
+        };
+        let ty = fe.var.ty.get();
+        self.check_assignable(elem, ty, fe.var.loc, IncompatibleBinary { l: ty, op: "=", r: elem });
+        self.scoped(ScopeOwner::Local(&fe.body), |st| {
+          st.push_loop(fe.label, s.loc);
+          st.block_stmt(&fe.body);
+          st.loop_stk.pop();
+        });
+        self.check_unused(&fe.body);
+        FALL_THROUGH
+      }
+      StmtKind::Switch(sw) => {
+        let ty = self.expr(&sw.expr);
+        if ty != Ty::int() { ty.error_or(|| self.issue(sw.expr.loc, SwitchNotInt)) }
+        let mut labels = Vec::new();
+        self.switch_cnt += 1;
+        // it returns only if all cases and the default return, so it never returns without default
+        // a case returns only if it has no `break` jumping out of the switch, even if its last statement returns
+        let mut ret = None;
+        for c in &sw.case {
+          let ty = self.expr(&c.label);
+          match c.label.result.get() {
+            Some(x) if ty == Ty::int() => if labels.contains(&x) { self.issue(c.label.loc, DuplicateCase(x)) } else { labels.push(x) },
+            _ => ty.error_or(|| self.issue(c.label.loc, CaseNotConst)),
+          }
+          let r = self.block(&c.body);
+          ret = ret.or(r).or_else(|| switch_break(&c.body));
+        }
+        // only the first default is used in computing the return, the others are reported
+        let mut d = Some(s.loc);
+        for (i, b) in sw.default.iter().enumerate() {
+          let r = self.block(b).or_else(|| switch_break(b));
+          if i == 0 { d = r } else { self.issue(b.loc, DuplicateDefault) }
+        }
+        self.switch_cnt -= 1;
+        ret.or(d)
+      }
+      StmtKind::Return(r) if !self.lambda_stk.is_empty() => {
+        // the return type of lambda is determined by all return statements, so they are checked in `lambda`
+        let actual = r.as_ref().map(|e| self.expr(e)).unwrap_or(Ty::void());
+        self.lambda_stk.last_mut().unwrap().ret.push((s.loc, actual));
+        if actual != Ty::void() { None } else { FALL_THROUGH }
+      }
       StmtKind::Return(r) => {
         let expect = self.cur_func.unwrap().ret_ty();
-        let actual = r.as_ref().map(|e| self.expr(e)).unwrap_or(Ty::void());
-        if !actual.assignable_to(expect) { self.issue(s.loc, ReturnMismatch { actual, expect }) }
-        actual != Ty::void()
+        let actual = r.as_ref().map(|e| self.expr_expect(e, expect)).unwrap_or(Ty::void());
+        // `return;` in a non-void function is still a ReturnMismatch
+        if expect == Ty::void() && actual != Ty::void() {
+          actual.error_or(|| self.issue(s.loc, ReturnInVoid(actual)))
+        } else { self.check_assignable(actual, expect, s.loc, ReturnMismatch { actual, expect }) }
+        if actual != Ty::void() { None } else { FALL_THROUGH }
       }
       StmtKind::Print(p) => {
         for (i, e) in p.iter().enumerate() {
           let ty = self.expr(e);
-          if ty != Ty::bool() && ty != Ty::int() && ty != Ty::string() {
+          if ty != Ty::bool() && ty != Ty::int() && ty != Ty::char() && ty != Ty::double() && ty != Ty::string() {
             ty.error_or(|| self.issue(e.loc, BadPrintArg { loc: i as u32 + 1, ty }))
           }
         }
-        false
+        FALL_THROUGH
+      }
+      StmtKind::Printf(p) => {
+        // None for a bad specifier, which matches no argument
+        let spec = p.parts().into_iter().filter_map(|x| match x { FmtPart::Lit(_) => None, FmtPart::Arg(t) => Some(Some(t)), FmtPart::Bad => Some(None) }).collect::<Vec<_>>();
+        for (i, e) in p.arg.iter().enumerate() {
+          let ty = self.expr(e);
+          match spec.get(i) {
+            Some(&Some(t)) if ty == t => {}
+            _ => ty.error_or(|| self.issue(e.loc, FormatMismatch(i as u32 + 1))),
+          }
+        }
+        // the specifiers without arguments
+        for i in p.arg.len()..spec.len() { self.issue(s.loc, FormatMismatch(i as u32 + 1)) }
+        FALL_THROUGH
       }
-      StmtKind::Break(_) => {
-        if self.loop_cnt == 0 { self.issue(s.loc, BreakOutOfLoop) }
-        false
+      StmtKind::Break(b) => {
+        if b.label.is_some() || self.switch_cnt == 0 { self.check_jump(b.label, s.loc, BreakOutOfLoop); }
+        FALL_THROUGH
+      }
+      StmtKind::Continue(c) => {
+        self.check_jump(c.label, s.loc, ContinueOutOfLoop);
+        FALL_THROUGH
       }
       StmtKind::Block(b) => self.block(b),
     }
   }
 
-  // e.ty is set to the return value
+  // e.ty is set to the return value, e.result is set if e can be statically evaluated
   fn expr(&mut self, e: &'a Expr<'a>) -> Ty<'a> {
     use ExprKind::*;
     let ty = match &e.kind {
@@ -111,47 +316,125 @@ impl<'a> TypePass<'a> {
       IndexSel(i) => {
         let (arr, idx) = (self.expr(&i.arr), self.expr(&i.idx));
         if idx != Ty::int() { idx.error_or(|| self.issue(e.loc, IndexNotInt)) }
+        if let (Some(x), Some((len, var))) = (i.idx.result.get(), static_len(&i.arr)) {
+          if x < 0 || x >= len { self.const_index.push((e.loc, x, len, var)); }
+        }
         match arr {
           Ty { arr, kind } if arr > 0 => Ty { arr: arr - 1, kind },
           e => e.error_or(|| self.issue(i.arr.loc, IndexNotArray)),
         }
       }
-      IntLit(_) | ReadInt(_) => Ty::int(), BoolLit(_) => Ty::bool(), StringLit(_) | ReadLine(_) => Ty::string(), NullLit(_) => Ty::null(),
+      Slice(s) => {
+        let (arr, lo, hi) = (self.expr(&s.arr), self.expr(&s.lo), self.expr(&s.hi));
+        if lo != Ty::int() { lo.error_or(|| self.issue(e.loc, IndexNotInt)) }
+        if hi != Ty::int() { hi.error_or(|| self.issue(e.loc, IndexNotInt)) }
+        if arr.arr > 0 { arr } else { arr.error_or(|| self.issue(s.arr.loc, IndexNotArray)) }
+      }
+      IntLit(_) | ReadInt(_) => Ty::int(), CharLit(_) => Ty::char(), DoubleLit(_) | ReadDouble(_) => Ty::double(), BoolLit(_) => Ty::bool(), StringLit(_) | ReadLine(_) => Ty::string(), NullLit(_) => Ty::null(),
       Call(c) => self.call(c, e.loc),
       Unary(u) => {
         let r = self.expr(&u.r);
-        let (ty, op) = match u.op { UnOp::Neg => (Ty::int(), "-"), UnOp::Not => (Ty::bool(), "!"), };
+        let (ty, op) = match u.op {
+          UnOp::Neg => (if r == Ty::double() { r } else { Ty::int() }, "-"),
+          UnOp::Not => (Ty::bool(), "!"),
+          UnOp::BitNot => (Ty::int(), "~"),
+        };
         if r != ty { r.error_or(|| self.issue(e.loc, IncompatibleUnary { op, r })) }
         ty
       }
       Binary(b) => {
-        use BinOp::*;
         let (l, r) = (self.expr(&b.l), self.expr(&b.r));
-        if l == Ty::error() || r == Ty::error() {
-          // not using wildcard match, so that if we add new operators in the future, compiler can tell us
-          match b.op { Add | Sub | Mul | Div | Mod => Ty::int(), And | Or | Eq | Ne | Lt | Le | Gt | Ge => Ty::bool() }
-        } else {
-          let (ret, ok) = match b.op {
-            Add | Sub | Mul | Div | Mod => (Ty::int(), l == Ty::int() && r == Ty::int()),
-            Lt | Le | Gt | Ge => (Ty::bool(), l == Ty::int() && r == Ty::int()),
-            Eq | Ne => (Ty::bool(), l.assignable_to(r) || r.assignable_to(l)),
-            And | Or => (Ty::bool(), l == Ty::bool() && r == Ty::bool())
-          };
-          if !ok { self.issue(e.loc, IncompatibleBinary { l, op: b.op.to_op_str(), r }) }
-          ret
+        self.check_const_rhs(b.op, l, r, &b.r, e.loc);
+        // null is not an object type, so `o == null` is not warned
+        if (b.op == BinOp::Eq || b.op == BinOp::Ne) && l.is_object() && r.is_object() { self.issue(e.loc, ObjectIdentityCompare) }
+        self.binary_ty(b.op, b.op.to_op_str(), l, r, e.loc).0
+      }
+      Ternary(t) => {
+        self.check_bool(&t.cond);
+        let (l, r) = (self.expr(&t.on_true), self.expr(&t.on_false));
+        // the common type of error and another type is that type, but the ternary expression should be error
+        if l == Ty::error() || r == Ty::error() { Ty::error() } else {
+          l.common(r).unwrap_or_else(|| self.issue(e.loc, IncompatibleBinary { l, op: ":", r }))
         }
       }
+      Assign(a) => self.assign(a, e.loc),
+      IncDec(i) => {
+        let r = self.expr(&i.e);
+        let op = if i.op == BinOp::Add { "++" } else { "--" };
+        match &i.e.kind {
+          VarSel(_) | IndexSel(_) if !r.is_func() => if r != Ty::int() { r.error_or(|| self.issue(e.loc, IncompatibleUnary { op, r })) }
+          _ => self.issue(e.loc, NotLValue(op)),
+        }
+        self.check_captured_assign(&i.e, e.loc);
+        self.check_final_assign(&i.e, e.loc);
+        if let VarSel(v) = &i.e.kind {
+          if let (None, Some(var)) = (&v.owner, v.var.get()) { self.assigned.insert(Ref(var)); }
+        }
+        Ty::int()
+      }
+      ArrayLit(a) => if a.is_empty() { self.issue(e.loc, ArrayLitNoElemTy) } else {
+        let mut elem = self.expr(&a[0]);
+        for x in &a[1..] {
+          let ty = self.expr(x);
+          elem = elem.common(ty).unwrap_or_else(|| self.issue(x.loc, IncompatibleBinary { l: elem, op: ",", r: ty }));
+        }
+        match elem {
+          e if e == Ty::error() => e,
+          e if e == Ty::void() => self.issue(a[0].loc, VoidArrayElement),
+          // e.g.: `[null, null]`
+          e if e == Ty::null() || e.is_class() => self.issue(a[0].loc, ArrayLitNoElemTy),
+          // the elements of an array can be assigned null later, so they are never non-null
+          e => { let Ty { arr, kind } = e.nullable(); Ty { arr: arr + 1, kind } }
+        }
+      }
+      Tuple(t) => {
+        let elem = t.elem.iter().map(|x| self.expr(x)).collect::<Vec<_>>();
+        if let Some((x, &ty)) = t.elem.iter().zip(&elem).find(|(_, ty)| **ty == Ty::void() || ty.is_class()) {
+          self.issue(x.loc, BadTupleElem(ty))
+        } else if elem.contains(&Ty::error()) { Ty::error() } else { Ty::new(TyKind::Tuple(self.alloc.ty.alloc_extend(elem))) }
+      }
+      TupleSel(t) => match self.expr(&t.tuple) {
+        Ty { arr: 0, kind: TyKind::Tuple(elem) } => if let Some(&ty) = elem.get(t.idx as usize) { ty } else {
+          self.issue(e.loc, TupleIndexOutOfRange { idx: t.idx, len: elem.len() as u32 })
+        }
+        ty => ty.error_or(|| self.issue(t.tuple.loc, NotTuple(ty))),
+      }
       This(_) => {
         if self.cur_func.unwrap().static_ { self.issue(e.loc, ThisInStatic) }
-        Ty::mk_obj(self.cur_class.unwrap())
+        self.this_ty(self.cur_class.unwrap())
+      }
+      Super(_) => {
+        let cur = self.cur_class.unwrap();
+        if self.cur_func.unwrap().static_ { self.issue(e.loc, SuperInStatic) }
+        if let Some(p) = cur.parent_ref.get() { Ty::mk_obj(p) } else { self.issue(e.loc, NoSuperClass(cur.name)) }
       }
       NewClass(n) => if let Some(c) = self.scopes.lookup_class(n.name) {
         n.class.set(Some(c));
-        Ty::mk_obj(c)
-      } else { self.issue(e.loc, NoSuchClass(n.name)) },
+        if c.abstract_ { self.issue(e.loc, NewAbstract(n.name)) }
+        let ty = self.obj_ty(c, true, n.ty_arg.as_ref(), e.loc);
+        // the arguments are checked like a call to the constructor, a class without constructor takes no argument
+        if let TyKind::Object(_, _, ty_arg) = ty {
+          let ty_arg = ty_arg.copied();
+          match c.ctors().as_slice() {
+            [] => if !n.arg.is_empty() {
+              self.check_args(&n.arg);
+              self.issue(e.loc, ArgcMismatch { name: n.name, min: 0, max: 0, actual: n.arg.len() as u32 })
+            }
+            &[f] => {
+              n.ctor.set(Some(f));
+              let ret_param = self.inst_all(f.ret_param_ty.get().unwrap(), ty_arg);
+              self.check_arg_param(&n.arg, ret_param, f.min_argc(), Some(n.name), e.loc);
+            }
+            funcs => if let Some(f) = self.overload(funcs, &n.arg, ty_arg, n.name, e.loc) { n.ctor.set(Some(f)); }
+          }
+        } else { self.check_args(&n.arg); }
+        Ty::new(ty)
+      } else { (self.check_args(&n.arg), self.issue(e.loc, NoSuchClass(n.name))).1 },
       NewArray(n) => {
-        let len = self.expr(&n.len);
-        if len != Ty::int() { len.error_or(|| self.issue(n.len.loc, NewArrayNotInt)) }
+        for l in iter::once(&*n.len).chain(&n.sub_len) {
+          let len = self.expr(l);
+          if len != Ty::int() { len.error_or(|| self.issue(l.loc, NewArrayNotInt)) }
+        }
         self.ty(&n.elem, true)
       }
       ClassTest(c) => {
@@ -167,11 +450,14 @@ impl<'a> TypePass<'a> {
         if !src.is_object() { src.error_or(|| self.issue(e.loc, NotObject(src))) }
         if let Some(cl) = self.scopes.lookup_class(c.name) {
           c.class.set(Some(cl));
-          Ty::mk_obj(cl)
+          // the type argument can't be checked in runtime, so casting to a generic class is not allowed
+          if cl.ty_param.is_some() { self.issue(e.loc, MissingTypeArg(c.name)) } else { Ty::mk_obj(cl) }
         } else { self.issue(e.loc, NoSuchClass(c.name)) }
       }
+      Lambda(l) => self.lambda(l, e.loc),
     };
     e.ty.set(ty);
+    if ty != Ty::error() { e.result.set(fold(e)); }
     ty
   }
 
@@ -181,39 +467,29 @@ impl<'a> TypePass<'a> {
     // (no owner)field_var && cur function is static => RefInStatic
     // <not object>.a (e.g.: Class.a, 1.a) / object.method => BadFieldAccess
     // object.field_var, where object's class is not self or any of ancestors => PrivateFieldAccess
+    // ClassName.static_field_var is allowed, and object.static_field_var gives a warning StaticFieldViaInstance
+    // ClassName.static_method (not overloaded) is a function value
 
     if let Some(owner) = &v.owner {
       self.cur_used = true;
-      let owner = self.expr(owner);
+      let owner_ty = self.expr(owner);
       self.cur_used = false;
-      match owner {
-        Ty { arr: 0, kind: TyKind::Object(Ref(c)) } => if let Some(sym) = c.lookup(v.name) {
-          match sym {
-            Symbol::Var(var) => {
-              v.var.set(Some(var));
-              // only allow self & descendents to access field
-              if !self.cur_class.unwrap().extends(c) {
-                self.issue(loc, PrivateFieldAccess { name: v.name, owner })
-              }
-              var.ty.get()
-            }
-            _ => self.issue(loc, BadFieldAccess { name: v.name, owner }),
-          }
-        } else { self.issue(loc, NoSuchField { name: v.name, owner }) },
-        e => e.error_or(|| self.issue(loc, BadFieldAccess { name: v.name, owner })),
-      }
+      self.check_null_deref(owner, loc);
+      self.field(v, owner_ty, loc)
     } else {
+      let lvalue = mem::take(&mut self.cur_lvalue);
       // if this stmt is in an VarDef, it cannot access the variable that is being declared
       if let Some(sym) = self.scopes.lookup_before(v.name, self.cur_var_def.map(|v| v.loc).unwrap_or(loc)) {
         match sym {
           Symbol::Var(var) => {
             v.var.set(Some(var));
+            if !lvalue { var.used.set(true); }
             if var.owner.get().unwrap().is_class() {
               let cur = self.cur_func.unwrap();
-              if cur.static_ {
+              if cur.static_ && !var.static_ {
                 self.issue(loc, RefInStatic { field: v.name, func: cur.name })
               }
-            }
+            } else if !self.capture(var) { return self.issue(loc, UndeclaredVar(v.name)); }
             var.ty.get()
           }
           Symbol::Class(c) if self.cur_used => { Ty::mk_class(c) }
@@ -223,27 +499,100 @@ impl<'a> TypePass<'a> {
     }
   }
 
+  // the field `v.name` of an evaluated owner, `v.var` is set if it is found
+  fn field(&mut self, v: &'a VarSel<'a>, owner: Ty<'a>, loc: Loc) -> Ty<'a> {
+    match owner {
+      Ty { arr: 0, kind: TyKind::Object(Ref(c), _, ty_arg) } => if let Some(sym) = c.lookup(v.name) {
+        match sym {
+          Symbol::Var(var) => {
+            v.var.set(Some(var));
+            // only allow self & descendents to access field
+            if !self.cur_class.unwrap().extends(c) {
+              self.issue(loc, PrivateFieldAccess { name: v.name, owner })
+            }
+            if var.static_ { self.issue(loc, StaticFieldViaInstance(v.name)) }
+            self.inst(var.ty.get(), ty_arg.copied())
+          }
+          _ => self.issue(loc, BadFieldAccess { name: v.name, owner }),
+        }
+      } else { self.issue(loc, NoSuchField { name: v.name, owner }) },
+      Ty { arr: 0, kind: TyKind::Class(Ref(c)) } => match c.lookup(v.name) {
+        Some(Symbol::Var(var)) if var.static_ => {
+          v.var.set(Some(var));
+          if !self.cur_class.unwrap().extends(c) {
+            self.issue(loc, PrivateFieldAccess { name: v.name, owner })
+          }
+          var.ty.get()
+        }
+        Some(Symbol::Func(f)) if f.static_ && c.lookup_funcs(v.name).len() == 1 => {
+          v.func.set(Some(f));
+          Ty::mk_func(f)
+        }
+        _ => self.issue(loc, BadFieldAccess { name: v.name, owner }),
+      },
+      e => e.error_or(|| self.issue(loc, BadFieldAccess { name: v.name, owner })),
+    }
+  }
+
   fn call(&mut self, c: &'a Call<'a>, loc: Loc) -> Ty<'a> {
-    let v = if let ExprKind::VarSel(v) = &c.func.kind { v } else { unimplemented!() };
+    let v = if let ExprKind::VarSel(v) = &c.func.kind { v } else {
+      let func = self.expr(&c.func);
+      return self.call_value(func, &c.arg, loc);
+    };
     let owner = if let Some(owner) = &v.owner {
       self.cur_used = true;
-      let owner = self.expr(owner);
+      let owner_ty = self.expr(owner);
       self.cur_used = false;
-      if owner == Ty::error() { return Ty::error(); }
+      self.check_null_deref(owner, loc);
+      let owner = owner_ty;
+      if owner == Ty::error() { return self.check_args(&c.arg); }
       if v.name == LENGTH && owner.is_arr() {
         if !c.arg.is_empty() {
           self.issue(loc, LengthWithArgument(c.arg.len() as u32))
         }
         return Ty::int();
       }
+      // the built-in methods of string: `length()`, and `substring(start, end)` for the chars in [start, end)
+      if owner == Ty::string() && (v.name == LENGTH || v.name == SUBSTRING) {
+        if v.name == SUBSTRING { return self.check_arg_param(&c.arg, &[Ty::string(), Ty::int(), Ty::int()], 2, Some(SUBSTRING), loc); }
+        if !c.arg.is_empty() {
+          self.issue(loc, LengthWithArgument(c.arg.len() as u32))
+        }
+        return Ty::int();
+      }
       owner
-    } else { Ty::mk_obj(self.cur_class.unwrap()) };
+    } else {
+      // `f(args)` where `f` is a variable (local, param or field) holding a function value
+      // a local variable or parameter not holding a function is reported with its type, like calling other values
+      if let Some(Symbol::Var(var)) = self.scopes.lookup_before(v.name, loc) {
+        let func = self.expr(&c.func);
+        return if func.is_func() || func == Ty::error() || !var.owner.get().map_or(false, |o| o.is_class()) { self.call_value(func, &c.arg, loc) } else {
+          let owner = Ty::mk_obj(self.cur_class.unwrap());
+          (self.check_args(&c.arg), self.issue(loc, NotFunc { name: v.name, owner })).1
+        };
+      }
+      // the built-in conversion functions, they are hidden by any symbol with the same name
+      if self.scopes.lookup(v.name).is_none() {
+        match v.name {
+          STRING_TO_INT => return self.check_arg_param(&c.arg, &[Ty::int(), Ty::string()], 1, Some(v.name), loc),
+          INT_TO_STRING => return self.check_arg_param(&c.arg, &[Ty::string(), Ty::int()], 1, Some(v.name), loc),
+          _ => {}
+        }
+      }
+      Ty::mk_obj(self.cur_class.unwrap())
+    };
+    let ty_arg = if let TyKind::Object(_, _, a) = owner.kind { a.copied() } else { None };
     match owner {
-      Ty { arr: 0, kind: TyKind::Object(Ref(cl)) } | Ty { arr: 0, kind: TyKind::Class(Ref(cl)) } => {
+      Ty { arr: 0, kind: TyKind::Object(Ref(cl), ..) } | Ty { arr: 0, kind: TyKind::Class(Ref(cl)) } => {
         if let Some(sym) = cl.lookup(v.name) {
           match sym {
             Symbol::Func(f) => {
+              let funcs = cl.lookup_funcs(v.name);
+              let f = if funcs.len() == 1 { f } else if let Some(f) = self.overload(&funcs, &c.arg, ty_arg, v.name, loc) { f } else { return Ty::error(); };
               c.func_ref.set(Some(f));
+              if let Some(ExprKind::Super(_)) = v.owner.as_ref().map(|o| &o.kind) {
+                if f.abstract_ { self.issue(loc, AbstractSuperCall(f.name)) }
+              }
               if owner.is_class() && !f.static_ {
                 // Class.not_static_method()
                 self.issue(loc, BadFieldAccess { name: v.name, owner })
@@ -254,36 +603,388 @@ impl<'a> TypePass<'a> {
                   self.issue(loc, RefInStatic { field: f.name, func: cur.name })
                 }
               }
-              self.check_arg_param(&c.arg, f.ret_param_ty.get().unwrap(), f.name, loc)
+              // for overloaded methods, the arguments are already checked in `overload`
+              if funcs.len() == 1 {
+                let ret_param = self.inst_all(f.ret_param_ty.get().unwrap(), ty_arg);
+                self.check_arg_param(&c.arg, ret_param, f.min_argc(), Some(f.name), loc)
+              } else { self.inst(f.ret_ty(), ty_arg) }
+            }
+            // `obj.f(args)` where the field `f` is a function value
+            Symbol::Var(_) if v.owner.is_some() => {
+              let func = self.field(v, owner, c.func.loc);
+              c.func.ty.set(func);
+              if func.is_func() || func == Ty::error() { self.call_value(func, &c.arg, loc) } else {
+                (self.check_args(&c.arg), self.issue(loc, NotFunc { name: v.name, owner })).1
+              }
             }
-            _ => self.issue(loc, NotFunc { name: v.name, owner }),
+            _ => (self.check_args(&c.arg), self.issue(loc, NotFunc { name: v.name, owner })).1,
           }
-        } else { self.issue(loc, NoSuchField { name: v.name, owner }) }
+        } else { (self.check_args(&c.arg), self.issue(loc, NoSuchField { name: v.name, owner })).1 }
+      }
+      _ => (self.check_args(&c.arg), self.issue(loc, BadFieldAccess { name: v.name, owner })).1,
+    }
+  }
+}
+
+// the value of `e` if it can be statically evaluated, requires that the children of `e` are already checked
+// the arithmetic wraps on overflow, which is consistent with mips's addu, subu, mul, etc.
+// div 0 or mod 0 is not evaluated, so that it is reported as DivideByZero, and left to runtime
+fn fold(e: &Expr) -> Option<i32> {
+  match &e.kind {
+    ExprKind::IntLit(i) => Some(*i),
+    ExprKind::BoolLit(b) => Some(*b as i32),
+    ExprKind::Unary(u) => u.r.result.get().map(|r| u.op.eval(r)),
+    // strings are never evaluated, so Eq / Ne here only compare int or bool
+    ExprKind::Binary(b) => b.op.try_eval(b.l.result.get()?, b.r.result.get()?),
+    // only the selected branch need to be constant, because the other one is never evaluated
+    ExprKind::Ternary(t) => if t.cond.result.get()? != 0 { t.on_true.result.get() } else { t.on_false.result.get() },
+    _ => None,
+  }
+}
+
+// whether the statements after `s` are never executed, i.e., `s` always jumps away
+// an if terminates if both arms terminate, a block terminates if any statement in it terminates
+fn terminates(s: &Stmt) -> bool {
+  match &s.kind {
+    StmtKind::Return(_) | StmtKind::Break(_) | StmtKind::Continue(_) => true,
+    StmtKind::If(i) => {
+      let on_false = i.on_false.as_ref().map_or(false, |f| f.stmt.iter().any(terminates));
+      match i.cond.result.get() { Some(0) => on_false, Some(_) => i.on_true.stmt.iter().any(terminates), None => on_false && i.on_true.stmt.iter().any(terminates) }
+    }
+    StmtKind::While(w) => infinite(w),
+    StmtKind::Block(b) => b.stmt.iter().any(terminates),
+    _ => false,
+  }
+}
+
+// whether `w` is `while (true)` (or with another non-zero constant condition) without any `break` jumping out of it
+fn infinite(w: &While) -> bool { w.cond.result.get().map_or(false, |c| c != 0) && !loop_break(&w.body, false) }
+
+// whether a `break` in `b` may jump out of the enclosing loop, `nested` is true if `b` is in a nested loop or switch
+// every labeled `break` is assumed to jump out, because its target may be any enclosing loop
+fn loop_break(b: &Block, nested: bool) -> bool {
+  b.stmt.iter().any(|s| match &s.kind {
+    StmtKind::Break(b) => b.label.is_some() || !nested,
+    StmtKind::If(i) => loop_break(&i.on_true, nested) || i.on_false.as_ref().map_or(false, |f| loop_break(f, nested)),
+    StmtKind::Block(b) => loop_break(b, nested),
+    StmtKind::While(w) => loop_break(&w.body, true),
+    StmtKind::DoWhile(d) => loop_break(&d.body, true),
+    StmtKind::For(f) => loop_break(&f.body, true),
+    StmtKind::Foreach(f) => loop_break(&f.body, true),
+    StmtKind::Switch(sw) => sw.case.iter().any(|c| loop_break(&c.body, true)) || sw.default.iter().any(|d| loop_break(d, true)),
+    _ => false,
+  })
+}
+
+// the loc of the first `break` in `b` which jumps out of the enclosing switch, i.e., not in a loop or a nested switch
+// the `break`s after a terminating statement are never executed, e.g.: the case `case 1: return 1; break;` still returns
+fn switch_break(b: &Block) -> Option<Loc> {
+  for s in &b.stmt {
+    let brk = match &s.kind {
+      StmtKind::Break(Break { label: None }) => Some(s.loc),
+      StmtKind::If(i) => switch_break(&i.on_true).or_else(|| i.on_false.as_ref().and_then(switch_break)),
+      StmtKind::Block(b) => switch_break(b),
+      _ => None,
+    };
+    if brk.is_some() || terminates(s) { return brk; }
+  }
+  None
+}
+
+// (`p`, whether `p` is null if `cond` is true), if `cond` is `p == null` or `p != null` (or with `null` on the left)
+// `p` should be a local variable or a parameter
+fn null_test<'a>(cond: &Expr<'a>) -> Option<(&'a VarDef<'a>, bool)> {
+  match &cond.kind {
+    ExprKind::Binary(b) if b.op == BinOp::Eq || b.op == BinOp::Ne => {
+      let p = if is_null(&b.l) { &b.r } else if is_null(&b.r) { &b.l } else { return None; };
+      match &p.kind {
+        ExprKind::VarSel(VarSel { owner: None, var, .. }) => var.get()
+          .filter(|v| v.owner.get().map_or(false, |o| o.is_local() || o.is_param())).map(|v| (v, b.op == BinOp::Eq)),
+        _ => None,
+      }
+    }
+    _ => None,
+  }
+}
+
+fn is_null(e: &Expr) -> bool { if let ExprKind::NullLit(_) = e.kind { true } else { false } }
+
+// `x = x`, where both sides are the same variable without owner
+fn self_assign(a: &Assign) -> bool {
+  match (&a.dst.kind, &a.src.kind) {
+    (ExprKind::VarSel(VarSel { owner: None, var: l, .. }), ExprKind::VarSel(VarSel { owner: None, var: r, .. })) =>
+      l.get().map_or(false, |l| r.get().map(Ref) == Some(Ref(l))),
+    _ => false,
+  }
+}
+
+// the length of array `e` if it is statically known, i.e., `e` is a NewArray with constant length or an ArrayLit
+// or a local variable whose init value is one of them, then the variable is also returned
+fn static_len<'a>(e: &Expr<'a>) -> Option<(i32, Option<&'a VarDef<'a>>)> {
+  match &e.kind {
+    ExprKind::NewArray(n) => n.len.result.get().map(|len| (len, None)),
+    ExprKind::ArrayLit(a) => Some((a.len() as i32, None)),
+    ExprKind::VarSel(VarSel { owner: None, var, .. }) => {
+      let var = var.get().filter(|v| v.owner.get().unwrap().is_local())?;
+      match &var.init()?.kind {
+        ExprKind::NewArray(_) | ExprKind::ArrayLit(_) => static_len(var.init()?).map(|(len, _)| (len, Some(var))),
+        _ => None,
       }
-      _ => self.issue(loc, BadFieldAccess { name: v.name, owner }),
     }
+    _ => None,
   }
 }
 
 impl<'a> TypePass<'a> {
+  // the default values must be constants, because they are evaluated at the caller side
+  fn check_default(&mut self, f: &'a FuncDef<'a>) {
+    let mut has_default = false;
+    for v in &f.param {
+      if let Some((loc, e)) = &v.init {
+        has_default = true;
+        let (l, r) = (v.ty.get(), self.expr(e));
+        match &e.kind {
+          ExprKind::StringLit(_) | ExprKind::CharLit(_) | ExprKind::DoubleLit(_) | ExprKind::NullLit(_) => {}
+          _ if e.result.get().is_some() => {}
+          _ => r.error_or(|| self.issue(e.loc, DefaultNotConst)),
+        }
+        self.check_assignable(r, l, *loc, IncompatibleBinary { l, op: "=", r });
+      } else if has_default { self.issue(v.loc, RequiredAfterDefault(v.name)) }
+    }
+  }
+
+  // like `expr`, but an empty array literal can get its type from the context, e.g.: `int[] a = {};`
+  fn expr_expect(&mut self, e: &'a Expr<'a>, expect: Ty<'a>) -> Ty<'a> {
+    match &e.kind {
+      ExprKind::ArrayLit(a) if a.is_empty() && expect.is_arr() => {
+        e.ty.set(expect);
+        expect
+      }
+      _ => self.expr(e),
+    }
+  }
+
+  // issue NullToNonNull if assigning null to non-null object, IntToDouble if assigning int to double, or `err` for other kinds of mismatch
+  fn check_assignable(&mut self, r: Ty<'a>, l: Ty<'a>, loc: Loc, err: ErrorKind<'a, Ty<'a>>) {
+    if !r.assignable_to(l) {
+      self.issue(loc, if r == Ty::null() && l.is_object() { NullToNonNull(l) } else if r == Ty::int() && l == Ty::double() { IntToDouble } else { err })
+    }
+  }
+
   fn check_bool(&mut self, e: &'a Expr<'a>) {
     let ty = self.expr(e);
     if ty != Ty::bool() { ty.error_or(|| self.issue(e.loc, TestNotBool)) }
   }
 
-  fn check_arg_param(&mut self, arg: &'a [Expr<'a>], ret_param: &[Ty<'a>], name: &'a str, loc: Loc) -> Ty<'a> {
+  // check the rhs of `l op r` or `l op= r` if it is a constant, i.e., shifting by a negative amount, or dividing by zero
+  // `1.0 / 0` is not DivideByZero, it is infinity, and if `l` is error, it may also be double
+  fn check_const_rhs(&mut self, op: BinOp, l: Ty<'a>, r: Ty<'a>, rhs: &'a Expr<'a>, loc: Loc) {
+    match (op, if r == Ty::int() && l == Ty::int() { rhs.result.get() } else { None }) {
+      (BinOp::Shl, Some(x)) | (BinOp::Shr, Some(x)) if x < 0 => self.issue(rhs.loc, NegativeShift(x)),
+      (BinOp::Div, Some(0)) | (BinOp::Mod, Some(0)) => self.issue(loc, DivideByZero),
+      _ => {}
+    }
+  }
+
+  // check the operand types of a binary operator, and return (the result type, whether it type checks without error)
+  // `op_str` is only used in error message, it may be different from `op.to_op_str()` (e.g.: in compound assignment)
+  fn binary_ty(&mut self, op: BinOp, op_str: &'a str, l: Ty<'a>, r: Ty<'a>, loc: Loc) -> (Ty<'a>, bool) {
+    use BinOp::*;
+    if l == Ty::error() || r == Ty::error() {
+      // not using wildcard match, so that if we add new operators in the future, compiler can tell us
+      (match op {
+        // if one side is string, the result is more likely to be string, which reduces cascading errors
+        Add => if l == Ty::string() || r == Ty::string() { Ty::string() } else if l.is_arr() || r.is_arr() { if l.is_arr() { l } else { r } } else if l == Ty::double() || r == Ty::double() { Ty::double() } else { Ty::int() },
+        Sub | Mul | Div => if l == Ty::double() || r == Ty::double() { Ty::double() } else { Ty::int() },
+        Mod | BitAnd | BitOr | BitXor | Shl | Shr => Ty::int(),
+        And | Or | Eq | Ne | Lt | Le | Gt | Ge => Ty::bool(),
+      }, false)
+    } else {
+      let num = |t: Ty| t == Ty::int() || t == Ty::double();
+      // mixed int and double are promoted to double
+      let arith = if l == Ty::double() || r == Ty::double() { Ty::double() } else { Ty::int() };
+      let (ret, ok) = match op {
+        Add if l == Ty::string() && r == Ty::string() => (Ty::string(), true),
+        // the result's element type is the more general one of the two element types
+        Add if l.is_arr() && r.is_arr() => {
+          let (le, re) = (Ty { arr: l.arr - 1, ..l }, Ty { arr: r.arr - 1, ..r });
+          if le.assignable_to(re) { (r, true) } else if re.assignable_to(le) { (l, true) } else { (l, false) }
+        }
+        Mod if arith == Ty::double() && num(l) && num(r) => return (self.issue(loc, DoubleMod), false),
+        Add | Sub | Mul | Div => (arith, num(l) && num(r)),
+        Mod | BitAnd | BitOr | BitXor | Shl | Shr => (Ty::int(), l == Ty::int() && r == Ty::int()),
+        Lt | Le | Gt | Ge => (Ty::bool(), (num(l) && num(r)) || (l == Ty::char() && r == Ty::char())),
+        // comparing non-null object with null is allowed
+        // the values of a type parameter can't be compared, since the comparison depends on the type argument
+        Eq | Ne => (Ty::bool(), (l.common(r).is_some() && !l.is_param()) || (num(l) && num(r))),
+        And | Or => (Ty::bool(), l == Ty::bool() && r == Ty::bool())
+      };
+      if !ok { self.issue(loc, IncompatibleBinary { l, op: op_str, r }) }
+      (ret, ok)
+    }
+  }
+
+  // enter a loop, its label can't be the same as an enclosing loop's, otherwise `break label` would be ambiguous
+  fn push_loop(&mut self, label: Option<&'a str>, loc: Loc) {
+    if let Some(l) = label {
+      if self.loop_stk.contains(&label) { self.issue(loc, DuplicateLabel(l)) }
+    }
+    self.loop_stk.push(label);
+  }
+
+  // check `break` or `continue`, `out_of_loop` is the error when not in any loop
+  fn check_jump(&mut self, label: Option<&'a str>, loc: Loc, out_of_loop: ErrorKind<'a, Ty<'a>>) {
+    if self.loop_stk.is_empty() { self.issue(loc, out_of_loop) } else if let Some(l) = label {
+      if !self.loop_stk.contains(&label) { self.issue(loc, NoSuchLabel(l)) }
+    }
+  }
+
+  // warn if the receiver `owner` is definitely null, i.e., `null`, a cast of it, or a local variable initialized with `null`
+  // or narrowed to null by an enclosing if, a narrowed variable is non-null or null according to the innermost if
+  // a local variable is definitely null only if it is never assigned a value other than `null`, this is known at the end of the function
+  fn check_null_deref(&mut self, owner: &'a Expr<'a>, loc: Loc) {
+    match &owner.kind {
+      ExprKind::NullLit(_) => self.issue(loc, NullDereference),
+      ExprKind::ClassCast(c) => self.check_null_deref(&c.expr, loc),
+      ExprKind::VarSel(VarSel { owner: None, var, .. }) => if let Some(var) = var.get() {
+        let null = match self.narrowed.iter().rev().find(|&&(v, _)| Ref(v) == Ref(var)) {
+          Some(&(_, null)) => null,
+          None => var.owner.get().map_or(false, |o| o.is_local()) && var.init().map_or(false, is_null),
+        };
+        if null { self.null_deref.push((loc, var)); }
+      }
+      _ => {}
+    }
+  }
+
+  // substitute the type argument of the owner into the type of a member, `ty_arg` is None if the owner is not generic
+  fn inst(&self, t: Ty<'a>, ty_arg: Option<Ty<'a>>) -> Ty<'a> { ty_arg.map_or(t, |a| t.subst(a, &self.alloc.ty)) }
+
+  fn inst_all(&self, t: &'a [Ty<'a>], ty_arg: Option<Ty<'a>>) -> &'a [Ty<'a>] {
+    if let Some(a) = ty_arg { self.alloc.ty.alloc_extend(t.iter().map(|t| t.subst(a, &self.alloc.ty))) } else { t }
+  }
+
+  // choose the most specific one among the methods applicable to the arguments
+  // nullability is ignored when choosing, so that passing null to a non-null parameter is reported as NullToNonNull on the chosen one
+  fn overload(&mut self, funcs: &[&'a FuncDef<'a>], arg: &'a [Expr<'a>], ty_arg: Option<Ty<'a>>, name: &'a str, loc: Loc) -> Option<&'a FuncDef<'a>> {
+    let arg_ty = arg.iter().map(|a| self.expr(a)).collect::<Vec<_>>();
+    if arg_ty.contains(&Ty::error()) { return None; } // error type is assignable to anything, it will cause a meaningless AmbiguousCall
+    let param_ty = funcs.iter().map(|f| self.inst_all(f.param_ty(), ty_arg)).collect::<Vec<_>>();
+    let applicable = |i: usize, arg: &[Ty<'a>]| (funcs[i].min_argc()..=funcs[i].param.len()).contains(&arg.len()) &&
+      arg.iter().zip(param_ty[i]).all(|(a, p)| a.assignable_to(p.nullable()));
+    let ok = (0..funcs.len()).filter(|&i| applicable(i, &arg_ty)).collect::<Vec<_>>();
+    // `f` is more specific than `g` if `g` is applicable to `f`'s parameters
+    let best = ok.iter().copied().filter(|&f| ok.iter().all(|&g| applicable(g, param_ty[f]))).collect::<Vec<_>>();
+    match best.as_slice() {
+      &[f] => {
+        for (idx, ((a, &a_ty), &p)) in arg.iter().zip(&arg_ty).zip(param_ty[f]).enumerate() {
+          self.check_assignable(a_ty, p, a.loc, ArgMismatch { loc: idx as u32 + 1, arg: a_ty, param: p });
+        }
+        Some(funcs[f])
+      }
+      _ if ok.is_empty() => self.issue(loc, NoMatchingOverload { name, funcs: funcs.iter().map(|f| Ty::mk_func(f)).collect() }),
+      _ => self.issue(loc, AmbiguousCall(name)),
+    }
+  }
+
+  // the parameters after the first `min_argc` ones have default values, so the corresponding arguments can be omitted
+  // `name` is None when calling a function value, which has no name
+  fn check_arg_param(&mut self, arg: &'a [Expr<'a>], ret_param: &[Ty<'a>], min_argc: usize, name: Option<&'a str>, loc: Loc) -> Ty<'a> {
     let (ret, param) = (ret_param[0], &ret_param[1..]);
-    if param.len() != arg.len() {
-      self.issue(loc, ArgcMismatch { name, expect: param.len() as u32, actual: arg.len() as u32 })
+    if arg.len() < min_argc || arg.len() > param.len() {
+      let (min, max, actual) = (min_argc as u32, param.len() as u32, arg.len() as u32);
+      self.issue(loc, if let Some(name) = name { ArgcMismatch { name, min, max, actual } } else { LambdaArgcMismatch { expect: max, actual } })
     }
     for (idx, arg0) in arg.iter().enumerate() {
-      let arg = self.expr(arg0);
+      let arg = match param.get(idx) { Some(&param) => self.expr_expect(arg0, param), None => self.expr(arg0) };
       if let Some(&param) = param.get(idx) {
-        if !arg.assignable_to(param) {
-          self.issue(arg0.loc, ArgMismatch { loc: idx as u32 + 1, arg, param })
-        }
+        self.check_assignable(arg, param, arg0.loc, ArgMismatch { loc: idx as u32 + 1, arg, param });
       }
     }
     ret
   }
-}
\ No newline at end of file
+  // a function value can be called, but it has no default values
+  fn call_value(&mut self, func: Ty<'a>, arg: &'a [Expr<'a>], loc: Loc) -> Ty<'a> {
+    match func {
+      Ty { arr: 0, kind: TyKind::Func(ret_param) } => self.check_arg_param(arg, ret_param, ret_param.len() - 1, None, loc),
+      _ => {
+        self.check_args(arg);
+        func.error_or(|| self.issue(loc, NotCallable(func)))
+      }
+    }
+  }
+
+  // when the callee can't be resolved, the arguments are still checked, because their errors are independent of it
+  // it returns error type, so that the enclosing expressions don't report errors caused by this call
+  fn check_args(&mut self, arg: &'a [Expr<'a>]) -> Ty<'a> {
+    for a in arg { self.expr(a); }
+    Ty::error()
+  }
+
+  // the parameters are already declared in SymbolPass
+  // the body is checked in a new context: `break` / `continue` can't jump out of it, and `return` only returns from it
+  fn lambda(&mut self, l: &'a Lambda<'a>, loc: Loc) -> Ty<'a> {
+    let var_def = self.cur_var_def.take();
+    self.lambda_stk.push(LambdaCtx { l, loc, var_def, ret: vec![] });
+    let (loop_stk, switch_cnt) = (mem::take(&mut self.loop_stk), mem::take(&mut self.switch_cnt));
+    let ret = self.scoped(ScopeOwner::Lambda(l), |s| match &l.body {
+      LambdaBody::Expr(e) => s.expr(e),
+      LambdaBody::Block(b) => {
+        let fall = s.block(b);
+        let mut ret: Option<Ty<'a>> = None;
+        for &(loc, r) in &s.lambda_stk.last().unwrap().ret.clone() {
+          ret = Some(match ret {
+            Some(ret) => ret.common(r).unwrap_or_else(|| s.issue(loc, LambdaReturnMismatch)),
+            None => r,
+          });
+        }
+        let ret = ret.unwrap_or(Ty::void());
+        if let Some(loc) = fall {
+          if ret != Ty::void() { ret.error_or(|| s.issue(loc, NoReturn)) }
+        }
+        ret
+      }
+    });
+    self.lambda_stk.pop();
+    (self.loop_stk = loop_stk, self.switch_cnt = switch_cnt, self.cur_var_def = var_def);
+    let ret_param = self.alloc.ty.alloc_extend(iter::once(ret).chain(l.param.iter().map(|v| v.ty.get())));
+    l.ret_param_ty.set(Some(ret_param));
+    Ty::new(TyKind::Func(ret_param))
+  }
+
+  // register `var` to the enclosing lambdas which it is declared outside, return false if it is actually not accessible
+  fn capture(&self, var: &'a VarDef<'a>) -> bool {
+    for ctx in self.lambda_stk.iter().rev() {
+      if var.loc >= ctx.loc { break; }
+      // the variable being declared, e.g.: `var f = () => f();`
+      if ctx.var_def.map(|d| var.loc >= d.loc).unwrap_or(false) { return false; }
+      let mut captured = ctx.l.captured.borrow_mut();
+      if !captured.iter().any(|&v| Ref(v) == Ref(var)) { captured.push(var); }
+    }
+    true
+  }
+
+  // the captured variables are copied into the closure, so assigning to them is meaningless
+  fn check_captured_assign(&mut self, e: &'a Expr<'a>, loc: Loc) {
+    if let (ExprKind::VarSel(VarSel { owner: None, var, name, .. }), Some(ctx)) = (&e.kind, self.lambda_stk.last()) {
+      if let Some(var) = var.get() {
+        if !var.owner.get().unwrap().is_class() && var.loc < ctx.loc { self.issue(loc, AssignCapturedVar(*name)) }
+      }
+    }
+  }
+
+  // a final local can't be assigned after its init, a final field can only be assigned through `this` in the constructors of its class
+  // (not in a lambda in the constructor, since the lambda may be called later)
+  fn check_final_assign(&mut self, e: &'a Expr<'a>, loc: Loc) {
+    if let ExprKind::VarSel(VarSel { owner, var, name, .. }) = &e.kind {
+      if let Some(var) = var.get().filter(|v| v.final_) {
+        let ok = match var.owner.get().unwrap() {
+          ScopeOwner::Class(c) => Ref(c) == Ref(self.cur_class.unwrap()) && self.cur_func.unwrap().ctor && self.lambda_stk.is_empty() &&
+            owner.as_ref().map(|o| if let ExprKind::This(_) = o.kind { true } else { false }).unwrap_or(true),
+          _ => false,
+        };
+        if !ok { self.issue(loc, AssignToFinal(*name)) }
+      }
+    }
+  }
+}